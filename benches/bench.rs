@@ -77,6 +77,39 @@ fn iterate_mut_100k(b: &mut Bencher) {
     })
 }
 
+/// Big enough that each entity's components span several cache lines
+struct Brain([f32; 32]);
+
+fn large_world() -> World {
+    let mut world = World::new();
+    for i in 0..100_000 {
+        world.spawn((Position(i as f32), Brain([0.0; 32])));
+    }
+    world
+}
+
+fn iterate_large_100k(b: &mut Bencher) {
+    let mut world = large_world();
+    b.iter(|| {
+        for (_, (pos, brain)) in world.query_mut::<(&Position, &mut Brain)>() {
+            brain.0[0] += pos.0;
+        }
+    })
+}
+
+fn iterate_large_prefetch_100k(b: &mut Bencher) {
+    let mut world = large_world();
+    b.iter(|| {
+        for (_, (pos, brain)) in world
+            .query_mut::<(&Position, &mut Brain)>()
+            .into_iter()
+            .prefetch(8)
+        {
+            brain.0[0] += pos.0;
+        }
+    })
+}
+
 fn build(b: &mut Bencher) {
     let mut world = World::new();
     let mut builder = EntityBuilder::new();
@@ -86,6 +119,156 @@ fn build(b: &mut Bencher) {
     });
 }
 
+/// Component shapes resembling those of a Minecraft server, for representative measurements
+#[allow(dead_code)]
+mod minecraft {
+    pub struct Position(pub [f64; 3]);
+    pub struct Velocity(pub [f64; 3]);
+    pub struct Rotation(pub f32, pub f32);
+    pub struct Health(pub f32);
+    pub struct OnGround(pub bool);
+    pub struct Name(pub String);
+    pub struct Inventory(pub [Option<(u16, u8)>; 36]);
+    pub struct Ai {
+        pub target: Option<hecs::Entity>,
+        pub path: Vec<[i32; 3]>,
+    }
+    pub struct ItemStack(pub u16, pub u8);
+    pub struct Age(pub u32);
+    /// Marks entities not simulated this tick, e.g. in unloaded chunks
+    pub struct Frozen;
+}
+
+/// Spawn a mix of players, mobs, and dropped items, a tenth of the mobs frozen
+fn spawn_minecraft(world: &mut World, count: u32) {
+    use minecraft::*;
+    for i in 0..count {
+        let pos = Position([i as f64, 64.0, -(i as f64)]);
+        let vel = Velocity([0.0, -0.08, 0.0]);
+        match i % 20 {
+            0 => {
+                world.spawn((
+                    pos,
+                    vel,
+                    Rotation(0.0, 0.0),
+                    Health(20.0),
+                    OnGround(true),
+                    Name(format!("player{}", i)),
+                    Inventory([None; 36]),
+                ));
+            }
+            1..=9 => {
+                let mob = world.spawn((
+                    pos,
+                    vel,
+                    Rotation(0.0, 0.0),
+                    Health(10.0),
+                    OnGround(false),
+                    Ai {
+                        target: None,
+                        path: Vec::new(),
+                    },
+                ));
+                if i % 10 == 1 {
+                    world.insert_one(mob, Frozen).unwrap();
+                }
+            }
+            _ => {
+                world.spawn((pos, vel, OnGround(false), ItemStack(1, 1), Age(0)));
+            }
+        }
+    }
+}
+
+fn spawn_minecraft_10k(b: &mut Bencher) {
+    let mut world = World::new();
+    b.iter(|| {
+        spawn_minecraft(&mut world, 10_000);
+        world.clear();
+    });
+}
+
+fn insert_remove_minecraft_10k(b: &mut Bencher) {
+    let mut world = World::new();
+    spawn_minecraft(&mut world, 10_000);
+    let entities = world.iter().map(|(e, _)| e).collect::<Vec<_>>();
+    b.iter(|| {
+        for &e in &entities {
+            world.insert_one(e, minecraft::Frozen).unwrap();
+        }
+        for &e in &entities {
+            world.remove_one::<minecraft::Frozen>(e).unwrap();
+        }
+    });
+}
+
+fn physics_minecraft_100k(b: &mut Bencher) {
+    use minecraft::*;
+    let mut world = World::new();
+    spawn_minecraft(&mut world, 100_000);
+    b.iter(|| {
+        for (_, (pos, vel, on_ground)) in
+            world.query_mut::<Without<Frozen, (&mut Position, &mut Velocity, &mut OnGround)>>()
+        {
+            for axis in 0..3 {
+                pos.0[axis] += vel.0[axis];
+            }
+            vel.0[1] -= 0.08;
+            on_ground.0 = pos.0[1] <= 0.0;
+        }
+    });
+}
+
+fn changed_minecraft_100k(b: &mut Bencher) {
+    use minecraft::*;
+    let mut world = World::new();
+    spawn_minecraft(&mut world, 100_000);
+    let movers = world
+        .query::<With<Ai, ()>>()
+        .iter()
+        .map(|(e, ())| e)
+        .step_by(8)
+        .collect::<Vec<_>>();
+    b.iter(|| {
+        world.clear_trackers();
+        for &e in &movers {
+            world.get_mut::<Position>(e).unwrap().0[0] += 1.0;
+        }
+        // Movement packets are sent only for entities that moved
+        world
+            .query::<Changed<Position, &Position>>()
+            .iter()
+            .map(|(_, pos)| pos.0[0])
+            .sum::<f64>()
+    });
+}
+
+fn parallel_minecraft_100k(b: &mut Bencher) {
+    use minecraft::*;
+    let mut world = World::new();
+    spawn_minecraft(&mut world, 100_000);
+    let threads = std::thread::available_parallelism().map_or(4, |x| x.get());
+    b.iter(|| {
+        let mut query = world.query::<(&mut Position, &Velocity)>();
+        let batches = std::sync::Mutex::new(query.iter_batched(4096));
+        std::thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| loop {
+                    let batch = match batches.lock().unwrap().next() {
+                        Some(x) => x,
+                        None => break,
+                    };
+                    for (_, (pos, vel)) in batch {
+                        for axis in 0..3 {
+                            pos.0[axis] += vel.0[axis];
+                        }
+                    }
+                });
+            }
+        });
+    });
+}
+
 benchmark_group!(
     benches,
     spawn_tuple,
@@ -93,6 +276,13 @@ benchmark_group!(
     spawn_batch,
     iterate_100k,
     iterate_mut_100k,
-    build
+    iterate_large_100k,
+    iterate_large_prefetch_100k,
+    build,
+    spawn_minecraft_10k,
+    insert_remove_minecraft_10k,
+    physics_minecraft_100k,
+    changed_minecraft_100k,
+    parallel_minecraft_100k
 );
 benchmark_main!(benches);
@@ -6,14 +6,14 @@ fn format_entity(entity: hecs::EntityRef<'_>) -> String {
         Some(entity.get::<T>()?.to_string())
     }
 
-    const FUNCTIONS: &[&dyn Fn(hecs::EntityRef<'_>) -> Option<String>] =
-        &[&fmt::<i32>, &fmt::<bool>, &fmt::<f64>];
+    type Formatter = dyn Fn(hecs::EntityRef<'_>) -> Option<String>;
+    const FUNCTIONS: &[&Formatter] = &[&fmt::<i32>, &fmt::<bool>, &fmt::<f64>];
 
     let mut out = String::new();
     for f in FUNCTIONS {
         if let Some(x) = f(entity) {
             if out.is_empty() {
-                out.push_str("[");
+                out.push('[');
             } else {
                 out.push_str(", ");
             }
@@ -21,7 +21,7 @@ fn format_entity(entity: hecs::EntityRef<'_>) -> String {
         }
     }
     if out.is_empty() {
-        out.push_str(&"[]");
+        out.push_str("[]");
     } else {
         out.push(']');
     }
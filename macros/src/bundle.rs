@@ -16,15 +16,14 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
             ))
         }
     };
-    let (tys, field_members) = struct_fields(&data.fields);
-    let field_idents = member_as_idents(&field_members);
+    let fields = struct_fields(&data.fields);
     let generics = add_additional_bounds_to_generic_params(input.generics);
 
-    let dyn_bundle_code = gen_dynamic_bundle_impl(&ident, &generics, &field_members, &tys);
-    let bundle_code = if tys.is_empty() {
+    let dyn_bundle_code = gen_dynamic_bundle_impl(&ident, &generics, &fields);
+    let bundle_code = if fields.components.tys.is_empty() && fields.bundles.tys.is_empty() {
         gen_unit_struct_bundle_impl(ident, &generics)
     } else {
-        gen_bundle_impl(&ident, &generics, &field_members, &field_idents, &tys)
+        gen_bundle_impl(&ident, &generics, &fields)
     };
     let mut ts = dyn_bundle_code;
     ts.extend(bundle_code);
@@ -34,10 +33,13 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
 fn gen_dynamic_bundle_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
-    field_members: &[syn::Member],
-    tys: &[&syn::Type],
+    fields: &Fields,
 ) -> TokenStream2 {
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let tys = &fields.components.tys;
+    let field_members = &fields.components.members;
+    let bundle_tys = &fields.bundles.tys;
+    let bundle_members = &fields.bundles.members;
     quote! {
         unsafe impl #impl_generics ::hecs::DynamicBundle for #ident #ty_generics #where_clause {
             fn with_ids<__hecs__T>(&self, f: impl ::std::ops::FnOnce(&[::std::any::TypeId]) -> __hecs__T) -> __hecs__T {
@@ -48,54 +50,54 @@ fn gen_dynamic_bundle_impl(
                 <Self as ::hecs::Bundle>::static_type_info()
             }
 
-            #[allow(clippy::forget_copy)]
+            #[allow(clippy::forget_copy, clippy::forget_non_drop)]
             unsafe fn put(mut self, mut f: impl ::std::ops::FnMut(*mut u8, ::hecs::TypeInfo)) {
                 #(
                     f((&mut self.#field_members as *mut #tys).cast::<u8>(), ::hecs::TypeInfo::of::<#tys>());
                     ::std::mem::forget(self.#field_members);
                 )*
+                #(
+                    <#bundle_tys as ::hecs::DynamicBundle>::put(self.#bundle_members, &mut f);
+                )*
             }
         }
     }
 }
 
-fn gen_bundle_impl(
-    ident: &syn::Ident,
-    generics: &syn::Generics,
-    field_members: &[syn::Member],
-    field_idents: &[Cow<syn::Ident>],
-    tys: &[&syn::Type],
-) -> TokenStream2 {
+fn gen_bundle_impl(ident: &syn::Ident, generics: &syn::Generics, fields: &Fields) -> TokenStream2 {
+    let tys = &fields.components.tys;
+    let field_members = &fields.components.members;
+    let field_idents = member_as_idents(field_members);
+    let bundle_tys = &fields.bundles.tys;
+    let bundle_members = &fields.bundles.members;
+    let bundle_idents = member_as_idents(bundle_members);
     let num_tys = tys.len();
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    let with_static_ids_inner = quote! {
-        {
-            let mut tys = [#((::std::mem::align_of::<#tys>(), ::std::any::TypeId::of::<#tys>())),*];
-            tys.sort_unstable_by(|x, y| {
-                ::std::cmp::Ord::cmp(&x.0, &y.0)
-                    .reverse()
-                    .then(::std::cmp::Ord::cmp(&x.1, &y.1))
-            });
-            let mut ids = [::std::any::TypeId::of::<()>(); #num_tys];
-            for (id, info) in ::std::iter::Iterator::zip(ids.iter_mut(), tys.iter()) {
-                *id = info.1;
+    let with_static_ids_body = if !bundle_tys.is_empty() {
+        // The number of components isn't known until the nested bundles are consulted. `Self`
+        // can't be named inside a static.
+        let ids = quote! {
+            ::std::iter::Iterator::collect::<::std::vec::Vec<_>>(
+                ::std::iter::Iterator::map(
+                    <#ident #ty_generics as ::hecs::Bundle>::static_type_info().iter(),
+                    |x| x.id(),
+                )
+            )
+        };
+        if generics.params.is_empty() {
+            quote! {
+                ::hecs::lazy_static::lazy_static! {
+                    static ref ELEMENTS: ::std::vec::Vec<::std::any::TypeId> = #ids;
+                }
+                f(&*ELEMENTS)
             }
-            ids
-        }
-    };
-    let with_static_ids_body = if generics.params.is_empty() {
-        quote! {
-            ::hecs::lazy_static::lazy_static! {
-                static ref ELEMENTS: [::std::any::TypeId; #num_tys] = {
-                    #with_static_ids_inner
-                };
+        } else {
+            quote! {
+                f(&#ids)
             }
-            f(&*ELEMENTS)
         }
     } else {
-        quote! {
-            f(&#with_static_ids_inner)
-        }
+        gen_with_static_ids_body(generics, tys, num_tys)
     };
     quote! {
         unsafe impl #impl_generics ::hecs::Bundle for #ident #ty_generics #where_clause {
@@ -105,7 +107,11 @@ fn gen_bundle_impl(
             }
 
             fn static_type_info() -> ::std::vec::Vec<::hecs::TypeInfo> {
+                #[allow(unused_mut)]
                 let mut info = ::std::vec![#(::hecs::TypeInfo::of::<#tys>()),*];
+                #(
+                    info.extend(<#bundle_tys as ::hecs::Bundle>::static_type_info());
+                )*
                 info.sort_unstable();
                 info
             }
@@ -119,8 +125,54 @@ fn gen_bundle_impl(
                             .cast::<#tys>()
                             .as_ptr();
                 )*
-                ::std::result::Result::Ok(Self { #( #field_members: #field_idents.read(), )* })
+                // Nested bundles are moved out as they're found, so they mustn't be dropped if a
+                // later one is missing a component; the originals still own them
+                #(
+                    let #bundle_idents = ::std::mem::ManuallyDrop::new(
+                        <#bundle_tys as ::hecs::Bundle>::get(&mut f)?
+                    );
+                )*
+                ::std::result::Result::Ok(Self {
+                    #( #field_members: #field_idents.read(), )*
+                    #( #bundle_members: ::std::mem::ManuallyDrop::into_inner(#bundle_idents), )*
+                })
+            }
+        }
+    }
+}
+
+fn gen_with_static_ids_body(
+    generics: &syn::Generics,
+    tys: &[&syn::Type],
+    num_tys: usize,
+) -> TokenStream2 {
+    let with_static_ids_inner = quote! {
+        {
+            let mut tys = [#((::std::mem::align_of::<#tys>(), ::std::any::TypeId::of::<#tys>())),*];
+            tys.sort_unstable_by(|x, y| {
+                ::std::cmp::Ord::cmp(&x.0, &y.0)
+                    .reverse()
+                    .then(::std::cmp::Ord::cmp(&x.1, &y.1))
+            });
+            let mut ids = [::std::any::TypeId::of::<()>(); #num_tys];
+            for (id, info) in ::std::iter::Iterator::zip(ids.iter_mut(), tys.iter()) {
+                *id = info.1;
             }
+            ids
+        }
+    };
+    if generics.params.is_empty() {
+        quote! {
+            ::hecs::lazy_static::lazy_static! {
+                static ref ELEMENTS: [::std::any::TypeId; #num_tys] = {
+                    #with_static_ids_inner
+                };
+            }
+            f(&*ELEMENTS)
+        }
+    } else {
+        quote! {
+            f(&#with_static_ids_inner)
         }
     }
 }
@@ -160,29 +212,40 @@ fn add_additional_bounds_to_generic_params(mut generics: syn::Generics) -> syn::
     generics
 }
 
-fn struct_fields(fields: &syn::Fields) -> (Vec<&syn::Type>, Vec<syn::Member>) {
-    match fields {
-        syn::Fields::Named(ref fields) => fields
-            .named
-            .iter()
-            .map(|f| (&f.ty, syn::Member::Named(f.ident.clone().unwrap())))
-            .unzip(),
-        syn::Fields::Unnamed(ref fields) => fields
-            .unnamed
-            .iter()
-            .enumerate()
-            .map(|(i, f)| {
-                (
-                    &f.ty,
-                    syn::Member::Unnamed(syn::Index {
-                        index: i as u32,
-                        span: Span::call_site(),
-                    }),
-                )
-            })
-            .unzip(),
-        syn::Fields::Unit => (Vec::new(), Vec::new()),
+/// The fields of a struct, split into components and nested bundles marked with `#[bundle]`
+struct Fields<'a> {
+    components: FieldList<'a>,
+    bundles: FieldList<'a>,
+}
+
+#[derive(Default)]
+struct FieldList<'a> {
+    tys: Vec<&'a syn::Type>,
+    members: Vec<syn::Member>,
+}
+
+fn struct_fields(fields: &syn::Fields) -> Fields<'_> {
+    let mut result = Fields {
+        components: FieldList::default(),
+        bundles: FieldList::default(),
+    };
+    for (i, field) in fields.iter().enumerate() {
+        let member = match field.ident {
+            Some(ref ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index {
+                index: i as u32,
+                span: Span::call_site(),
+            }),
+        };
+        let list = if field.attrs.iter().any(|x| x.path.is_ident("bundle")) {
+            &mut result.bundles
+        } else {
+            &mut result.components
+        };
+        list.tys.push(&field.ty);
+        list.members.push(member);
     }
+    result
 }
 
 fn member_as_idents(members: &[syn::Member]) -> Vec<Cow<'_, syn::Ident>> {
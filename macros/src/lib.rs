@@ -20,6 +20,11 @@ use syn::{parse_macro_input, DeriveInput};
 /// polymorphic implementations for tuples, and can be convenient when combined with other derives
 /// like `serde::Deserialize`.
 ///
+/// Named, tuple, and unit structs are supported, including generic ones, with each field
+/// becoming one component. Fields marked `#[bundle]` must themselves be bundles, whose components
+/// are included in place of the field, so large bundles can be composed from smaller ones without
+/// running into the limit on tuple sizes.
+///
 /// # Example
 /// ```ignore
 /// #[derive(Bundle)]
@@ -31,8 +36,18 @@ use syn::{parse_macro_input, DeriveInput};
 /// let mut world = World::new();
 /// let e = world.spawn(Foo { x: 42, y: 'a' });
 /// assert_eq!(*world.get::<i32>(e).unwrap(), 42);
+///
+/// #[derive(Bundle)]
+/// struct Bar {
+///     #[bundle]
+///     foo: Foo,
+///     z: bool,
+/// }
+///
+/// let e = world.spawn(Bar { foo: Foo { x: 42, y: 'a' }, z: true });
+/// assert_eq!(*world.get::<char>(e).unwrap(), 'a');
 /// ```
-#[proc_macro_derive(Bundle)]
+#[proc_macro_derive(Bundle, attributes(bundle))]
 pub fn derive_bundle(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     match bundle::derive(input) {
@@ -109,9 +109,27 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
                 ::std::option::Option::Some(access)
             }
 
-            #[allow(unused_variables)]
-            fn borrow(archetype: &::hecs::Archetype) {
-                #(#fetches::borrow(archetype);)*
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn borrow(archetype: &::hecs::Archetype) -> ::std::result::Result<(), ::hecs::BorrowError> {
+                // Release everything acquired so far if any borrow fails
+                let mut result = ::std::result::Result::Ok(());
+                let mut acquired = 0;
+                #(
+                    if result.is_ok() {
+                        result = #fetches::borrow(archetype);
+                        acquired += result.is_ok() as usize;
+                    }
+                )*
+                if result.is_err() {
+                    let mut i = 0;
+                    #(
+                        if i < acquired {
+                            #fetches::release(archetype);
+                        }
+                        i += 1;
+                    )*
+                }
+                result
             }
 
             #[allow(unused_variables)]
@@ -128,6 +146,15 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
                 #(#fetches::release(archetype);)*
             }
 
+            #[allow(unused_variables, unused_mut)]
+            fn for_each_borrow(mut f: impl ::core::ops::FnMut(::core::any::TypeId, bool)) {
+                #(#fetches::for_each_borrow(&mut f);)*
+            }
+
+            fn for_each_requirement(mut f: impl ::core::ops::FnMut(::core::any::TypeId, bool)) {
+                #(#fetches::for_each_requirement(&mut f);)*
+            }
+
             #[allow(unused_variables)]
             unsafe fn get(&self, n: usize) -> Self::Item {
                 #ident {
@@ -136,6 +163,20 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
                     )*
                 }
             }
+
+            const FILTERS: bool = false #(|| <#fetches as ::hecs::Fetch<'a>>::FILTERS)*;
+
+            #[allow(unused_variables)]
+            #[inline(always)]
+            unsafe fn skip(&self, n: usize) -> bool {
+                false #(|| <#fetches as ::hecs::Fetch<'a>>::skip(&self.#fields, n))*
+            }
+
+            #[allow(unused_variables)]
+            #[inline(always)]
+            fn prefetch(&self, n: usize) {
+                #(<#fetches as ::hecs::Fetch<'a>>::prefetch(&self.#fields, n);)*
+            }
         }
     })
 }
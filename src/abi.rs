@@ -0,0 +1,354 @@
+//! FFI-safe access to a [`World`] for separately compiled plugins; requires the `abi` feature
+//!
+//! Rust makes no ABI guarantees between separately compiled binaries: `TypeId`s, the layout of
+//! [`World`], and the calling convention of its methods may all differ between a host and a
+//! plugin library loaded at runtime. Instead, the host wraps its world in an [`AbiHost`], whose
+//! [`AbiWorld`] handle is `#[repr(C)]` and dispatches to the host's copy of hecs through
+//! `extern "C"` functions. Component types are identified by [`StableId`]s assigned by the
+//! application and registered with an [`AbiRegistry`].
+//!
+//! Every type crossing the boundary is FFI-safe, so the interface can also be wrapped by crates
+//! such as `abi_stable`. Component types themselves must have the same layout on both sides,
+//! e.g. by being `#[repr(C)]`. Panics must not unwind across the boundary.
+//!
+//! # Example
+//! ```
+//! # use hecs::{*, abi::*};
+//! #[repr(C)]
+//! struct Health(u32);
+//! const HEALTH: StableId = StableId(1);
+//!
+//! // Compiled into the plugin
+//! extern "C" fn plugin_tick(mut world: AbiWorld<'_>) {
+//!     let e = world.spawn();
+//!     unsafe {
+//!         world.insert(e, HEALTH, Health(10));
+//!         world.for_each::<Health, _>(HEALTH, |_, health| health.0 -= 1);
+//!     }
+//! }
+//!
+//! // Compiled into the host
+//! let mut registry = AbiRegistry::new();
+//! registry.register::<Health>(HEALTH);
+//! let mut world = World::new();
+//! let mut host = AbiHost::new(&mut world, &registry);
+//! plugin_tick(host.handle());
+//! drop(host);
+//! let health = world.query::<&Health>().iter().map(|(_, x)| x.0).collect::<Vec<_>>();
+//! assert_eq!(health, [9]);
+//! ```
+
+use core::alloc::Layout;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr::{self, NonNull};
+
+use hashbrown::HashMap;
+
+use crate::archetype::TypeInfo;
+use crate::{CommandBuffer, Component, Entity, World};
+
+/// Identifier of a component type that is consistent across separately compiled binaries
+///
+/// Chosen by the application, e.g. as constants shared by the host and its plugins.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct StableId(pub u64);
+
+/// The component types an [`AbiHost`] exposes to plugins
+#[derive(Default)]
+pub struct AbiRegistry {
+    types: HashMap<StableId, TypeInfo>,
+}
+
+impl AbiRegistry {
+    /// Create a registry exposing no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expose `T` to plugins as `id`
+    ///
+    /// Panics if `id` is already registered to a different type.
+    pub fn register<T: Component>(&mut self, id: StableId) -> &mut Self {
+        let ty = TypeInfo::of::<T>();
+        let existing = *self.types.entry(id).or_insert(ty);
+        assert!(
+            existing.id() == ty.id(),
+            "{:?} already registered to another component type",
+            id
+        );
+        self
+    }
+}
+
+/// A [`World`] exposed to plugins through [`AbiWorld`] handles
+///
+/// Despawns deferred by plugins with [`AbiWorld::despawn_deferred`] are applied when the host is
+/// dropped.
+pub struct AbiHost<'a> {
+    world: &'a mut World,
+    registry: &'a AbiRegistry,
+    commands: CommandBuffer,
+}
+
+impl<'a> AbiHost<'a> {
+    /// Expose `world` with the component types in `registry`
+    pub fn new(world: &'a mut World, registry: &'a AbiRegistry) -> Self {
+        Self {
+            world,
+            registry,
+            commands: CommandBuffer::new(),
+        }
+    }
+
+    /// Construct a handle to pass to a plugin
+    pub fn handle(&mut self) -> AbiWorld<'_> {
+        AbiWorld {
+            host: NonNull::from(self).cast(),
+            vtable: &VTABLE,
+            _marker: PhantomData,
+        }
+    }
+
+    fn ty(&self, id: StableId) -> Option<TypeInfo> {
+        self.registry.types.get(&id).copied()
+    }
+}
+
+impl Drop for AbiHost<'_> {
+    fn drop(&mut self) {
+        self.commands.run_on(self.world);
+    }
+}
+
+/// FFI-safe handle to a host's [`World`], obtained from [`AbiHost::handle`]
+///
+/// Entities and components are exchanged as raw data; methods that transfer components are
+/// `unsafe` because the host cannot verify that a [`StableId`] names the expected Rust type, only
+/// that its layout matches.
+#[repr(C)]
+pub struct AbiWorld<'a> {
+    host: NonNull<c_void>,
+    vtable: &'static VTable,
+    _marker: PhantomData<&'a mut AbiHost<'a>>,
+}
+
+impl AbiWorld<'_> {
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        unsafe { (self.vtable.contains)(self.host, entity.to_bits()) }
+    }
+
+    /// Create an entity without components
+    pub fn spawn(&mut self) -> Entity {
+        Entity::from_bits(unsafe { (self.vtable.spawn)(self.host) }).unwrap()
+    }
+
+    /// Destroy `entity` and all its components, returning whether it existed
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        unsafe { (self.vtable.despawn)(self.host, entity.to_bits()) }
+    }
+
+    /// Destroy `entity` once the host has regained control
+    ///
+    /// Entity handles held by the host remain valid until the [`AbiHost`] is dropped.
+    pub fn despawn_deferred(&mut self, entity: Entity) {
+        unsafe { (self.vtable.despawn_deferred)(self.host, entity.to_bits()) }
+    }
+
+    /// Access the `id` component of `entity`, if both exist
+    ///
+    /// # Safety
+    ///
+    /// `id` must be registered to a component type with the same representation as `T`.
+    pub unsafe fn get<T>(&mut self, entity: Entity, id: StableId) -> Option<&mut T> {
+        self.check::<T>(id);
+        let ptr = (self.vtable.get)(self.host, entity.to_bits(), id);
+        ptr.cast::<T>().as_mut()
+    }
+
+    /// Add `component` to `entity` as its `id` component, replacing any existing one
+    ///
+    /// Returns `false`, dropping `component`, if `entity` does not exist.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be registered to a component type with the same representation as `T`.
+    pub unsafe fn insert<T>(&mut self, entity: Entity, id: StableId, component: T) -> bool {
+        self.check::<T>(id);
+        // Ownership passes to the host regardless of success
+        let mut component = ManuallyDrop::new(component);
+        let ptr = (&mut *component as *mut T).cast::<u8>();
+        (self.vtable.insert)(self.host, entity.to_bits(), id, ptr)
+    }
+
+    /// Remove and drop the `id` component of `entity`, returning whether it existed
+    pub fn remove(&mut self, entity: Entity, id: StableId) -> bool {
+        unsafe { (self.vtable.remove)(self.host, entity.to_bits(), id) }
+    }
+
+    /// Invoke `f` on every entity with an `id` component
+    ///
+    /// # Safety
+    ///
+    /// `id` must be registered to a component type with the same representation as `T`.
+    pub unsafe fn for_each<T, F>(&mut self, id: StableId, mut f: F)
+    where
+        F: FnMut(Entity, &mut T),
+    {
+        unsafe extern "C" fn visit<T, F: FnMut(Entity, &mut T)>(
+            f: *mut c_void,
+            entity: u64,
+            component: *mut u8,
+        ) {
+            (*f.cast::<F>())(
+                Entity::from_bits(entity).unwrap(),
+                &mut *component.cast::<T>(),
+            );
+        }
+
+        self.check::<T>(id);
+        let f = (&mut f as *mut F).cast::<c_void>();
+        (self.vtable.for_each)(self.host, id, visit::<T, F>, f);
+    }
+
+    /// Panic unless `id` is registered to a type with the same layout as `T`
+    fn check<T>(&self, id: StableId) {
+        let layout = Layout::new::<T>();
+        let mut size = 0;
+        let mut align = 0;
+        unsafe {
+            assert!(
+                (self.vtable.layout)(self.host, id, &mut size, &mut align),
+                "{:?} is not registered",
+                id
+            );
+        }
+        assert!(
+            size == layout.size() && align == layout.align(),
+            "{:?} is registered to a type with a different layout",
+            id
+        );
+    }
+}
+
+#[repr(C)]
+struct VTable {
+    layout: unsafe extern "C" fn(NonNull<c_void>, StableId, &mut usize, &mut usize) -> bool,
+    contains: unsafe extern "C" fn(NonNull<c_void>, u64) -> bool,
+    spawn: unsafe extern "C" fn(NonNull<c_void>) -> u64,
+    despawn: unsafe extern "C" fn(NonNull<c_void>, u64) -> bool,
+    despawn_deferred: unsafe extern "C" fn(NonNull<c_void>, u64),
+    get: unsafe extern "C" fn(NonNull<c_void>, u64, StableId) -> *mut u8,
+    insert: unsafe extern "C" fn(NonNull<c_void>, u64, StableId, *mut u8) -> bool,
+    remove: unsafe extern "C" fn(NonNull<c_void>, u64, StableId) -> bool,
+    for_each: unsafe extern "C" fn(
+        NonNull<c_void>,
+        StableId,
+        unsafe extern "C" fn(*mut c_void, u64, *mut u8),
+        *mut c_void,
+    ),
+}
+
+static VTABLE: VTable = VTable {
+    layout: host_layout,
+    contains: host_contains,
+    spawn: host_spawn,
+    despawn: host_despawn,
+    despawn_deferred: host_despawn_deferred,
+    get: host_get,
+    insert: host_insert,
+    remove: host_remove,
+    for_each: host_for_each,
+};
+
+unsafe fn host<'a>(ptr: NonNull<c_void>) -> &'a mut AbiHost<'a> {
+    &mut *ptr.cast::<AbiHost<'a>>().as_ptr()
+}
+
+unsafe extern "C" fn host_layout(
+    host_ptr: NonNull<c_void>,
+    id: StableId,
+    size: &mut usize,
+    align: &mut usize,
+) -> bool {
+    match host(host_ptr).ty(id) {
+        Some(ty) => {
+            *size = ty.layout().size();
+            *align = ty.layout().align();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Decode an entity passed by a plugin, mapping invalid bits to a handle no world contains
+fn entity_from_bits(bits: u64) -> Entity {
+    Entity::from_bits(bits).unwrap_or(Entity::DANGLING)
+}
+
+unsafe extern "C" fn host_contains(host_ptr: NonNull<c_void>, entity: u64) -> bool {
+    host(host_ptr).world.contains(entity_from_bits(entity))
+}
+
+unsafe extern "C" fn host_spawn(host_ptr: NonNull<c_void>) -> u64 {
+    host(host_ptr).world.spawn(()).to_bits()
+}
+
+unsafe extern "C" fn host_despawn(host_ptr: NonNull<c_void>, entity: u64) -> bool {
+    host(host_ptr)
+        .world
+        .despawn(entity_from_bits(entity))
+        .is_ok()
+}
+
+unsafe extern "C" fn host_despawn_deferred(host_ptr: NonNull<c_void>, entity: u64) {
+    host(host_ptr).commands.despawn(entity_from_bits(entity));
+}
+
+unsafe extern "C" fn host_get(host_ptr: NonNull<c_void>, entity: u64, id: StableId) -> *mut u8 {
+    let host = host(host_ptr);
+    host.ty(id)
+        .and_then(|ty| host.world.get_dynamic_mut(entity_from_bits(entity), ty))
+        .map_or(ptr::null_mut(), |x| x.as_ptr())
+}
+
+unsafe extern "C" fn host_insert(
+    host_ptr: NonNull<c_void>,
+    entity: u64,
+    id: StableId,
+    component: *mut u8,
+) -> bool {
+    let host = host(host_ptr);
+    let ty = host.ty(id).expect("unregistered component type");
+    host.world
+        .insert_dynamic(entity_from_bits(entity), ty, component)
+        .is_ok()
+}
+
+unsafe extern "C" fn host_remove(host_ptr: NonNull<c_void>, entity: u64, id: StableId) -> bool {
+    let host = host(host_ptr);
+    match host.ty(id) {
+        Some(ty) => host
+            .world
+            .remove_dynamic(entity_from_bits(entity), ty)
+            .is_ok(),
+        None => false,
+    }
+}
+
+unsafe extern "C" fn host_for_each(
+    host_ptr: NonNull<c_void>,
+    id: StableId,
+    visit: unsafe extern "C" fn(*mut c_void, u64, *mut u8),
+    data: *mut c_void,
+) {
+    let host = host(host_ptr);
+    if let Some(ty) = host.ty(id) {
+        host.world.for_each_dynamic(ty, |entity, component| {
+            visit(data, entity.to_bits(), component.as_ptr())
+        });
+    }
+}
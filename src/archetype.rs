@@ -14,20 +14,48 @@ use crate::{
 use core::any::{type_name, TypeId};
 use core::cell::UnsafeCell;
 use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
-use core::ops::Deref;
+use core::ops::{Deref, DerefMut};
 use core::ptr::{self, NonNull};
+#[cfg(feature = "stats")]
+use core::sync::atomic::AtomicU64;
+#[cfg(any(feature = "track-borrows", feature = "stats"))]
+use core::sync::atomic::Ordering;
 use core::{fmt, mem, slice};
+#[cfg(feature = "track-borrows")]
+use core::{panic::Location, sync::atomic::AtomicPtr};
 
 use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
 
-use crate::borrow::AtomicBorrow;
+#[cfg(feature = "track-borrows")]
+use crate::borrow::BorrowLabel;
+use crate::borrow::{AtomicBorrow, BorrowError};
 use crate::query::Fetch;
-use crate::{align, Access, Component, Query};
+use crate::type_mask::TypeMask;
+use crate::{align, Access, Component, Query, Tick, WorldConfig};
 
 /// A collection of entities having the same component types
 ///
-/// Accessing `Archetype`s is only required for complex dynamic scheduling. To manipulate entities,
-/// go through the `World`.
+/// Accessing `Archetype`s is only required for complex dynamic scheduling, or for processing
+/// whole columns of components at once, e.g. in a lighting engine that operates on slices. Each
+/// archetype exposes the raw [`ids`](Self::ids) of its entities alongside columns borrowed with
+/// [`get`](Self::get) and [`get_mut`](Self::get_mut), which are parallel slices subject to the same
+/// dynamic borrow checks as queries. To manipulate entities, go through the `World`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1u8, true));
+/// world.spawn((2u8,));
+/// for archetype in world.archetypes().filter(|x| x.has::<u8>()) {
+///     let light = archetype.get::<u8>().unwrap();
+///     for (&id, &level) in archetype.ids().iter().zip(light.iter()) {
+///         if id == a.id() {
+///             assert_eq!(level, 1);
+///         }
+///     }
+/// }
+/// ```
 pub struct Archetype {
     types: Vec<TypeInfo>,
     state: TypeIdMap<TypeState>,
@@ -37,48 +65,80 @@ pub struct Archetype {
     // containing the `Archetype` exist
     data: UnsafeCell<NonNull<u8>>,
     data_size: usize,
+    /// When each component was last changed, as of the last `record_ticks`, by row then column
+    ticks: Vec<Tick>,
+    /// Minimum number of entities to make room for when growing
+    min_growth: u32,
+    /// Entities that entered and exited since the last tick, if tracked
+    membership: Option<Membership>,
+    /// Archetypes reached by inserting or removing bundles, keyed by `DynamicBundle::key`
+    insert_edges: TypeIdMap<u32>,
+    remove_edges: TypeIdMap<u32>,
+    /// Set of `types`, as indexed by the world
+    mask: TypeMask,
 }
 
+// Safety: `data` owns components, which are `Send + Sync`. References into it are only handed out
+// through `&Archetype` under the per-column borrow flags, which are atomic, or through
+// `&mut Archetype`.
+unsafe impl Send for Archetype {}
+unsafe impl Sync for Archetype {}
+
 impl Archetype {
     fn assert_type_info(types: &[TypeInfo]) {
         types.windows(2).for_each(|x| match x[0].cmp(&x[1]) {
             core::cmp::Ordering::Less => (),
-            #[cfg(debug_assertions)]
             core::cmp::Ordering::Equal => panic!(
                 "attempted to allocate entity with duplicate {} components; \
                  each type must occur at most once!",
                 x[0].type_name
             ),
-            #[cfg(not(debug_assertions))]
-            core::cmp::Ordering::Equal => panic!(
-                "attempted to allocate entity with duplicate components; \
-                 each type must occur at most once!"
-            ),
             core::cmp::Ordering::Greater => panic!("type info is unsorted"),
         });
     }
 
     pub(crate) fn new(types: Vec<TypeInfo>) -> Self {
+        Self::with_config(types, &WorldConfig::default())
+    }
+
+    pub(crate) fn with_config(types: Vec<TypeInfo>, config: &WorldConfig) -> Self {
         Self::assert_type_info(&types);
-        Self {
+        let mut this = Self {
             types,
             state: HashMap::default(),
             entities: Box::new([]),
             len: 0,
             data: UnsafeCell::new(NonNull::dangling()),
             data_size: 0,
+            ticks: Vec::new(),
+            min_growth: 1,
+            membership: None,
+            insert_edges: HashMap::default(),
+            remove_edges: HashMap::default(),
+            mask: TypeMask::default(),
+        };
+        this.configure(config);
+        this
+    }
+
+    /// Adopt the tunables of the world this archetype belongs to
+    pub(crate) fn configure(&mut self, config: &WorldConfig) {
+        self.min_growth = config.archetype_min_capacity.max(1);
+        if config.track_membership != self.membership.is_some() {
+            self.membership = config.track_membership.then(Membership::default);
         }
     }
 
     pub(crate) fn clear(&mut self) {
+        if let Some(ref mut membership) = self.membership {
+            membership
+                .exited
+                .extend_from_slice(&self.entities[..self.len as usize]);
+        }
         for ty in &self.types {
             for index in 0..self.len {
                 unsafe {
-                    let removed = self
-                        .get_dynamic(ty.id, ty.layout.size(), index)
-                        .unwrap()
-                        .as_ptr();
-                    (ty.drop)(removed);
+                    (ty.drop)(self.component(ty, index));
                 }
             }
         }
@@ -92,48 +152,134 @@ impl Archetype {
 
     /// Whether this archetype contains components with the type identified by `id`
     pub fn has_dynamic(&self, id: TypeId) -> bool {
+        // Column state is only set up once storage is first allocated
         self.state.contains_key(&id)
+            || (self.state.is_empty() && self.types.iter().any(|x| x.id == id))
+    }
+
+    pub(crate) fn mask(&self) -> &TypeMask {
+        &self.mask
+    }
+
+    pub(crate) fn set_mask(&mut self, mask: TypeMask) {
+        self.mask = mask;
     }
 
     pub(crate) fn get_base<T: Component>(&self) -> Option<NonNull<T>> {
+        let state = self.state.get(&TypeId::of::<T>())?;
+        Some(unsafe {
+            NonNull::new_unchecked((*self.data.get()).as_ptr().add(state.offset).cast::<T>())
+        })
+    }
+
+    /// Pointer to the change flags of the `T` column, if present
+    pub(crate) fn get_changed_base<T: Component>(&self) -> Option<NonNull<bool>> {
         let state = self.state.get(&TypeId::of::<T>())?;
         Some(unsafe {
             NonNull::new_unchecked(
-                (*self.data.get()).as_ptr().add(state.offset).cast::<T>() as *mut T
+                (*self.data.get())
+                    .as_ptr()
+                    .add(state.changed)
+                    .cast::<bool>(),
             )
         })
     }
 
+    /// Pointer to the addition flags of the `T` column, if present
+    pub(crate) fn get_added_base<T: Component>(&self) -> Option<NonNull<bool>> {
+        let state = self.state.get(&TypeId::of::<T>())?;
+        Some(unsafe {
+            NonNull::new_unchecked((*self.data.get()).as_ptr().add(state.added).cast::<bool>())
+        })
+    }
+
     /// Get the `T` components of these entities, if present
     ///
-    /// Useful for efficient serialization.
+    /// Useful for efficient serialization, or custom iteration alongside [`ids`](Self::ids).
+    ///
+    /// Panics if the components are already uniquely borrowed.
+    #[doc(alias = "column")]
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub fn get<T: Component>(&self) -> Option<ColumnRef<'_, T>> {
         let ptr = self.get_base::<T>()?;
-        let column = unsafe { slice::from_raw_parts_mut(ptr.as_ptr(), self.len as usize) };
+        // Borrow first, so no reference to the column exists while it may be uniquely borrowed
         self.borrow::<T>();
+        let column = unsafe { slice::from_raw_parts(ptr.as_ptr(), self.len as usize) };
         Some(ColumnRef {
             archetype: self,
             column,
         })
     }
 
+    /// Uniquely borrow the `T` components of these entities, if present
+    ///
+    /// Every component in the column is flagged as changed, as with a `&mut T` query.
+    ///
+    /// Panics if the components are already borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, true));
+    /// world.spawn((2, false));
+    /// for archetype in world.archetypes() {
+    ///     if let Some(mut column) = archetype.get_mut::<i32>() {
+    ///         for x in column.iter_mut() {
+    ///             *x *= 10;
+    ///         }
+    ///     }
+    /// }
+    /// let mut values = world.query::<&i32>().iter().map(|(_, &x)| x).collect::<Vec<_>>();
+    /// values.sort_unstable();
+    /// assert_eq!(values, [10, 20]);
+    /// ```
+    #[doc(alias = "column_mut")]
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn get_mut<T: Component>(&self) -> Option<ColumnRefMut<'_, T>> {
+        let ptr = self.get_base::<T>()?;
+        self.borrow_mut::<T>();
+        let column = unsafe { slice::from_raw_parts_mut(ptr.as_ptr(), self.len as usize) };
+        unsafe {
+            ptr::write_bytes(
+                self.get_changed_base::<T>().unwrap().as_ptr(),
+                1,
+                self.len as usize,
+            );
+        }
+        Some(ColumnRefMut {
+            archetype: self,
+            column,
+        })
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub(crate) fn borrow<T: Component>(&self) {
-        if self
-            .state
-            .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow())
-        {
-            panic!("{} already borrowed uniquely", type_name::<T>());
+        if let Err(e) = self.try_borrow::<T>() {
+            e.panic();
         }
     }
 
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub(crate) fn borrow_mut<T: Component>(&self) {
-        if self
-            .state
-            .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow_mut())
-        {
-            panic!("{} already borrowed", type_name::<T>());
+        if let Err(e) = self.try_borrow_mut::<T>() {
+            e.panic();
+        }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) fn try_borrow<T: Component>(&self) -> Result<(), BorrowError> {
+        match self.state.get(&TypeId::of::<T>()) {
+            Some(x) => x.borrow(type_name::<T>()),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) fn try_borrow_mut<T: Component>(&self) -> Result<(), BorrowError> {
+        match self.state.get(&TypeId::of::<T>()) {
+            Some(x) => x.borrow_mut(type_name::<T>()),
+            None => Ok(()),
         }
     }
 
@@ -145,7 +291,47 @@ impl Archetype {
 
     pub(crate) fn release_mut<T: Component>(&self) {
         if let Some(x) = self.state.get(&TypeId::of::<T>()) {
-            x.borrow.release_mut();
+            x.release_mut();
+        }
+    }
+
+    /// Like `borrow`, for the component type identified by `ty`
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) fn borrow_dynamic(&self, ty: TypeId) {
+        if let Some(x) = self.state.get(&ty) {
+            if let Err(e) = x.borrow("component") {
+                e.panic();
+            }
+        }
+    }
+
+    /// Like `release`, for the component type identified by `ty`
+    pub(crate) fn release_dynamic(&self, ty: TypeId) {
+        if let Some(x) = self.state.get(&ty) {
+            x.borrow.release();
+        }
+    }
+
+    /// The component type of a column that's currently borrowed, if any
+    pub(crate) fn borrowed_type(&self) -> Option<&TypeInfo> {
+        self.types
+            .iter()
+            .find(|ty| !self.state[&ty.id()].borrow.is_free())
+    }
+
+    /// Total borrows of this archetype's columns since the counts were last reset
+    #[cfg(feature = "stats")]
+    pub(crate) fn borrow_count(&self) -> u64 {
+        self.state
+            .values()
+            .map(|x| x.borrows.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn reset_borrow_count(&mut self) {
+        for x in self.state.values_mut() {
+            *x.borrows.get_mut() = 0;
         }
     }
 
@@ -174,9 +360,13 @@ impl Archetype {
         self.entities[index as usize]
     }
 
+    /// Assign the ID of an entity whose components were stored without one, e.g. by `merge`
     #[inline]
     pub(crate) fn set_entity_id(&mut self, index: usize, id: u32) {
         self.entities[index] = id;
+        if let Some(ref mut membership) = self.membership {
+            membership.entered.push(id);
+        }
     }
 
     pub(crate) fn types(&self) -> &[TypeInfo] {
@@ -198,6 +388,150 @@ impl Archetype {
         self.types.iter().map(|typeinfo| typeinfo.id)
     }
 
+    /// Pointer to the change flag of the `ty` component of the entity at `index`
+    ///
+    /// `index` must be in-bounds or just past the end
+    pub(crate) unsafe fn changed_dynamic(&self, ty: TypeId, index: u32) -> Option<NonNull<bool>> {
+        debug_assert!(index <= self.len);
+        Some(NonNull::new_unchecked(
+            (*self.data.get())
+                .as_ptr()
+                .add(self.state.get(&ty)?.changed + index as usize)
+                .cast::<bool>(),
+        ))
+    }
+
+    /// Pointer to the addition flag of the `ty` component of the entity at `index`
+    ///
+    /// `index` must be in-bounds or just past the end
+    pub(crate) unsafe fn added_dynamic(&self, ty: TypeId, index: u32) -> Option<NonNull<bool>> {
+        debug_assert!(index <= self.len);
+        Some(NonNull::new_unchecked(
+            (*self.data.get())
+                .as_ptr()
+                .add(self.state.get(&ty)?.added + index as usize)
+                .cast::<bool>(),
+        ))
+    }
+
+    /// Whether the `T` component of the entity at `index` has been flagged as changed
+    ///
+    /// `index` must be in-bounds.
+    pub(crate) unsafe fn is_changed<T: Component>(&self, index: u32) -> Option<bool> {
+        Some(*self.changed_dynamic(TypeId::of::<T>(), index)?.as_ptr())
+    }
+
+    /// `index` must be in-bounds
+    pub(crate) unsafe fn set_changed(&mut self, ty: TypeId, index: u32, changed: bool) {
+        if let Some(flag) = self.changed_dynamic(ty, index) {
+            *flag.as_ptr() = changed;
+        }
+    }
+
+    /// Exchange the storage of the `a` and `b` columns, if both exist, flagging both as changed
+    ///
+    /// # Safety
+    ///
+    /// The component types identified by `a` and `b` must have the same layout, and every value
+    /// of either must be a valid value of the other.
+    pub(crate) unsafe fn swap_columns(&mut self, a: TypeId, b: TypeId) {
+        let (a_offset, b_offset) = match (self.state.get(&a), self.state.get(&b)) {
+            (Some(x), Some(y)) => (x.offset, y.offset),
+            _ => return,
+        };
+        self.state.get_mut(&a).unwrap().offset = b_offset;
+        self.state.get_mut(&b).unwrap().offset = a_offset;
+        for &ty in &[a, b] {
+            let base = self.changed_dynamic(ty, 0).unwrap();
+            ptr::write_bytes(base.as_ptr(), 1, self.len as usize);
+        }
+    }
+
+    /// When the `ty` component at `index`, which must be in-bounds, was last changed, as of the
+    /// last `record_ticks`
+    pub(crate) fn tick(&self, ty: TypeId, index: u32) -> Option<Tick> {
+        let column = self.state.get(&ty)?.column;
+        Some(self.ticks[index as usize * self.types.len() + column])
+    }
+
+    /// `index` must be in-bounds
+    pub(crate) fn set_tick(&mut self, ty: TypeId, index: u32, tick: Tick) {
+        if let Some(state) = self.state.get(&ty) {
+            self.ticks[index as usize * self.types.len() + state.column] = tick;
+        }
+    }
+
+    /// Forget when every component was last changed, as if it never had been
+    pub(crate) fn reset_ticks(&mut self) {
+        for tick in &mut self.ticks {
+            *tick = Tick::default();
+        }
+    }
+
+    /// Stamp every component flagged as changed with `tick`
+    ///
+    /// Must be called before the flags are reset.
+    pub(crate) fn record_ticks(&mut self, tick: Tick) {
+        let n = self.types.len();
+        for (column, ty) in self.types.iter().enumerate() {
+            let flags = match unsafe { self.changed_dynamic(ty.id, 0) } {
+                Some(x) => unsafe { slice::from_raw_parts(x.as_ptr(), self.len as usize) },
+                None => continue,
+            };
+            for (row, _) in flags.iter().enumerate().filter(|x| *x.1) {
+                self.ticks[row * n + column] = tick;
+            }
+        }
+    }
+
+    /// Reset the change flags of every component
+    pub(crate) fn clear_changed(&mut self) {
+        for ty in &self.types {
+            unsafe {
+                if let Some(base) = self.changed_dynamic(ty.id, 0) {
+                    ptr::write_bytes(base.as_ptr(), 0, self.len as usize);
+                }
+            }
+        }
+    }
+
+    /// The archetype reached by inserting bundles identified by `key`, if known
+    pub(crate) fn insert_edge(&self, key: TypeId) -> Option<u32> {
+        self.insert_edges.get(&key).copied()
+    }
+
+    pub(crate) fn set_insert_edge(&mut self, key: TypeId, target: u32) {
+        self.insert_edges.insert(key, target);
+    }
+
+    /// The archetype reached by removing bundles identified by `key`, if known
+    pub(crate) fn remove_edge(&self, key: TypeId) -> Option<u32> {
+        self.remove_edges.get(&key).copied()
+    }
+
+    pub(crate) fn set_remove_edge(&mut self, key: TypeId, target: u32) {
+        self.remove_edges.insert(key, target);
+    }
+
+    /// Forget which entities entered and exited, if tracked
+    pub(crate) fn clear_membership(&mut self) {
+        if let Some(ref mut membership) = self.membership {
+            membership.entered.clear();
+            membership.exited.clear();
+        }
+    }
+
+    /// Reset the addition flags of every component
+    pub(crate) fn clear_added(&mut self) {
+        for ty in &self.types {
+            unsafe {
+                if let Some(base) = self.added_dynamic(ty.id, 0) {
+                    ptr::write_bytes(base.as_ptr(), 0, self.len as usize);
+                }
+            }
+        }
+    }
+
     /// `index` must be in-bounds or just past the end
     pub(crate) unsafe fn get_dynamic(
         &self,
@@ -214,14 +548,29 @@ impl Archetype {
         ))
     }
 
+    /// `index` must be in-bounds
+    pub(crate) unsafe fn set_added(&mut self, ty: TypeId, index: u32, added: bool) {
+        if let Some(flag) = self.added_dynamic(ty, index) {
+            *flag.as_ptr() = added;
+        }
+    }
+
     /// Every type must be written immediately after this call
     pub(crate) unsafe fn allocate(&mut self, id: u32) -> u32 {
         if self.len as usize == self.entities.len() {
-            self.grow(self.len.max(64));
+            self.grow(self.len.max(self.min_growth));
         }
 
         self.entities[self.len as usize] = id;
         self.len += 1;
+        if let Some(ref mut membership) = self.membership {
+            membership.entered.push(id);
+        }
+        // Newly written components are considered changed and added
+        for ty in &self.types {
+            *self.changed_dynamic(ty.id, self.len - 1).unwrap().as_ptr() = true;
+            *self.added_dynamic(ty.id, self.len - 1).unwrap().as_ptr() = true;
+        }
         self.len - 1
     }
 
@@ -236,25 +585,73 @@ impl Archetype {
         }
     }
 
-    pub(crate) fn capacity(&self) -> u32 {
+    /// Number of entities this archetype can hold without reallocating
+    pub fn capacity(&self) -> u32 {
         self.entities.len() as u32
     }
 
+    /// Bytes allocated for this archetype's components, their change flags, addition flags, and
+    /// ticks, and the IDs of its entities
+    ///
+    /// Proportional to `capacity`, not `len`, so archetypes that once held many more entities
+    /// than they do now stand out.
+    pub fn memory_usage(&self) -> usize {
+        self.data_size
+            + mem::size_of_val(&*self.entities)
+            + self.ticks.capacity() * mem::size_of::<Tick>()
+    }
+
     fn grow(&mut self, increment: u32) {
+        self.realloc(self.entities.len() + increment as usize);
+    }
+
+    /// Release memory if much more is allocated than needed, returning whether any was released
+    ///
+    /// Keeps room for as many entities again as are currently stored, to avoid growing again soon.
+    pub(crate) fn shrink(&mut self) -> bool {
+        let len = self.len as usize;
+        let target = if len == 0 {
+            0
+        } else {
+            (2 * len).max(self.min_growth as usize)
+        };
+        if self.entities.len() < 4 * len || self.entities.len() <= target {
+            return false;
+        }
+        self.realloc(target);
+        true
+    }
+
+    /// Release all memory not needed by the entities currently stored
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if self.entities.len() > self.len as usize {
+            self.realloc(self.len as usize);
+        }
+    }
+
+    /// Move storage to a new allocation with room for exactly `new_cap` entities
+    fn realloc(&mut self, new_cap: usize) {
+        debug_assert!(new_cap >= self.len as usize);
         unsafe {
             let old_count = self.len as usize;
-            let new_cap = self.entities.len() + increment as usize;
             let mut new_entities = vec![!0; new_cap].into_boxed_slice();
             new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
             self.entities = new_entities;
 
             let old_data_size = mem::replace(&mut self.data_size, 0);
             let mut state = HashMap::with_capacity_and_hasher(self.types.len(), Default::default());
-            for ty in &self.types {
+            for (column, ty) in self.types.iter().enumerate() {
                 self.data_size = align(self.data_size, ty.layout.align());
-                state.insert(ty.id, TypeState::new(self.data_size));
+                state.insert(ty.id, TypeState::new(self.data_size, column));
                 self.data_size += ty.layout.size() * new_cap;
             }
+            // Change and addition flags are stored after all component data to avoid padding
+            for ty in &self.types {
+                let state = state.get_mut(&ty.id).unwrap();
+                state.changed = self.data_size;
+                state.added = self.data_size + new_cap;
+                self.data_size += 2 * new_cap;
+            }
             let new_data = if self.data_size == 0 {
                 NonNull::dangling()
             } else {
@@ -267,15 +664,40 @@ impl Archetype {
                 ))
                 .unwrap()
             };
+            for ty in &self.types {
+                // Rows that become live without `allocate`, as in `ColumnBatch`, count as changed
+                // and added. The two flag arrays of a type are adjacent.
+                let new_changed = state.get(&ty.id).unwrap().changed;
+                ptr::write_bytes(
+                    new_data.as_ptr().add(new_changed + old_count),
+                    1,
+                    new_cap - old_count,
+                );
+                ptr::write_bytes(
+                    new_data.as_ptr().add(new_changed + new_cap + old_count),
+                    1,
+                    new_cap - old_count,
+                );
+            }
             if old_data_size != 0 {
                 for ty in &self.types {
-                    let old_off = self.state.get(&ty.id).unwrap().offset;
-                    let new_off = state.get(&ty.id).unwrap().offset;
+                    let old = self.state.get(&ty.id).unwrap();
+                    let new = state.get(&ty.id).unwrap();
                     ptr::copy_nonoverlapping(
-                        (*self.data.get()).as_ptr().add(old_off),
-                        new_data.as_ptr().add(new_off),
+                        (*self.data.get()).as_ptr().add(old.offset),
+                        new_data.as_ptr().add(new.offset),
                         ty.layout.size() * old_count,
                     );
+                    ptr::copy_nonoverlapping(
+                        (*self.data.get()).as_ptr().add(old.changed),
+                        new_data.as_ptr().add(new.changed),
+                        old_count,
+                    );
+                    ptr::copy_nonoverlapping(
+                        (*self.data.get()).as_ptr().add(old.added),
+                        new_data.as_ptr().add(new.added),
+                        old_count,
+                    );
                 }
                 dealloc(
                     (*self.data.get()).as_ptr().cast(),
@@ -289,27 +711,31 @@ impl Archetype {
             self.data = UnsafeCell::new(new_data);
             self.state = state;
         }
+        let ticks = new_cap * self.types.len();
+        if ticks < self.ticks.len() {
+            self.ticks.truncate(ticks);
+            self.ticks.shrink_to_fit();
+        } else {
+            self.ticks.reserve_exact(ticks - self.ticks.len());
+            self.ticks.resize(ticks, Tick::default());
+        }
     }
 
     /// Returns the ID of the entity moved into `index`, if any
     pub(crate) unsafe fn remove(&mut self, index: u32) -> Option<u32> {
+        self.note_exit(index);
         let last = self.len - 1;
         for ty in &self.types {
-            let removed = self
-                .get_dynamic(ty.id, ty.layout.size(), index)
-                .unwrap()
-                .as_ptr();
+            let removed = self.component(ty, index);
             (ty.drop)(removed);
             if index != last {
-                ptr::copy_nonoverlapping(
-                    self.get_dynamic(ty.id, ty.layout.size(), last)
-                        .unwrap()
-                        .as_ptr(),
-                    removed,
-                    ty.layout.size(),
-                );
+                if ty.layout.size() != 0 {
+                    ptr::copy_nonoverlapping(self.component(ty, last), removed, ty.layout.size());
+                }
+                self.move_changed(ty.id, last, index);
             }
         }
+        self.move_ticks(last, index);
         self.len = last;
         if index != last {
             self.entities[index as usize] = self.entities[last as usize];
@@ -319,29 +745,66 @@ impl Archetype {
         }
     }
 
+    /// Address of the component of type `ty`, which must be stored here, at `index`
+    ///
+    /// Zero-sized components, such as markers, occupy no storage, so they're found without a
+    /// lookup.
+    unsafe fn component(&self, ty: &TypeInfo, index: u32) -> *mut u8 {
+        if ty.layout.size() == 0 {
+            return ty.layout.align() as *mut u8;
+        }
+        self.get_dynamic(ty.id, ty.layout.size(), index)
+            .unwrap()
+            .as_ptr()
+    }
+
+    fn note_exit(&mut self, index: u32) {
+        if let Some(ref mut membership) = self.membership {
+            membership.exited.push(self.entities[index as usize]);
+        }
+    }
+
+    /// Copy the change and addition flags of `ty` from row `src` to row `dst`
+    unsafe fn move_changed(&self, ty: TypeId, src: u32, dst: u32) {
+        let flag = *self.changed_dynamic(ty, src).unwrap().as_ptr();
+        *self.changed_dynamic(ty, dst).unwrap().as_ptr() = flag;
+        let flag = *self.added_dynamic(ty, src).unwrap().as_ptr();
+        *self.added_dynamic(ty, dst).unwrap().as_ptr() = flag;
+    }
+
+    /// Copy the ticks of every component from row `src` to row `dst`
+    fn move_ticks(&mut self, src: u32, dst: u32) {
+        if src != dst {
+            let n = self.types.len();
+            let src = src as usize * n;
+            self.ticks.copy_within(src..src + n, dst as usize * n);
+        }
+    }
+
     /// Returns the ID of the entity moved into `index`, if any
+    ///
+    /// `f` is passed each component's pointer, type, size, change flag, addition flag, and tick.
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
-        mut f: impl FnMut(*mut u8, TypeId, usize),
+        mut f: impl FnMut(*mut u8, TypeId, usize, bool, bool, Tick),
     ) -> Option<u32> {
+        self.note_exit(index);
         let last = self.len - 1;
-        for ty in &self.types {
-            let moved = self
-                .get_dynamic(ty.id, ty.layout.size(), index)
-                .unwrap()
-                .as_ptr();
-            f(moved, ty.id(), ty.layout().size());
+        for (column, ty) in self.types.iter().enumerate() {
+            let moved = self.component(ty, index);
+            let changed = *self.changed_dynamic(ty.id, index).unwrap().as_ptr();
+            let added = *self.added_dynamic(ty.id, index).unwrap().as_ptr();
+            let tick = self.ticks[index as usize * self.types.len() + column];
+            f(moved, ty.id(), ty.layout().size(), changed, added, tick);
             if index != last {
-                ptr::copy_nonoverlapping(
-                    self.get_dynamic(ty.id, ty.layout.size(), last)
-                        .unwrap()
-                        .as_ptr(),
-                    moved,
-                    ty.layout.size(),
-                );
+                if ty.layout.size() != 0 {
+                    ptr::copy_nonoverlapping(self.component(ty, last), moved, ty.layout.size());
+                }
+                self.move_changed(ty.id, last, index);
             }
         }
+        self.move_ticks(last, index);
         self.len -= 1;
         if index != last {
             self.entities[index as usize] = self.entities[last as usize];
@@ -358,12 +821,16 @@ impl Archetype {
         size: usize,
         index: u32,
     ) {
-        let ptr = self
-            .get_dynamic(ty, size, index)
-            .unwrap()
-            .as_ptr()
-            .cast::<u8>();
-        ptr::copy_nonoverlapping(component, ptr, size);
+        // Zero-sized components have nothing to copy
+        if size != 0 {
+            let ptr = self
+                .get_dynamic(ty, size, index)
+                .unwrap()
+                .as_ptr()
+                .cast::<u8>();
+            ptr::copy_nonoverlapping(component, ptr, size);
+        }
+        self.set_changed(ty, index, true);
     }
 
     /// How, if at all, `Q` will access entities in this archetype
@@ -396,7 +863,7 @@ impl Archetype {
             .find(|typ| typ.id == component_type)
             .map(|info| info.layout)
     }
-    
+
     /// Add components from another archetype with identical components
     ///
     /// # Safety
@@ -411,21 +878,62 @@ impl Archetype {
             let dst = (*self.data.get())
                 .as_ptr()
                 .add(dst_off + self.len as usize * info.layout.size());
-            dst.copy_from_nonoverlapping(src, other.len as usize * info.layout.size())
+            dst.copy_from_nonoverlapping(src, other.len as usize * info.layout.size());
+            let src = other.changed_dynamic(info.id(), 0).unwrap().as_ptr();
+            let dst = self.changed_dynamic(info.id(), self.len).unwrap().as_ptr();
+            dst.copy_from_nonoverlapping(src, other.len as usize);
+            let src = other.added_dynamic(info.id(), 0).unwrap().as_ptr();
+            let dst = self.added_dynamic(info.id(), self.len).unwrap().as_ptr();
+            dst.copy_from_nonoverlapping(src, other.len as usize);
         }
+        let n = self.types.len();
+        let start = self.len as usize * n;
+        self.ticks[start..start + other.len as usize * n]
+            .copy_from_slice(&other.ticks[..other.len as usize * n]);
         self.len += other.len;
         other.len = 0;
     }
 
     /// Raw IDs of the entities in this archetype
     ///
+    /// Ordered consistently with the columns returned by [`get`](Self::get) and
+    /// [`get_mut`](Self::get_mut).
+    ///
     /// Convertible into [`Entity`](crate::Entity)s with
     /// [`World::find_entity_from_id()`](crate::World::find_entity_from_id). Useful for efficient
     /// serialization.
+    #[doc(alias = "entities")]
     #[inline]
     pub fn ids(&self) -> &[u32] {
         &self.entities[0..self.len as usize]
     }
+
+    /// Raw IDs of the entities that entered this archetype since the last call to
+    /// [`World::clear_trackers`](crate::World::clear_trackers), in the order they entered
+    ///
+    /// Entities enter an archetype when spawned into it or when inserting or removing components
+    /// moves them there. Returns `None` unless the world was created with
+    /// [`WorldConfig::track_membership`](crate::WorldConfig::track_membership). An entity that
+    /// entered then exited again is listed here and by [`exited`](Self::exited), and IDs of
+    /// despawned entities may since have been reused.
+    pub fn entered(&self) -> Option<&[u32]> {
+        self.membership.as_ref().map(|x| &x.entered[..])
+    }
+
+    /// Raw IDs of the entities that exited this archetype since the last call to
+    /// [`World::clear_trackers`](crate::World::clear_trackers), in the order they exited
+    ///
+    /// Entities exit an archetype when despawned or when inserting or removing components moves
+    /// them elsewhere. See [`entered`](Self::entered).
+    pub fn exited(&self) -> Option<&[u32]> {
+        self.membership.as_ref().map(|x| &x.exited[..])
+    }
+}
+
+#[derive(Default)]
+struct Membership {
+    entered: Vec<u32>,
+    exited: Vec<u32>,
 }
 
 impl Drop for Archetype {
@@ -491,14 +999,96 @@ pub(crate) type TypeIdMap<V> = HashMap<TypeId, V, BuildHasherDefault<TypeIdHashe
 
 struct TypeState {
     offset: usize,
+    /// Position among the archetype's types, locating its ticks
+    column: usize,
+    /// Offset of the change flags
+    changed: usize,
+    /// Offset of the addition flags
+    added: usize,
     borrow: AtomicBorrow,
+    /// Where the most recent borrow was acquired, or null
+    #[cfg(feature = "track-borrows")]
+    origin: AtomicPtr<Location<'static>>,
+    /// Label in effect where the most recent borrow was acquired, or null
+    #[cfg(feature = "track-borrows")]
+    holder: AtomicPtr<BorrowLabel>,
+    /// Number of borrows acquired, for `World::stats`
+    #[cfg(feature = "stats")]
+    borrows: AtomicU64,
 }
 
 impl TypeState {
-    fn new(offset: usize) -> Self {
+    fn new(offset: usize, column: usize) -> Self {
         Self {
             offset,
+            column,
+            changed: 0,
+            added: 0,
             borrow: AtomicBorrow::new(),
+            #[cfg(feature = "track-borrows")]
+            origin: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "track-borrows")]
+            holder: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "stats")]
+            borrows: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow(&self, name: &'static str) -> Result<(), BorrowError> {
+        if !self.borrow.borrow() {
+            return Err(self.conflict(name, true));
+        }
+        self.record_origin();
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow_mut(&self, name: &'static str) -> Result<(), BorrowError> {
+        if !self.borrow.borrow_mut() {
+            return Err(self.conflict(name, false));
+        }
+        self.record_origin();
+        Ok(())
+    }
+
+    fn release_mut(&self) {
+        #[cfg(feature = "track-borrows")]
+        {
+            self.origin.store(ptr::null_mut(), Ordering::Relaxed);
+            self.holder.store(ptr::null_mut(), Ordering::Relaxed);
+        }
+        self.borrow.release_mut();
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    #[inline(always)]
+    fn record_origin(&self) {
+        #[cfg(feature = "stats")]
+        self.borrows.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "track-borrows")]
+        {
+            self.origin.store(
+                Location::caller() as *const Location<'static> as *mut _,
+                Ordering::Relaxed,
+            );
+            let holder = crate::borrow::current_label().map_or(ptr::null(), |x| x as *const _);
+            self.holder.store(holder as *mut _, Ordering::Relaxed);
+        }
+    }
+
+    #[cold]
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn conflict(&self, name: &'static str, unique: bool) -> BorrowError {
+        BorrowError {
+            type_name: name,
+            unique,
+            #[cfg(feature = "track-borrows")]
+            origin: unsafe { self.origin.load(Ordering::Relaxed).as_ref() },
+            #[cfg(feature = "track-borrows")]
+            holder: unsafe { self.holder.load(Ordering::Relaxed).as_ref() },
+            #[cfg(feature = "track-borrows")]
+            conflict: Location::caller(),
         }
     }
 }
@@ -509,7 +1099,6 @@ pub struct TypeInfo {
     id: TypeId,
     layout: Layout,
     drop: unsafe fn(*mut u8),
-    #[cfg(debug_assertions)]
     type_name: &'static str,
 }
 
@@ -524,19 +1113,40 @@ impl TypeInfo {
             id: TypeId::of::<T>(),
             layout: Layout::new::<T>(),
             drop: drop_ptr::<T>,
-            #[cfg(debug_assertions)]
             type_name: core::any::type_name::<T>(),
         }
     }
 
-    pub(crate) fn id(&self) -> TypeId {
+    /// Metadata for a type described at runtime, such as a component defined by a script
+    ///
+    /// `id` must uniquely identify the type among all component types, e.g. by being the `TypeId`
+    /// of a private marker type, and `drop` must be safe to call on a pointer to a value of the
+    /// type, with its `layout`.
+    pub fn from_parts(id: TypeId, layout: Layout, drop: unsafe fn(*mut u8)) -> Self {
+        Self {
+            id,
+            layout,
+            drop,
+            type_name: "<dynamic>",
+        }
+    }
+
+    /// The `TypeId` of the type
+    pub fn id(&self) -> TypeId {
         self.id
     }
 
-    pub(crate) fn layout(&self) -> Layout {
+    /// The size and alignment of the type
+    pub fn layout(&self) -> Layout {
         self.layout
     }
 
+    /// Name of the type, as reported by `core::any::type_name`, or `"<dynamic>"` for types
+    /// described with `from_parts`
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
     pub(crate) unsafe fn drop(&self, data: *mut u8) {
         (self.drop)(data)
     }
@@ -587,6 +1197,7 @@ impl<T: Component> Drop for ColumnRef<'_, T> {
 }
 
 impl<T: Component> Clone for ColumnRef<'_, T> {
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     fn clone(&self) -> Self {
         self.archetype.borrow::<T>();
         Self {
@@ -601,3 +1212,34 @@ impl<T: Component + fmt::Debug> fmt::Debug for ColumnRef<'_, T> {
         self.column.fmt(f)
     }
 }
+
+/// Unique borrow of a single component type from an [`Archetype`]
+pub struct ColumnRefMut<'a, T: Component> {
+    archetype: &'a Archetype,
+    column: &'a mut [T],
+}
+
+impl<T: Component> Deref for ColumnRefMut<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.column
+    }
+}
+
+impl<T: Component> DerefMut for ColumnRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.column
+    }
+}
+
+impl<T: Component> Drop for ColumnRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.archetype.release_mut::<T>();
+    }
+}
+
+impl<T: Component + fmt::Debug> fmt::Debug for ColumnRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.column.fmt(f)
+    }
+}
@@ -126,6 +126,27 @@ impl<T> BatchWriter<'_, T> {
     pub fn fill(&self) -> u32 {
         *self.fill
     }
+
+    /// Fill all remaining space with a copy of `bytes`, returning `false` if it's the wrong size
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must hold valid `T`s, which must not have drop glue.
+    #[cfg(feature = "serde")]
+    pub(crate) unsafe fn fill_from_bytes(&mut self, bytes: &[u8]) -> bool {
+        let remaining = core::mem::take(&mut self.storage).into_slice();
+        if bytes.len() != core::mem::size_of_val(remaining) {
+            self.storage = remaining.iter_mut();
+            return false;
+        }
+        core::ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            remaining.as_mut_ptr().cast::<u8>(),
+            bytes.len(),
+        );
+        *self.fill += remaining.len() as u32;
+        true
+    }
 }
 
 /// Error indicating that a `ColumnBatchBuilder` was missing components
@@ -0,0 +1,91 @@
+use crate::alloc::boxed::Box;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+use crate::archetype::TypeIdMap;
+use crate::Component;
+
+/// A component storing at most one value of any number of types
+///
+/// Handy for attaching small, heterogeneous, frequently changing scratch data to an entity, such as
+/// AI state flags, without moving the entity between archetypes every time a value is added or
+/// removed. Values are boxed individually, so prefer ordinary components for data that is accessed
+/// in bulk by queries.
+///
+/// See [`World::blackboard_mut`](crate::World::blackboard_mut).
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Target(Entity);
+///
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// world.blackboard_mut(a).unwrap().insert(Target(b));
+/// let board = world.get::<Blackboard>(a).unwrap();
+/// assert_eq!(board.get::<Target>().unwrap().0, b);
+/// ```
+#[derive(Default)]
+pub struct Blackboard {
+    entries: TypeIdMap<Box<dyn Any + Send + Sync>>,
+}
+
+impl Blackboard {
+    /// Create an empty blackboard
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `value`, returning the previously stored `T`, if any
+    pub fn insert<T: Component>(&mut self, value: T) -> Option<T> {
+        self.entries
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    /// Borrow the stored `T`, if any
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.entries.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Uniquely borrow the stored `T`, if any
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.entries.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Remove and return the stored `T`, if any
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.entries
+            .remove(&TypeId::of::<T>())
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    /// Whether a `T` is stored
+    pub fn contains<T: Component>(&self) -> bool {
+        self.entries.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Number of stored values
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no values are stored
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop all stored values
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl fmt::Debug for Blackboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blackboard")
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
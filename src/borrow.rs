@@ -6,13 +6,22 @@
 // copied, modified, or distributed except according to those terms.
 
 use core::any::TypeId;
+use core::fmt;
 use core::ops::{Deref, DerefMut};
+#[cfg(feature = "track-borrows")]
+use core::panic::Location;
 use core::ptr::NonNull;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::alloc::sync::Arc;
 use crate::archetype::Archetype;
-use crate::{Component, MissingComponent};
+use crate::{Component, ComponentError, MissingComponent, TypeInfo, World};
 
+/// Borrow state of a single archetype column, resource, or sparse set
+///
+/// Aligned to a cache line of its own, so that threads concurrently borrowing different columns,
+/// or reading the column offsets stored alongside each flag, don't contend on the same line.
+#[repr(align(64))]
 pub struct AtomicBorrow(AtomicUsize);
 
 impl AtomicBorrow {
@@ -50,9 +59,179 @@ impl AtomicBorrow {
         let value = self.0.fetch_and(!UNIQUE_BIT, Ordering::Release);
         debug_assert_ne!(value & UNIQUE_BIT, 0, "unique release of shared borrow");
     }
+
+    /// Whether no borrows are outstanding
+    pub fn is_free(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == 0
+    }
+}
+
+const UNIQUE_BIT: usize = !(usize::MAX >> 1);
+
+/// Error indicating that a component couldn't be borrowed due to a conflicting borrow
+///
+/// With the `track-borrows` feature, also records where the conflicting borrows were acquired,
+/// and the [`BorrowLabel`] in effect when the existing borrow was acquired.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BorrowError {
+    pub(crate) type_name: &'static str,
+    /// Whether the existing borrow is known to be unique
+    pub(crate) unique: bool,
+    /// Where the existing borrow was most recently acquired, if known
+    #[cfg(feature = "track-borrows")]
+    pub(crate) origin: Option<&'static Location<'static>>,
+    /// Label in effect where the existing borrow was most recently acquired, if any
+    #[cfg(feature = "track-borrows")]
+    pub(crate) holder: Option<&'static BorrowLabel>,
+    /// Where the failed borrow was attempted
+    #[cfg(feature = "track-borrows")]
+    pub(crate) conflict: &'static Location<'static>,
+}
+
+impl BorrowError {
+    /// Name of the component type that couldn't be borrowed
+    ///
+    /// Just `"component"` for components borrowed without static type information.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Label of the code holding the existing borrow, if it was acquired within
+    /// [`with_borrow_label`]
+    ///
+    /// Always `None` without the `track-borrows` feature.
+    pub fn holder(&self) -> Option<&'static str> {
+        #[cfg(feature = "track-borrows")]
+        {
+            self.holder.map(|x| x.0)
+        }
+        #[cfg(not(feature = "track-borrows"))]
+        {
+            None
+        }
+    }
+
+    /// Panic, reporting the conflict
+    #[cold]
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) fn panic(&self) -> ! {
+        panic!("{}", self)
+    }
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.unique { " uniquely" } else { "" };
+        write!(f, "{} already borrowed{}", self.type_name, kind)?;
+        if let Some(holder) = self.holder() {
+            write!(f, " by {}", holder)?;
+        }
+        #[cfg(feature = "track-borrows")]
+        {
+            if let Some(origin) = self.origin {
+                write!(
+                    f,
+                    " (most recently at {}); conflicting borrow at {}",
+                    origin, self.conflict
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BorrowError {}
+
+/// A name for the code acquiring borrows, such as a system, reported by [`BorrowError::holder`]
+///
+/// Labels are compared by address, so each should be declared once as a `static`.
+#[derive(Debug)]
+pub struct BorrowLabel(&'static str);
+
+impl BorrowLabel {
+    /// Create a label reported as `name`
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    /// The name the label was created with
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for BorrowLabel {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+impl Eq for BorrowLabel {}
+
+impl core::hash::Hash for BorrowLabel {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (self as *const Self).hash(state);
+    }
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static LABEL: core::cell::Cell<Option<&'static BorrowLabel>> =
+        const { core::cell::Cell::new(None) };
+}
+
+/// Run `f`, attributing the borrows it acquires on this thread to `label`
+///
+/// With the `track-borrows` feature, a borrow that conflicts with one acquired within `f` reports
+/// `label` through [`BorrowError::holder`] and in its message, identifying which system is
+/// fighting over a component rather than just where. Otherwise, just runs `f`. Labels nest, with
+/// the innermost taking effect.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// static PHYSICS: BorrowLabel = BorrowLabel::new("physics");
+///
+/// let mut world = World::new();
+/// let a = world.spawn((1.0f32,));
+/// let held = with_borrow_label(&PHYSICS, || world.get_mut::<f32>(a).unwrap());
+/// let err = match world.try_get::<f32>(a) {
+///     Err(ComponentError::Borrowed(err)) => err,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(err.type_name(), "f32");
+/// # #[cfg(feature = "track-borrows")]
+/// assert_eq!(err.holder(), Some("physics"));
+/// drop(held);
+/// assert!(world.try_get::<f32>(a).is_ok());
+/// ```
+#[cfg(feature = "std")]
+pub fn with_borrow_label<R>(label: &'static BorrowLabel, f: impl FnOnce() -> R) -> R {
+    /// Restores the enclosing label even if `f` panics
+    struct Restore(Option<&'static BorrowLabel>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LABEL.with(|x| x.set(self.0));
+        }
+    }
+
+    let _restore = Restore(LABEL.with(|x| x.replace(Some(label))));
+    f()
 }
 
-const UNIQUE_BIT: usize = !(usize::max_value() >> 1);
+/// The label set by the innermost enclosing `with_borrow_label` on this thread, if any
+#[cfg(feature = "track-borrows")]
+pub(crate) fn current_label() -> Option<&'static BorrowLabel> {
+    #[cfg(feature = "std")]
+    {
+        LABEL.with(|x| x.get())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        None
+    }
+}
 
 /// Shared borrow of an entity's component
 #[derive(Clone)]
@@ -62,6 +241,7 @@ pub struct Ref<'a, T: Component> {
 }
 
 impl<'a, T: Component> Ref<'a, T> {
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
         index: u32,
@@ -76,6 +256,32 @@ impl<'a, T: Component> Ref<'a, T> {
         archetype.borrow::<T>();
         Ok(Self { archetype, target })
     }
+
+    /// Like `new`, but reports a conflicting borrow rather than panicking
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let target = NonNull::new_unchecked(
+            archetype
+                .get_base::<T>()
+                .ok_or_else(MissingComponent::new::<T>)?
+                .as_ptr()
+                .add(index as usize),
+        );
+        archetype
+            .try_borrow::<T>()
+            .map_err(ComponentError::Borrowed)?;
+        Ok(Self { archetype, target })
+    }
+
+    /// Give up the borrow without releasing it, returning the component's address
+    pub(crate) fn leak(self) -> NonNull<T> {
+        let target = self.target;
+        core::mem::forget(self);
+        target
+    }
 }
 
 unsafe impl<T: Component> Send for Ref<'_, T> {}
@@ -95,12 +301,16 @@ impl<'a, T: Component> Deref for Ref<'a, T> {
 }
 
 /// Unique borrow of an entity's component
+///
+/// The component is flagged as changed only if it is accessed through `DerefMut`.
 pub struct RefMut<'a, T: Component> {
     archetype: &'a Archetype,
     target: NonNull<T>,
+    changed: NonNull<bool>,
 }
 
 impl<'a, T: Component> RefMut<'a, T> {
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
         index: u32,
@@ -112,8 +322,57 @@ impl<'a, T: Component> RefMut<'a, T> {
                 .as_ptr()
                 .add(index as usize),
         );
+        let changed = NonNull::new_unchecked(
+            archetype
+                .get_changed_base::<T>()
+                .unwrap()
+                .as_ptr()
+                .add(index as usize),
+        );
         archetype.borrow_mut::<T>();
-        Ok(Self { archetype, target })
+        Ok(Self {
+            archetype,
+            target,
+            changed,
+        })
+    }
+
+    /// Like `new`, but reports a conflicting borrow rather than panicking
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let target = NonNull::new_unchecked(
+            archetype
+                .get_base::<T>()
+                .ok_or_else(MissingComponent::new::<T>)?
+                .as_ptr()
+                .add(index as usize),
+        );
+        let changed = NonNull::new_unchecked(
+            archetype
+                .get_changed_base::<T>()
+                .unwrap()
+                .as_ptr()
+                .add(index as usize),
+        );
+        archetype
+            .try_borrow_mut::<T>()
+            .map_err(ComponentError::Borrowed)?;
+        Ok(Self {
+            archetype,
+            target,
+            changed,
+        })
+    }
+
+    /// Give up the borrow without releasing it, returning the addresses of the component and its
+    /// change flag
+    pub(crate) fn leak(self) -> (NonNull<T>, NonNull<bool>) {
+        let result = (self.target, self.changed);
+        core::mem::forget(self);
+        result
     }
 }
 
@@ -135,7 +394,112 @@ impl<'a, T: Component> Deref for RefMut<'a, T> {
 
 impl<'a, T: Component> DerefMut for RefMut<'a, T> {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.target.as_mut() }
+        unsafe {
+            *self.changed.as_ptr() = true;
+            self.target.as_mut()
+        }
+    }
+}
+
+/// Shared borrow of an entity's component that keeps its world alive
+///
+/// Obtained from [`World::get_owned`]. Unlike `Ref`, this has no lifetime, so it can be held
+/// across `.await` points or moved into spawned tasks. Because the world is shared through an
+/// `Arc`, it cannot be modified until every guard is dropped, which is what keeps the borrowed
+/// component in place.
+pub struct OwnedRef<T: Component> {
+    world: Arc<World>,
+    archetype: u32,
+    target: NonNull<T>,
+}
+
+impl<T: Component> OwnedRef<T> {
+    /// `target` must be a `T` in archetype `archetype` of `world`, already borrowed
+    pub(crate) unsafe fn new(world: Arc<World>, archetype: u32, target: NonNull<T>) -> Self {
+        Self {
+            world,
+            archetype,
+            target,
+        }
+    }
+
+    /// The world the component belongs to
+    pub fn world(&self) -> &Arc<World> {
+        &self.world
+    }
+}
+
+unsafe impl<T: Component> Send for OwnedRef<T> {}
+unsafe impl<T: Component> Sync for OwnedRef<T> {}
+
+impl<T: Component> Drop for OwnedRef<T> {
+    fn drop(&mut self) {
+        self.world.archetype_at(self.archetype).release::<T>();
+    }
+}
+
+impl<T: Component> Deref for OwnedRef<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+/// Unique borrow of an entity's component that keeps its world alive
+///
+/// Obtained from [`World::get_owned_mut`]. See [`OwnedRef`]. The component is flagged as changed
+/// only if it is accessed through `DerefMut`.
+pub struct OwnedRefMut<T: Component> {
+    world: Arc<World>,
+    archetype: u32,
+    target: NonNull<T>,
+    changed: NonNull<bool>,
+}
+
+impl<T: Component> OwnedRefMut<T> {
+    /// `target` must be a `T` in archetype `archetype` of `world`, already uniquely borrowed, and
+    /// `changed` its change flag
+    pub(crate) unsafe fn new(
+        world: Arc<World>,
+        archetype: u32,
+        (target, changed): (NonNull<T>, NonNull<bool>),
+    ) -> Self {
+        Self {
+            world,
+            archetype,
+            target,
+            changed,
+        }
+    }
+
+    /// The world the component belongs to
+    pub fn world(&self) -> &Arc<World> {
+        &self.world
+    }
+}
+
+unsafe impl<T: Component> Send for OwnedRefMut<T> {}
+unsafe impl<T: Component> Sync for OwnedRefMut<T> {}
+
+impl<T: Component> Drop for OwnedRefMut<T> {
+    fn drop(&mut self) {
+        self.world.archetype_at(self.archetype).release_mut::<T>();
+    }
+}
+
+impl<T: Component> Deref for OwnedRefMut<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+impl<T: Component> DerefMut for OwnedRefMut<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            *self.changed.as_ptr() = true;
+            self.target.as_mut()
+        }
     }
 }
 
@@ -162,10 +526,18 @@ impl<'a> EntityRef<'a> {
         }
     }
 
+    /// Whether the entity has a component of type `T`
+    ///
+    /// Unlike `get`, never borrows the component, so can't panic.
+    pub fn has<T: Component>(&self) -> bool {
+        self.archetype.is_some_and(|a| a.has::<T>())
+    }
+
     /// Borrow the component of type `T`, if it exists
     ///
     /// Panics if the component is already uniquely borrowed from another entity with the same
     /// components.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub fn get<T: Component>(&self) -> Option<Ref<'a, T>> {
         Some(unsafe { Ref::new(self.archetype?, self.index).ok()? })
     }
@@ -173,6 +545,7 @@ impl<'a> EntityRef<'a> {
     /// Uniquely borrow the component of type `T`, if it exists
     ///
     /// Panics if the component is already borrowed from another entity with the same components.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub fn get_mut<T: Component>(&self) -> Option<RefMut<'a, T>> {
         Some(unsafe { RefMut::new(self.archetype?, self.index).ok()? })
     }
@@ -189,6 +562,36 @@ impl<'a> EntityRef<'a> {
             .flat_map(|arch| arch.types().iter().map(|ty| ty.id()))
     }
 
+    /// Describe each of the entity's components, including where it's stored
+    ///
+    /// Enough to build a debug inspector that lists an entity's components, and displays those
+    /// whose types it knows by looking them up by `TypeId`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let entity = world.entity(a).unwrap();
+    /// let mut names = entity.components().map(|x| x.type_name()).collect::<Vec<_>>();
+    /// names.sort_unstable();
+    /// assert_eq!(names, ["bool", "i32"]);
+    /// let number = entity.components().find(|x| x.id() == std::any::TypeId::of::<i32>()).unwrap();
+    /// assert_eq!(unsafe { *number.as_ptr().cast::<i32>() }, 123);
+    /// ```
+    pub fn components(&self) -> impl Iterator<Item = TypeInfoView<'a>> + 'a {
+        let index = self.index;
+        self.archetype.into_iter().flat_map(move |arch| {
+            arch.types().iter().map(move |ty| TypeInfoView {
+                info: ty,
+                ptr: unsafe {
+                    arch.get_dynamic(ty.id(), ty.layout().size(), index)
+                        .unwrap()
+                },
+            })
+        })
+    }
+
     /// Number of components in this entity
     pub fn len(&self) -> usize {
         self.archetype.map_or(0, |a| a.types().len())
@@ -202,3 +605,47 @@ impl<'a> EntityRef<'a> {
 
 unsafe impl<'a> Send for EntityRef<'a> {}
 unsafe impl<'a> Sync for EntityRef<'a> {}
+
+/// Type-erased description of one of an entity's components, from [`EntityRef::components`]
+#[derive(Copy, Clone)]
+pub struct TypeInfoView<'a> {
+    info: &'a TypeInfo,
+    ptr: NonNull<u8>,
+}
+
+impl<'a> TypeInfoView<'a> {
+    /// The `TypeId` of the component
+    pub fn id(&self) -> TypeId {
+        self.info.id()
+    }
+
+    /// Name of the component's type, as reported by `core::any::type_name`
+    pub fn type_name(&self) -> &'static str {
+        self.info.type_name()
+    }
+
+    /// The size and alignment of the component
+    pub fn layout(&self) -> core::alloc::Layout {
+        self.info.layout()
+    }
+
+    /// Address of the component
+    ///
+    /// No borrow is acquired, so the component may only be read through this pointer while no
+    /// `&mut` to it can exist, such as while the world is uniquely borrowed, and never written.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+}
+
+impl fmt::Debug for TypeInfoView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypeInfoView")
+            .field("type_name", &self.type_name())
+            .field("layout", &self.layout())
+            .finish()
+    }
+}
+
+unsafe impl Send for TypeInfoView<'_> {}
+unsafe impl Sync for TypeInfoView<'_> {}
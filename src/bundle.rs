@@ -14,6 +14,10 @@ use crate::archetype::TypeInfo;
 use crate::Component;
 
 /// A dynamically typed collection of components
+///
+/// # Safety
+///
+/// `with_ids` and `type_info` must describe exactly the components passed to `f` by `put`.
 pub unsafe trait DynamicBundle {
     /// Invoke a callback on the fields' type IDs, sorted by descending alignment then id
     #[doc(hidden)]
@@ -27,9 +31,24 @@ pub unsafe trait DynamicBundle {
     /// called at most once on any given value.
     #[doc(hidden)]
     unsafe fn put(self, f: impl FnMut(*mut u8, TypeInfo));
+    /// Identify bundles with the same component types, if cheaply possible
+    ///
+    /// Used to cache archetype transitions. Must be `None` or the `TypeId` of a type uniquely
+    /// determining `with_ids`.
+    #[doc(hidden)]
+    fn key(&self) -> Option<TypeId> {
+        None
+    }
 }
 
 /// A statically typed collection of components
+///
+/// The component type IDs of tuples are sorted on the stack, and those of `#[derive(Bundle)]`
+/// structs are computed once, so spawning a bundle into an existing archetype doesn't allocate.
+///
+/// # Safety
+///
+/// `with_static_ids` and `static_type_info` must describe exactly the components of `Self`.
 pub unsafe trait Bundle: DynamicBundle {
     #[doc(hidden)]
     fn with_static_ids<T>(f: impl FnOnce(&[TypeId]) -> T) -> T;
@@ -38,6 +57,12 @@ pub unsafe trait Bundle: DynamicBundle {
     #[doc(hidden)]
     fn static_type_info() -> Vec<TypeInfo>;
 
+    /// Statically known counterpart of [`DynamicBundle::key`]
+    #[doc(hidden)]
+    fn static_key() -> Option<TypeId> {
+        None
+    }
+
     /// Construct `Self` by moving components out of pointers fetched by `f`
     ///
     /// # Safety
@@ -59,6 +84,17 @@ impl MissingComponent {
     pub fn new<T: Component>() -> Self {
         Self(type_name::<T>())
     }
+
+    /// Name of the missing component type, as reported by `core::any::type_name`
+    pub fn type_name(&self) -> &'static str {
+        self.0
+    }
+
+    /// Construct an error representing a missing component of type `ty`
+    #[cfg(feature = "abi")]
+    pub(crate) fn dynamic(ty: TypeInfo) -> Self {
+        Self(ty.type_name())
+    }
 }
 
 impl fmt::Display for MissingComponent {
@@ -70,6 +106,26 @@ impl fmt::Display for MissingComponent {
 #[cfg(feature = "std")]
 impl std::error::Error for MissingComponent {}
 
+/// A single component of dynamic type, to be moved out of `ptr`
+pub(crate) struct RawComponent {
+    pub(crate) ty: TypeInfo,
+    pub(crate) ptr: *mut u8,
+}
+
+unsafe impl DynamicBundle for RawComponent {
+    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+        f(&[self.ty.id()])
+    }
+
+    fn type_info(&self) -> Vec<TypeInfo> {
+        vec![self.ty]
+    }
+
+    unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeInfo)) {
+        f(self.ptr, self.ty)
+    }
+}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         unsafe impl<$($name: Component),*> DynamicBundle for ($($name,)*) {
@@ -81,6 +137,10 @@ macro_rules! tuple_impl {
                 Self::static_type_info()
             }
 
+            fn key(&self) -> Option<TypeId> {
+                Self::static_key()
+            }
+
             #[allow(unused_variables, unused_mut)]
             unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeInfo)) {
                 #[allow(non_snake_case)]
@@ -113,6 +173,10 @@ macro_rules! tuple_impl {
                 xs
             }
 
+            fn static_key() -> Option<TypeId> {
+                Some(TypeId::of::<Self>())
+            }
+
             #[allow(unused_variables, unused_mut)]
             unsafe fn get(mut f: impl FnMut(TypeInfo) -> Option<NonNull<u8>>) -> Result<Self, MissingComponent> {
                 #[allow(non_snake_case)]
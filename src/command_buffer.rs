@@ -0,0 +1,384 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::mem;
+use core::ops::Range;
+use core::ptr::{self, NonNull};
+
+use crate::archetype::TypeInfo;
+use crate::{align, Bundle, DespawnReason, DynamicBundle, Entity, World};
+
+/// Records operations for future application to a [`World`]
+///
+/// Useful when operations cannot be applied directly due to ongoing borrows, such as while
+/// iterating a query, or when work is spread across threads that each record their own changes.
+///
+/// Recorded operations are applied by kind: first spawns and insertions, then removals, and
+/// finally despawns.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let mut cmd = CommandBuffer::new();
+/// for (e, &flag) in world.query::<&bool>().iter() {
+///     if flag {
+///         cmd.insert(e, ("abc",));
+///     }
+/// }
+/// cmd.spawn((456,));
+/// cmd.run_on(&mut world);
+/// assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+/// assert_eq!(world.len(), 2);
+/// ```
+pub struct CommandBuffer {
+    entities: Vec<EntityIndex>,
+    remove_comps: Vec<RemovedComps>,
+    despawn_ent: Vec<(Entity, Option<DespawnReason>)>,
+    storage: NonNull<u8>,
+    layout: Layout,
+    cursor: usize,
+    components: Vec<ComponentInfo>,
+    ids: Vec<TypeId>,
+}
+
+impl CommandBuffer {
+    /// Create an empty command buffer
+    pub fn new() -> Self {
+        Self {
+            entities: Vec::new(),
+            remove_comps: Vec::new(),
+            despawn_ent: Vec::new(),
+            storage: NonNull::dangling(),
+            layout: Layout::from_size_align(0, 8).unwrap(),
+            cursor: 0,
+            components: Vec::new(),
+            ids: Vec::new(),
+        }
+    }
+
+    /// Add components from `bundle` to `entity`, if it exists
+    ///
+    /// Pairs well with [`World::reserve_entity`] to spawn entities with a known handle.
+    pub fn insert(&mut self, entity: Entity, components: impl DynamicBundle) {
+        self.record(Some(entity), components);
+    }
+
+    /// Add `component` to `entity`, if the entity exists
+    ///
+    /// See [`insert`](Self::insert).
+    pub fn insert_one(&mut self, entity: Entity, component: impl crate::Component) {
+        self.insert(entity, (component,));
+    }
+
+    /// Spawn a new entity with `components`
+    ///
+    /// If the [`Entity`] is needed immediately, consider combining [`World::reserve_entity`] with
+    /// [`insert`](CommandBuffer::insert) instead.
+    pub fn spawn(&mut self, components: impl DynamicBundle) {
+        self.record(None, components);
+    }
+
+    /// Remove components from `entity` if they exist
+    ///
+    /// Has no effect if `entity` does not have every component in `T`.
+    pub fn remove<T: Bundle + 'static>(&mut self, entity: Entity) {
+        fn remove_bundle_and_ignore_result<T: Bundle + 'static>(world: &mut World, ent: Entity) {
+            let _ = world.remove::<T>(ent);
+        }
+        self.remove_comps.push(RemovedComps {
+            remove: remove_bundle_and_ignore_result::<T>,
+            entity,
+        });
+    }
+
+    /// Remove the `T` component from `entity`, if it exists
+    ///
+    /// See [`remove`](Self::remove).
+    pub fn remove_one<T: crate::Component>(&mut self, entity: Entity) {
+        self.remove::<(T,)>(entity);
+    }
+
+    /// Despawn `entity` from the world, if it exists
+    pub fn despawn(&mut self, entity: Entity) {
+        self.despawn_ent.push((entity, None));
+    }
+
+    /// Despawn `entity` from the world with a reason, if it exists
+    ///
+    /// See [`World::despawn_with_reason`].
+    pub fn despawn_with_reason(&mut self, entity: Entity, reason: DespawnReason) {
+        self.despawn_ent.push((entity, Some(reason)));
+    }
+
+    /// Move all operations recorded in `other` into `self`, leaving `other` empty
+    ///
+    /// Operations from `other` are ordered after those already recorded in `self`. Useful for
+    /// combining buffers recorded by independent threads before applying them to a world in one
+    /// pass. See also [`World::apply_all`].
+    pub fn merge(&mut self, other: &mut CommandBuffer) {
+        let base = self.components.len();
+        for info in other.components.drain(..) {
+            unsafe {
+                self.add_inner(other.storage.as_ptr().add(info.offset), info.ty);
+            }
+        }
+        other.cursor = 0;
+        self.ids.append(&mut other.ids);
+        self.entities
+            .extend(other.entities.drain(..).map(|x| EntityIndex {
+                entity: x.entity,
+                components: (x.components.start + base)..(x.components.end + base),
+            }));
+        self.remove_comps.append(&mut other.remove_comps);
+        self.despawn_ent.append(&mut other.despawn_ent);
+    }
+
+    /// Apply all recorded operations to `world`, leaving the buffer empty
+    ///
+    /// Operations on entities that no longer exist are ignored.
+    pub fn run_on(&mut self, world: &mut World) {
+        world.apply_all(core::slice::from_mut(self));
+    }
+
+    /// Number of operations recorded
+    ///
+    /// Useful for deciding when a buffer has grown large enough to be worth applying.
+    pub fn len(&self) -> usize {
+        self.entities.len() + self.remove_comps.len() + self.despawn_ent.len()
+    }
+
+    /// Whether no operations have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all recorded operations
+    pub fn clear(&mut self) {
+        self.ids.clear();
+        self.entities.clear();
+        self.remove_comps.clear();
+        self.despawn_ent.clear();
+        self.cursor = 0;
+        unsafe {
+            for info in self.components.drain(..) {
+                info.ty.drop(self.storage.as_ptr().add(info.offset));
+            }
+        }
+    }
+
+    fn record(&mut self, entity: Option<Entity>, components: impl DynamicBundle) {
+        let first = self.components.len();
+        unsafe {
+            components.put(|ptr, ty| self.add_inner(ptr, ty));
+        }
+        self.components[first..].sort_unstable_by_key(|x| x.ty);
+        self.ids
+            .extend(self.components[first..].iter().map(|x| x.ty.id()));
+        self.entities.push(EntityIndex {
+            entity,
+            components: first..self.components.len(),
+        });
+    }
+
+    unsafe fn add_inner(&mut self, ptr: *mut u8, ty: TypeInfo) {
+        let offset = align(self.cursor, ty.layout().align());
+        let end = offset + ty.layout().size();
+        if end > self.layout.size() || ty.layout().align() > self.layout.align() {
+            let new_align = self.layout.align().max(ty.layout().align());
+            let new_layout =
+                Layout::from_size_align(end.next_power_of_two().max(64), new_align).unwrap();
+            let new_storage = NonNull::new(alloc(new_layout)).unwrap();
+            ptr::copy_nonoverlapping(self.storage.as_ptr(), new_storage.as_ptr(), self.cursor);
+            if self.layout.size() != 0 {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+            self.storage = new_storage;
+            self.layout = new_layout;
+        }
+
+        ptr::copy_nonoverlapping(ptr, self.storage.as_ptr().add(offset), ty.layout().size());
+        self.components.push(ComponentInfo { ty, offset });
+        self.cursor = end;
+    }
+}
+
+unsafe impl Send for CommandBuffer {}
+unsafe impl Sync for CommandBuffer {}
+
+impl Drop for CommandBuffer {
+    fn drop(&mut self) {
+        self.clear();
+        if self.layout.size() != 0 {
+            unsafe {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+        }
+    }
+}
+
+impl Default for CommandBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply the operations recorded in `buffers` to `world`, grouping spawns by component types
+pub(crate) fn apply_all(world: &mut World, buffers: &mut [CommandBuffer]) {
+    // Take ownership of the bookkeeping so that a panic part-way through leaks unapplied
+    // components rather than dropping them twice.
+    let mut taken = Vec::with_capacity(buffers.len());
+    for buffer in buffers.iter_mut() {
+        buffer.cursor = 0;
+        taken.push(Taken {
+            storage: buffer.storage,
+            entities: mem::take(&mut buffer.entities),
+            components: mem::take(&mut buffer.components),
+            ids: mem::take(&mut buffer.ids),
+        });
+    }
+
+    // Spawns are independent of each other, so those sharing component types can be placed in
+    // their archetype in one go.
+    {
+        let mut spawns = taken
+            .iter()
+            .flat_map(|buffer| {
+                buffer
+                    .entities
+                    .iter()
+                    .filter(|x| x.entity.is_none())
+                    .map(move |x| buffer.recorded(x))
+            })
+            .collect::<Vec<_>>();
+        spawns.sort_by(|x, y| x.ids.cmp(y.ids));
+        let mut spawns = spawns.into_iter().peekable();
+        let mut group = Vec::new();
+        while let Some(first) = spawns.next() {
+            let ids = first.ids;
+            group.push(first);
+            while let Some(next) = spawns.next_if(|x| x.ids == ids) {
+                group.push(next);
+            }
+            unsafe {
+                world.spawn_same_archetype(group.drain(..));
+            }
+        }
+    }
+
+    for buffer in &taken {
+        for index in &buffer.entities {
+            if let Some(entity) = index.entity {
+                let _ = world.insert(entity, buffer.recorded(index));
+            }
+        }
+    }
+
+    for buffer in buffers.iter_mut() {
+        for x in buffer.remove_comps.drain(..) {
+            (x.remove)(world, x.entity);
+        }
+    }
+
+    for buffer in buffers.iter_mut() {
+        for (entity, reason) in buffer.despawn_ent.drain(..) {
+            let _ = match reason {
+                Some(reason) => world.despawn_with_reason(entity, reason),
+                None => world.despawn(entity),
+            };
+        }
+    }
+
+    // Hand allocations back for reuse
+    for (buffer, taken) in buffers.iter_mut().zip(taken) {
+        let Taken {
+            mut entities,
+            mut components,
+            mut ids,
+            ..
+        } = taken;
+        entities.clear();
+        components.clear();
+        ids.clear();
+        buffer.entities = entities;
+        buffer.components = components;
+        buffer.ids = ids;
+    }
+}
+
+/// Bookkeeping removed from a `CommandBuffer` while its operations are applied
+struct Taken {
+    storage: NonNull<u8>,
+    entities: Vec<EntityIndex>,
+    components: Vec<ComponentInfo>,
+    ids: Vec<TypeId>,
+}
+
+impl Taken {
+    fn recorded(&self, index: &EntityIndex) -> RecordedEntity<'_> {
+        RecordedEntity {
+            storage: self.storage,
+            ids: &self.ids[index.components.clone()],
+            components: &self.components[index.components.clone()],
+        }
+    }
+}
+
+/// The components of a single recorded spawn or insert
+struct RecordedEntity<'a> {
+    storage: NonNull<u8>,
+    ids: &'a [TypeId],
+    components: &'a [ComponentInfo],
+}
+
+unsafe impl DynamicBundle for RecordedEntity<'_> {
+    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+        f(self.ids)
+    }
+
+    fn type_info(&self) -> Vec<TypeInfo> {
+        self.components.iter().map(|x| x.ty).collect()
+    }
+
+    unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeInfo)) {
+        for info in self.components {
+            f(self.storage.as_ptr().add(info.offset), info.ty);
+        }
+        mem::forget(self);
+    }
+}
+
+impl Drop for RecordedEntity<'_> {
+    fn drop(&mut self) {
+        // Reached only if the components were never moved into the world, e.g. because the target
+        // entity was despawned.
+        for info in self.components {
+            unsafe {
+                info.ty.drop(self.storage.as_ptr().add(info.offset));
+            }
+        }
+    }
+}
+
+struct EntityIndex {
+    entity: Option<Entity>,
+    components: Range<usize>,
+}
+
+#[derive(Copy, Clone)]
+struct ComponentInfo {
+    ty: TypeInfo,
+    offset: usize,
+}
+
+struct RemovedComps {
+    remove: fn(&mut World, Entity),
+    entity: Entity,
+}
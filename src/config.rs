@@ -0,0 +1,206 @@
+/// Tunable parameters of a [`World`](crate::World)
+///
+/// A world's behavior is a deterministic function of its configuration and the sequence of
+/// operations applied to it: hashing uses fixed keys, and archetypes are iterated in creation
+/// order. Storing the configuration alongside saves or replays, and restoring it with
+/// [`World::from_config`](crate::World::from_config), therefore reproduces the same entity IDs,
+/// iteration orders, and allocation patterns.
+///
+/// Serializable when the `serde` feature is enabled.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut config = WorldConfig::default();
+/// config.archetype_min_capacity = 1024;
+/// let world = World::from_config(config);
+/// assert_eq!(world.config().archetype_min_capacity, 1024);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct WorldConfig {
+    /// Minimum number of entities an archetype makes room for when it runs out of space
+    ///
+    /// Archetypes otherwise double in capacity. Larger values trade memory for fewer
+    /// reallocations in worlds with many entities per archetype. Defaults to 64.
+    pub archetype_min_capacity: u32,
+    /// Whether archetypes record the entities that enter and exit them
+    ///
+    /// Enables [`Archetype::entered`](crate::Archetype::entered) and
+    /// [`Archetype::exited`](crate::Archetype::exited), letting systems that mirror the world, e.g.
+    /// into a collision world, process only membership changes. Records are kept until
+    /// [`World::clear_trackers`](crate::World::clear_trackers) is called. Defaults to `false`.
+    pub track_membership: bool,
+    /// Whether to check the world's consistency whenever change flags are reset
+    ///
+    /// In builds with debug assertions, [`World::clear_changed`](crate::World::clear_changed) and
+    /// [`World::clear_trackers`](crate::World::clear_trackers) then call
+    /// [`World::validate`](crate::World::validate), panicking if it fails, so that corruption is
+    /// caught within a frame of occurring. Has no effect in release builds. Defaults to `false`.
+    pub validate: bool,
+    /// Whether freed entity IDs are reused in the order they were freed
+    ///
+    /// By default, the most recently freed ID is reused first, which keeps the set of IDs in use
+    /// compact but means a despawned entity's ID is typically handed to the very next entity
+    /// spawned. Reusing the least recently freed ID first instead maximizes the time before an
+    /// ID reappears, making logs easier to follow and giving network peers longer to learn of a
+    /// despawn. Defaults to `false`.
+    pub fifo_id_reuse: bool,
+    /// Minimum number of frames before a freed entity ID is reused
+    ///
+    /// Frames are counted by [`World::end_frame`](crate::World::end_frame), which
+    /// [`World::advance_tick`](crate::World::advance_tick),
+    /// [`World::clear_changed`](crate::World::clear_changed), and
+    /// [`World::clear_trackers`](crate::World::clear_trackers) call, and which flushes reserved
+    /// entities. No other operation advances the count. While IDs wait, spawns use fresh IDs
+    /// instead. IDs of entities in namespaces reserved with
+    /// [`World::reserve_id_range`](crate::World::reserve_id_range) are exempt from both this and
+    /// `fifo_id_reuse`. Defaults to 0.
+    pub id_reuse_delay: u32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            archetype_min_capacity: 64,
+            track_membership: false,
+            validate: false,
+            fifo_id_reuse: false,
+            id_reuse_delay: 0,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use crate::alloc::string::String;
+    use core::fmt;
+
+    use serde::{
+        de::{self, MapAccess, SeqAccess, Visitor},
+        ser::SerializeStruct,
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use super::WorldConfig;
+
+    const FIELDS: &[&str] = &[
+        "archetype_min_capacity",
+        "track_membership",
+        "validate",
+        "fifo_id_reuse",
+        "id_reuse_delay",
+    ];
+
+    impl Serialize for WorldConfig {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("WorldConfig", FIELDS.len())?;
+            s.serialize_field("archetype_min_capacity", &self.archetype_min_capacity)?;
+            s.serialize_field("track_membership", &self.track_membership)?;
+            s.serialize_field("validate", &self.validate)?;
+            s.serialize_field("fifo_id_reuse", &self.fifo_id_reuse)?;
+            s.serialize_field("id_reuse_delay", &self.id_reuse_delay)?;
+            s.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for WorldConfig {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_struct("WorldConfig", FIELDS, ConfigVisitor)
+        }
+    }
+
+    struct ConfigVisitor;
+
+    impl<'de> Visitor<'de> for ConfigVisitor {
+        type Value = WorldConfig;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a world configuration")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<WorldConfig, A::Error> {
+            let archetype_min_capacity = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let track_membership = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let validate = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            let fifo_id_reuse = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+            let id_reuse_delay = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(4, &self))?;
+            Ok(WorldConfig {
+                archetype_min_capacity,
+                track_membership,
+                validate,
+                fifo_id_reuse,
+                id_reuse_delay,
+            })
+        }
+
+        /// Fields missing from the input take their default values, so that configurations saved
+        /// by older versions remain loadable
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<WorldConfig, A::Error> {
+            let mut config = WorldConfig::default();
+            while let Some(key) = map.next_key::<String>()? {
+                match &*key {
+                    "archetype_min_capacity" => config.archetype_min_capacity = map.next_value()?,
+                    "track_membership" => config.track_membership = map.next_value()?,
+                    "validate" => config.validate = map.next_value()?,
+                    "fifo_id_reuse" => config.fifo_id_reuse = map.next_value()?,
+                    "id_reuse_delay" => config.id_reuse_delay = map.next_value()?,
+                    _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                }
+            }
+            Ok(config)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde_test::{assert_de_tokens, assert_tokens, Token};
+
+        use super::*;
+
+        #[test]
+        #[rustfmt::skip]
+        fn roundtrip() {
+            let config = WorldConfig {
+                archetype_min_capacity: 7,
+                track_membership: true,
+                validate: true,
+                fifo_id_reuse: true,
+                id_reuse_delay: 3,
+            };
+            assert_tokens(&config, &[
+                Token::Struct { name: "WorldConfig", len: 5 },
+                Token::Str("archetype_min_capacity"),
+                Token::U32(7),
+                Token::Str("track_membership"),
+                Token::Bool(true),
+                Token::Str("validate"),
+                Token::Bool(true),
+                Token::Str("fifo_id_reuse"),
+                Token::Bool(true),
+                Token::Str("id_reuse_delay"),
+                Token::U32(3),
+                Token::StructEnd,
+            ]);
+        }
+
+        #[test]
+        #[rustfmt::skip]
+        fn missing_fields() {
+            assert_de_tokens(&WorldConfig::default(), &[
+                Token::Struct { name: "WorldConfig", len: 0 },
+                Token::StructEnd,
+            ]);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::Entity;
+
+/// Mapping from entities to indices into an application's own dense arrays
+///
+/// Lookups index directly by `Entity::id`, avoiding the hashing a `HashMap<Entity, usize>` would
+/// require, which matters when correlating every entity visited by a query with external
+/// struct-of-arrays data. See [`QueryBorrow::iter_with_dense_index`](crate::QueryBorrow::iter_with_dense_index).
+///
+/// Memory use is proportional to the largest entity ID inserted.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1.0f32,));
+/// let b = world.spawn((2.0f32,));
+/// let mut masses = vec![10.0f32, 20.0];
+/// let mut map = EntityDenseMap::new();
+/// map.insert(a, 1);
+/// map.insert(b, 0);
+/// for (_, index, x) in world.query::<&f32>().iter_with_dense_index(&map) {
+///     masses[index.unwrap()] += x;
+/// }
+/// assert_eq!(masses, [12.0, 21.0]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct EntityDenseMap {
+    /// Generation and index of each entity ID, or `EMPTY`
+    slots: Vec<(u32, u32)>,
+    len: usize,
+}
+
+const EMPTY: (u32, u32) = (0, u32::MAX);
+
+impl EntityDenseMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Associate `entity` with `index`, returning the index it was previously associated with
+    ///
+    /// Replaces the entry of any other entity sharing `entity`'s ID, as only one of them can be
+    /// live.
+    pub fn insert(&mut self, entity: Entity, index: usize) -> Option<usize> {
+        let index = u32::try_from(index)
+            .ok()
+            .filter(|&x| x != u32::MAX)
+            .expect("dense index too large");
+        let id = entity.id as usize;
+        if id >= self.slots.len() {
+            self.slots.resize(id + 1, EMPTY);
+        }
+        let old = core::mem::replace(&mut self.slots[id], (entity.generation.get(), index));
+        if old.1 == u32::MAX {
+            self.len += 1;
+            None
+        } else if old.0 == entity.generation.get() {
+            Some(old.1 as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The index associated with `entity`, if any
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<usize> {
+        match self.slots.get(entity.id as usize) {
+            Some(&(generation, index))
+                if index != u32::MAX && generation == entity.generation.get() =>
+            {
+                Some(index as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Forget `entity`, returning the index it was associated with
+    pub fn remove(&mut self, entity: Entity) -> Option<usize> {
+        let index = self.get(entity)?;
+        self.slots[entity.id as usize] = EMPTY;
+        self.len -= 1;
+        Some(index)
+    }
+
+    /// Number of entities with an associated index
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no entities have an associated index
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forget every entity
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+}
+
+/// Stable indices handed out by `World::dense_index`
+///
+/// Indices are recycled, most recently freed first, so they stay below the peak number of
+/// entities holding one at once.
+#[derive(Default, Clone)]
+pub(crate) struct DenseIndices {
+    /// Index of each entity ID, or `u32::MAX`
+    by_id: Vec<u32>,
+    free: Vec<u32>,
+    /// One past the largest index ever handed out
+    bound: u32,
+}
+
+impl DenseIndices {
+    pub(crate) fn get(&self, id: u32) -> Option<u32> {
+        self.by_id
+            .get(id as usize)
+            .copied()
+            .filter(|&x| x != u32::MAX)
+    }
+
+    /// The index of the entity with ID `id`, allocating one if necessary
+    pub(crate) fn get_or_alloc(&mut self, id: u32) -> u32 {
+        if let Some(index) = self.get(id) {
+            return index;
+        }
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.bound += 1;
+            self.bound - 1
+        });
+        if id as usize >= self.by_id.len() {
+            self.by_id.resize(id as usize + 1, u32::MAX);
+        }
+        self.by_id[id as usize] = index;
+        index
+    }
+
+    /// Free the index of the entity with ID `id`, returning it
+    pub(crate) fn forget(&mut self, id: u32) -> Option<u32> {
+        let index = self.get(id)?;
+        self.by_id[id as usize] = u32::MAX;
+        self.free.push(index);
+        Some(index)
+    }
+
+    pub(crate) fn bound(&self) -> u32 {
+        self.bound
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.by_id.clear();
+        self.free.clear();
+        self.bound = 0;
+    }
+}
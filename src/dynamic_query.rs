@@ -65,7 +65,7 @@ impl ComponentSlice {
     /// Panics if `T` is not the type of the components in this slice.
     pub fn as_slice<T: 'static>(&self) -> &[T] {
         assert_eq!(TypeId::of::<T>(), self.typ, "component type does not match");
-        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr() as *const T, self.len) }
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr() as *const T, self.len) }
     }
 }
 
@@ -125,6 +125,6 @@ impl<'q> DynamicQuery<'q> {
         self.archetypes
             .iter()
             .filter(move |archetype| archetype.access_dynamic(&self.types).is_some())
-            .filter(|archetype| archetype.len() > 0)
+            .filter(|archetype| !archetype.is_empty())
     }
 }
@@ -1,7 +1,9 @@
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use core::cmp;
 use core::convert::TryFrom;
 use core::iter::ExactSizeIterator;
+use core::num::NonZeroU32;
 use core::ops::Range;
 use core::sync::atomic::{AtomicI64, Ordering};
 use core::{fmt, mem};
@@ -14,33 +16,57 @@ use std::error::Error;
 ///
 /// Enable the `serde` feature on the crate to make this `Serialize`able. Some applications may be
 /// able to save space by only serializing the output of `Entity::id`.
+///
+/// Generations are never zero, so `Option<Entity>` is the same size as `Entity`.
 #[derive(Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Entity {
-    pub(crate) generation: u32,
+    pub(crate) generation: NonZeroU32,
     pub(crate) id: u32,
 }
 
 impl Entity {
+    /// An `Entity` that doesn't correspond to any entity in a `World`
+    ///
+    /// Useful as a placeholder, e.g. for slots of a data structure that haven't been filled in
+    /// yet. Like any handle to a despawned entity, it's rejected by `World` methods. Its
+    /// generation, the maximum, is never handed out, and can't be passed to `World::spawn_at`.
+    pub const DANGLING: Entity = Entity {
+        generation: NonZeroU32::MAX,
+        id: u32::MAX,
+    };
+
     /// Convert to a form convenient for passing outside of rust
     ///
-    /// No particular structure is guaranteed for the returned bits.
+    /// The generation occupies the high 32 bits and the `id` the low 32 bits, a layout which is
+    /// stable and may be relied upon by network protocols and save files.
     ///
     /// Useful for storing entity IDs externally, or in conjunction with `Entity::from_bits` and
     /// `World::spawn_at` for easy serialization. Alternatively, consider `id` for more compact
     /// representation.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let bits = a.to_bits();
+    /// assert_eq!(bits as u32, a.id());
+    /// assert_eq!(Entity::from_bits(bits), Some(a));
+    /// ```
     pub fn to_bits(self) -> u64 {
-        u64::from(self.generation) << 32 | u64::from(self.id)
+        u64::from(self.generation.get()) << 32 | u64::from(self.id)
     }
 
     /// Reconstruct an `Entity` previously destructured with `to_bits`
     ///
     /// Useful for storing entity IDs externally, or in conjunction with `Entity::to_bits` and
-    /// `World::spawn_at` for easy serialization.
-    pub fn from_bits(bits: u64) -> Self {
-        Self {
-            generation: (bits >> 32) as u32,
+    /// `World::spawn_at` for easy serialization. Returns `None` if the generation, the high 32
+    /// bits, is zero, which no `Entity` has.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Some(Self {
+            generation: NonZeroU32::new((bits >> 32) as u32)?,
             id: bits as u32,
-        }
+        })
     }
 
     /// Extract a transiently unique identifier
@@ -78,7 +104,12 @@ impl<'de> serde::Deserialize<'de> for Entity {
         D: serde::Deserializer<'de>,
     {
         let bits = u64::deserialize(deserializer)?;
-        Ok(Entity::from_bits(bits))
+        Entity::from_bits(bits).ok_or_else(|| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(bits),
+                &"an entity with a nonzero generation",
+            )
+        })
     }
 }
 
@@ -104,7 +135,12 @@ impl<'a> Iterator for ReserveEntitiesIterator<'a> {
                 generation: self.meta[id as usize].generation,
                 id,
             })
-            .or_else(|| self.id_range.next().map(|id| Entity { generation: 0, id }))
+            .or_else(|| {
+                self.id_range.next().map(|id| Entity {
+                    generation: FIRST_GENERATION,
+                    id,
+                })
+            })
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -157,13 +193,85 @@ pub(crate) struct Entities {
     pending: Vec<u32>,
     free_cursor: AtomicI64,
     len: u32,
+    // IDs set aside by `reserve_namespace`, which are never in `pending`
+    namespaces: Vec<Namespace>,
+    // Freed IDs that aren't yet in `pending`, if the reuse policy isn't the default
+    recycling: Recycling,
+    // Totals for `World::stats`, which aren't copied by `clone_from`
+    #[cfg(feature = "stats")]
+    pub spawned: u64,
+    #[cfg(feature = "stats")]
+    pub despawned: u64,
+}
+
+/// Order and delay with which freed IDs become reusable
+///
+/// Under the default policy, IDs are pushed straight onto `pending` and the most recently freed is
+/// reused first. Otherwise, freed IDs are queued here and moved into `pending` by `release`.
+#[derive(Clone, Default)]
+struct Recycling {
+    fifo: bool,
+    /// Frames an ID must wait before it's reused
+    delay: u32,
+    /// Number of calls to `end_frame`
+    frame: u64,
+    /// IDs and the frame they were freed in, oldest first
+    queue: VecDeque<(u32, u64)>,
+}
+
+impl Recycling {
+    fn active(&self) -> bool {
+        self.fifo || self.delay != 0
+    }
+}
+
+#[derive(Clone)]
+struct Namespace {
+    ids: Range<u32>,
+    free: Vec<u32>,
+}
+
+impl Clone for Entities {
+    fn clone(&self) -> Self {
+        let mut clone = Self::default();
+        clone.clone_from(self);
+        clone
+    }
+
+    /// Reuses `self`'s allocations, so that e.g. restoring a `WorldSnapshot` needn't allocate
+    fn clone_from(&mut self, source: &Self) {
+        self.meta.clone_from(&source.meta);
+        self.pending.clone_from(&source.pending);
+        self.free_cursor.store(
+            source.free_cursor.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.len = source.len;
+        self.namespaces.clone_from(&source.namespaces);
+        self.recycling.clone_from(&source.recycling);
+    }
 }
 
 impl Entities {
+    /// Bytes allocated for entity metadata and ID bookkeeping
+    pub fn memory_usage(&self) -> usize {
+        self.meta.capacity() * mem::size_of::<EntityMeta>()
+            + self.pending.capacity() * mem::size_of::<u32>()
+            + self.namespaces.capacity() * mem::size_of::<Namespace>()
+            + self.recycling.queue.capacity() * mem::size_of::<(u32, u64)>()
+    }
+
+    /// Release memory not needed to track the current entities
+    pub fn shrink_to_fit(&mut self) {
+        self.verify_flushed();
+        self.meta.shrink_to_fit();
+        self.pending.shrink_to_fit();
+    }
+
     /// Reserve entity IDs concurrently
     ///
     /// Storage for entity generation and location is lazily allocated by calling `flush`.
-    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator {
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator<'_> {
         // Use one atomic subtract to grab a range of new IDs. The range might be
         // entirely nonnegative, meaning all IDs come from the freelist, or entirely
         // negative, meaning they are all new IDs to allocate, or a mix of both.
@@ -221,7 +329,7 @@ impl Entities {
             // As `self.free_cursor` goes more and more negative, we return IDs farther
             // and farther beyond `meta.len()`.
             Entity {
-                generation: 0,
+                generation: FIRST_GENERATION,
                 id: u32::try_from(self.meta.len() as i64 - n).expect("too many entities"),
             }
         }
@@ -240,8 +348,11 @@ impl Entities {
     /// Location should be written immediately.
     pub fn alloc(&mut self) -> Entity {
         self.verify_flushed();
+        if self.pending.is_empty() {
+            self.release();
+        }
 
-        self.len += 1;
+        self.count_spawns(1);
         if let Some(id) = self.pending.pop() {
             let new_free_cursor = self.pending.len() as i64;
             self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
@@ -252,7 +363,10 @@ impl Entities {
         } else {
             let id = u32::try_from(self.meta.len()).expect("too many entities");
             self.meta.push(EntityMeta::EMPTY);
-            Entity { generation: 0, id }
+            Entity {
+                generation: FIRST_GENERATION,
+                id,
+            }
         }
     }
 
@@ -261,6 +375,9 @@ impl Entities {
     /// `self.finish_alloc_many()` must be called after!
     pub fn alloc_many(&mut self, n: u32, archetype: u32, mut first_index: u32) -> AllocManyState {
         self.verify_flushed();
+        if self.pending.len() < n as usize {
+            self.release();
+        }
 
         let fresh = (n as usize).saturating_sub(self.pending.len()) as u32;
         assert!(
@@ -279,12 +396,13 @@ impl Entities {
         let fresh_start = self.meta.len() as u32;
         self.meta.extend(
             (first_index..(first_index + fresh)).map(|index| EntityMeta {
-                generation: 0,
+                generation: FIRST_GENERATION,
                 location: Location { archetype, index },
+                user_data: 0,
             }),
         );
 
-        self.len += n;
+        self.count_spawns(n);
 
         AllocManyState {
             fresh: fresh_start..(fresh_start + fresh),
@@ -301,54 +419,107 @@ impl Entities {
 
     /// Allocate a specific entity ID, overwriting its generation
     ///
-    /// Returns the location of the entity currently using the given ID, if any. Location should be written immediately.
-    pub fn alloc_at(&mut self, entity: Entity) -> Option<Location> {
+    /// Returns the entity currently using the given ID, if any, and its location. Location should be written immediately.
+    pub fn alloc_at(&mut self, entity: Entity) -> Option<(Entity, Location)> {
         self.verify_flushed();
+        assert!(
+            entity.generation != RETIRED,
+            "the maximum generation is reserved for retired entity IDs"
+        );
 
         let loc = if entity.id as usize >= self.meta.len() {
             self.pending.extend((self.meta.len() as u32)..entity.id);
             let new_free_cursor = self.pending.len() as i64;
             self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
             self.meta.resize(entity.id as usize + 1, EntityMeta::EMPTY);
-            self.len += 1;
+            self.count_spawns(1);
+            None
+        } else if let Some(namespace) = self.namespace_of(entity.id) {
+            let free = &mut self.namespaces[namespace].free;
+            if let Some(index) = free.iter().position(|item| *item == entity.id) {
+                free.swap_remove(index);
+                self.count_spawns(1);
+                None
+            } else {
+                Some(self.evict(entity.id))
+            }
+        } else if let Some(index) = self
+            .recycling
+            .queue
+            .iter()
+            .position(|&(id, _)| id == entity.id)
+        {
+            self.recycling.queue.remove(index);
+            self.count_spawns(1);
             None
         } else if let Some(index) = self.pending.iter().position(|item| *item == entity.id) {
             self.pending.swap_remove(index);
             let new_free_cursor = self.pending.len() as i64;
             self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
-            self.len += 1;
+            self.count_spawns(1);
+            None
+        } else if self.meta[entity.id as usize].generation == RETIRED {
+            self.count_spawns(1);
             None
         } else {
-            Some(mem::replace(
-                &mut self.meta[entity.id as usize].location,
-                EntityMeta::EMPTY.location,
-            ))
+            Some(self.evict(entity.id))
         };
 
         self.meta[entity.id as usize].generation = entity.generation;
+        self.meta[entity.id as usize].user_data = 0;
 
         loc
     }
 
+    /// The live entity with ID `id`, and its location, which is reset
+    fn evict(&mut self, id: u32) -> (Entity, Location) {
+        let meta = &mut self.meta[id as usize];
+        let entity = Entity {
+            id,
+            generation: meta.generation,
+        };
+        (
+            entity,
+            mem::replace(&mut meta.location, EntityMeta::EMPTY.location),
+        )
+    }
+
     /// Destroy an entity, allowing it to be reused
     ///
     /// Must not be called while reserved entities are awaiting `flush()`.
     pub fn free(&mut self, entity: Entity) -> Result<Location, NoSuchEntity> {
         self.verify_flushed();
 
-        let meta = &mut self.meta[entity.id as usize];
-        if meta.generation != entity.generation {
+        let meta = self.meta.get_mut(entity.id as usize).ok_or(NoSuchEntity)?;
+        if !meta.is(entity) {
             return Err(NoSuchEntity);
         }
-        meta.generation += 1;
-
         let loc = mem::replace(&mut meta.location, EntityMeta::EMPTY.location);
-
-        self.pending.push(entity.id);
-
-        let new_free_cursor = self.pending.len() as i64;
-        self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        meta.user_data = 0;
         self.len -= 1;
+        #[cfg(feature = "stats")]
+        {
+            self.despawned += 1;
+        }
+
+        match next_generation(meta.generation) {
+            Some(generation) => meta.generation = generation,
+            None => {
+                // Reusing the ID would alias a stale handle, so it's never handed out again
+                meta.generation = RETIRED;
+                return Ok(loc);
+            }
+        }
+        if let Some(namespace) = self.namespace_of(entity.id) {
+            self.namespaces[namespace].free.push(entity.id);
+        } else if self.recycling.active() {
+            let frame = self.recycling.frame;
+            self.recycling.queue.push_back((entity.id, frame));
+        } else {
+            self.pending.push(entity.id);
+            let new_free_cursor = self.pending.len() as i64;
+            self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        }
 
         Ok(loc)
     }
@@ -365,25 +536,132 @@ impl Entities {
     }
 
     pub fn contains(&self, entity: Entity) -> bool {
-        // Note that out-of-range IDs are considered to be "contained" because
-        // they must be reserved IDs that we haven't flushed yet.
-        self.meta
-            .get(entity.id as usize)
-            .map_or(true, |meta| meta.generation == entity.generation)
+        match self.meta.get(entity.id as usize) {
+            Some(meta) => meta.is(entity),
+            None => self.is_reserved(entity),
+        }
+    }
+
+    /// Whether `entity`, whose ID is outside of `meta`, was reserved and awaits `flush()`
+    ///
+    /// IDs beyond those reserved, e.g. from another world or corrupted data, are rejected.
+    fn is_reserved(&self, entity: Entity) -> bool {
+        let num_pending = cmp::max(-self.free_cursor.load(Ordering::Relaxed), 0) as usize;
+        entity.generation == FIRST_GENERATION
+            && (entity.id as usize) < self.meta.len() + num_pending
     }
 
+    /// Free every entity, retaining their IDs for reuse
     pub fn clear(&mut self) {
-        self.meta.clear();
+        self.verify_flushed();
+
         self.pending.clear();
-        self.free_cursor.store(0, Ordering::Relaxed); // Not racey due to &mut self
+        self.recycling.queue.clear();
+        for namespace in &mut self.namespaces {
+            namespace.free.clear();
+        }
+        // Pushed in reverse so that low IDs are handed out first
+        for (id, meta) in self.meta.iter_mut().enumerate().rev() {
+            meta.location = EntityMeta::EMPTY.location;
+            meta.user_data = 0;
+            meta.generation = match next_generation(meta.generation) {
+                Some(x) => x,
+                None => {
+                    meta.generation = RETIRED;
+                    continue;
+                }
+            };
+            let id = id as u32;
+            match self.namespaces.iter_mut().find(|x| x.ids.contains(&id)) {
+                Some(namespace) => namespace.free.push(id),
+                None if self.recycling.fifo => {
+                    self.recycling.queue.push_front((id, self.recycling.frame))
+                }
+                None if self.recycling.active() => {
+                    self.recycling.queue.push_back((id, self.recycling.frame))
+                }
+                None => self.pending.push(id),
+            }
+        }
+        let new_free_cursor = self.pending.len() as i64;
+        self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        #[cfg(feature = "stats")]
+        {
+            self.despawned += u64::from(self.len);
+        }
+        self.len = 0;
+    }
+
+    /// Set aside `ids` for allocation exclusively by `alloc_in`, returning the namespace's index
+    ///
+    /// Fails if any ID in `ids` is in use or belongs to another namespace.
+    pub fn reserve_namespace(&mut self, ids: Range<u32>) -> Option<u32> {
+        self.verify_flushed();
+
+        if ids.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|x| x.ids.start < ids.end && ids.start < x.ids.end)
+        {
+            return None;
+        }
+        // IDs in `meta` are unused iff they're in the freelist or awaiting reuse, which hold no
+        // duplicates
+        let existing = ids.start as usize..cmp::min(ids.end as usize, self.meta.len());
+        let free = self.pending.iter().filter(|&&id| ids.contains(&id)).count()
+            + (self.recycling.queue.iter())
+                .filter(|&&(id, _)| ids.contains(&id))
+                .count();
+        if free != existing.len() {
+            return None;
+        }
+
+        self.pending.retain(|id| !ids.contains(id));
+        self.recycling.queue.retain(|(id, _)| !ids.contains(id));
+        if ids.end as usize > self.meta.len() {
+            self.pending
+                .extend((self.meta.len() as u32)..cmp::max(ids.start, self.meta.len() as u32));
+            self.meta.resize(ids.end as usize, EntityMeta::EMPTY);
+        }
+        let new_free_cursor = self.pending.len() as i64;
+        self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+
+        self.namespaces.push(Namespace {
+            // Reversed so that low IDs are handed out first
+            free: ids.clone().rev().collect(),
+            ids,
+        });
+        Some(self.namespaces.len() as u32 - 1)
+    }
+
+    /// The IDs set aside for `namespace`
+    pub fn namespace_ids(&self, namespace: u32) -> Range<u32> {
+        self.namespaces[namespace as usize].ids.clone()
+    }
+
+    /// Allocate an entity ID from `namespace`, if any are unused
+    ///
+    /// Location should be written immediately.
+    pub fn alloc_in(&mut self, namespace: u32) -> Option<Entity> {
+        let id = self.namespaces[namespace as usize].free.pop()?;
+        self.count_spawns(1);
+        Some(Entity {
+            generation: self.meta[id as usize].generation,
+            id,
+        })
+    }
+
+    fn namespace_of(&self, id: u32) -> Option<usize> {
+        self.namespaces.iter().position(|x| x.ids.contains(&id))
     }
 
     /// Access the location storage of an entity
     ///
     /// Must not be called on pending entities.
     pub fn get_mut(&mut self, entity: Entity) -> Result<&mut Location, NoSuchEntity> {
-        let meta = &mut self.meta[entity.id as usize];
-        if meta.generation == entity.generation {
+        let meta = self.meta.get_mut(entity.id as usize).ok_or(NoSuchEntity)?;
+        if meta.is(entity) {
             Ok(&mut meta.location)
         } else {
             Err(NoSuchEntity)
@@ -393,19 +671,22 @@ impl Entities {
     /// Returns `Ok(Location { archetype: 0, index: undefined })` for pending entities
     pub fn get(&self, entity: Entity) -> Result<Location, NoSuchEntity> {
         if self.meta.len() <= entity.id as usize {
+            if !self.is_reserved(entity) {
+                return Err(NoSuchEntity);
+            }
             return Ok(Location {
                 archetype: 0,
-                index: u32::max_value(),
+                index: u32::MAX,
             });
         }
         let meta = &self.meta[entity.id as usize];
-        if meta.generation != entity.generation {
+        if !meta.is(entity) {
             return Err(NoSuchEntity);
         }
         if meta.location.archetype == 0 {
             return Ok(Location {
                 archetype: 0,
-                index: u32::max_value(),
+                index: u32::MAX,
             });
         }
         Ok(meta.location)
@@ -430,8 +711,11 @@ impl Entities {
             let num_pending = cmp::max(-free_cursor, 0) as usize;
 
             if meta_len + num_pending > id as usize {
-                // Pending entities will have generation 0.
-                Entity { generation: 0, id }
+                // Pending entities will have the first generation.
+                Entity {
+                    generation: FIRST_GENERATION,
+                    id,
+                }
             } else {
                 panic!("entity id is out of range");
             }
@@ -456,7 +740,7 @@ impl Entities {
             let new_meta_len = old_meta_len + -free_cursor as usize;
             self.meta.resize(new_meta_len, EntityMeta::EMPTY);
 
-            self.len += -free_cursor as u32;
+            self.count_spawns(-free_cursor as u32);
             for (id, meta) in self.meta.iter_mut().enumerate().skip(old_meta_len) {
                 init(id as u32, &mut meta.location);
             }
@@ -465,32 +749,120 @@ impl Entities {
             0
         };
 
-        self.len += (self.pending.len() - new_free_cursor) as u32;
+        self.count_spawns((self.pending.len() - new_free_cursor) as u32);
         for id in self.pending.drain(new_free_cursor..) {
             init(id, &mut self.meta[id as usize].location);
         }
     }
 
+    /// Account for `n` newly live entities
+    #[inline]
+    fn count_spawns(&mut self, n: u32) {
+        self.len += n;
+        #[cfg(feature = "stats")]
+        {
+            self.spawned += u64::from(n);
+        }
+    }
+
     #[inline]
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    /// IDs in `meta` that no live entity uses: those awaiting reuse or `flush()`, and those retired
+    pub fn idle_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        let retired = self
+            .meta
+            .iter()
+            .enumerate()
+            .filter(|(_, meta)| meta.generation == RETIRED)
+            .map(|(id, _)| id as u32);
+        self.pending
+            .iter()
+            .chain(self.namespaces.iter().flat_map(|x| x.free.iter()))
+            .copied()
+            .chain(self.recycling.queue.iter().map(|&(id, _)| id))
+            .chain(retired)
+    }
+
+    /// Choose the order and delay with which freed IDs are reused
+    ///
+    /// Must be called before any IDs are freed.
+    pub fn set_recycling(&mut self, fifo: bool, delay: u32) {
+        debug_assert!(self.recycling.queue.is_empty());
+        self.recycling.fifo = fifo;
+        self.recycling.delay = delay;
+    }
+
+    /// Count a frame towards the delay before freed IDs are reused
+    pub fn end_frame(&mut self) {
+        self.verify_flushed();
+        self.recycling.frame += 1;
+        self.release();
+    }
+
+    /// Make queued IDs that have waited long enough available for reuse
+    fn release(&mut self) {
+        let recycling = &mut self.recycling;
+        let ready = recycling
+            .queue
+            .iter()
+            .take_while(|&&(_, freed)| freed + u64::from(recycling.delay) <= recycling.frame)
+            .count();
+        if ready == 0 {
+            return;
+        }
+        let ids = recycling.queue.drain(..ready).map(|(id, _)| id);
+        if recycling.fifo {
+            // `pending` is used from the back, so older IDs go behind those already there
+            let older = mem::take(&mut self.pending);
+            self.pending.extend(ids.rev());
+            self.pending.extend(older);
+        } else {
+            self.pending.extend(ids);
+        }
+        let new_free_cursor = self.pending.len() as i64;
+        self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+    }
+}
+
+/// Generation of entity IDs that have never been freed
+const FIRST_GENERATION: NonZeroU32 = NonZeroU32::MIN;
+
+/// Generation of entity IDs that have been freed with every other generation, which is never
+/// handed out
+const RETIRED: NonZeroU32 = NonZeroU32::MAX;
+
+/// Generation of an entity ID after a handle with `generation` is freed, if any remain
+fn next_generation(generation: NonZeroU32) -> Option<NonZeroU32> {
+    generation.checked_add(1).filter(|&x| x != RETIRED)
 }
 
 #[derive(Copy, Clone)]
 pub(crate) struct EntityMeta {
-    pub generation: u32,
+    pub generation: NonZeroU32,
     pub location: Location,
+    /// Set by `World::set_user_data`, and reset to 0 when the entity is freed
+    pub user_data: u32,
 }
 
 impl EntityMeta {
     const EMPTY: EntityMeta = EntityMeta {
-        generation: 0,
+        generation: FIRST_GENERATION,
         location: Location {
             archetype: 0,
-            index: u32::max_value(), // dummy value, to be filled in
+            index: u32::MAX, // dummy value, to be filled in
         },
+        user_data: 0,
     };
+
+    /// Whether this describes the live entity `entity`
+    ///
+    /// Retired IDs store `RETIRED` as their generation, which no live handle may have.
+    fn is(&self, entity: Entity) -> bool {
+        self.generation == entity.generation && entity.generation != RETIRED
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -512,6 +884,26 @@ impl fmt::Display for NoSuchEntity {
 #[cfg(feature = "std")]
 impl Error for NoSuchEntity {}
 
+/// A contiguous range of entity IDs set aside from general allocation
+///
+/// Obtained from [`World::reserve_id_range`](crate::World::reserve_id_range). Only meaningful to
+/// the world it came from.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct IdNamespace(pub(crate) u32);
+
+/// Error indicating that a range of entity IDs could not be reserved
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdRangeUnavailable;
+
+impl fmt::Display for IdRangeUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("entity ID range unavailable")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for IdRangeUnavailable {}
+
 #[derive(Clone)]
 pub(crate) struct AllocManyState {
     pub pending_end: usize,
@@ -543,10 +935,11 @@ mod tests {
     #[test]
     fn entity_bits_roundtrip() {
         let e = Entity {
-            generation: 0xDEADBEEF,
+            generation: NonZeroU32::new(0xDEADBEEF).unwrap(),
             id: 0xBAADF00D,
         };
-        assert_eq!(Entity::from_bits(e.to_bits()), e);
+        assert_eq!(Entity::from_bits(e.to_bits()), Some(e));
+        assert_eq!(Entity::from_bits(0xBAADF00D), None);
     }
 
     #[test]
@@ -555,7 +948,7 @@ mod tests {
 
         let mut e = Entities::default();
         let mut first_unused = 0u32;
-        let mut id_to_gen: HashMap<u32, u32> = Default::default();
+        let mut id_to_gen: HashMap<u32, NonZeroU32> = Default::default();
         let mut free_set: HashSet<u32> = Default::default();
         let mut len = 0;
 
@@ -583,7 +976,7 @@ mod tests {
                 let generation = id_to_gen.remove(&id);
                 let entity = Entity {
                     id,
-                    generation: generation.unwrap_or(0),
+                    generation: generation.unwrap_or(FIRST_GENERATION),
                 };
 
                 assert_eq!(e.free(entity).is_ok(), generation.is_some());
@@ -637,7 +1030,7 @@ mod tests {
         assert!(e
             .alloc_at(Entity {
                 id: 3,
-                generation: 2,
+                generation: NonZeroU32::new(2).unwrap(),
             })
             .is_none());
         assert_eq!(e.pending.len(), 2);
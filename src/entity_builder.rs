@@ -17,7 +17,10 @@ use crate::{align, Component, DynamicBundle};
 
 /// Helper for incrementally constructing a bundle of components with dynamic component types
 ///
-/// Prefer reusing the same builder over creating new ones repeatedly.
+/// Components are packed into a single byte buffer alongside their `TypeInfo`, rather than boxed
+/// individually, so building an entity allocates at most a few times however many components it
+/// has. Prefer reusing the same builder over creating new ones repeatedly, which avoids even
+/// those allocations once the buffer is large enough.
 ///
 /// ```
 /// # use hecs::*;
@@ -66,45 +69,77 @@ impl EntityBuilder {
     pub fn add_bundle(&mut self, bundle: impl DynamicBundle) -> &mut Self {
         unsafe {
             bundle.put(|ptr, ty| {
-                match self.indices.entry(ty.id()) {
-                    Entry::Occupied(occupied) => {
-                        let index = *occupied.get();
-                        let (ty, offset) = self.info[index];
-                        let storage = self.storage.as_ptr().add(offset);
-
-                        // Drop the existing value
-                        ty.drop(storage);
-
-                        // Overwrite the old value with our new one.
-                        ptr::copy_nonoverlapping(ptr, storage, ty.layout().size());
-                    }
-                    Entry::Vacant(vacant) => {
-                        let offset = align(self.cursor, ty.layout().align());
-                        let end = offset + ty.layout().size();
-                        if end > self.layout.size() || ty.layout().align() > self.layout.align() {
-                            let new_align = self.layout.align().max(ty.layout().align());
-                            let (new_storage, new_layout) =
-                                Self::grow(end, self.cursor, new_align, self.storage);
-                            if self.layout.size() != 0 {
-                                dealloc(self.storage.as_ptr(), self.layout);
-                            }
-                            self.storage = new_storage;
-                            self.layout = new_layout;
-                        }
-
-                        let addr = self.storage.as_ptr().add(offset);
-                        ptr::copy_nonoverlapping(ptr, addr, ty.layout().size());
-
-                        vacant.insert(self.info.len());
-                        self.info.push((ty, offset));
-                        self.cursor = end;
-                    }
+                if let Some(&index) = self.indices.get(&ty.id()) {
+                    let (ty, offset) = self.info[index];
+                    let storage = self.storage.as_ptr().add(offset);
+
+                    // Drop the existing value
+                    ty.drop(storage);
+
+                    // Overwrite the old value with our new one.
+                    ptr::copy_nonoverlapping(ptr, storage, ty.layout().size());
+                } else {
+                    let offset = self.alloc(ty);
+                    let addr = self.storage.as_ptr().add(offset);
+                    ptr::copy_nonoverlapping(ptr, addr, ty.layout().size());
+
+                    self.indices.insert(ty.id(), self.info.len());
+                    self.info.push((ty, offset));
                 }
             });
         }
         self
     }
 
+    /// Add a component of type `ty`, initialized in place by `init`
+    ///
+    /// The component is recorded only once `init` returns, so nothing is dropped twice or left
+    /// uninitialized if it panics.
+    ///
+    /// # Safety
+    ///
+    /// `init` must write a valid value of type `ty` to the address it's passed.
+    pub(crate) unsafe fn add_with(&mut self, ty: TypeInfo, init: impl FnOnce(*mut u8)) {
+        let offset = self.alloc(ty);
+        init(self.storage.as_ptr().add(offset));
+        match self.indices.entry(ty.id()) {
+            Entry::Occupied(occupied) => {
+                let slot = &mut self.info[*occupied.get()];
+                ty.drop(self.storage.as_ptr().add(slot.1));
+                slot.1 = offset;
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(self.info.len());
+                self.info.push((ty, offset));
+            }
+        }
+    }
+
+    /// Reserve uninitialized space for a component of type `ty`, returning its offset
+    unsafe fn alloc(&mut self, ty: TypeInfo) -> usize {
+        let offset = align(self.cursor, ty.layout().align());
+        let end = offset + ty.layout().size();
+        if end > self.layout.size() || ty.layout().align() > self.layout.align() {
+            let new_align = self.layout.align().max(ty.layout().align());
+            let (new_storage, new_layout) = Self::grow(end, self.cursor, new_align, self.storage);
+            if self.layout.size() != 0 {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+            self.storage = new_storage;
+            self.layout = new_layout;
+        }
+        self.cursor = end;
+        offset
+    }
+
+    /// Enumerate the components' types and addresses
+    pub(crate) fn components(&self) -> impl Iterator<Item = (TypeInfo, *const u8)> + '_ {
+        self.info.iter().map(move |&(ty, offset)| {
+            (ty, unsafe { self.storage.as_ptr().add(offset) }
+                as *const u8)
+        })
+    }
+
     /// Checks to see if the component of type `T` exists
     pub fn has<T: Component>(&self) -> bool {
         self.indices.contains_key(&TypeId::of::<T>())
@@ -130,6 +165,33 @@ impl EntityBuilder {
         }
     }
 
+    /// Take the component of type `T` out of the builder, if it exists
+    ///
+    /// Useful for overriding parts of a template, such as a prefab or a loot table entry, before
+    /// spawning it.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut builder = EntityBuilder::new();
+    /// builder.add_bundle((123, "abc"));
+    /// assert_eq!(builder.remove::<i32>(), Some(123));
+    /// assert!(!builder.has::<i32>());
+    /// assert_eq!(builder.remove::<i32>(), None);
+    /// let mut world = World::new();
+    /// let e = world.spawn(builder.build());
+    /// assert_eq!(world.entity(e).unwrap().component_types().count(), 1);
+    /// ```
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        let index = self.indices.remove(&TypeId::of::<T>())?;
+        let (_, offset) = self.info.swap_remove(index);
+        if let Some(&(moved, _)) = self.info.get(index) {
+            self.indices.insert(moved.id(), index);
+        }
+        // The storage isn't reclaimed until the builder is cleared
+        unsafe { Some(self.storage.as_ptr().add(offset).cast::<T>().read()) }
+    }
+
     /// Enumerate the types of the entity builder's components
     pub fn component_types(&self) -> impl Iterator<Item = TypeId> + '_ {
         self.info.iter().map(|(info, _)| info.id())
@@ -149,9 +211,15 @@ impl EntityBuilder {
 
     /// Construct a `Bundle` suitable for spawning
     pub fn build(&mut self) -> BuiltEntity<'_> {
+        self.prepare();
+        BuiltEntity { builder: self }
+    }
+
+    /// Sort components as required by `DynamicBundle`
+    fn prepare(&mut self) {
         self.info.sort_unstable_by_key(|x| x.0);
+        self.ids.clear();
         self.ids.extend(self.info.iter().map(|x| x.0.id()));
-        BuiltEntity { builder: self }
     }
 
     /// Drop previously `add`ed components
@@ -221,3 +289,62 @@ impl Drop for BuiltEntity<'_> {
         self.builder.clear();
     }
 }
+
+/// Components taken from a despawned entity by [`World::take`](crate::World::take)
+///
+/// Can be passed to `World::spawn` or `World::insert` of any world to restore the components.
+/// Components that are never restored are dropped along with the `TakenEntity`.
+#[derive(Default)]
+pub struct TakenEntity {
+    builder: EntityBuilder,
+}
+
+impl TakenEntity {
+    pub(crate) fn new(mut builder: EntityBuilder) -> Self {
+        builder.prepare();
+        Self { builder }
+    }
+
+    /// Checks to see if the component of type `T` exists
+    pub fn has<T: Component>(&self) -> bool {
+        self.builder.has::<T>()
+    }
+
+    /// Borrow the component of type `T`, if it exists
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.builder.get::<T>()
+    }
+
+    /// Uniquely borrow the component of type `T`, if it exists
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.builder.get_mut::<T>()
+    }
+
+    /// Enumerate the types of the taken components
+    pub fn component_types(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.builder.component_types()
+    }
+
+    /// Convert into a builder, e.g. to add more components before spawning
+    pub fn into_builder(self) -> EntityBuilder {
+        self.builder
+    }
+}
+
+unsafe impl DynamicBundle for TakenEntity {
+    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+        f(&self.builder.ids)
+    }
+
+    #[doc(hidden)]
+    fn type_info(&self) -> Vec<TypeInfo> {
+        self.builder.info.iter().map(|x| x.0).collect()
+    }
+
+    unsafe fn put(mut self, f: impl FnMut(*mut u8, TypeInfo)) {
+        BuiltEntity {
+            builder: &mut self.builder,
+        }
+        .put(f)
+    }
+}
@@ -0,0 +1,58 @@
+use crate::alloc::vec::Vec;
+
+use crate::Entity;
+
+/// Mapping from the handles entities had in one world to their handles in another
+///
+/// Returned by [`World::absorb`](crate::World::absorb), so that references to absorbed entities
+/// held outside of the world, or stored in their components, can be patched up. Lookups index
+/// directly by the old `Entity::id`, so memory use is proportional to the largest ID mapped.
+#[derive(Debug, Default, Clone)]
+pub struct EntityMap {
+    /// Old and new handle of each old entity ID
+    slots: Vec<Option<(Entity, Entity)>>,
+    len: usize,
+}
+
+impl EntityMap {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, old: Entity, new: Entity) {
+        let id = old.id as usize;
+        if id >= self.slots.len() {
+            self.slots.resize(id + 1, None);
+        }
+        if self.slots[id].replace((old, new)).is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// The new handle of the entity that was `old`, if it was mapped
+    #[inline]
+    pub fn get(&self, old: Entity) -> Option<Entity> {
+        match self.slots.get(old.id as usize) {
+            Some(&Some((x, new))) if x == old => Some(new),
+            _ => None,
+        }
+    }
+
+    /// Number of entities mapped
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no entities were mapped
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the old and new handle of every mapped entity, in order of old ID
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.slots.iter().filter_map(|x| *x)
+    }
+}
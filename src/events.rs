@@ -0,0 +1,80 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::mem;
+
+use crate::archetype::TypeIdMap;
+use crate::Component;
+
+/// Queues of events of distinct types, sent with `World::send_event`
+///
+/// Each queue is double-buffered: `update` retires the events sent since the previous `update`
+/// rather than discarding them, so that every event remains readable for one full update cycle
+/// regardless of whether its readers run before or after its sender.
+#[derive(Default)]
+pub(crate) struct Events {
+    map: TypeIdMap<Box<dyn Queue>>,
+}
+
+impl Events {
+    pub(crate) fn send<E: Component>(&mut self, event: E) {
+        self.map
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| {
+                Box::new(EventQueue::<E> {
+                    previous: Vec::new(),
+                    current: Vec::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut::<EventQueue<E>>()
+            .unwrap()
+            .current
+            .push(event);
+    }
+
+    pub(crate) fn iter<E: Component>(&self) -> impl Iterator<Item = &E> + '_ {
+        let queue = self
+            .map
+            .get(&TypeId::of::<E>())
+            .map(|x| x.as_any().downcast_ref::<EventQueue<E>>().unwrap());
+        let (previous, current) = queue.map_or((&[][..], &[][..]), |x| (&x.previous, &x.current));
+        previous.iter().chain(current)
+    }
+
+    pub(crate) fn update(&mut self) {
+        for queue in self.map.values_mut() {
+            queue.update();
+        }
+    }
+}
+
+trait Queue: Send + Sync {
+    /// Drop the retired events and retire the current ones
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct EventQueue<E> {
+    /// Events sent before the last update
+    previous: Vec<E>,
+    /// Events sent since the last update
+    current: Vec<E>,
+}
+
+impl<E: Component> Queue for EventQueue<E> {
+    fn update(&mut self) {
+        mem::swap(&mut self.previous, &mut self.current);
+        // Reuse the retired events' storage
+        self.current.clear();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
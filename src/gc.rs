@@ -0,0 +1,100 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{Archetype, Component, Entity, Query, World};
+
+/// Removes components that no longer belong to any entity of interest
+///
+/// Each component type to be collected is registered with one or more root queries. A component
+/// is kept if its entity matches any root query for its type, and removed otherwise. Useful for
+/// keeping long-running worlds from accumulating orphaned data, such as pathfinding caches whose
+/// owner lost the AI component that maintained them.
+///
+/// Roots are matched by the component types their queries require, as with [`World::archetypes`]
+/// and [`Archetype::access`]; per-entity filters such as [`Changed`](crate::Changed) are ignored.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Ai;
+/// struct PathCache(Vec<[i32; 3]>);
+///
+/// let mut gc = ComponentGc::new();
+/// gc.root::<PathCache, &Ai>();
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Ai, PathCache(Vec::new())));
+/// let b = world.spawn((Ai, PathCache(Vec::new())));
+/// world.remove_one::<Ai>(b).unwrap();
+/// assert_eq!(gc.collect(&mut world), 1);
+/// assert!(world.get::<PathCache>(a).is_ok());
+/// assert!(world.get::<PathCache>(b).is_err());
+/// ```
+#[derive(Default)]
+pub struct ComponentGc {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    ty: TypeId,
+    remove: fn(&mut World, Entity),
+    roots: Vec<fn(&Archetype) -> bool>,
+}
+
+impl ComponentGc {
+    /// Create a collector that removes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `T` components of entities matching `Q`, and collect those of entities matching no
+    /// root registered for `T`
+    pub fn root<T: Component, Q: Query>(&mut self) -> &mut Self {
+        fn remove<T: Component>(world: &mut World, entity: Entity) {
+            let _ = world.remove_one::<T>(entity);
+        }
+        fn matches<Q: Query>(archetype: &Archetype) -> bool {
+            archetype.access::<Q>().is_some()
+        }
+
+        let ty = TypeId::of::<T>();
+        let index = match self.rules.iter().position(|x| x.ty == ty) {
+            Some(index) => index,
+            None => {
+                self.rules.push(Rule {
+                    ty,
+                    remove: remove::<T>,
+                    roots: Vec::new(),
+                });
+                self.rules.len() - 1
+            }
+        };
+        self.rules[index].roots.push(matches::<Q>);
+        self
+    }
+
+    /// Remove every collectable component from `world`, returning the number removed
+    pub fn collect(&self, world: &mut World) -> usize {
+        let mut orphans = Vec::new();
+        for rule in &self.rules {
+            for archetype in world.archetypes() {
+                if archetype.is_empty()
+                    || !archetype.has_dynamic(rule.ty)
+                    || rule.roots.iter().any(|matches| matches(archetype))
+                {
+                    continue;
+                }
+                orphans.extend(
+                    archetype
+                        .ids()
+                        .iter()
+                        .map(|&id| (rule.remove, unsafe { world.find_entity_from_id(id) })),
+                );
+            }
+        }
+        for &(remove, entity) in &orphans {
+            remove(world, entity);
+        }
+        orphans.len()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::alloc::{boxed::Box, vec::Vec};
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+use crate::archetype::Archetype;
+use crate::query::{ChunkFetch, QueryChunk};
+use crate::{Fetch, Query};
+
+/// Sets of component types registered with `World::register_group`, and the archetypes storing
+/// each
+#[derive(Default)]
+pub(crate) struct Groups {
+    groups: Vec<Group>,
+}
+
+struct Group {
+    /// Sorted component types
+    types: Box<[TypeId]>,
+    /// Indices of the archetypes storing every one of `types`, in order of creation
+    archetypes: Vec<u32>,
+}
+
+impl Group {
+    fn admits(&self, archetype: &Archetype) -> bool {
+        self.types.iter().all(|&ty| archetype.has_dynamic(ty))
+    }
+}
+
+impl Groups {
+    /// Register a group of `types`, unless already registered, finding its members among
+    /// `archetypes`
+    pub fn register(&mut self, types: &[TypeId], archetypes: &[Archetype]) {
+        let types = sorted(types.iter().copied());
+        if self.get(&types).is_some() {
+            return;
+        }
+        let mut group = Group {
+            types,
+            archetypes: Vec::new(),
+        };
+        group.archetypes = (0..archetypes.len() as u32)
+            .filter(|&index| group.admits(&archetypes[index as usize]))
+            .collect();
+        self.groups.push(group);
+    }
+
+    /// Add the last of `archetypes`, which was just created, to the groups it belongs to
+    pub fn archetype_created(&mut self, archetypes: &[Archetype]) {
+        let index = archetypes.len() - 1;
+        for group in &mut self.groups {
+            if group.admits(&archetypes[index]) {
+                group.archetypes.push(index as u32);
+            }
+        }
+    }
+
+    /// Indices of the archetypes of the group of exactly the sorted `types`, if registered
+    fn get(&self, types: &[TypeId]) -> Option<&[u32]> {
+        self.groups
+            .iter()
+            .find(|group| &*group.types == types)
+            .map(|group| &group.archetypes[..])
+    }
+
+    /// Indices of the archetypes of the group of exactly the component types `Q` borrows
+    pub fn of<Q: Query>(&self) -> Option<&[u32]> {
+        let mut types = Vec::new();
+        Q::Fetch::for_each_borrow(|ty, _| types.push(ty));
+        self.get(&sorted(types))
+    }
+}
+
+fn sorted(types: impl IntoIterator<Item = TypeId>) -> Box<[TypeId]> {
+    let mut types = types.into_iter().collect::<Vec<_>>();
+    types.sort_unstable();
+    types.dedup();
+    types.into()
+}
+
+/// Iterator over the archetypes of a group, yielded by `World::query_group`
+pub struct GroupChunks<'q, Q: Query> {
+    archetypes: &'q [Archetype],
+    matching: core::slice::Iter<'q, u32>,
+    _marker: PhantomData<Q>,
+}
+
+impl<'q, Q: Query> GroupChunks<'q, Q> {
+    /// # Safety
+    ///
+    /// `Q` must not alias, nothing else may access `archetypes` for `'q`, and `matching` must
+    /// index `archetypes`.
+    pub(crate) unsafe fn new(archetypes: &'q [Archetype], matching: &'q [u32]) -> Self {
+        Self {
+            archetypes,
+            matching: matching.iter(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'q, Q: Query> Iterator for GroupChunks<'q, Q>
+where
+    Q::Fetch: ChunkFetch<'q>,
+{
+    type Item = (&'q [u32], QueryChunk<'q, Q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let archetype = &self.archetypes[*self.matching.next()? as usize];
+            if archetype.is_empty() {
+                continue;
+            }
+            let fetch = match Q::Fetch::new(archetype) {
+                Some(x) => x,
+                None => continue,
+            };
+            let slice = unsafe { fetch.slice(archetype.len() as usize) };
+            return Some((archetype.ids(), slice));
+        }
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for GroupChunks<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for GroupChunks<'q, Q> {}
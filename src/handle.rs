@@ -0,0 +1,70 @@
+use crate::{Entity, EntityRef, World};
+
+/// An `Entity` tied to the world it came from, which reports when the entity has died
+///
+/// A bare `Entity` gives no indication of whether it's still alive, so code holding on to one,
+/// such as an AI's current target or an inventory slot, must remember to call
+/// [`World::contains`] before every use. An `EntityHandle` instead funnels every access through
+/// a liveness check, and also rejects worlds other than the one it was created from. Checks cost
+/// the same as `World::contains`. Created by [`World::handle`].
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let mut target = world.handle(a);
+/// assert_eq!(target.unwrap().get(&world).unwrap().get::<i32>().as_deref(), Some(&123));
+///
+/// world.despawn(a).unwrap();
+/// let b = world.spawn((456,)); // reuses `a`'s ID
+/// assert!(!target.unwrap().is_alive(&world));
+/// assert_eq!(EntityHandle::resolve(&mut target, &world), None);
+/// assert!(target.is_none());
+/// # let _ = b;
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct EntityHandle {
+    entity: Entity,
+    world_id: usize,
+}
+
+impl EntityHandle {
+    pub(crate) fn new(entity: Entity, world_id: usize) -> Self {
+        Self { entity, world_id }
+    }
+
+    /// The entity referred to, which may have been despawned
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Whether the entity still exists in `world`
+    ///
+    /// Always `false` if `world` isn't the world the handle was created from.
+    pub fn is_alive(&self, world: &World) -> bool {
+        self.world_id == world.id() && world.contains(self.entity)
+    }
+
+    /// Access the entity in `world`, if it's still alive
+    pub fn get<'a>(&self, world: &'a World) -> Option<EntityRef<'a>> {
+        if !self.is_alive(world) {
+            return None;
+        }
+        world.entity(self.entity).ok()
+    }
+
+    /// The entity `slot` refers to if it's still alive in `world`, clearing `slot` otherwise
+    ///
+    /// Convenient for fields such as targets or inventory slots, which then drop dead references
+    /// the first time they're used after their entity is despawned.
+    pub fn resolve(slot: &mut Option<Self>, world: &World) -> Option<Entity> {
+        match *slot {
+            Some(handle) if handle.is_alive(world) => Some(handle.entity),
+            _ => {
+                *slot = None;
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::Entity;
+
+/// Parent/child relations established with `World::attach`
+///
+/// Both directions are stored so that despawning an entity can find its children, and detaching
+/// a child can find its parent, without scanning.
+#[derive(Default, Clone)]
+pub(crate) struct Hierarchy {
+    parents: HashMap<Entity, Entity>,
+    /// Children of each entity that has any, in the order they were attached
+    children: HashMap<Entity, Vec<Entity>>,
+}
+
+impl Hierarchy {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Make `child` a child of `parent`, detaching it from any previous parent
+    ///
+    /// # Panics
+    /// - if `parent` is `child` or one of its descendants
+    pub(crate) fn attach(&mut self, child: Entity, parent: Entity) {
+        let mut ancestor = Some(parent);
+        while let Some(x) = ancestor {
+            assert!(
+                x != child,
+                "entity cannot be attached to itself or its descendants"
+            );
+            ancestor = self.parent(x);
+        }
+        self.detach(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+    }
+
+    /// Detach `child` from its parent, if any, returning the former parent
+    pub(crate) fn detach(&mut self, child: Entity) -> Option<Entity> {
+        let parent = self.parents.remove(&child)?;
+        let siblings = self.children.get_mut(&parent).unwrap();
+        let index = siblings.iter().position(|&x| x == child).unwrap();
+        siblings.remove(index);
+        if siblings.is_empty() {
+            self.children.remove(&parent);
+        }
+        Some(parent)
+    }
+
+    pub(crate) fn parent(&self, child: Entity) -> Option<Entity> {
+        self.parents.get(&child).copied()
+    }
+
+    pub(crate) fn children(&self, parent: Entity) -> &[Entity] {
+        self.children.get(&parent).map_or(&[], |x| &x[..])
+    }
+
+    /// Iterate over every entity that has children, along with its children
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Entity, &[Entity])> + '_ {
+        self.children
+            .iter()
+            .map(|(&parent, children)| (parent, &children[..]))
+    }
+
+    /// Remove all relations involving `entity`, which was despawned, returning its children
+    pub(crate) fn forget(&mut self, entity: Entity) -> Vec<Entity> {
+        self.detach(entity);
+        let children = self.children.remove(&entity).unwrap_or_default();
+        for child in &children {
+            self.parents.remove(child);
+        }
+        children
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.parents.clear();
+        self.children.clear();
+    }
+}
@@ -0,0 +1,88 @@
+use crate::alloc::collections::{vec_deque, VecDeque};
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use hashbrown::HashMap;
+
+use crate::archetype::TypeInfo;
+
+/// A change in the set of component types an entity has, recorded by the `debug-history` feature
+///
+/// See [`World::debug_history`](crate::World::debug_history).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Transition {
+    tick: u64,
+    added: Vec<TypeId>,
+    removed: Vec<TypeId>,
+}
+
+impl Transition {
+    /// Number of calls to `World::clear_changed` or `World::clear_trackers` made before the
+    /// transition
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Component types the entity gained
+    pub fn added(&self) -> &[TypeId] {
+        &self.added
+    }
+
+    /// Component types the entity lost
+    pub fn removed(&self) -> &[TypeId] {
+        &self.removed
+    }
+}
+
+/// The most recent archetype transitions of each entity
+#[derive(Default)]
+pub(crate) struct History {
+    tick: u64,
+    /// Keyed by entity ID, which is unique among live entities
+    entities: HashMap<u32, VecDeque<Transition>>,
+}
+
+impl History {
+    /// Number of transitions retained per entity
+    pub(crate) const LEN: usize = 16;
+
+    /// Record entity `id` moving from an archetype with `source` types to one with `target` types
+    pub(crate) fn record(&mut self, id: u32, source: &[TypeInfo], target: &[TypeInfo]) {
+        let transition = Transition {
+            tick: self.tick,
+            added: target
+                .iter()
+                .filter(|x| !source.contains(x))
+                .map(|x| x.id())
+                .collect(),
+            removed: source
+                .iter()
+                .filter(|x| !target.contains(x))
+                .map(|x| x.id())
+                .collect(),
+        };
+        let log = self.entities.entry(id).or_default();
+        if log.len() == Self::LEN {
+            log.pop_front();
+        }
+        log.push_back(transition);
+    }
+
+    pub(crate) fn get(&self, id: u32) -> vec_deque::Iter<'_, Transition> {
+        self.entities
+            .get(&id)
+            .map_or_else(Default::default, |x| x.iter())
+    }
+
+    pub(crate) fn forget(&mut self, id: u32) {
+        self.entities.remove(&id);
+    }
+
+    pub(crate) fn advance(&mut self) {
+        self.tick += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
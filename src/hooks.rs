@@ -0,0 +1,258 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::archetype::{Archetype, TypeIdMap};
+use crate::entities::EntityMeta;
+use crate::secondary_index::ErasedIndex;
+use crate::{Component, DespawnReason, Entity, SecondaryIndex};
+
+/// Callbacks registered with `World::on_insert`, `World::on_remove`, `World::on_despawn`, and
+/// `World::on_archetype_created`, and indexes registered with `World::add_index`
+#[derive(Default)]
+pub(crate) struct Hooks {
+    insert: TypeIdMap<ComponentHooks>,
+    remove: TypeIdMap<ComponentHooks>,
+    despawn: Vec<Box<DespawnHook>>,
+    archetype: Vec<Box<ArchetypeHook>>,
+    indexes: Vec<Index>,
+    /// Position in `indexes` of each index type
+    index_types: TypeIdMap<usize>,
+}
+
+type DespawnHook = dyn FnMut(Entity, Option<DespawnReason>) + Send + Sync;
+type ArchetypeHook = dyn FnMut(usize, &Archetype) + Send + Sync;
+
+struct ComponentHooks {
+    size: usize,
+    hooks: Vec<Box<dyn FnMut(Entity, *const u8) + Send + Sync>>,
+    /// Positions in `Hooks::indexes` of the indexes derived from this component type
+    indexes: Vec<usize>,
+}
+
+struct Index {
+    source: TypeId,
+    size: usize,
+    index: Box<dyn ErasedIndex>,
+}
+
+impl Hooks {
+    pub fn on_insert<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        add(&mut self.insert, f);
+    }
+
+    pub fn on_remove<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        add(&mut self.remove, f);
+    }
+
+    pub fn on_despawn(
+        &mut self,
+        f: impl FnMut(Entity, Option<DespawnReason>) + Send + Sync + 'static,
+    ) {
+        self.despawn.push(Box::new(f));
+    }
+
+    pub fn on_archetype_created(
+        &mut self,
+        f: impl FnMut(usize, &Archetype) + Send + Sync + 'static,
+    ) {
+        self.archetype.push(Box::new(f));
+    }
+
+    /// Run archetype creation hooks for the last of `archetypes`, which was just created
+    pub fn archetype_created(&mut self, archetypes: &[Archetype]) {
+        for hook in &mut self.archetype {
+            hook(archetypes.len() - 1, archetypes.last().unwrap());
+        }
+    }
+
+    /// Register `index`, which must already reflect the world's current `I::Source` components
+    pub fn add_index<I: SecondaryIndex>(&mut self, index: I) {
+        assert!(
+            !self.index_types.contains_key(&TypeId::of::<I>()),
+            "index already added"
+        );
+        let position = self.indexes.len();
+        self.indexes.push(Index {
+            source: TypeId::of::<I::Source>(),
+            size: core::mem::size_of::<I::Source>(),
+            index: Box::new(index),
+        });
+        self.index_types.insert(TypeId::of::<I>(), position);
+        entry::<I::Source>(&mut self.insert).indexes.push(position);
+        entry::<I::Source>(&mut self.remove).indexes.push(position);
+    }
+
+    pub fn index<I: SecondaryIndex>(&self) -> Option<&I> {
+        let position = *self.index_types.get(&TypeId::of::<I>())?;
+        self.indexes[position].index.as_any().downcast_ref()
+    }
+
+    /// Notify indexes of their source components that were flagged as changed
+    pub fn update_indexes(&mut self, meta: &[EntityMeta], archetypes: &[Archetype]) {
+        for index in &mut self.indexes {
+            let source = index.source;
+            for archetype in archetypes.iter().filter(|x| x.has_dynamic(source)) {
+                for (i, &id) in archetype.ids().iter().enumerate() {
+                    let i = i as u32;
+                    unsafe {
+                        if !*archetype.changed_dynamic(source, i).unwrap().as_ptr() {
+                            continue;
+                        }
+                        let entity = Entity {
+                            id,
+                            generation: meta[id as usize].generation,
+                        };
+                        let ptr = archetype.get_dynamic(source, index.size, i).unwrap();
+                        index.index.insert(entity, ptr.as_ptr());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether any insertion hooks are registered
+    pub fn watches_inserts(&self) -> bool {
+        !self.insert.is_empty()
+    }
+
+    /// Whether any removal or despawn hooks are registered
+    pub fn watches_despawns(&self) -> bool {
+        !self.remove.is_empty() || !self.despawn.is_empty()
+    }
+
+    /// Run insertion hooks for the components of `entity`, at `index` in `archetype`, of `types`
+    pub fn inserted(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: &[TypeId],
+    ) {
+        for &ty in types {
+            run(
+                &mut self.insert,
+                &mut self.indexes,
+                false,
+                entity,
+                archetype,
+                index,
+                ty,
+            );
+        }
+    }
+
+    /// Run insertion hooks for every component of `entity`, which was just spawned at `index` in
+    /// `archetype`
+    pub fn spawned(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        if !self.watches_inserts() {
+            return;
+        }
+        for ty in archetype.types() {
+            run(
+                &mut self.insert,
+                &mut self.indexes,
+                false,
+                entity,
+                archetype,
+                index,
+                ty.id(),
+            );
+        }
+    }
+
+    /// Run removal hooks for the components of `entity`, at `index` in `archetype`, of `types`
+    pub fn removed(&mut self, entity: Entity, archetype: &Archetype, index: u32, types: &[TypeId]) {
+        for &ty in types {
+            run(
+                &mut self.remove,
+                &mut self.indexes,
+                true,
+                entity,
+                archetype,
+                index,
+                ty,
+            );
+        }
+    }
+
+    /// Run removal hooks for every component of `entity`, which is about to be despawned from
+    /// `index` in `archetype` for `reason`, followed by despawn hooks
+    pub fn despawned(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        reason: Option<DespawnReason>,
+    ) {
+        if !self.watches_despawns() {
+            return;
+        }
+        for ty in archetype.types() {
+            run(
+                &mut self.remove,
+                &mut self.indexes,
+                true,
+                entity,
+                archetype,
+                index,
+                ty.id(),
+            );
+        }
+        for hook in &mut self.despawn {
+            hook(entity, reason);
+        }
+    }
+}
+
+fn entry<T: Component>(map: &mut TypeIdMap<ComponentHooks>) -> &mut ComponentHooks {
+    map.entry(TypeId::of::<T>())
+        .or_insert_with(|| ComponentHooks {
+            size: core::mem::size_of::<T>(),
+            hooks: Vec::new(),
+            indexes: Vec::new(),
+        })
+}
+
+fn add<T: Component>(
+    map: &mut TypeIdMap<ComponentHooks>,
+    mut f: impl FnMut(Entity, &T) + Send + Sync + 'static,
+) {
+    entry::<T>(map).hooks.push(Box::new(move |entity, ptr| {
+        f(entity, unsafe { &*ptr.cast::<T>() })
+    }));
+}
+
+/// Invoke the hooks and notify the indexes in `map` for the component of type `ty` of `entity`,
+/// which is being removed if `removing`
+fn run(
+    map: &mut TypeIdMap<ComponentHooks>,
+    indexes: &mut [Index],
+    removing: bool,
+    entity: Entity,
+    archetype: &Archetype,
+    index: u32,
+    ty: TypeId,
+) {
+    let hooks = match map.get_mut(&ty) {
+        Some(x) => x,
+        None => return,
+    };
+    let ptr = match unsafe { archetype.get_dynamic(ty, hooks.size, index) } {
+        Some(x) => x.as_ptr(),
+        None => return,
+    };
+    for hook in &mut hooks.hooks {
+        hook(entity, ptr);
+    }
+    for &position in &hooks.indexes {
+        let index = &mut indexes[position].index;
+        unsafe {
+            if removing {
+                index.remove(entity, ptr);
+            } else {
+                index.insert(entity, ptr);
+            }
+        }
+    }
+}
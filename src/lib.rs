@@ -55,43 +55,120 @@ macro_rules! smaller_tuples_too {
     };
 }
 
+#[cfg(feature = "abi")]
+pub mod abi;
 mod archetype;
 mod batch;
+mod blackboard;
 mod borrow;
 mod bundle;
+mod command_buffer;
+mod config;
+mod dense_map;
 mod dynamic_query;
 mod entities;
 mod entity_builder;
+mod entity_map;
+mod events;
+mod gc;
+mod group;
+mod handle;
+mod hierarchy;
+#[cfg(feature = "debug-history")]
+mod history;
+mod hooks;
+mod local;
+mod names;
+mod prefab;
 mod query;
 mod query_one;
+mod registry;
+mod resources;
+#[cfg(feature = "executor")]
+pub mod schedule;
+mod scope;
+mod secondary_index;
 #[cfg(feature = "serde")]
 pub mod serialize;
+mod snapshot;
+mod sparse;
+#[cfg(feature = "spatial")]
+pub mod spatial;
+mod spawn_staging;
+mod split;
+mod staged;
+#[cfg(feature = "stats")]
+mod stats;
+mod tags;
+mod ticks;
+mod traits;
+mod type_mask;
+mod view;
 mod world;
+mod world_diff;
+mod world_snapshot;
+mod world_view;
 
-pub use archetype::Archetype;
+pub use archetype::{Archetype, ColumnRef, ColumnRefMut, TypeInfo};
 pub use batch::{ColumnBatch, ColumnBatchBuilder, ColumnBatchType};
-pub use borrow::{EntityRef, Ref, RefMut};
+pub use blackboard::Blackboard;
+#[cfg(feature = "std")]
+pub use borrow::with_borrow_label;
+pub use borrow::{
+    BorrowError, BorrowLabel, EntityRef, OwnedRef, OwnedRefMut, Ref, RefMut, TypeInfoView,
+};
 pub use bundle::{Bundle, DynamicBundle, MissingComponent};
+pub use command_buffer::CommandBuffer;
+pub use config::WorldConfig;
+pub use dense_map::EntityDenseMap;
 pub use dynamic_query::{DynamicQuery, DynamicQueryTypes};
-pub use entities::{Entity, NoSuchEntity};
-pub use entity_builder::{BuiltEntity, EntityBuilder};
+pub use entities::{Entity, IdNamespace, IdRangeUnavailable, NoSuchEntity};
+pub use entity_builder::{BuiltEntity, EntityBuilder, TakenEntity};
+pub use entity_map::EntityMap;
+pub use gc::ComponentGc;
+pub use group::GroupChunks;
+pub use handle::EntityHandle;
+#[cfg(feature = "debug-history")]
+pub use history::Transition;
+pub use local::LocalWorld;
+pub use prefab::{CloneError, Prefab};
 pub use query::{
-    Access, BatchedIter, Query, QueryBorrow, QueryItem, QueryIter, QueryMut, With, Without,
+    Access, Added, BatchedIter, Changed, Copied, Mut, PreparedQuery, PreparedQueryBorrow,
+    PreparedQueryIter, Query, QueryAccess, QueryBorrow, QueryChunk, QueryChunks, QueryComponents,
+    QueryFlatMap, QueryItem, QueryIter, QueryMap, QueryMut, SortCache, SortedIter, With, Without,
 };
 pub use query_one::QueryOne;
+pub use registry::{ComponentMeta, ComponentRegistry, RegistrationConflict};
+pub use resources::{ResourceRef, ResourceRefMut};
+pub use scope::Scope;
+pub use secondary_index::SecondaryIndex;
+pub use snapshot::Snapshot;
+pub use sparse::{SparseRef, SparseRefMut, SparseSet};
+pub use spawn_staging::SpawnStaging;
+pub use split::{ComponentStorage, EntityAllocator};
+pub use staged::{StagedColumn, StaleColumn};
+#[cfg(feature = "stats")]
+pub use stats::WorldStats;
+pub use tags::{Tags, WithBits};
+pub use ticks::Tick;
+pub use traits::{QueryTrait, TraitIter};
+pub use view::{Includes, SubView, View, ViewQuery, ViewSubset};
 pub use world::{
-    ArchetypesGeneration, Component, ComponentError, Iter, SpawnBatchIter, SpawnColumnBatchIter,
-    World,
+    ArchetypeId, ArchetypesGeneration, Component, ComponentError, ComponentVisitor, DespawnReason,
+    EntityBatch, EntityBatches, IntegrityError, Iter, SpawnBatchIter, SpawnColumnBatchIter, World,
 };
+pub use world_diff::WorldDiff;
+pub use world_snapshot::WorldSnapshot;
+pub use world_view::WorldView;
 
 // Unstable implementation details needed by the macros
-#[doc(hidden)]
-pub use archetype::TypeInfo;
 #[cfg(feature = "macros")]
 #[doc(hidden)]
 pub use lazy_static;
 #[doc(hidden)]
-pub use query::Fetch;
+pub use query::{ChunkFetch, Fetch};
+#[doc(hidden)]
+pub use view::index;
 
 #[cfg(feature = "macros")]
 pub use hecs_macros::{Bundle, Query};
@@ -0,0 +1,191 @@
+use crate::alloc::boxed::Box;
+use core::any::{Any, TypeId};
+use core::ops::{Deref, DerefMut};
+
+use crate::archetype::TypeIdMap;
+use crate::{Entity, NoSuchEntity, SparseSet, World};
+
+/// A `World` confined to one thread, which can also hold components that aren't `Send` or `Sync`
+///
+/// Components wrapping thread-bound handles, such as `Rc`s to scripting VM objects, can't be
+/// stored in a `World`, which may be sent between threads. A `LocalWorld` stores such "local"
+/// components alongside its world, and is itself neither `Send` nor `Sync`, so the compiler
+/// ensures they are only ever accessed and dropped on the thread that created them.
+///
+/// The wrapped world is reachable through `Deref` for everything else. Local components live in
+/// a [`SparseSet`] per type rather than in archetypes, so they aren't visible to queries, and
+/// inserting or removing them never moves entities between archetypes. They're dropped when
+/// their entity is despawned through [`despawn`](Self::despawn) or [`clear`](Self::clear), or
+/// otherwise on the next call to [`prune`](Self::prune); until then, lookups ignore entities that
+/// no longer exist.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// use std::rc::Rc;
+///
+/// let mut world = LocalWorld::new();
+/// let a = world.spawn((123,));
+/// world.insert_local(a, Rc::new("script object")).unwrap();
+/// assert_eq!(**world.get_local::<Rc<&str>>(a).unwrap(), "script object");
+/// assert_eq!(*world.get::<i32>(a).unwrap(), 123);
+/// world.despawn(a).unwrap();
+/// assert!(world.local::<Rc<&str>>().unwrap().is_empty());
+/// ```
+///
+/// Moving a `LocalWorld` to another thread is rejected at compile time:
+/// ```compile_fail
+/// # use hecs::*;
+/// let world = LocalWorld::new();
+/// std::thread::spawn(move || drop(world));
+/// ```
+#[derive(Default)]
+pub struct LocalWorld {
+    world: World,
+    locals: TypeIdMap<Box<dyn AnyLocalSet>>,
+}
+
+/// Operations on a `SparseSet` of unknown local component type
+trait AnyLocalSet {
+    fn forget(&mut self, id: u32);
+    fn retain(&mut self, world: &World);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyLocalSet for SparseSet<T> {
+    fn forget(&mut self, id: u32) {
+        self.remove_id(id);
+    }
+
+    fn retain(&mut self, world: &World) {
+        SparseSet::retain(self, |entity| world.contains(entity));
+    }
+
+    fn clear(&mut self) {
+        SparseSet::clear(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl LocalWorld {
+    /// Create an empty local world
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give `entity` a local `T` component, returning the one it replaced, if any
+    pub fn insert_local<T: 'static>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        if !self.world.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        let set = self
+            .locals
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
+            .unwrap();
+        Ok(set.insert(entity, component))
+    }
+
+    /// Remove and return `entity`'s local `T` component, if any
+    pub fn remove_local<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.local_set_mut::<T>()?.remove(entity)
+    }
+
+    /// Borrow `entity`'s local `T` component, if it exists and has one
+    pub fn get_local<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.world.contains(entity) {
+            return None;
+        }
+        self.local::<T>()?.get(entity)
+    }
+
+    /// Uniquely borrow `entity`'s local `T` component, if it exists and has one
+    pub fn get_local_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.world.contains(entity) {
+            return None;
+        }
+        self.local_set_mut::<T>()?.get_mut(entity)
+    }
+
+    /// Every local `T` component, or `None` if none were ever inserted
+    ///
+    /// May include components of entities despawned since the last `prune`.
+    pub fn local<T: 'static>(&self) -> Option<&SparseSet<T>> {
+        let set = self.locals.get(&TypeId::of::<T>())?;
+        Some(set.as_any().downcast_ref().unwrap())
+    }
+
+    fn local_set_mut<T: 'static>(&mut self) -> Option<&mut SparseSet<T>> {
+        let set = self.locals.get_mut(&TypeId::of::<T>())?;
+        Some(set.as_any_mut().downcast_mut().unwrap())
+    }
+
+    /// Destroy an entity along with all its components, local or not
+    pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.world.despawn(entity)?;
+        for set in self.locals.values_mut() {
+            set.forget(entity.id);
+        }
+        Ok(())
+    }
+
+    /// Despawn all entities, dropping their local components
+    pub fn clear(&mut self) {
+        self.world.clear();
+        for set in self.locals.values_mut() {
+            set.clear();
+        }
+    }
+
+    /// Drop the local components of entities that have been despawned through the wrapped world
+    ///
+    /// Entities despawned with methods other than `despawn` and `clear`, such as
+    /// `despawn_batch` or `take`, keep their local components until this is called.
+    pub fn prune(&mut self) {
+        for set in self.locals.values_mut() {
+            set.retain(&self.world);
+        }
+    }
+
+    /// Drop every local component, returning the wrapped world
+    pub fn into_inner(self) -> World {
+        self.world
+    }
+}
+
+impl From<World> for LocalWorld {
+    fn from(world: World) -> Self {
+        Self {
+            world,
+            locals: TypeIdMap::default(),
+        }
+    }
+}
+
+impl Deref for LocalWorld {
+    type Target = World;
+    fn deref(&self) -> &World {
+        &self.world
+    }
+}
+
+impl DerefMut for LocalWorld {
+    fn deref_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+}
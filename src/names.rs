@@ -0,0 +1,51 @@
+use crate::alloc::borrow::Cow;
+
+use hashbrown::HashMap;
+
+/// Names given to entities with `World::set_name`, indexed both ways
+///
+/// Entities are identified by ID alone, as names are forgotten when their entity is despawned.
+#[derive(Default, Clone)]
+pub(crate) struct Names {
+    by_id: HashMap<u32, Cow<'static, str>>,
+    by_name: HashMap<Cow<'static, str>, u32>,
+}
+
+impl Names {
+    /// Name the entity with ID `id`, taking the name from any other entity that had it
+    pub(crate) fn set(&mut self, id: u32, name: Cow<'static, str>) {
+        self.forget(id);
+        if let Some(previous) = self.by_name.insert(name.clone(), id) {
+            self.by_id.remove(&previous);
+        }
+        self.by_id.insert(id, name);
+    }
+
+    pub(crate) fn get(&self, id: u32) -> Option<&str> {
+        self.by_id.get(&id).map(|x| &**x)
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<u32> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Iterate over the ID and name of every named entity
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u32, &Cow<'static, str>)> + '_ {
+        self.by_id.iter().map(|(&id, name)| (id, name))
+    }
+
+    /// Remove the name of the entity with ID `id`, returning it
+    pub(crate) fn forget(&mut self, id: u32) -> Option<Cow<'static, str>> {
+        if self.by_id.is_empty() {
+            return None;
+        }
+        let name = self.by_id.remove(&id)?;
+        self.by_name.remove(&name);
+        Some(name)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.by_id.clear();
+        self.by_name.clear();
+    }
+}
@@ -0,0 +1,154 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use crate::{Component, ComponentRegistry, Entity, EntityBuilder, NoSuchEntity, TypeInfo, World};
+
+/// A template entity that can be instantiated any number of times
+///
+/// Each instantiation clones the template's components, using the clone functions recorded in a
+/// [`ComponentRegistry`], so that frequently spawned entities such as arrows or dropped items
+/// needn't be assembled by hand every time.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// #[derive(Clone)]
+/// struct Damage(u32);
+///
+/// let mut registry = ComponentRegistry::new();
+/// register_component!(registry, Damage, clone);
+/// register_component!(registry, &'static str, clone);
+///
+/// let mut world = World::new();
+/// let template = world.spawn((Damage(4), "arrow"));
+/// let prefab = Prefab::from_entity(&world, template, &registry).unwrap();
+/// let arrows = (0..3).map(|_| world.spawn_prefab(&prefab)).collect::<Vec<_>>();
+/// assert_eq!(world.get::<Damage>(arrows[2]).unwrap().0, 4);
+/// ```
+pub struct Prefab {
+    template: EntityBuilder,
+    clones: Vec<unsafe fn(*const u8, *mut u8)>,
+}
+
+impl Prefab {
+    /// Create a prefab from the components in `template`
+    ///
+    /// Fails if any component isn't registered as cloneable in `registry`.
+    pub fn new(template: EntityBuilder, registry: &ComponentRegistry) -> Result<Self, CloneError> {
+        let clones = template
+            .components()
+            .map(|(ty, _)| clone_fn(registry, ty.id()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { template, clones })
+    }
+
+    /// Create a prefab from clones of `entity`'s components
+    ///
+    /// Fails if any component isn't registered as cloneable in `registry`. Panics if a component
+    /// of `entity` is uniquely borrowed.
+    pub fn from_entity(
+        world: &World,
+        entity: Entity,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, CloneError> {
+        let mut template = EntityBuilder::new();
+        clone_entity(world, entity, registry, &mut template)?;
+        Self::new(template, registry)
+    }
+
+    /// Add clones of the template's components to `builder`
+    ///
+    /// Reusing `builder` avoids allocating for each instance.
+    pub fn clone_into<'a>(&self, builder: &'a mut EntityBuilder) -> &'a mut EntityBuilder {
+        for ((ty, src), &clone) in self.template.components().zip(&self.clones) {
+            unsafe {
+                builder.add_with(ty, |dst| clone(src, dst));
+            }
+        }
+        builder
+    }
+
+    /// Checks to see if the template has a component of type `T`
+    pub fn has<T: Component>(&self) -> bool {
+        self.template.has::<T>()
+    }
+
+    /// Borrow the template's component of type `T`, if it exists
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.template.get::<T>()
+    }
+
+    /// Uniquely borrow the template's component of type `T`, if it exists, e.g. to adjust future
+    /// instances
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.template.get_mut::<T>()
+    }
+
+    /// Enumerate the types of the template's components
+    pub fn component_types(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.template.component_types()
+    }
+}
+
+/// Add clones of `entity`'s components to `builder`
+///
+/// Nothing is cloned if any component isn't cloneable.
+pub(crate) fn clone_entity(
+    world: &World,
+    entity: Entity,
+    registry: &ComponentRegistry,
+    builder: &mut EntityBuilder,
+) -> Result<(), CloneError> {
+    for id in world.entity(entity)?.component_types() {
+        clone_fn(registry, id)?;
+    }
+    world.visit_entity(entity, &mut |ty: TypeInfo, src: *const u8| {
+        let clone = clone_fn(registry, ty.id()).unwrap();
+        unsafe {
+            builder.add_with(ty, |dst| clone(src, dst));
+        }
+    })?;
+    Ok(())
+}
+
+fn clone_fn(
+    registry: &ComponentRegistry,
+    id: TypeId,
+) -> Result<unsafe fn(*const u8, *mut u8), CloneError> {
+    registry
+        .get_by_id(id)
+        .and_then(|meta| meta.clone_fn())
+        .ok_or(CloneError::Uncloneable(id))
+}
+
+/// Errors that arise when cloning entities
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum CloneError {
+    /// The entity was already despawned
+    NoSuchEntity,
+    /// A component type wasn't registered as cloneable
+    Uncloneable(TypeId),
+}
+
+#[cfg(feature = "std")]
+impl Error for CloneError {}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CloneError::*;
+        match *self {
+            NoSuchEntity => f.write_str("no such entity"),
+            Uncloneable(id) => write!(f, "component type {:?} is not registered as cloneable", id),
+        }
+    }
+}
+
+impl From<NoSuchEntity> for CloneError {
+    fn from(NoSuchEntity: NoSuchEntity) -> Self {
+        CloneError::NoSuchEntity
+    }
+}
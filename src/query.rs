@@ -5,17 +5,101 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::cmp::Ordering;
+use core::fmt;
 use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
 use crate::archetype::Archetype;
 use crate::entities::EntityMeta;
-use crate::{Component, Entity};
+use crate::{BorrowError, Component, Entity, EntityDenseMap, World};
 
 /// A collection of component types to fetch from a `World`
 pub trait Query {
     #[doc(hidden)]
     type Fetch: for<'a> Fetch<'a>;
+
+    /// The component types this query borrows, and whether each borrow is unique
+    ///
+    /// Lets schedulers work out which queries may run concurrently without running them.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let movement = <(&mut f32, &bool)>::access();
+    /// assert!(movement.writes::<f32>());
+    /// assert!(movement.reads::<bool>() && !movement.writes::<bool>());
+    /// assert!(movement.conflicts_with(&<&f32>::access()));
+    /// assert!(!movement.conflicts_with(&<(&bool, Without<f32, &i32>)>::access()));
+    /// ```
+    fn access() -> QueryAccess
+    where
+        Self: Sized,
+    {
+        let mut access = QueryAccess::default();
+        Self::Fetch::for_each_borrow(|ty, unique| access.add(ty, unique));
+        access
+    }
+}
+
+/// The component types borrowed by a query, as reported by [`Query::access`]
+///
+/// Access descriptors of several queries can be combined with `extend`, e.g. to describe a system
+/// that runs them all.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryAccess {
+    /// Borrowed component types, and whether each borrow is unique
+    borrows: Vec<(TypeId, bool)>,
+}
+
+impl QueryAccess {
+    /// Record a borrow of `ty`, upgrading any existing shared borrow if `unique`
+    pub fn add(&mut self, ty: TypeId, unique: bool) {
+        match self.borrows.iter_mut().find(|x| x.0 == ty) {
+            Some(x) => x.1 |= unique,
+            None => self.borrows.push((ty, unique)),
+        }
+    }
+
+    /// Record every borrow made by `other`
+    pub fn extend(&mut self, other: &QueryAccess) {
+        for &(ty, unique) in &other.borrows {
+            self.add(ty, unique);
+        }
+    }
+
+    /// Whether `T` components are borrowed, uniquely or not
+    pub fn reads<T: Component>(&self) -> bool {
+        self.borrows.iter().any(|x| x.0 == TypeId::of::<T>())
+    }
+
+    /// Whether `T` components are borrowed uniquely
+    pub fn writes<T: Component>(&self) -> bool {
+        self.borrows.iter().any(|x| x.0 == TypeId::of::<T>() && x.1)
+    }
+
+    /// Iterate over the borrowed component types, and whether each borrow is unique
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (TypeId, bool)> + '_ {
+        self.borrows.iter().copied()
+    }
+
+    /// Whether no component is borrowed uniquely
+    pub fn is_read_only(&self) -> bool {
+        self.borrows.iter().all(|x| !x.1)
+    }
+
+    /// Whether borrows made under this descriptor could conflict with those under `other`
+    pub fn conflicts_with(&self, other: &QueryAccess) -> bool {
+        self.borrows.iter().any(|&(ty, unique)| {
+            other
+                .borrows
+                .iter()
+                .any(|&(other_ty, other_unique)| ty == other_ty && (unique || other_unique))
+        })
+    }
 }
 
 /// Type of values yielded by a query
@@ -24,6 +108,11 @@ pub trait Query {
 pub type QueryItem<'a, Q> = <<Q as Query>::Fetch as Fetch<'a>>::Item;
 
 /// Streaming iterators over contiguous homogeneous ranges of components
+///
+/// # Safety
+///
+/// Implementations must only hand out references permitted by the borrows acquired in `borrow`,
+/// and `access` must accurately describe the access performed by `get`.
 pub unsafe trait Fetch<'a>: Sized {
     /// Type of value to be fetched
     type Item;
@@ -35,11 +124,29 @@ pub unsafe trait Fetch<'a>: Sized {
     fn access(archetype: &Archetype) -> Option<Access>;
 
     /// Acquire dynamic borrows from `archetype`
-    fn borrow(archetype: &Archetype);
+    ///
+    /// On failure, no borrows may remain acquired.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError>;
     /// Construct a `Fetch` for `archetype` if it should be traversed
     fn new(archetype: &'a Archetype) -> Option<Self>;
     /// Release dynamic borrows acquired by `borrow`
     fn release(archetype: &Archetype);
+    /// Invoke `f` for every component type that may be borrowed and whether the borrow is unique
+    ///
+    /// Must report the same borrows as `borrow` acquires. Used to reject queries that would alias
+    /// when dynamic borrow checks are skipped.
+    fn for_each_borrow(f: impl FnMut(TypeId, bool));
+
+    /// Invoke `f` for component types that `access` requires to be present, with `true`, or
+    /// absent, with `false`
+    ///
+    /// Lets archetypes be ruled out with a few bitwise operations before calling `access`.
+    /// Reporting every requirement is optional, but none may be reported that `access` doesn't
+    /// enforce.
+    fn for_each_requirement(f: impl FnMut(TypeId, bool)) {
+        let _ = f;
+    }
 
     /// Access the `n`th item in this archetype without bounds checking
     ///
@@ -49,6 +156,66 @@ pub unsafe trait Fetch<'a>: Sized {
     /// - Bounds-checking must be performed externally
     /// - Any resulting borrows must be legal (e.g. no &mut to something another iterator might access)
     unsafe fn get(&self, n: usize) -> Self::Item;
+
+    /// Whether `skip` may return `true`, i.e. whether individual entities are filtered out
+    const FILTERS: bool = false;
+
+    /// Whether the `n`th item in this archetype should be omitted from the results
+    ///
+    /// # Safety
+    /// Same as `get`
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        let _ = n;
+        false
+    }
+
+    /// Hint that the `n`th item in this archetype will be accessed soon
+    ///
+    /// Must not dereference anything; `n` may be out of bounds.
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        let _ = n;
+    }
+}
+
+/// Fetches able to expose all of an archetype's items at once as contiguous slices
+///
+/// Implemented for queries made of `&T`, `&mut T`, `Copied<T>`, `Option`, `With`, `Without`, and
+/// tuples thereof, which don't filter individual entities.
+///
+/// # Safety
+///
+/// `slice` must only hand out references permitted by the borrows acquired in `Fetch::borrow`.
+pub unsafe trait ChunkFetch<'a>: Fetch<'a> {
+    /// Type of the slices covering an archetype
+    type Slice;
+
+    /// Access the first `len` items in this archetype
+    ///
+    /// # Safety
+    /// Same as `Fetch::get`, for every item in `0..len`
+    unsafe fn slice(&self, len: usize) -> Self::Slice;
+}
+
+/// Type of slices yielded by `QueryBorrow::iter_chunks`
+pub type QueryChunk<'a, Q> = <<Q as Query>::Fetch as ChunkFetch<'a>>::Slice;
+
+/// Ask the CPU to start loading the cache line containing `ptr`, where supported
+#[inline(always)]
+fn prefetch<T>(ptr: NonNull<T>, n: usize) {
+    let ptr = ptr.as_ptr().wrapping_add(n);
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse"))]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    #[cfg(all(target_arch = "x86", target_feature = "sse"))]
+    unsafe {
+        use core::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    let _ = ptr;
 }
 
 /// Type of access a `Query` may have to an `Archetype`
@@ -62,7 +229,7 @@ pub enum Access {
     Write,
 }
 
-impl<'a, T: Component> Query for &'a T {
+impl<T: Component> Query for &'_ T {
     type Fetch = FetchRead<T>;
 }
 
@@ -84,8 +251,8 @@ unsafe impl<'a, T: Component> Fetch<'a> for FetchRead<T> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow::<T>();
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
     }
     fn new(archetype: &'a Archetype) -> Option<Self> {
         archetype.get_base::<T>().map(Self)
@@ -93,24 +260,103 @@ unsafe impl<'a, T: Component> Fetch<'a> for FetchRead<T> {
     fn release(archetype: &Archetype) {
         archetype.release::<T>();
     }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), false);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
 
     unsafe fn get(&self, n: usize) -> Self::Item {
         &*self.0.as_ptr().add(n)
     }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        prefetch(self.0, n);
+    }
+}
+
+/// Query transformer yielding copies of a `Copy` component by value
+///
+/// Equivalent to `&T` followed by a dereference, but the yielded item holds no borrow of the
+/// world, which is convenient for small components like flags and identifiers.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+/// let mut entities = world
+///     .query::<Copied<i32>>()
+///     .iter()
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, [(a, 123), (b, 456)]);
+/// ```
+pub struct Copied<T>(PhantomData<T>);
+
+impl<T: Component + Copy> Query for Copied<T> {
+    type Fetch = FetchCopied<T>;
+}
+
+#[doc(hidden)]
+pub struct FetchCopied<T>(NonNull<T>);
+
+unsafe impl<'a, T: Component + Copy> Fetch<'a> for FetchCopied<T> {
+    type Item = T;
+
+    fn dangling() -> Self {
+        Self(NonNull::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    fn new(archetype: &'a Archetype) -> Option<Self> {
+        archetype.get_base::<T>().map(Self)
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), false);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
+
+    unsafe fn get(&self, n: usize) -> Self::Item {
+        *self.0.as_ptr().add(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        prefetch(self.0, n);
+    }
 }
 
-impl<'a, T: Component> Query for &'a mut T {
+impl<T: Component> Query for &'_ mut T {
     type Fetch = FetchWrite<T>;
 }
 
 #[doc(hidden)]
-pub struct FetchWrite<T>(NonNull<T>);
+pub struct FetchWrite<T>(NonNull<T>, NonNull<bool>);
 
 unsafe impl<'a, T: Component> Fetch<'a> for FetchWrite<T> {
     type Item = &'a mut T;
 
     fn dangling() -> Self {
-        Self(NonNull::dangling())
+        Self(NonNull::dangling(), NonNull::dangling())
     }
 
     fn access(archetype: &Archetype) -> Option<Access> {
@@ -121,19 +367,149 @@ unsafe impl<'a, T: Component> Fetch<'a> for FetchWrite<T> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow_mut::<T>();
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow_mut::<T>()
     }
     fn new(archetype: &'a Archetype) -> Option<Self> {
-        archetype.get_base::<T>().map(Self)
+        Some(Self(
+            archetype.get_base::<T>()?,
+            archetype.get_changed_base::<T>()?,
+        ))
     }
     fn release(archetype: &Archetype) {
         archetype.release_mut::<T>();
     }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
 
     unsafe fn get(&self, n: usize) -> Self::Item {
+        // Whether or not the reference is actually written through is unknown, so assume it is
+        *self.1.as_ptr().add(n) = true;
         &mut *self.0.as_ptr().add(n)
     }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        prefetch(self.0, n);
+    }
+}
+
+/// Unique access to a component that flags it as changed only when actually written
+///
+/// Can be used in queries in place of `&mut T` so that passes which usually only read a component
+/// don't flag every visited entity as changed. Obtaining a `&mut T` through `DerefMut` flags the
+/// component, while reading through `Deref` does not.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1,));
+/// let b = world.spawn((-1,));
+/// world.clear_changed();
+/// for (_, mut x) in world.query_mut::<Mut<i32>>() {
+///     if *x < 0 {
+///         *x = 0;
+///     }
+/// }
+/// assert!(!world.is_changed::<i32>(a).unwrap());
+/// assert!(world.is_changed::<i32>(b).unwrap());
+/// ```
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    changed: &'a mut bool,
+}
+
+impl<'a, T> Mut<'a, T> {
+    /// Whether the component is currently flagged as changed
+    pub fn is_changed(&self) -> bool {
+        *self.changed
+    }
+
+    /// Access the component without flagging it as changed
+    ///
+    /// Useful for writes that should not be observable as changes, such as bookkeeping.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        *self.changed = true;
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: Component> Query for Mut<'_, T> {
+    type Fetch = FetchMut<T>;
+}
+
+#[doc(hidden)]
+pub struct FetchMut<T>(NonNull<T>, NonNull<bool>);
+
+unsafe impl<'a, T: Component> Fetch<'a> for FetchMut<T> {
+    type Item = Mut<'a, T>;
+
+    fn dangling() -> Self {
+        Self(NonNull::dangling(), NonNull::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Write)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow_mut::<T>()
+    }
+    fn new(archetype: &'a Archetype) -> Option<Self> {
+        Some(Self(
+            archetype.get_base::<T>()?,
+            archetype.get_changed_base::<T>()?,
+        ))
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release_mut::<T>();
+    }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+    }
+
+    unsafe fn get(&self, n: usize) -> Self::Item {
+        Mut {
+            value: &mut *self.0.as_ptr().add(n),
+            changed: &mut *self.1.as_ptr().add(n),
+        }
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        prefetch(self.0, n);
+    }
 }
 
 impl<T: Query> Query for Option<T> {
@@ -154,7 +530,7 @@ unsafe impl<'a, T: Fetch<'a>> Fetch<'a> for TryFetch<T> {
         Some(T::access(archetype).unwrap_or(Access::Iterate))
     }
 
-    fn borrow(archetype: &Archetype) {
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
         T::borrow(archetype)
     }
     fn new(archetype: &'a Archetype) -> Option<Self> {
@@ -163,15 +539,30 @@ unsafe impl<'a, T: Fetch<'a>> Fetch<'a> for TryFetch<T> {
     fn release(archetype: &Archetype) {
         T::release(archetype)
     }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        T::for_each_borrow(&mut f);
+    }
 
     unsafe fn get(&self, n: usize) -> Option<T::Item> {
-        Some(self.0.as_ref()?.get(n))
+        let fetch = self.0.as_ref()?;
+        if T::FILTERS && fetch.skip(n) {
+            return None;
+        }
+        Some(fetch.get(n))
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        if let Some(fetch) = self.0.as_ref() {
+            fetch.prefetch(n);
+        }
     }
 }
 
 /// Query transformer skipping entities that have a `T` component
 ///
-/// See also `QueryBorrow::without`.
+/// Matching is decided per archetype, so entities with a `T` are never visited and cost nothing
+/// to skip. See also `QueryBorrow::without`.
 ///
 /// # Example
 /// ```
@@ -210,7 +601,7 @@ unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
         F::borrow(archetype)
     }
     fn new(archetype: &'a Archetype) -> Option<Self> {
@@ -222,15 +613,35 @@ unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
     fn release(archetype: &Archetype) {
         F::release(archetype)
     }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(&mut f);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), false);
+        F::for_each_requirement(&mut f);
+    }
 
     unsafe fn get(&self, n: usize) -> F::Item {
         self.0.get(n)
     }
+
+    const FILTERS: bool = F::FILTERS;
+
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        self.0.skip(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        self.0.prefetch(n)
+    }
 }
 
 /// Query transformer skipping entities that do not have a `T` component
 ///
-/// See also `QueryBorrow::with`.
+/// Matching is decided per archetype, so entities without a `T` are never visited and cost
+/// nothing to skip. `T` itself is not borrowed. See also `QueryBorrow::with`.
 ///
 /// # Example
 /// ```
@@ -271,7 +682,7 @@ unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
         F::borrow(archetype)
     }
     fn new(archetype: &'a Archetype) -> Option<Self> {
@@ -283,70 +694,519 @@ unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
     fn release(archetype: &Archetype) {
         F::release(archetype)
     }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(&mut f);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+        F::for_each_requirement(&mut f);
+    }
 
     unsafe fn get(&self, n: usize) -> F::Item {
         self.0.get(n)
     }
+
+    const FILTERS: bool = F::FILTERS;
+
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        self.0.skip(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        self.0.prefetch(n)
+    }
 }
 
-/// A borrow of a `World` sufficient to execute the query `Q`
+/// Query transformer skipping entities whose `T` component has not been flagged as changed
 ///
-/// Note that borrows are not released until this object is dropped.
-pub struct QueryBorrow<'w, Q: Query> {
-    meta: &'w [EntityMeta],
-    archetypes: &'w [Archetype],
-    borrowed: bool,
-    _marker: PhantomData<Q>,
+/// Entities without a `T` are skipped as well. See [`World::is_changed`] for when components are
+/// flagged, and [`World::clear_trackers`] to reset the flags. Shares access to `T`, so `Q` must not
+/// uniquely borrow it.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456, false));
+/// world.clear_trackers();
+/// *world.get_mut::<i32>(b).unwrap() = 42;
+/// let entities = world.query::<Changed<i32, &bool>>()
+///     .iter()
+///     .map(|(e, &x)| (e, x))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(b, false)]);
+/// ```
+pub struct Changed<T, Q>(PhantomData<(Q, fn(T))>);
+
+impl<T: Component, Q: Query> Query for Changed<T, Q> {
+    type Fetch = FetchChanged<T, Q::Fetch>;
 }
 
-impl<'w, Q: Query> QueryBorrow<'w, Q> {
-    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
-        Self {
-            meta,
-            archetypes,
-            borrowed: false,
-            _marker: PhantomData,
-        }
-    }
+#[doc(hidden)]
+pub struct FetchChanged<T, F>(F, NonNull<bool>, PhantomData<fn(T)>);
 
-    /// Execute the query
-    ///
-    /// Must be called only once per query.
-    // The lifetime narrowing here is required for soundness.
-    pub fn iter(&mut self) -> QueryIter<'_, Q> {
-        self.borrow();
-        unsafe { QueryIter::new(self.meta, self.archetypes) }
-    }
+unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchChanged<T, F> {
+    type Item = F::Item;
 
-    /// Like `iter`, but returns child iterators of at most `batch_size` elements
-    ///
-    /// Useful for distributing work over a threadpool.
-    // The lifetime narrowing here is required for soundness.
-    pub fn iter_batched(&mut self, batch_size: u32) -> BatchedIter<'_, Q> {
-        self.borrow();
-        unsafe { BatchedIter::new(self.meta, self.archetypes, batch_size) }
+    fn dangling() -> Self {
+        Self(F::dangling(), NonNull::dangling(), PhantomData)
     }
 
-    fn borrow(&mut self) {
-        if self.borrowed {
-            panic!(
-                "called QueryBorrow::iter twice on the same borrow; construct a new query instead"
-            );
-        }
-        for x in self.archetypes {
-            // TODO: Release prior borrows on failure?
-            if Q::Fetch::access(x) >= Some(Access::Read) {
-                Q::Fetch::borrow(x);
-            }
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if !archetype.has::<T>() {
+            return None;
         }
-        self.borrowed = true;
+        Some(F::access(archetype)?.max(Access::Read))
     }
 
-    /// Transform the query into one that requires a certain component without borrowing it
-    ///
-    /// This can be useful when the component needs to be borrowed elsewhere and it isn't necessary
-    /// for the iterator to expose its data directly.
-    ///
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()?;
+        F::borrow(archetype).inspect_err(|_| archetype.release::<T>())
+    }
+    fn new(archetype: &'a Archetype) -> Option<Self> {
+        Some(Self(
+            F::new(archetype)?,
+            archetype.get_changed_base::<T>()?,
+            PhantomData,
+        ))
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+        F::release(archetype);
+    }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), false);
+        F::for_each_borrow(&mut f);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+        F::for_each_requirement(&mut f);
+    }
+
+    unsafe fn get(&self, n: usize) -> F::Item {
+        self.0.get(n)
+    }
+
+    const FILTERS: bool = true;
+
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        !*self.1.as_ptr().add(n) || self.0.skip(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        self.0.prefetch(n)
+    }
+}
+
+/// Query transformer skipping entities whose `T` component has not been added since the last call
+/// to [`World::clear_trackers`]
+///
+/// A component counts as added when it is spawned with its entity or inserted into an entity that
+/// did not have one; replacing an existing component does not. Entities without a `T` are skipped
+/// as well. Shares access to `T`, so `Q` must not uniquely borrow it.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// world.clear_trackers();
+/// world.insert_one(b, true).unwrap();
+/// let c = world.spawn((true,));
+/// let mut entities = world.query::<Added<bool, ()>>()
+///     .iter()
+///     .map(|(e, ())| e)
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, &[b, c]);
+/// ```
+pub struct Added<T, Q>(PhantomData<(Q, fn(T))>);
+
+impl<T: Component, Q: Query> Query for Added<T, Q> {
+    type Fetch = FetchAdded<T, Q::Fetch>;
+}
+
+#[doc(hidden)]
+pub struct FetchAdded<T, F>(F, NonNull<bool>, PhantomData<fn(T)>);
+
+unsafe impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchAdded<T, F> {
+    type Item = F::Item;
+
+    fn dangling() -> Self {
+        Self(F::dangling(), NonNull::dangling(), PhantomData)
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if !archetype.has::<T>() {
+            return None;
+        }
+        Some(F::access(archetype)?.max(Access::Read))
+    }
+
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()?;
+        F::borrow(archetype).inspect_err(|_| archetype.release::<T>())
+    }
+    fn new(archetype: &'a Archetype) -> Option<Self> {
+        Some(Self(
+            F::new(archetype)?,
+            archetype.get_added_base::<T>()?,
+            PhantomData,
+        ))
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+        F::release(archetype);
+    }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), false);
+        F::for_each_borrow(&mut f);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), true);
+        F::for_each_requirement(&mut f);
+    }
+
+    unsafe fn get(&self, n: usize) -> F::Item {
+        self.0.get(n)
+    }
+
+    const FILTERS: bool = true;
+
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        !*self.1.as_ptr().add(n) || self.0.skip(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        self.0.prefetch(n)
+    }
+}
+
+/// A borrow of a `World` sufficient to execute the query `Q`
+///
+/// Note that borrows are not released until this object is dropped.
+pub struct QueryBorrow<'w, Q: Query> {
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    /// Component types added by `with_dynamic` and `without_dynamic`, and whether each is required
+    filter: Vec<(TypeId, bool)>,
+    borrowed: bool,
+    _marker: PhantomData<Q>,
+}
+
+impl<'w, Q: Query> QueryBorrow<'w, Q> {
+    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
+        Self {
+            meta,
+            archetypes,
+            filter: Vec::new(),
+            borrowed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        self.borrow();
+        unsafe { QueryIter::new_filtered(self.meta, self.archetypes, &self.filter) }
+    }
+
+    /// Like `iter`, but yields only components, without their entities' handles
+    ///
+    /// See [`QueryIter::components`].
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_components(&mut self) -> QueryComponents<'_, Q> {
+        self.iter().components()
+    }
+
+    /// Like `iter`, but fails instead of panicking if a component is already borrowed in a
+    /// conflicting way
+    ///
+    /// Nothing remains borrowed on failure, so the query can be attempted again later. Useful
+    /// for skipping a misbehaving system rather than bringing down the whole application.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, true));
+    /// let held = world.get_mut::<i32>(e).unwrap();
+    /// let mut query = world.query::<(&bool, &i32)>();
+    /// let err = query.try_iter().err().unwrap();
+    /// assert_eq!(err.type_name(), "i32");
+    /// drop(held);
+    /// assert_eq!(query.try_iter().unwrap().count(), 1);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn try_iter(&mut self) -> Result<QueryIter<'_, Q>, BorrowError> {
+        self.try_borrow()?;
+        Ok(unsafe { QueryIter::new_filtered(self.meta, self.archetypes, &self.filter) })
+    }
+
+    /// Like `iter`, but yields entities in order of increasing ID
+    ///
+    /// The order doesn't depend on the history of archetypes or of the moves within them caused by
+    /// despawns and component changes, so identical worlds built by identical operations, such as
+    /// those of lockstep simulation peers or replays, visit entities identically. Costs an
+    /// allocation of 4 bytes per matching entity and a sort up front, plus a random access per
+    /// entity rather than a linear scan, so prefer `iter` where order doesn't matter.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn((3, true));
+    /// world.remove_one::<bool>(a).unwrap();
+    /// let order = world.query::<&i32>().iter_sorted().map(|(e, _)| e).collect::<Vec<_>>();
+    /// assert_eq!(order, [a, b, c]);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_sorted(&mut self) -> SortedIter<'_, Q> {
+        self.borrow();
+        unsafe { SortedIter::new(self.meta, self.archetypes, &self.filter) }
+    }
+
+    /// Like `iter`, but yields entities in order of increasing `key`
+    ///
+    /// Useful for e.g. drawing back to front or prioritizing network updates by distance. `key` is
+    /// evaluated once per matched entity up front, and the sort is stable, so entities with equal
+    /// keys are yielded in the order `iter` would. Like `iter_sorted`, costs an allocation and a
+    /// random access per entity. See `sorted_by_cached_key` to skip the sort when keys are
+    /// unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((3.0f32,));
+    /// let b = world.spawn((1.0f32, true));
+    /// let c = world.spawn((2.0f32,));
+    /// let order = world
+    ///     .query::<&f32>()
+    ///     .sorted_by_key(|&&y| (y * 100.0) as i32)
+    ///     .map(|(e, _)| e)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(order, [b, c, a]);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn sorted_by_key<'a, K: Ord>(
+        &'a mut self,
+        key: impl FnMut(&QueryItem<'a, Q>) -> K,
+    ) -> SortedIter<'a, Q> {
+        self.borrow();
+        unsafe { SortedIter::new_by_key(self.meta, self.archetypes, &self.filter, key) }
+    }
+
+    /// Like `sorted_by_key`, but reuses the order computed by the previous call with the same
+    /// `cache` if no `T` component was flagged as changed and the same entities matched
+    ///
+    /// `T` should be the component `key` is derived from. Change flags are only observed while
+    /// set, so like the [`Changed`] transformer, this must be called before `World::clear_changed`
+    /// resets flags set since the previous call. Borrowing `T` uniquely in this query would flag
+    /// every `T` it visits, defeating the cache.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((3,));
+    /// let b = world.spawn((1,));
+    /// let mut cache = SortCache::new();
+    /// let order = |world: &World, cache: &mut SortCache| {
+    ///     world
+    ///         .query::<&i32>()
+    ///         .sorted_by_cached_key::<i32, _>(cache, |&&x| x)
+    ///         .map(|(e, _)| e)
+    ///         .collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(order(&world, &mut cache), [b, a]);
+    /// world.clear_changed();
+    /// assert_eq!(order(&world, &mut cache), [b, a]); // Not re-sorted
+    /// *world.get_mut::<i32>(a).unwrap() = 0;
+    /// assert_eq!(order(&world, &mut cache), [a, b]);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn sorted_by_cached_key<'a, T: Component, K: Ord>(
+        &'a mut self,
+        cache: &mut SortCache,
+        key: impl FnMut(&QueryItem<'a, Q>) -> K,
+    ) -> SortedIter<'a, Q> {
+        self.borrow();
+        unsafe {
+            SortedIter::new_by_cached_key::<T, K>(
+                self.meta,
+                self.archetypes,
+                &self.filter,
+                cache,
+                key,
+            )
+        }
+    }
+
+    /// Like `iter`, but returns child iterators of at most `batch_size` elements
+    ///
+    /// Useful for distributing work over a threadpool.
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_batched(&mut self, batch_size: u32) -> BatchedIter<'_, Q> {
+        self.borrow();
+        unsafe { BatchedIter::new(self.meta, self.archetypes, &self.filter, batch_size) }
+    }
+
+    /// Like `iter_batched`, but with the batch size chosen to split the matched entities into
+    /// `parts` batches of roughly equal size
+    ///
+    /// Batches never span archetypes, so up to `parts - 1` more batches may be yielded when the
+    /// matched entities are spread over several archetypes. Each batch is `Send`, so batches can
+    /// be handed to the worker threads of any job system.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (i,)));
+    /// let mut query = world.query::<&i32>();
+    /// let batches = query.iter_split(4).collect::<Vec<_>>();
+    /// let sums = std::thread::scope(|s| {
+    ///     let handles = batches
+    ///         .into_iter()
+    ///         .map(|batch| s.spawn(move || batch.map(|(_, &x)| x).sum::<i32>()))
+    ///         .collect::<Vec<_>>();
+    ///     handles.into_iter().map(|x| x.join().unwrap()).collect::<Vec<_>>()
+    /// });
+    /// assert_eq!(sums.len(), 4);
+    /// assert_eq!(sums.iter().sum::<i32>(), 4950);
+    /// ```
+    ///
+    /// # Panics
+    /// - if `parts` is 0
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_split(&mut self, parts: u32) -> BatchedIter<'_, Q> {
+        assert!(parts > 0, "can't split a query into 0 parts");
+        // Borrow first, since counting matches may read change flags
+        self.borrow();
+        let matched = self
+            .archetypes
+            .iter()
+            .filter(|x| admits(&self.filter, x))
+            .map(QueryIter::<Q>::matches)
+            .sum::<usize>();
+        let batch_size = matched.div_ceil(parts as usize).clamp(1, u32::MAX as usize) as u32;
+        unsafe { BatchedIter::new(self.meta, self.archetypes, &self.filter, batch_size) }
+    }
+
+    /// Like `iter`, but yields the IDs and components of all matched entities in each archetype
+    /// at once, as contiguous slices
+    ///
+    /// Useful for passes that benefit from SIMD or other whole-array processing. Only available
+    /// for queries that don't filter individual entities. Empty archetypes are skipped.
+    ///
+    /// Components are stored per archetype, so a hot pair of components such as a position and a
+    /// velocity is split into one chunk per combination of other components its entities have.
+    /// Keeping rarely-needed or short-lived state out of such entities' archetypes, e.g. with
+    /// [`World::insert_sparse`](crate::World::insert_sparse), keeps the number of chunks, and so
+    /// the cost of each sweep, close to that of a single array. Registering the pair with
+    /// [`World::register_group`](crate::World::register_group) also spares each sweep from
+    /// scanning unrelated archetypes.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (Position(i as f32), Velocity(1.0))));
+    /// world.spawn((Position(0.0), Velocity(-1.0), true));
+    /// for (ids, (pos, vel)) in world.query::<(&mut Position, &Velocity)>().iter_chunks() {
+    ///     assert_eq!(ids.len(), pos.len());
+    ///     for (p, v) in pos.iter_mut().zip(vel) {
+    ///         p.0 += v.0;
+    ///     }
+    /// }
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_chunks<'q>(&'q mut self) -> QueryChunks<'q, Q>
+    where
+        Q::Fetch: ChunkFetch<'q>,
+    {
+        self.borrow();
+        QueryChunks {
+            archetypes: self.archetypes.iter(),
+            filter: &self.filter,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `iter`, but also yields the index `map` associates with each entity, if any
+    ///
+    /// Convenient for correlating query results with data stored outside the world in dense
+    /// arrays, without hashing each entity.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_with_dense_index<'q>(
+        &'q mut self,
+        map: &'q EntityDenseMap,
+    ) -> impl Iterator<Item = (Entity, Option<usize>, QueryItem<'q, Q>)> + 'q {
+        self.iter()
+            .map(move |(entity, item)| (entity, map.get(entity), item))
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow(&mut self) {
+        if let Err(e) = self.try_borrow() {
+            e.panic();
+        }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn try_borrow(&mut self) -> Result<(), BorrowError> {
+        if self.borrowed {
+            panic!(
+                "called QueryBorrow::iter twice on the same borrow; construct a new query instead"
+            );
+        }
+        for (i, x) in self.archetypes.iter().enumerate() {
+            if access::<Q>(&self.filter, x) >= Some(Access::Read) {
+                if let Err(e) = Q::Fetch::borrow(x) {
+                    for x in &self.archetypes[..i] {
+                        if access::<Q>(&self.filter, x) >= Some(Access::Read) {
+                            Q::Fetch::release(x);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        self.borrowed = true;
+        Ok(())
+    }
+
+    /// Transform the query into one that requires a certain component without borrowing it
+    ///
+    /// This can be useful when the component needs to be borrowed elsewhere and it isn't necessary
+    /// for the iterator to expose its data directly.
+    ///
     /// Equivalent to using a query type wrapped in `With`.
     ///
     /// # Example
@@ -368,49 +1228,970 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         self.transform()
     }
 
-    /// Transform the query into one that skips entities having a certain component
+    /// Transform the query into one that skips entities having a certain component
+    ///
+    /// Equivalent to using a query type wrapped in `Without`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<&i32>()
+    ///     .without::<bool>()
+    ///     .iter()
+    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities, &[(c, 42)]);
+    /// ```
+    pub fn without<T: Component>(self) -> QueryBorrow<'w, Without<T, Q>> {
+        self.transform()
+    }
+
+    /// Skip archetypes lacking the component type `ty`, without borrowing it
+    ///
+    /// Like `with`, but for filters that are only known at runtime, such as those chosen by
+    /// configuration or scripts.
+    ///
+    /// # Panics
+    /// - if the query has already been executed
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456, "abc"));
+    /// let required = [TypeId::of::<bool>()];
+    /// let mut query = world.query::<&i32>();
+    /// for &ty in &required {
+    ///     query = query.with_dynamic(ty);
+    /// }
+    /// assert_eq!(query.iter().map(|(e, _)| e).collect::<Vec<_>>(), [a]);
+    /// ```
+    pub fn with_dynamic(self, ty: TypeId) -> Self {
+        self.filter_dynamic(ty, true)
+    }
+
+    /// Skip archetypes having the component type `ty`
+    ///
+    /// Like `without`, but for filters that are only known at runtime.
+    ///
+    /// # Panics
+    /// - if the query has already been executed
+    pub fn without_dynamic(self, ty: TypeId) -> Self {
+        self.filter_dynamic(ty, false)
+    }
+
+    fn filter_dynamic(mut self, ty: TypeId, present: bool) -> Self {
+        // Borrows are released according to the filter, so it mustn't change while they're held
+        assert!(
+            !self.borrowed,
+            "query filters must be added before the query is executed"
+        );
+        self.filter.push((ty, present));
+        self
+    }
+
+    /// Whether any entity matches the query
+    ///
+    /// Stops at the first match, and unless `Q` filters individual entities, doesn't need to
+    /// borrow any components at all.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// assert!(world.query::<&i32>().any());
+    /// assert!(!world.query::<&i32>().without::<bool>().any());
+    /// ```
+    pub fn any(self) -> bool {
+        self.at_most(1) != 0
+    }
+
+    /// Count the entities matching the query, stopping once `n` have been found
+    ///
+    /// Returns at most `n`. Like `any`, avoids borrowing components when possible.
+    pub fn at_most(mut self, n: usize) -> usize {
+        if Q::Fetch::FILTERS {
+            return self.iter().take(n).count();
+        }
+        count_at_most::<Q>(self.archetypes, &self.filter, n)
+    }
+
+    /// Post-process each query result with `f`
+    ///
+    /// Unlike mapping the iterator returned by `iter`, the resulting [`QueryMap`] still offers
+    /// batched iteration for distribution over a thread pool, and its plain iterator remains an
+    /// `ExactSizeIterator`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (i as f32,)));
+    /// let mut query = world.query::<&f32>().map(|_, &x| x * 2.0);
+    /// let iter = query.iter();
+    /// assert_eq!(iter.len(), 100);
+    /// assert_eq!(iter.fold(f32::MIN, f32::max), 198.0);
+    /// ```
+    pub fn map<F, R>(self, f: F) -> QueryMap<'w, Q, F>
+    where
+        F: for<'q> Fn(Entity, QueryItem<'q, Q>) -> R,
+    {
+        QueryMap { borrow: self, f }
+    }
+
+    /// Expand each query result into any number of values with `f`
+    ///
+    /// Like `map`, the resulting [`QueryFlatMap`] supports batched iteration.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((vec![1, 2],));
+    /// world.spawn((vec![3],));
+    /// let mut query = world.query::<&Vec<i32>>().flat_map(|_, x| x.clone());
+    /// assert_eq!(query.iter().sum::<i32>(), 6);
+    /// ```
+    pub fn flat_map<F, U>(self, f: F) -> QueryFlatMap<'w, Q, F>
+    where
+        F: for<'q> Fn(Entity, QueryItem<'q, Q>) -> U,
+        U: IntoIterator,
+    {
+        QueryFlatMap { borrow: self, f }
+    }
+
+    /// Helper to change the type of the query
+    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
+        let x = QueryBorrow {
+            meta: self.meta,
+            archetypes: self.archetypes,
+            filter: core::mem::take(&mut self.filter),
+            borrowed: self.borrowed,
+            _marker: PhantomData,
+        };
+        // Ensure `Drop` won't fire redundantly
+        self.borrowed = false;
+        x
+    }
+}
+
+unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> {}
+unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> {}
+
+impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
+    fn drop(&mut self) {
+        if self.borrowed {
+            for x in self.archetypes {
+                if access::<Q>(&self.filter, x) >= Some(Access::Read) {
+                    Q::Fetch::release(x);
+                }
+            }
+        }
+    }
+}
+
+impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
+    type Item = (Entity, QueryItem<'q, Q>);
+    type IntoIter = QueryIter<'q, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A query whose results are post-processed by `F`
+///
+/// Obtained from [`QueryBorrow::map`].
+pub struct QueryMap<'w, Q: Query, F> {
+    borrow: QueryBorrow<'w, Q>,
+    f: F,
+}
+
+impl<'w, Q: Query, F, R> QueryMap<'w, Q, F>
+where
+    F: for<'q> Fn(Entity, QueryItem<'q, Q>) -> R,
+{
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter(&mut self) -> impl ExactSizeIterator<Item = R> + '_ {
+        let f = &self.f;
+        self.borrow
+            .iter()
+            .map(move |(entity, item)| f(entity, item))
+    }
+
+    /// Like `iter`, but returns child iterators of at most `batch_size` elements
+    ///
+    /// The child iterators are `Send` if `F` is `Sync`, so they can be distributed over a thread
+    /// pool.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_batched(
+        &mut self,
+        batch_size: u32,
+    ) -> impl Iterator<Item = impl Iterator<Item = R> + '_> + '_ {
+        let f = &self.f;
+        self.borrow
+            .iter_batched(batch_size)
+            .map(move |batch| batch.map(move |(entity, item)| f(entity, item)))
+    }
+}
+
+/// A query whose results are each expanded into any number of values by `F`
+///
+/// Obtained from [`QueryBorrow::flat_map`].
+pub struct QueryFlatMap<'w, Q: Query, F> {
+    borrow: QueryBorrow<'w, Q>,
+    f: F,
+}
+
+impl<'w, Q: Query, F, U> QueryFlatMap<'w, Q, F>
+where
+    F: for<'q> Fn(Entity, QueryItem<'q, Q>) -> U,
+    U: IntoIterator,
+{
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter<'a>(&'a mut self) -> impl Iterator<Item = U::Item> + 'a
+    where
+        U: 'a,
+    {
+        let f = &self.f;
+        self.borrow
+            .iter()
+            .flat_map(move |(entity, item)| f(entity, item))
+    }
+
+    /// Like `iter`, but expands batches of at most `batch_size` query results at a time
+    ///
+    /// The child iterators are `Send` if `F` is `Sync` and `U` and its iterator are `Send`, so
+    /// they can be distributed over a thread pool.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter_batched<'a>(
+        &'a mut self,
+        batch_size: u32,
+    ) -> impl Iterator<Item = impl Iterator<Item = U::Item> + 'a> + 'a
+    where
+        U: 'a,
+    {
+        let f = &self.f;
+        self.borrow
+            .iter_batched(batch_size)
+            .map(move |batch| batch.flat_map(move |(entity, item)| f(entity, item)))
+    }
+}
+
+/// Iterator over the set of entities with the components in `Q`
+pub struct QueryIter<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    filter: &'q [(TypeId, bool)],
+    archetype_index: usize,
+    iter: ChunkIter<Q>,
+    prefetch: usize,
+    /// Number of entities yet to be yielded, counted up front so that `len` is cheap
+    remaining: usize,
+}
+
+impl<'q, Q: Query> QueryIter<'q, Q> {
+    /// # Safety
+    ///
+    /// `'q` must be sufficient to guarantee that `Q` cannot violate borrow safety, either with
+    /// dynamic borrow checks or by representing exclusive access to the `World`.
+    pub(crate) unsafe fn new(meta: &'q [EntityMeta], archetypes: &'q [Archetype]) -> Self {
+        Self::new_filtered(meta, archetypes, &[])
+    }
+
+    /// Like `new`, but skipping archetypes not admitted by `filter`
+    ///
+    /// # Safety
+    ///
+    /// As for `new`.
+    unsafe fn new_filtered(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        filter: &'q [(TypeId, bool)],
+    ) -> Self {
+        Self {
+            meta,
+            archetypes,
+            filter,
+            archetype_index: 0,
+            iter: ChunkIter::empty(),
+            prefetch: 0,
+            remaining: archetypes
+                .iter()
+                .filter(|x| admits(filter, x))
+                .map(Self::matches)
+                .sum(),
+        }
+    }
+
+    /// Number of entities in `archetype` matched by `Q`
+    fn matches(archetype: &Archetype) -> usize {
+        if Q::Fetch::access(archetype).is_none() {
+            return 0;
+        }
+        if !Q::Fetch::FILTERS {
+            return archetype.len() as usize;
+        }
+        Q::Fetch::new(archetype).map_or(0, |fetch| {
+            (0..archetype.len() as usize)
+                .filter(|&n| unsafe { !fetch.skip(n) })
+                .count()
+        })
+    }
+
+    /// Issue software prefetches for the components of the entity `distance` positions ahead
+    ///
+    /// Can speed up memory-bound passes over large archetypes whose components are big enough
+    /// that the hardware prefetcher falls behind. The best distance depends on the hardware and the
+    /// work done per entity, so measure; 0, the default, disables prefetching. Has no effect on
+    /// targets without a prefetch instruction.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1000).map(|i| (i,)));
+    /// let sum = world
+    ///     .query_mut::<&i32>()
+    ///     .into_iter()
+    ///     .prefetch(16)
+    ///     .map(|(_, &x)| x)
+    ///     .sum::<i32>();
+    /// assert_eq!(sum, 499500);
+    /// ```
+    pub fn prefetch(mut self, distance: usize) -> Self {
+        self.prefetch = distance;
+        self
+    }
+
+    /// Append every remaining entity and its components to `out`
+    ///
+    /// Reserves the exact space needed up front, so `out` is grown at most once. Keeping `out`
+    /// around and clearing it between uses avoids allocating at all once it's large enough.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// let mut query = world.query::<&i32>();
+    /// let mut out = Vec::new();
+    /// query.iter().collect_into(&mut out);
+    /// assert_eq!(out.len(), 10);
+    /// ```
+    pub fn collect_into(self, out: &mut Vec<(Entity, QueryItem<'q, Q>)>) {
+        out.reserve(self.len());
+        out.extend(self);
+    }
+
+    /// Yield only the components of each entity, skipping the lookup of its `Entity` handle
+    ///
+    /// Shaves a random access per entity off tight loops that don't need the handle.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i, 1.0f32)));
+    /// for (x, y) in world.query_mut::<(&mut i32, &f32)>().into_iter().components() {
+    ///     *x += *y as i32;
+    /// }
+    /// assert_eq!(world.query::<&i32>().iter_components().sum::<i32>(), 55);
+    /// ```
+    pub fn components(self) -> QueryComponents<'q, Q> {
+        QueryComponents { iter: self }
+    }
+
+    /// The ID and components of the next entity
+    #[inline(always)]
+    fn next_row(&mut self) -> Option<(u32, QueryItem<'q, Q>)> {
+        if self.prefetch != 0 {
+            let ahead = self.iter.position + self.prefetch;
+            if ahead < self.iter.len {
+                self.iter.fetch.prefetch(ahead);
+            }
+        }
+        loop {
+            match unsafe { self.iter.next() } {
+                None => {
+                    let archetype = self.archetypes.get(self.archetype_index)?;
+                    self.archetype_index += 1;
+                    self.iter =
+                        fetch::<Q>(self.filter, archetype).map_or(ChunkIter::empty(), |fetch| {
+                            ChunkIter {
+                                entities: archetype.entities(),
+                                fetch,
+                                position: 0,
+                                len: archetype.len() as usize,
+                            }
+                        });
+                    continue;
+                }
+                Some(row) => {
+                    self.remaining -= 1;
+                    return Some(row);
+                }
+            }
+        }
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for QueryIter<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for QueryIter<'q, Q> {}
+
+impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
+    type Item = (Entity, QueryItem<'q, Q>);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, components) = self.next_row()?;
+        Some((
+            Entity {
+                id,
+                generation: unsafe { self.meta.get_unchecked(id as usize).generation },
+            },
+            components,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for QueryIter<'q, Q> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+/// Iterator over the components of the entities matched by `Q`, without their `Entity` handles
+///
+/// See [`QueryIter::components`].
+pub struct QueryComponents<'q, Q: Query> {
+    iter: QueryIter<'q, Q>,
+}
+
+impl<'q, Q: Query> Iterator for QueryComponents<'q, Q> {
+    type Item = QueryItem<'q, Q>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next_row().map(|(_, components)| components)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for QueryComponents<'q, Q> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+/// A query builder that's convertible directly into an iterator
+pub struct QueryMut<'q, Q: Query> {
+    iter: QueryIter<'q, Q>,
+}
+
+impl<'q, Q: Query> QueryMut<'q, Q> {
+    pub(crate) fn new(meta: &'q [EntityMeta], archetypes: &'q mut [Archetype]) -> Self {
+        assert_borrow::<Q>();
+        Self {
+            iter: unsafe { QueryIter::new(meta, archetypes) },
+        }
+    }
+
+    /// Like `new`, for archetypes that are shared but whose components accessed by `Q` aren't
+    ///
+    /// # Safety
+    ///
+    /// No components `Q` borrows may be accessed elsewhere for `'q`.
+    pub(crate) unsafe fn new_shared(meta: &'q [EntityMeta], archetypes: &'q [Archetype]) -> Self {
+        assert_borrow::<Q>();
+        Self {
+            iter: QueryIter::new(meta, archetypes),
+        }
+    }
+
+    /// Transform the query into one that requires a certain component without borrowing it
+    ///
+    /// See `QueryBorrow::with`
+    pub fn with<T: Component>(self) -> QueryMut<'q, With<T, Q>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities having a certain component
+    ///
+    /// See `QueryBorrow::without`
+    pub fn without<T: Component>(self) -> QueryMut<'q, Without<T, Q>> {
+        self.transform()
+    }
+
+    /// Whether any entity matches the query
+    ///
+    /// See `QueryBorrow::any`.
+    pub fn any(self) -> bool {
+        self.at_most(1) != 0
+    }
+
+    /// Count the entities matching the query, stopping once `n` have been found
+    ///
+    /// See `QueryBorrow::at_most`.
+    pub fn at_most(self, n: usize) -> usize {
+        if Q::Fetch::FILTERS {
+            return self.iter.take(n).count();
+        }
+        count_at_most::<Q>(self.iter.archetypes, self.iter.filter, n)
+    }
+
+    /// Like `into_iter`, but yields entities in order of increasing ID
+    ///
+    /// See `QueryBorrow::iter_sorted`.
+    pub fn into_iter_sorted(self) -> SortedIter<'q, Q> {
+        unsafe { SortedIter::new(self.iter.meta, self.iter.archetypes, self.iter.filter) }
+    }
+
+    /// Helper to change the type of the query
+    fn transform<R: Query>(self) -> QueryMut<'q, R> {
+        QueryMut {
+            iter: unsafe { QueryIter::new(self.iter.meta, self.iter.archetypes) },
+        }
+    }
+}
+
+impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
+    type Item = <QueryIter<'q, Q> as Iterator>::Item;
+    type IntoIter = QueryIter<'q, Q>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter
+    }
+}
+
+/// Iterator over the entities matched by a query in order of increasing ID
+///
+/// Returned by `QueryBorrow::iter_sorted` and `QueryMut::into_iter_sorted`.
+pub struct SortedIter<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    /// Fetch for each archetype, if matched
+    fetches: Vec<Option<Q::Fetch>>,
+    /// IDs of the matched entities yet to be yielded
+    ids: crate::alloc::vec::IntoIter<u32>,
+}
+
+impl<'q, Q: Query> SortedIter<'q, Q> {
+    /// # Safety
+    ///
+    /// As for `QueryIter::new`.
+    unsafe fn new(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        filter: &[(TypeId, bool)],
+    ) -> Self {
+        let mut ids = Vec::new();
+        let fetches = Self::fetch_all(archetypes, filter, |_, archetype, n| {
+            ids.push(archetype.ids()[n]);
+        });
+        ids.sort_unstable();
+        Self {
+            meta,
+            fetches,
+            ids: ids.into_iter(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// As for `QueryIter::new`.
+    unsafe fn new_by_key<K: Ord>(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        filter: &[(TypeId, bool)],
+        mut key: impl FnMut(&QueryItem<'q, Q>) -> K,
+    ) -> Self {
+        let mut keyed = Vec::new();
+        let fetches = Self::fetch_all(archetypes, filter, |fetch, archetype, n| {
+            keyed.push((key(&fetch.get(n)), archetype.ids()[n]));
+        });
+        keyed.sort_by(|x, y| x.0.cmp(&y.0));
+        Self {
+            meta,
+            fetches,
+            ids: keyed
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// As for `QueryIter::new`.
+    unsafe fn new_by_cached_key<T: Component, K: Ord>(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        filter: &[(TypeId, bool)],
+        cache: &mut SortCache,
+        key: impl FnMut(&QueryItem<'q, Q>) -> K,
+    ) -> Self {
+        let mut matched = Vec::with_capacity(cache.matched.len());
+        let mut changed = false;
+        let fetches = Self::fetch_all(archetypes, filter, |_, archetype, n| {
+            matched.push(archetype.ids()[n]);
+            if let Some(flags) = archetype.get_changed_base::<T>() {
+                changed |= *flags.as_ptr().add(n);
+            }
+        });
+        if !changed && matched == cache.matched {
+            return Self {
+                meta,
+                fetches,
+                ids: cache.order.clone().into_iter(),
+            };
+        }
+        let sorted = Self::new_by_key(meta, archetypes, filter, key);
+        cache.matched = matched;
+        cache.order.clear();
+        cache.order.extend_from_slice(sorted.ids.as_slice());
+        sorted
+    }
+
+    /// Find the fetch for each archetype, passing each matched entity to `f`
+    unsafe fn fetch_all(
+        archetypes: &'q [Archetype],
+        filter: &[(TypeId, bool)],
+        mut f: impl FnMut(&Q::Fetch, &'q Archetype, usize),
+    ) -> Vec<Option<Q::Fetch>> {
+        let mut fetches = Vec::with_capacity(archetypes.len());
+        for archetype in archetypes {
+            let fetch = fetch::<Q>(filter, archetype);
+            if let Some(ref fetch) = fetch {
+                for n in 0..archetype.len() as usize {
+                    if !Q::Fetch::FILTERS || !fetch.skip(n) {
+                        f(fetch, archetype, n);
+                    }
+                }
+            }
+            fetches.push(fetch);
+        }
+        fetches
+    }
+}
+
+/// The order computed by [`QueryBorrow::sorted_by_cached_key`], kept for reuse by later calls
+#[derive(Debug, Default, Clone)]
+pub struct SortCache {
+    /// IDs of the matched entities in archetype order, as of the last sort
+    matched: Vec<u32>,
+    /// `matched`, sorted by key
+    order: Vec<u32>,
+}
+
+impl SortCache {
+    /// Create an empty cache, which the first sort using it will fill
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for SortedIter<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for SortedIter<'q, Q> {}
+
+impl<'q, Q: Query> Iterator for SortedIter<'q, Q> {
+    type Item = (Entity, QueryItem<'q, Q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.ids.next()?;
+        let meta = &self.meta[id as usize];
+        let fetch = self.fetches[meta.location.archetype as usize]
+            .as_ref()
+            .unwrap();
+        let entity = Entity {
+            id,
+            generation: meta.generation,
+        };
+        Some((entity, unsafe { fetch.get(meta.location.index as usize) }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for SortedIter<'q, Q> {}
+
+/// Panic if `Q` borrows any component uniquely while also borrowing it elsewhere
+///
+/// Makes skipping dynamic borrow checks sound for queries of a uniquely borrowed `World`. Every
+/// `for_each_borrow` call is constant after inlining, so this typically compiles to nothing.
+#[inline(always)]
+pub(crate) fn assert_borrow<Q: Query>() {
+    let mut i = 0;
+    Q::Fetch::for_each_borrow(|a, unique| {
+        if unique {
+            let mut j = 0;
+            Q::Fetch::for_each_borrow(|b, _| {
+                assert!(i == j || a != b, "query violates a unique borrow");
+                j += 1;
+            });
+        }
+        i += 1;
+    });
+}
+
+/// Count entities in archetypes matched by `Q`, which must not filter individual entities, up to `n`
+fn count_at_most<Q: Query>(archetypes: &[Archetype], filter: &[(TypeId, bool)], n: usize) -> usize {
+    let mut count = 0;
+    for archetype in archetypes {
+        if count >= n {
+            break;
+        }
+        if access::<Q>(filter, archetype).is_some() {
+            count += archetype.len() as usize;
+        }
+    }
+    count.min(n)
+}
+
+/// Whether `archetype` has each component type in `filter` that's required, and none that aren't
+fn admits(filter: &[(TypeId, bool)], archetype: &Archetype) -> bool {
+    filter
+        .iter()
+        .all(|&(ty, present)| archetype.has_dynamic(ty) == present)
+}
+
+/// `Q::Fetch::access`, for archetypes admitted by `filter`
+fn access<Q: Query>(filter: &[(TypeId, bool)], archetype: &Archetype) -> Option<Access> {
+    if !admits(filter, archetype) {
+        return None;
+    }
+    Q::Fetch::access(archetype)
+}
+
+/// `Q::Fetch::new`, for archetypes admitted by `filter`
+fn fetch<Q: Query>(filter: &[(TypeId, bool)], archetype: &Archetype) -> Option<Q::Fetch> {
+    if !admits(filter, archetype) {
+        return None;
+    }
+    Q::Fetch::new(archetype)
+}
+
+struct ChunkIter<Q: Query> {
+    entities: NonNull<u32>,
+    fetch: Q::Fetch,
+    position: usize,
+    len: usize,
+}
+
+impl<Q: Query> ChunkIter<Q> {
+    fn empty() -> Self {
+        Self {
+            entities: NonNull::dangling(),
+            fetch: Q::Fetch::dangling(),
+            position: 0,
+            len: 0,
+        }
+    }
+
+    #[inline]
+    unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
+        if self.position == self.len {
+            return None;
+        }
+        if <Q::Fetch as Fetch<'a>>::FILTERS {
+            while self.fetch.skip(self.position) {
+                self.position += 1;
+                if self.position == self.len {
+                    return None;
+                }
+            }
+        }
+        let entity = self.entities.as_ptr().add(self.position);
+        let item = self.fetch.get(self.position);
+        self.position += 1;
+        Some((*entity, item))
+    }
+}
+
+/// A query that remembers which archetypes it matches
+///
+/// `World::query` checks every archetype for the queried components each time it's executed,
+/// which adds up for systems run every tick in worlds with hundreds of archetypes. A
+/// `PreparedQuery` caches the indices of the matching archetypes, checking only archetypes created
+/// since it was last executed, so that executing it takes time proportional to the number of
+/// matching archetypes only. New archetypes are checked against bitmasks of the component types
+/// the query requires and forbids, so most mismatches are found without any hashing. In
+/// particular, a query that matches nothing, such as `&Boss` before any boss has spawned, costs a
+/// single comparison per execution until the world's set of archetypes grows.
+///
+/// May be executed on any world, but the cache is rebuilt whenever the world differs from the
+/// last one.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn((123, true));
+/// world.spawn((456,));
+/// let mut query = PreparedQuery::<&mut i32>::new();
+/// for (_, x) in query.query_mut(&mut world) {
+///     *x += 1;
+/// }
+/// world.spawn((789, "abc"));
+/// let mut values = query.query(&world).iter().map(|(_, &mut x)| x).collect::<Vec<_>>();
+/// values.sort();
+/// assert_eq!(values, [124, 457, 789]);
+/// ```
+pub struct PreparedQuery<Q: Query> {
+    /// Identifies the world `matching` was computed for, or 0 if none
+    world_id: usize,
+    /// Number of the world's archetypes checked for matches
+    seen: usize,
+    /// Indices of the archetypes matched by `Q`
+    matching: Vec<u32>,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> PreparedQuery<Q> {
+    /// Create a prepared query that hasn't yet been executed on any world
+    pub fn new() -> Self {
+        Self {
+            world_id: 0,
+            seen: 0,
+            matching: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query on a shared world
     ///
-    /// Equivalent to using a query type wrapped in `Without`.
+    /// Components are borrowed dynamically, as with `World::query`.
+    pub fn query<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
+        self.prepare(world);
+        let (meta, archetypes) = world.storage();
+        PreparedQueryBorrow {
+            meta,
+            archetypes,
+            matching: &self.matching,
+            borrowed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query on a uniquely borrowed world
     ///
-    /// # Example
-    /// ```
-    /// # use hecs::*;
-    /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<&i32>()
-    ///     .without::<bool>()
-    ///     .iter()
-    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities, &[(c, 42)]);
-    /// ```
-    pub fn without<T: Component>(self) -> QueryBorrow<'w, Without<T, Q>> {
-        self.transform()
+    /// Avoids dynamic borrow checking, as with `World::query_mut`.
+    pub fn query_mut<'q>(&'q mut self, world: &'q mut World) -> PreparedQueryIter<'q, Q> {
+        assert_borrow::<Q>();
+        self.prepare(world);
+        let (meta, archetypes) = world.storage_mut();
+        unsafe { PreparedQueryIter::new(meta, archetypes, &self.matching) }
     }
 
-    /// Helper to change the type of the query
-    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
-        let x = QueryBorrow {
-            meta: self.meta,
-            archetypes: self.archetypes,
-            borrowed: self.borrowed,
-            _marker: PhantomData,
-        };
-        // Ensure `Drop` won't fire redundantly
-        self.borrowed = false;
-        x
+    /// Bring the cached matches up to date with `world`
+    fn prepare(&mut self, world: &World) {
+        if world.id() != self.world_id {
+            self.world_id = world.id();
+            self.seen = 0;
+            self.matching.clear();
+        }
+        let (_, archetypes) = world.storage();
+        if self.seen == archetypes.len() {
+            return;
+        }
+        #[cfg(feature = "stats")]
+        world.count_scans(archetypes.len() - self.seen);
+        // Archetypes are never removed, so only new ones need checking
+        let mask = world.query_mask::<Q>();
+        for (index, archetype) in archetypes.iter().enumerate().skip(self.seen) {
+            if mask.admits(archetype) && Q::Fetch::access(archetype).is_some() {
+                self.matching.push(index as u32);
+            }
+        }
+        self.seen = archetypes.len();
     }
 }
 
-unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> {}
-unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> {}
+impl<Q: Query> Default for PreparedQuery<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
+/// A borrow of a `World` sufficient to execute a `PreparedQuery`
+///
+/// Note that borrows are not released until this object is dropped.
+pub struct PreparedQueryBorrow<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    matching: &'q [u32],
+    borrowed: bool,
+    _marker: PhantomData<Q>,
+}
+
+impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter(&mut self) -> PreparedQueryIter<'_, Q> {
+        self.borrow();
+        unsafe { PreparedQueryIter::new(self.meta, self.archetypes, self.matching) }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow(&mut self) {
+        if self.borrowed {
+            panic!(
+                "called PreparedQueryBorrow::iter twice on the same borrow; construct a new query \
+                 instead"
+            );
+        }
+        for (i, &index) in self.matching.iter().enumerate() {
+            let x = &self.archetypes[index as usize];
+            if Q::Fetch::access(x) >= Some(Access::Read) {
+                if let Err(e) = Q::Fetch::borrow(x) {
+                    for &index in &self.matching[..i] {
+                        let x = &self.archetypes[index as usize];
+                        if Q::Fetch::access(x) >= Some(Access::Read) {
+                            Q::Fetch::release(x);
+                        }
+                    }
+                    e.panic();
+                }
+            }
+        }
+        self.borrowed = true;
+    }
+}
+
+impl<'q, Q: Query> Drop for PreparedQueryBorrow<'q, Q> {
     fn drop(&mut self) {
         if self.borrowed {
-            for x in self.archetypes {
+            for &index in self.matching {
+                let x = &self.archetypes[index as usize];
                 if Q::Fetch::access(x) >= Some(Access::Read) {
                     Q::Fetch::release(x);
                 }
@@ -419,42 +2200,51 @@ impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
     }
 }
 
-impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
-    type Item = (Entity, QueryItem<'q, Q>);
-    type IntoIter = QueryIter<'q, Q>;
+impl<'a, 'q, Q: Query> IntoIterator for &'a mut PreparedQueryBorrow<'q, Q> {
+    type Item = (Entity, QueryItem<'a, Q>);
+    type IntoIter = PreparedQueryIter<'a, Q>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-/// Iterator over the set of entities with the components in `Q`
-pub struct QueryIter<'q, Q: Query> {
+/// Iterator over the set of entities matched by a `PreparedQuery`
+pub struct PreparedQueryIter<'q, Q: Query> {
     meta: &'q [EntityMeta],
     archetypes: &'q [Archetype],
-    archetype_index: usize,
+    matching: core::slice::Iter<'q, u32>,
     iter: ChunkIter<Q>,
+    /// Number of entities yet to be yielded
+    remaining: usize,
 }
 
-impl<'q, Q: Query> QueryIter<'q, Q> {
+impl<'q, Q: Query> PreparedQueryIter<'q, Q> {
     /// # Safety
     ///
-    /// `'q` must be sufficient to guarantee that `Q` cannot violate borrow safety, either with
-    /// dynamic borrow checks or by representing exclusive access to the `World`.
-    pub(crate) unsafe fn new(meta: &'q [EntityMeta], archetypes: &'q [Archetype]) -> Self {
+    /// As for `QueryIter::new`, and `matching` must index `archetypes`.
+    unsafe fn new(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        matching: &'q [u32],
+    ) -> Self {
         Self {
             meta,
             archetypes,
-            archetype_index: 0,
+            matching: matching.iter(),
             iter: ChunkIter::empty(),
+            remaining: matching
+                .iter()
+                .map(|&index| QueryIter::<Q>::matches(&archetypes[index as usize]))
+                .sum(),
         }
     }
 }
 
-unsafe impl<'q, Q: Query> Send for QueryIter<'q, Q> {}
-unsafe impl<'q, Q: Query> Sync for QueryIter<'q, Q> {}
+unsafe impl<'q, Q: Query> Send for PreparedQueryIter<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for PreparedQueryIter<'q, Q> {}
 
-impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
+impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
     type Item = (Entity, QueryItem<'q, Q>);
 
     #[inline(always)]
@@ -462,8 +2252,7 @@ impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
         loop {
             match unsafe { self.iter.next() } {
                 None => {
-                    let archetype = self.archetypes.get(self.archetype_index)?;
-                    self.archetype_index += 1;
+                    let archetype = &self.archetypes[*self.matching.next()? as usize];
                     self.iter =
                         Q::Fetch::new(archetype).map_or(ChunkIter::empty(), |fetch| ChunkIter {
                             entities: archetype.entities(),
@@ -474,6 +2263,7 @@ impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
                     continue;
                 }
                 Some((id, components)) => {
+                    self.remaining -= 1;
                     return Some((
                         Entity {
                             id,
@@ -492,86 +2282,10 @@ impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
     }
 }
 
-impl<'q, Q: Query> ExactSizeIterator for QueryIter<'q, Q> {
-    fn len(&self) -> usize {
-        self.archetypes
-            .iter()
-            .filter(|&x| Q::Fetch::access(x).is_some())
-            .map(|x| x.len() as usize)
-            .sum()
-    }
-}
-
-/// A query builder that's convertible directly into an iterator
-pub struct QueryMut<'q, Q: Query> {
-    iter: QueryIter<'q, Q>,
-}
-
-impl<'q, Q: Query> QueryMut<'q, Q> {
-    pub(crate) fn new(meta: &'q [EntityMeta], archetypes: &'q mut [Archetype]) -> Self {
-        Self {
-            iter: unsafe { QueryIter::new(meta, archetypes) },
-        }
-    }
-
-    /// Transform the query into one that requires a certain component without borrowing it
-    ///
-    /// See `QueryBorrow::with`
-    pub fn with<T: Component>(self) -> QueryMut<'q, With<T, Q>> {
-        self.transform()
-    }
-
-    /// Transform the query into one that skips entities having a certain component
-    ///
-    /// See `QueryBorrow::without`
-    pub fn without<T: Component>(self) -> QueryMut<'q, Without<T, Q>> {
-        self.transform()
-    }
-
-    /// Helper to change the type of the query
-    fn transform<R: Query>(self) -> QueryMut<'q, R> {
-        QueryMut {
-            iter: unsafe { QueryIter::new(self.iter.meta, self.iter.archetypes) },
-        }
-    }
-}
-
-impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
-    type Item = <QueryIter<'q, Q> as Iterator>::Item;
-    type IntoIter = QueryIter<'q, Q>;
-
-    #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter
-    }
-}
-
-struct ChunkIter<Q: Query> {
-    entities: NonNull<u32>,
-    fetch: Q::Fetch,
-    position: usize,
-    len: usize,
-}
-
-impl<Q: Query> ChunkIter<Q> {
-    fn empty() -> Self {
-        Self {
-            entities: NonNull::dangling(),
-            fetch: Q::Fetch::dangling(),
-            position: 0,
-            len: 0,
-        }
-    }
-
+impl<'q, Q: Query> ExactSizeIterator for PreparedQueryIter<'q, Q> {
     #[inline]
-    unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
-        if self.position == self.len {
-            return None;
-        }
-        let entity = self.entities.as_ptr().add(self.position);
-        let item = self.fetch.get(self.position);
-        self.position += 1;
-        Some((*entity, item))
+    fn len(&self) -> usize {
+        self.remaining
     }
 }
 
@@ -580,6 +2294,9 @@ pub struct BatchedIter<'q, Q: Query> {
     _marker: PhantomData<&'q Q>,
     meta: &'q [EntityMeta],
     archetypes: &'q [Archetype],
+    filter: &'q [(TypeId, bool)],
+    /// Order in which to visit `archetypes`, if not their natural order
+    order: Option<Vec<u32>>,
     archetype_index: usize,
     batch_size: u32,
     batch: u32,
@@ -593,17 +2310,68 @@ impl<'q, Q: Query> BatchedIter<'q, Q> {
     pub(crate) unsafe fn new(
         meta: &'q [EntityMeta],
         archetypes: &'q [Archetype],
+        filter: &'q [(TypeId, bool)],
         batch_size: u32,
     ) -> Self {
         Self {
             _marker: PhantomData,
             meta,
             archetypes,
+            filter,
+            order: None,
             archetype_index: 0,
             batch_size,
             batch: 0,
         }
     }
+
+    /// Visit the remaining archetypes in the order given by `compare`
+    ///
+    /// The archetype currently being split into batches, if any, is finished first.
+    pub fn sort_archetypes_by(
+        mut self,
+        mut compare: impl FnMut(&Archetype, &Archetype) -> Ordering,
+    ) -> Self {
+        let archetypes = self.archetypes;
+        let mut order = self
+            .order
+            .take()
+            .unwrap_or_else(|| (0..archetypes.len() as u32).collect());
+        let first = (self.archetype_index + (self.batch != 0) as usize).min(order.len());
+        order[first..].sort_by(|&x, &y| compare(&archetypes[x as usize], &archetypes[y as usize]));
+        self.order = Some(order);
+        self
+    }
+
+    /// Visit the remaining archetypes with the most entities first
+    ///
+    /// Helps parallel executors keep all threads busy until the end by scheduling the longest
+    /// work first.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// world.spawn_batch((0..100).map(|i| (i, true)));
+    /// let sizes = world
+    ///     .query::<&i32>()
+    ///     .iter_batched(1000)
+    ///     .largest_first()
+    ///     .map(|batch| batch.count())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(sizes, [100, 10]);
+    /// ```
+    pub fn largest_first(self) -> Self {
+        self.sort_archetypes_by(|x, y| y.len().cmp(&x.len()))
+    }
+
+    fn archetype(&self, index: usize) -> Option<&'q Archetype> {
+        match self.order {
+            None => self.archetypes.get(index),
+            Some(ref order) => Some(&self.archetypes[*order.get(index)? as usize]),
+        }
+    }
 }
 
 unsafe impl<'q, Q: Query> Send for BatchedIter<'q, Q> {}
@@ -614,14 +2382,14 @@ impl<'q, Q: Query> Iterator for BatchedIter<'q, Q> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let archetype = self.archetypes.get(self.archetype_index)?;
+            let archetype = self.archetype(self.archetype_index)?;
             let offset = self.batch_size * self.batch;
             if offset >= archetype.len() {
                 self.archetype_index += 1;
                 self.batch = 0;
                 continue;
             }
-            if let Some(fetch) = Q::Fetch::new(archetype) {
+            if let Some(fetch) = fetch::<Q>(self.filter, archetype) {
                 self.batch += 1;
                 return Some(Batch {
                     meta: self.meta,
@@ -668,11 +2436,93 @@ impl<'q, Q: Query> Iterator for Batch<'q, Q> {
 unsafe impl<'q, Q: Query> Send for Batch<'q, Q> {}
 unsafe impl<'q, Q: Query> Sync for Batch<'q, Q> {}
 
+/// Iterator over whole archetypes matched by `Q`, yielded by `QueryBorrow::iter_chunks`
+pub struct QueryChunks<'q, Q: Query> {
+    archetypes: core::slice::Iter<'q, Archetype>,
+    filter: &'q [(TypeId, bool)],
+    _marker: PhantomData<Q>,
+}
+
+impl<'q, Q: Query> Iterator for QueryChunks<'q, Q>
+where
+    Q::Fetch: ChunkFetch<'q>,
+{
+    type Item = (&'q [u32], QueryChunk<'q, Q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let archetype = self.archetypes.next()?;
+            if archetype.is_empty() {
+                continue;
+            }
+            let fetch = match fetch::<Q>(self.filter, archetype) {
+                Some(x) => x,
+                None => continue,
+            };
+            let slice = unsafe { fetch.slice(archetype.len() as usize) };
+            return Some((archetype.ids(), slice));
+        }
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for QueryChunks<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for QueryChunks<'q, Q> {}
+
+unsafe impl<'a, T: Component> ChunkFetch<'a> for FetchRead<T> {
+    type Slice = &'a [T];
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        core::slice::from_raw_parts(self.0.as_ptr(), len)
+    }
+}
+
+unsafe impl<'a, T: Component + Copy> ChunkFetch<'a> for FetchCopied<T> {
+    type Slice = &'a [T];
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        core::slice::from_raw_parts(self.0.as_ptr(), len)
+    }
+}
+
+unsafe impl<'a, T: Component> ChunkFetch<'a> for FetchWrite<T> {
+    type Slice = &'a mut [T];
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        core::ptr::write_bytes(self.1.as_ptr(), true as u8, len);
+        core::slice::from_raw_parts_mut(self.0.as_ptr(), len)
+    }
+}
+
+unsafe impl<'a, T: ChunkFetch<'a>> ChunkFetch<'a> for TryFetch<T> {
+    type Slice = Option<T::Slice>;
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        Some(self.0.as_ref()?.slice(len))
+    }
+}
+
+unsafe impl<'a, T: Component, F: ChunkFetch<'a>> ChunkFetch<'a> for FetchWith<T, F> {
+    type Slice = F::Slice;
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        self.0.slice(len)
+    }
+}
+
+unsafe impl<'a, T: Component, F: ChunkFetch<'a>> ChunkFetch<'a> for FetchWithout<T, F> {
+    type Slice = F::Slice;
+
+    unsafe fn slice(&self, len: usize) -> Self::Slice {
+        self.0.slice(len)
+    }
+}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         unsafe impl<'a, $($name: Fetch<'a>),*> Fetch<'a> for ($($name,)*) {
             type Item = ($($name::Item,)*);
 
+            #[allow(clippy::unused_unit)]
             fn dangling() -> Self {
                 ($($name::dangling(),)*)
             }
@@ -686,9 +2536,27 @@ macro_rules! tuple_impl {
                 Some(access)
             }
 
-            #[allow(unused_variables)]
-            fn borrow(archetype: &Archetype) {
-                $($name::borrow(archetype);)*
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+                // Release everything acquired so far if any borrow fails
+                let mut result = Ok(());
+                let mut acquired = 0;
+                $(
+                    if result.is_ok() {
+                        result = $name::borrow(archetype);
+                        acquired += result.is_ok() as usize;
+                    }
+                )*
+                if result.is_err() {
+                    let mut i = 0;
+                    $(
+                        if i < acquired {
+                            $name::release(archetype);
+                        }
+                        i += 1;
+                    )*
+                }
+                result
             }
             #[allow(unused_variables)]
             fn new(archetype: &'a Archetype) -> Option<Self> {
@@ -698,13 +2566,51 @@ macro_rules! tuple_impl {
             fn release(archetype: &Archetype) {
                 $($name::release(archetype);)*
             }
+            #[allow(unused_variables, unused_mut)]
+            fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+                $($name::for_each_borrow(&mut f);)*
+            }
 
-            #[allow(unused_variables)]
+            #[allow(unused_variables, unused_mut)]
+            fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+                $($name::for_each_requirement(&mut f);)*
+            }
+
+            #[allow(unused_variables, clippy::unused_unit)]
             unsafe fn get(&self, n: usize) -> Self::Item {
                 #[allow(non_snake_case)]
                 let ($($name,)*) = self;
                 ($($name.get(n),)*)
             }
+
+            const FILTERS: bool = false $(|| $name::FILTERS)*;
+
+            #[allow(unused_variables)]
+            #[inline(always)]
+            unsafe fn skip(&self, n: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                false $(|| ($name::FILTERS && $name.skip(n)))*
+            }
+
+            #[allow(unused_variables)]
+            #[inline(always)]
+            fn prefetch(&self, n: usize) {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                $($name.prefetch(n);)*
+            }
+        }
+
+        unsafe impl<'a, $($name: ChunkFetch<'a>),*> ChunkFetch<'a> for ($($name,)*) {
+            type Slice = ($($name::Slice,)*);
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn slice(&self, len: usize) -> Self::Slice {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                ($($name.slice(len),)*)
+            }
         }
 
         impl<$($name: Query),*> Query for ($($name,)*) {
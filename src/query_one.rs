@@ -1,7 +1,7 @@
 use core::marker::PhantomData;
 
 use crate::query::{Fetch, With, Without};
-use crate::{Archetype, Component, Query, QueryItem};
+use crate::{Archetype, BorrowError, Component, Query, QueryItem};
 
 /// A borrow of a `World` sufficient to execute the query `Q` on a single entity
 pub struct QueryOne<'a, Q: Query> {
@@ -33,15 +33,36 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     /// Panics if called more than once or if it would construct a borrow that clashes with another
     /// pre-existing borrow.
     // Note that this uses self's lifetime, not 'a, for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
     pub fn get(&mut self) -> Option<QueryItem<'_, Q>> {
+        match self.try_get() {
+            Ok(x) => x,
+            Err(e) => e.panic(),
+        }
+    }
+
+    /// Like `get`, but fails instead of panicking if a component is already borrowed in a
+    /// conflicting way
+    ///
+    /// May be attempted again after a failure.
+    // Note that this uses self's lifetime, not 'a, for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn try_get(&mut self) -> Result<Option<QueryItem<'_, Q>>, BorrowError> {
         if self.borrowed {
             panic!("called QueryOnce::get twice; construct a new query instead");
         }
         unsafe {
-            let fetch = Q::Fetch::new(self.archetype)?;
+            let fetch = match Q::Fetch::new(self.archetype) {
+                Some(x) => x,
+                None => return Ok(None),
+            };
+            Q::Fetch::borrow(self.archetype)?;
+            if Q::Fetch::FILTERS && fetch.skip(self.index as usize) {
+                Q::Fetch::release(self.archetype);
+                return Ok(None);
+            }
             self.borrowed = true;
-            Q::Fetch::borrow(self.archetype);
-            Some(fetch.get(self.index as usize))
+            Ok(Some(fetch.get(self.index as usize)))
         }
     }
 
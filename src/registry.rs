@@ -0,0 +1,382 @@
+use core::alloc::Layout;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use hashbrown::HashMap;
+
+use crate::archetype::{TypeIdMap, TypeInfo};
+use crate::Component;
+
+/// Runtime metadata for component types, keyed by both type and name
+///
+/// Useful for tooling, scripting, and serialization code that must handle components whose types
+/// aren't statically known. Crates defining components can each expose a function registering their
+/// own types, typically using [`register_component!`](crate::register_component), so that no
+/// central list of every component in an application is needed.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// #[derive(Clone)]
+/// struct Health(u32);
+/// struct Position(f32, f32);
+///
+/// let mut registry = ComponentRegistry::new();
+/// register_component!(registry, Health, "health", clone);
+/// register_component!(registry, Position, "position");
+/// let meta = registry.get_by_name("health").unwrap();
+/// assert_eq!(meta.type_id(), std::any::TypeId::of::<Health>());
+/// assert!(meta.is_clone());
+/// assert!(!registry.get::<Position>().unwrap().is_clone());
+/// ```
+///
+/// Independently developed crates may unknowingly pick the same name for different components.
+/// Such collisions are detected when the second type is registered; see
+/// [`try_register`](Self::try_register). The registered types can be enumerated with
+/// [`iter`](Self::iter), e.g. to list them in admin tooling.
+///
+/// Serde's serializers are generic, so serialization functions can't be stored in a type-erased
+/// registry without an extra dependency. With the `serde` feature, components are instead
+/// registered for serialization under the same stable names with
+/// `serialize::registry::SerdeRegistry`.
+#[derive(Default)]
+pub struct ComponentRegistry {
+    types: TypeIdMap<ComponentMeta>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl ComponentRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `T` under `name`, replacing any previous registration of `T`
+    ///
+    /// Panics if `name` is registered to a different type. See [`try_register`](Self::try_register).
+    pub fn register<T: Component>(&mut self, name: &'static str) -> &mut ComponentMeta {
+        match self.try_register::<T>(name) {
+            Ok(meta) => meta,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Record `T` under `name` along with the ability to clone it
+    ///
+    /// Panics if `name` is registered to a different type.
+    pub fn register_clone<T: Component + Clone>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut ComponentMeta {
+        unsafe fn clone<T: Clone>(src: *const u8, dst: *mut u8) {
+            dst.cast::<T>().write((*src.cast::<T>()).clone());
+        }
+
+        let meta = self.register::<T>(name);
+        meta.clone = Some(clone::<T>);
+        meta
+    }
+
+    /// Record `T` under `name` along with the ability to copy it bitwise
+    ///
+    /// Copyable components are also cloneable, and can be cloned in bulk, e.g. by
+    /// [`World::snapshot`](crate::World::snapshot). Panics if `name` is registered to a different
+    /// type.
+    pub fn register_copy<T: Component + Copy>(&mut self, name: &'static str) -> &mut ComponentMeta {
+        let meta = self.register_clone::<T>(name);
+        meta.copy = true;
+        meta
+    }
+
+    /// Record `T` under `name`, replacing any previous registration of `T`, unless `name` is
+    /// registered to a different type
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// mod plugin_a { pub struct Health(pub u32); }
+    /// mod plugin_b { pub struct Health(pub f32); }
+    ///
+    /// let mut registry = ComponentRegistry::new();
+    /// registry.register::<plugin_a::Health>("health");
+    /// let err = registry.try_register::<plugin_b::Health>("health").unwrap_err();
+    /// assert!(matches!(err, RegistrationConflict::Name { name: "health", .. }));
+    /// ```
+    pub fn try_register<T: Component>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<&mut ComponentMeta, RegistrationConflict> {
+        self.insert(ComponentMeta {
+            name,
+            type_name: type_name::<T>(),
+            info: TypeInfo::of::<T>(),
+            clone: None,
+            copy: false,
+        })
+    }
+
+    /// Record a component type known only at runtime, such as one defined by a script, under
+    /// `name`
+    ///
+    /// Fails if `name` is registered to a different type, or if the type identified by `info` is
+    /// already registered with a different layout, indicating that two parties disagree on what
+    /// the type is.
+    pub fn try_register_dynamic(
+        &mut self,
+        name: &'static str,
+        type_name: &'static str,
+        info: TypeInfo,
+    ) -> Result<&mut ComponentMeta, RegistrationConflict> {
+        self.insert(ComponentMeta {
+            name,
+            type_name,
+            info,
+            clone: None,
+            copy: false,
+        })
+    }
+
+    fn insert(&mut self, meta: ComponentMeta) -> Result<&mut ComponentMeta, RegistrationConflict> {
+        let id = meta.info.id();
+        if let Some(other) = self.names.get(meta.name).filter(|&&x| x != id) {
+            return Err(RegistrationConflict::Name {
+                name: meta.name,
+                existing: self.types[other].type_name,
+                new: meta.type_name,
+            });
+        }
+        if let Some(old) = self.types.get(&id) {
+            if old.info.layout() != meta.info.layout() {
+                return Err(RegistrationConflict::Layout {
+                    type_name: meta.type_name,
+                    existing: old.info.layout(),
+                    new: meta.info.layout(),
+                });
+            }
+            self.names.remove(old.name);
+        }
+        self.names.insert(meta.name, id);
+        self.types.insert(id, meta);
+        Ok(self.types.get_mut(&id).unwrap())
+    }
+
+    /// Look up the metadata of `T`, if registered
+    pub fn get<T: Component>(&self) -> Option<&ComponentMeta> {
+        self.get_by_id(TypeId::of::<T>())
+    }
+
+    /// Look up the metadata of the type identified by `id`, if registered
+    pub fn get_by_id(&self, id: TypeId) -> Option<&ComponentMeta> {
+        self.types.get(&id)
+    }
+
+    /// Look up the metadata of the type registered under `name`, if any
+    pub fn get_by_name(&self, name: &str) -> Option<&ComponentMeta> {
+        self.types.get(self.names.get(name)?)
+    }
+
+    /// Enumerate all registered component types
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &ComponentMeta> + '_ {
+        self.types.values()
+    }
+
+    /// Number of registered component types
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Whether no component types are registered
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+impl fmt::Debug for ComponentRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.types.values()).finish()
+    }
+}
+
+/// Error indicating that a component type couldn't be registered in a [`ComponentRegistry`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RegistrationConflict {
+    /// The name is already registered to a different type
+    Name {
+        /// The contested name
+        name: &'static str,
+        /// Name of the type the name is registered to
+        existing: &'static str,
+        /// Name of the type that was being registered
+        new: &'static str,
+    },
+    /// The type is already registered with a different layout
+    Layout {
+        /// Name of the type that was being registered
+        type_name: &'static str,
+        /// Layout of the existing registration
+        existing: Layout,
+        /// Layout of the type that was being registered
+        new: Layout,
+    },
+}
+
+impl fmt::Display for RegistrationConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RegistrationConflict::Name {
+                name,
+                existing,
+                new,
+            } => write!(
+                f,
+                "component name {:?} is registered to {}, not {}",
+                name, existing, new
+            ),
+            RegistrationConflict::Layout {
+                type_name,
+                existing,
+                new,
+            } => write!(
+                f,
+                "component {} is registered with size {} and alignment {}, not size {} and \
+                 alignment {}",
+                type_name,
+                existing.size(),
+                existing.align(),
+                new.size(),
+                new.align()
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RegistrationConflict {}
+
+/// Metadata describing a component type recorded in a [`ComponentRegistry`]
+#[derive(Copy, Clone)]
+pub struct ComponentMeta {
+    name: &'static str,
+    type_name: &'static str,
+    info: TypeInfo,
+    clone: Option<unsafe fn(*const u8, *mut u8)>,
+    copy: bool,
+}
+
+impl ComponentMeta {
+    /// The name the type was registered under
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The Rust type name of the component, as reported by `core::any::type_name`
+    ///
+    /// Not guaranteed to be stable across compiler versions; prefer `name` for persistent data.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// The `TypeId` of the component
+    pub fn type_id(&self) -> TypeId {
+        self.info.id()
+    }
+
+    /// Layout and drop information for the component
+    pub fn type_info(&self) -> TypeInfo {
+        self.info
+    }
+
+    /// Whether the component was registered with the ability to be cloned
+    pub fn is_clone(&self) -> bool {
+        self.clone.is_some()
+    }
+
+    /// Whether the component was registered as bitwise copyable
+    pub fn is_copy(&self) -> bool {
+        self.copy
+    }
+
+    pub(crate) fn clone_fn(&self) -> Option<unsafe fn(*const u8, *mut u8)> {
+        self.clone
+    }
+
+    /// Clone the component at `src` into the uninitialized memory at `dst`
+    ///
+    /// Returns `false` without touching `dst` if the type was not registered as cloneable.
+    ///
+    /// # Safety
+    ///
+    /// `src` must point to a valid value of the registered type, and `dst` must be valid for
+    /// writes and suitably aligned for that type.
+    pub unsafe fn clone_raw(&self, src: *const u8, dst: *mut u8) -> bool {
+        match self.clone {
+            Some(clone) => {
+                clone(src, dst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop the component at `ptr` in place
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid value of the registered type, which must not be used again.
+    pub unsafe fn drop_raw(&self, ptr: *mut u8) {
+        self.info.drop(ptr);
+    }
+}
+
+impl fmt::Debug for ComponentMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentMeta")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .field("clone", &self.clone.is_some())
+            .field("copy", &self.copy)
+            .finish()
+    }
+}
+
+/// Register a component type with a [`ComponentRegistry`]
+///
+/// The name defaults to the type's path as written, prefixed by the invoking module's path. Append
+/// `clone` to also record the ability to clone the component, or `copy` for components that are
+/// `Copy`.
+///
+/// ```
+/// # use hecs::*;
+/// #[derive(Clone)]
+/// struct Velocity(f32);
+///
+/// // Typically defined once per plugin crate and called during startup
+/// fn register(registry: &mut ComponentRegistry) {
+///     register_component!(registry, Velocity, clone);
+/// }
+///
+/// let mut registry = ComponentRegistry::new();
+/// register(&mut registry);
+/// assert!(registry.get::<Velocity>().unwrap().name().ends_with("::Velocity"));
+/// ```
+#[macro_export]
+macro_rules! register_component {
+    ($registry:expr, $ty:ty) => {
+        $registry.register::<$ty>(concat!(module_path!(), "::", stringify!($ty)))
+    };
+    ($registry:expr, $ty:ty, clone) => {
+        $registry.register_clone::<$ty>(concat!(module_path!(), "::", stringify!($ty)))
+    };
+    ($registry:expr, $ty:ty, copy) => {
+        $registry.register_copy::<$ty>(concat!(module_path!(), "::", stringify!($ty)))
+    };
+    ($registry:expr, $ty:ty, $name:expr) => {
+        $registry.register::<$ty>($name)
+    };
+    ($registry:expr, $ty:ty, $name:expr, clone) => {
+        $registry.register_clone::<$ty>($name)
+    };
+    ($registry:expr, $ty:ty, $name:expr, copy) => {
+        $registry.register_copy::<$ty>($name)
+    };
+}
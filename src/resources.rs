@@ -0,0 +1,125 @@
+use crate::alloc::boxed::Box;
+use core::any::{type_name, Any, TypeId};
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::archetype::TypeIdMap;
+use crate::borrow::AtomicBorrow;
+use crate::Component;
+
+/// World-global values of distinct types, such as a tick counter, RNG, or block registry
+#[derive(Default)]
+pub(crate) struct Resources {
+    map: TypeIdMap<Resource>,
+}
+
+struct Resource {
+    // UnsafeCell allows unique references to be constructed while `borrow` guards access
+    value: UnsafeCell<Box<dyn Any + Send + Sync>>,
+    borrow: AtomicBorrow,
+}
+
+// Safety: the value is `Send + Sync`, and `borrow` ensures that shared references obtained
+// through `&Resource` never coexist with a unique one
+unsafe impl Sync for Resource {}
+
+impl Resources {
+    pub(crate) fn insert<T: Component>(&mut self, value: T) -> Option<T> {
+        let old = self.map.insert(
+            TypeId::of::<T>(),
+            Resource {
+                value: UnsafeCell::new(Box::new(value)),
+                borrow: AtomicBorrow::new(),
+            },
+        )?;
+        Some(*old.value.into_inner().downcast::<T>().unwrap())
+    }
+
+    pub(crate) fn remove<T: Component>(&mut self) -> Option<T> {
+        let old = self.map.remove(&TypeId::of::<T>())?;
+        Some(*old.value.into_inner().downcast::<T>().unwrap())
+    }
+
+    pub(crate) fn contains<T: Component>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    pub(crate) fn get<T: Component>(&self) -> Option<ResourceRef<'_, T>> {
+        let resource = self.map.get(&TypeId::of::<T>())?;
+        if !resource.borrow.borrow() {
+            panic!("{} already borrowed uniquely", type_name::<T>());
+        }
+        Some(ResourceRef {
+            borrow: &resource.borrow,
+            target: unsafe { NonNull::from((*resource.value.get()).downcast_ref::<T>().unwrap()) },
+        })
+    }
+
+    pub(crate) fn get_mut<T: Component>(&self) -> Option<ResourceRefMut<'_, T>> {
+        let resource = self.map.get(&TypeId::of::<T>())?;
+        if !resource.borrow.borrow_mut() {
+            panic!("{} already borrowed", type_name::<T>());
+        }
+        // The unique borrow makes this the only access to the value
+        let target = unsafe { NonNull::from((*resource.value.get()).downcast_mut::<T>().unwrap()) };
+        Some(ResourceRefMut {
+            borrow: &resource.borrow,
+            target,
+        })
+    }
+}
+
+/// Shared borrow of a world's resource
+///
+/// See [`World::resource`](crate::World::resource).
+pub struct ResourceRef<'a, T: Component> {
+    borrow: &'a AtomicBorrow,
+    target: NonNull<T>,
+}
+
+unsafe impl<T: Component> Send for ResourceRef<'_, T> {}
+unsafe impl<T: Component> Sync for ResourceRef<'_, T> {}
+
+impl<T: Component> Drop for ResourceRef<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.release();
+    }
+}
+
+impl<T: Component> Deref for ResourceRef<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+/// Unique borrow of a world's resource
+///
+/// See [`World::resource_mut`](crate::World::resource_mut).
+pub struct ResourceRefMut<'a, T: Component> {
+    borrow: &'a AtomicBorrow,
+    target: NonNull<T>,
+}
+
+unsafe impl<T: Component> Send for ResourceRefMut<'_, T> {}
+unsafe impl<T: Component> Sync for ResourceRefMut<'_, T> {}
+
+impl<T: Component> Drop for ResourceRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.release_mut();
+    }
+}
+
+impl<T: Component> Deref for ResourceRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+impl<T: Component> DerefMut for ResourceRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.target.as_mut() }
+    }
+}
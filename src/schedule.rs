@@ -0,0 +1,156 @@
+//! Running systems in parallel over a shared world
+//!
+//! Enabled by the `executor` feature. See [`Schedule`].
+
+use std::boxed::Box;
+use std::thread;
+use std::vec::Vec;
+
+use core::any::TypeId;
+
+use crate::{Query, QueryAccess, World};
+
+/// A unit of work over a `World`, along with the component types it borrows
+///
+/// Systems only receive `&World`, so they can't spawn or despawn entities directly. Structural
+/// changes can instead be recorded into a [`CommandBuffer`](crate::CommandBuffer) held by the
+/// system and applied after the schedule has run.
+pub struct System {
+    name: &'static str,
+    access: QueryAccess,
+    run: Box<dyn FnMut(&World) + Send>,
+}
+
+impl System {
+    /// Create a system that runs `f`, which may borrow whatever components `Q` does
+    ///
+    /// A system that runs several queries can declare them all as a tuple, e.g.
+    /// `System::new::<((&mut Position, &Velocity), &Health), _>(..)`.
+    pub fn new<Q: Query, F>(name: &'static str, f: F) -> Self
+    where
+        F: FnMut(&World) + Send + 'static,
+    {
+        Self {
+            name,
+            access: Q::access(),
+            run: Box::new(f),
+        }
+    }
+
+    /// Declare that the system also reads `T` components
+    pub fn reads<T: 'static>(mut self) -> Self {
+        self.access.add(TypeId::of::<T>(), false);
+        self
+    }
+
+    /// Declare that the system also writes `T` components
+    pub fn writes<T: 'static>(mut self) -> Self {
+        self.access.add(TypeId::of::<T>(), true);
+        self
+    }
+
+    /// The name the system was created with
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The component types the system borrows
+    pub fn access(&self) -> &QueryAccess {
+        &self.access
+    }
+}
+
+/// An ordered list of systems, run with as much parallelism as their borrows allow
+///
+/// Each system runs after every earlier-added system it conflicts with, so the outcome is the same
+/// as running them one by one in the order they were added. Systems that don't conflict run
+/// concurrently on scoped threads. Queries made by systems still acquire the usual dynamic
+/// borrows, so a system that borrows more than it declared panics rather than causing undefined
+/// behavior.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use hecs::schedule::*;
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Position(0.0), Velocity(1.0)));
+/// let mut schedule = Schedule::new();
+/// schedule.add(System::new::<(&mut Position, &Velocity), _>("movement", |world| {
+///     for (_, (pos, vel)) in world.query::<(&mut Position, &Velocity)>().iter() {
+///         pos.0 += vel.0;
+///     }
+/// }));
+/// schedule.add(System::new::<&mut Velocity, _>("drag", |world| {
+///     for (_, vel) in world.query::<&mut Velocity>().iter() {
+///         vel.0 *= 0.5;
+///     }
+/// }));
+/// schedule.run(&world);
+/// schedule.run(&world);
+/// assert_eq!(world.get::<Position>(a).unwrap().0, 1.5);
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<System>,
+    /// Indices into `systems` of each group of systems that may run concurrently, in order
+    stages: Vec<Vec<usize>>,
+}
+
+impl Schedule {
+    /// Create an empty schedule
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `system` to the schedule
+    pub fn add(&mut self, system: System) -> &mut Self {
+        // Run after the latest stage containing a conflicting system
+        let stage = self
+            .stages
+            .iter()
+            .rposition(|stage| {
+                stage
+                    .iter()
+                    .any(|&i| self.systems[i].access.conflicts_with(&system.access))
+            })
+            .map_or(0, |x| x + 1);
+        if stage == self.stages.len() {
+            self.stages.push(Vec::new());
+        }
+        self.stages[stage].push(self.systems.len());
+        self.systems.push(system);
+        self
+    }
+
+    /// Names of the systems in each group that runs concurrently, in the order the groups run
+    pub fn stages(&self) -> impl Iterator<Item = Vec<&'static str>> + '_ {
+        self.stages
+            .iter()
+            .map(move |stage| stage.iter().map(|&i| self.systems[i].name).collect())
+    }
+
+    /// Run every system once
+    ///
+    /// The calling thread takes part in running each stage. Panics in systems are propagated
+    /// once their stage has finished.
+    pub fn run(&mut self, world: &World) {
+        for stage in &self.stages {
+            let mut systems = self
+                .systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| stage.contains(i))
+                .map(|(_, x)| x);
+            let first = systems.next().unwrap();
+            thread::scope(|s| {
+                for system in systems {
+                    s.spawn(move || (system.run)(world));
+                }
+                (first.run)(world);
+            });
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+
+use crate::{DynamicBundle, Entity, World};
+
+/// A borrow of a [`World`] that despawns the entities it spawned when dropped
+///
+/// Obtained from [`World::scope`]. Dereferences to the underlying `World`, so entities can be
+/// queried and modified as usual; only entities created through [`Scope::spawn`] are tracked.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let kept;
+/// {
+///     let mut scope = world.scope();
+///     let ghost = scope.spawn((123, "preview"));
+///     kept = scope.spawn((456,));
+///     scope.promote(kept);
+///     assert!(scope.contains(ghost));
+/// }
+/// assert_eq!(world.len(), 1);
+/// assert!(world.contains(kept));
+/// ```
+pub struct Scope<'w> {
+    world: &'w mut World,
+    entities: Vec<Entity>,
+}
+
+impl<'w> Scope<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            entities: Vec::new(),
+        }
+    }
+
+    /// Create an entity that will be despawned when the scope ends
+    ///
+    /// See [`World::spawn`].
+    pub fn spawn(&mut self, components: impl DynamicBundle) -> Entity {
+        let entity = self.world.spawn(components);
+        self.entities.push(entity);
+        entity
+    }
+
+    /// Exempt `entity` from being despawned when the scope ends
+    ///
+    /// Returns `false` if `entity` was not spawned by this scope, or was already promoted.
+    pub fn promote(&mut self, entity: Entity) -> bool {
+        match self.entities.iter().position(|&x| x == entity) {
+            Some(index) => {
+                self.entities.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Exempt every entity spawned so far from being despawned when the scope ends
+    pub fn promote_all(&mut self) {
+        self.entities.clear();
+    }
+
+    /// Entities that will be despawned when the scope ends
+    pub fn scoped_entities(&self) -> &[Entity] {
+        &self.entities
+    }
+}
+
+impl Deref for Scope<'_> {
+    type Target = World;
+    fn deref(&self) -> &World {
+        self.world
+    }
+}
+
+impl DerefMut for Scope<'_> {
+    fn deref_mut(&mut self) -> &mut World {
+        self.world
+    }
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        for entity in self.entities.drain(..) {
+            // The entity may already have been despawned explicitly
+            let _ = self.world.despawn(entity);
+        }
+    }
+}
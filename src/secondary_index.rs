@@ -0,0 +1,88 @@
+use core::any::Any;
+
+use crate::{Component, Entity};
+
+/// A data structure kept in sync with a world's components of type `Source`
+///
+/// Once added with [`World::add_index`](crate::World::add_index), an index is notified whenever a
+/// `Source` component is added, replaced, or removed, including when its entity is spawned or
+/// despawned by any means, in the same circumstances as
+/// [`World::on_insert`](crate::World::on_insert) and [`World::on_remove`](crate::World::on_remove)
+/// hooks. Components modified in place are reported by
+/// [`World::update_indexes`](crate::World::update_indexes). Indexes can then answer questions such
+/// as which entities are near a point without scanning every component; see `spatial::GridIndex`
+/// for an example.
+///
+/// The one exception is [`World::restore`](crate::World::restore), which reinstates entities
+/// without running hooks, so indexes must be rebuilt afterwards.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// use std::collections::HashMap;
+///
+/// struct Name(&'static str);
+///
+/// #[derive(Default)]
+/// struct ByName(HashMap<&'static str, Entity>);
+///
+/// impl SecondaryIndex for ByName {
+///     type Source = Name;
+///     fn insert(&mut self, entity: Entity, name: &Name) {
+///         self.0.insert(name.0, entity);
+///     }
+///     fn remove(&mut self, _: Entity, name: &Name) {
+///         self.0.remove(name.0);
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let steve = world.spawn((Name("steve"),));
+/// world.add_index(ByName::default());
+/// let alex = world.spawn((Name("alex"),));
+/// assert_eq!(world.index::<ByName>().unwrap().0["steve"], steve);
+/// world.despawn(alex).unwrap();
+/// assert!(!world.index::<ByName>().unwrap().0.contains_key("alex"));
+/// ```
+pub trait SecondaryIndex: Send + Sync + 'static {
+    /// The component type the index is derived from
+    type Source: Component;
+
+    /// Record `entity`, whose `Source` component is now `source`
+    ///
+    /// Called when the component is added, replaced, or found to be modified, so `entity` may
+    /// already be recorded.
+    fn insert(&mut self, entity: Entity, source: &Self::Source);
+
+    /// Forget `entity`, whose `Source` component `source` is being removed
+    fn remove(&mut self, entity: Entity, source: &Self::Source);
+}
+
+/// Type-erased interface to a `SecondaryIndex`
+pub(crate) trait ErasedIndex: Send + Sync {
+    /// # Safety
+    ///
+    /// `source` must point to a valid `Source` component.
+    unsafe fn insert(&mut self, entity: Entity, source: *const u8);
+
+    /// # Safety
+    ///
+    /// `source` must point to a valid `Source` component.
+    unsafe fn remove(&mut self, entity: Entity, source: *const u8);
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<I: SecondaryIndex> ErasedIndex for I {
+    unsafe fn insert(&mut self, entity: Entity, source: *const u8) {
+        SecondaryIndex::insert(self, entity, &*source.cast::<I::Source>());
+    }
+
+    unsafe fn remove(&mut self, entity: Entity, source: *const u8) {
+        SecondaryIndex::remove(self, entity, &*source.cast::<I::Source>());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
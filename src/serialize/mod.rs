@@ -9,4 +9,5 @@
 //! versioning stability guarantees as the hecs API.
 
 pub mod column;
+pub mod registry;
 pub mod row;
@@ -0,0 +1,767 @@
+//! Whole-world serialization driven by a registry of named component types
+//!
+//! Builds on [`row`](super::row) serialization, sparing applications from writing their own
+//! contexts. Each entity is stored under its full `Entity` handle, so entity IDs and generations
+//! survive a round trip, and with them any handles stored inside components.
+//!
+//! Component types are registered under stable names, which are used as keys in the serialized
+//! data in place of Rust type names that may change between builds.
+
+use crate::alloc::{string::String, vec, vec::Vec};
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed, Error as _, MapAccess, SeqAccess, Visitor},
+    ser::{Error as _, SerializeMap, SerializeTuple},
+    Deserializer, Serialize, Serializer,
+};
+
+use super::row::{self, DeserializeComponents, DeserializeContext, SerializeContext};
+use crate::{Component, Entity, EntityBuilder, EntityRef, World, WorldDiff};
+
+/// Statically typed mapping between component types and stable names
+///
+/// Built by chaining calls to [`register`](Self::register). Unlike
+/// [`ComponentRegistry`](crate::ComponentRegistry), the set of types is encoded in the registry's
+/// type, allowing serde's generic serializers to be invoked without type erasure.
+///
+/// # Example
+/// ```
+/// # use serde::{Serialize, Deserialize, Serializer, Deserializer};
+/// use hecs::{*, serialize::registry::*};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Position([f32; 3]);
+/// #[derive(Serialize, Deserialize)]
+/// struct Health(u32);
+///
+/// fn registry() -> SerdeRegistry<impl ComponentList> {
+///     SerdeRegistry::new()
+///         .register::<Position>("position")
+///         .register::<Health>("health")
+/// }
+///
+/// fn save<S: Serializer>(world: &World, serializer: S) -> Result<S::Ok, S::Error> {
+///     serialize_world(world, &registry(), serializer)
+/// }
+///
+/// fn load<'de, D: Deserializer<'de>>(deserializer: D) -> Result<World, D::Error> {
+///     deserialize_world(&registry(), deserializer)
+/// }
+/// ```
+pub struct SerdeRegistry<L = ()> {
+    list: L,
+}
+
+impl SerdeRegistry {
+    /// Create a registry with no component types
+    pub fn new() -> Self {
+        Self { list: () }
+    }
+}
+
+impl Default for SerdeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L: ComponentList> SerdeRegistry<L> {
+    /// Record `T` under `name`
+    ///
+    /// Components of types that aren't registered are omitted when serializing.
+    pub fn register<T>(self, name: &'static str) -> SerdeRegistry<(Entry<T>, L)>
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        SerdeRegistry {
+            list: (
+                Entry {
+                    name,
+                    _marker: PhantomData,
+                },
+                self.list,
+            ),
+        }
+    }
+}
+
+/// Serialize every entity in `world`, with the components registered in `registry`
+pub fn serialize_world<L, S>(
+    world: &World,
+    registry: &SerdeRegistry<L>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    L: ComponentList,
+    S: Serializer,
+{
+    row::serialize(world, &mut Context(&registry.list), serializer)
+}
+
+/// Deserialize a world produced by [`serialize_world`] with an equivalent `registry`
+///
+/// Fails on components whose names aren't registered.
+pub fn deserialize_world<'de, L, D>(
+    registry: &SerdeRegistry<L>,
+    deserializer: D,
+) -> Result<World, D::Error>
+where
+    L: ComponentList,
+    D: Deserializer<'de>,
+{
+    row::deserialize(&mut Context(&registry.list), deserializer)
+}
+
+/// Serialize the changes recorded in `diff`, with the components registered in `registry`
+///
+/// Spawned entities are written with all their registered components, and surviving entities
+/// with just their changed ones, so `world` must be in the state `diff` was computed from.
+/// Unregistered components are omitted. Fails if an entity in `diff` no longer exists.
+///
+/// # Example
+/// ```
+/// # use serde::{Serializer, Deserializer};
+/// use hecs::{*, serialize::registry::*};
+///
+/// fn registry() -> SerdeRegistry<impl ComponentList> {
+///     SerdeRegistry::new().register::<i32>("number")
+/// }
+///
+/// // On the server, once per tick
+/// fn send<S: Serializer>(
+///     world: &mut World,
+///     baseline: &mut WorldSnapshot,
+///     components: &ComponentRegistry,
+///     serializer: S,
+/// ) -> Result<S::Ok, S::Error> {
+///     let diff = world.diff(baseline);
+///     let result = serialize_diff(world, &diff, &registry(), serializer);
+///     *baseline = world.snapshot(components).unwrap();
+///     world.clear_changed();
+///     result
+/// }
+///
+/// // On the client
+/// fn receive<'de, D: Deserializer<'de>>(world: &mut World, deserializer: D) -> Result<(), D::Error> {
+///     apply_diff(world, &registry(), deserializer)
+/// }
+/// ```
+pub fn serialize_diff<L, S>(
+    world: &World,
+    diff: &WorldDiff,
+    registry: &SerdeRegistry<L>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    L: ComponentList,
+    S: Serializer,
+{
+    let list = &registry.list;
+    let components = |entity: Entity, types: Option<Vec<TypeId>>| DiffComponents {
+        world,
+        list,
+        entity,
+        types,
+    };
+    let spawned = diff
+        .spawned()
+        .iter()
+        .map(|&entity| (entity, components(entity, None)))
+        .collect::<Vec<_>>();
+    let changed = group(diff.changed(), |ty| list.name(ty).map(|_| ty))
+        .into_iter()
+        .map(|(entity, types)| (entity, components(entity, Some(types))))
+        .collect::<Vec<_>>();
+    let removed = group(diff.removed(), |ty| list.name(ty));
+
+    let mut tuple = serializer.serialize_tuple(4)?;
+    tuple.serialize_element(diff.despawned())?;
+    tuple.serialize_element(&EntityMap(&spawned))?;
+    tuple.serialize_element(&EntityMap(&changed))?;
+    tuple.serialize_element(&EntityMap(&removed))?;
+    tuple.end()
+}
+
+/// Apply changes produced by [`serialize_diff`] with an equivalent `registry` to `world`
+///
+/// Despawned entities and removed components that are already absent are ignored, while
+/// changes to entities that don't exist are an error, as are components whose names aren't
+/// registered. Spawned entities replace any entities with the same IDs.
+pub fn apply_diff<'de, L, D>(
+    world: &mut World,
+    registry: &SerdeRegistry<L>,
+    deserializer: D,
+) -> Result<(), D::Error>
+where
+    L: ComponentList,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(
+        4,
+        DiffVisitor {
+            world,
+            list: &registry.list,
+        },
+    )
+}
+
+/// Collect the entries of `pairs` accepted by `f` by entity, omitting entities with none
+fn group<T>(
+    pairs: &[(Entity, TypeId)],
+    mut f: impl FnMut(TypeId) -> Option<T>,
+) -> Vec<(Entity, Vec<T>)> {
+    let mut groups = Vec::<(Entity, Vec<T>)>::new();
+    for &(entity, ty) in pairs {
+        let x = match f(ty) {
+            Some(x) => x,
+            None => continue,
+        };
+        // Pairs for the same entity are adjacent
+        match groups.last_mut() {
+            Some((last, xs)) if *last == entity => xs.push(x),
+            _ => groups.push((entity, vec![x])),
+        }
+    }
+    groups
+}
+
+struct EntityMap<'a, T>(&'a [(Entity, T)]);
+
+impl<T: Serialize> Serialize for EntityMap<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (entity, x) in self.0 {
+            map.serialize_entry(entity, x)?;
+        }
+        map.end()
+    }
+}
+
+/// The registered components of `entity`, or just those of `types`
+struct DiffComponents<'a, L> {
+    world: &'a World,
+    list: &'a L,
+    entity: Entity,
+    types: Option<Vec<TypeId>>,
+}
+
+impl<L: ComponentList> Serialize for DiffComponents<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entity = self
+            .world
+            .entity(self.entity)
+            .map_err(|_| S::Error::custom(format_args!("no such entity {:?}", self.entity)))?;
+        match self.types {
+            None => {
+                let mut map = serializer.serialize_map(Some(self.list.count(&entity)))?;
+                self.list.serialize(&entity, &mut map)?;
+                map.end()
+            }
+            Some(ref types) => {
+                let mut map = serializer.serialize_map(Some(types.len()))?;
+                for &ty in types {
+                    self.list.serialize_one(ty, &entity, &mut map)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct DiffVisitor<'a, L> {
+    world: &'a mut World,
+    list: &'a L,
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DiffVisitor<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a world diff")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let despawned = seq
+            .next_element::<Vec<Entity>>()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        for entity in despawned {
+            let _ = self.world.despawn(entity);
+        }
+        for (i, spawn) in [(1, true), (2, false)] {
+            seq.next_element_seed(EntitiesSeed {
+                world: &mut *self.world,
+                list: self.list,
+                spawn,
+            })?
+            .ok_or_else(|| A::Error::invalid_length(i, &"a world diff"))?;
+        }
+        seq.next_element_seed(RemovedSeed {
+            world: &mut *self.world,
+            list: self.list,
+        })?
+        .ok_or_else(|| A::Error::invalid_length(3, &"a world diff"))?;
+        Ok(())
+    }
+}
+
+/// Spawns entities with the deserialized components, or inserts the components into existing
+/// entities
+struct EntitiesSeed<'a, L> {
+    world: &'a mut World,
+    list: &'a L,
+    spawn: bool,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for EntitiesSeed<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for EntitiesSeed<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entities to components")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<(), M::Error> {
+        let mut builder = EntityBuilder::new();
+        while let Some(entity) = map.next_key::<Entity>()? {
+            map.next_value_seed(DeserializeComponents(&mut Context(self.list), &mut builder))?;
+            if self.spawn {
+                self.world.spawn_at(entity, builder.build());
+            } else {
+                self.world
+                    .insert(entity, builder.build())
+                    .map_err(|_| M::Error::custom(format_args!("no such entity {:?}", entity)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the named components from entities
+struct RemovedSeed<'a, L> {
+    world: &'a mut World,
+    list: &'a L,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for RemovedSeed<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for RemovedSeed<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entities to component names")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<(), M::Error> {
+        while let Some(entity) = map.next_key::<Entity>()? {
+            for name in map.next_value::<Vec<String>>()? {
+                if !self.list.remove(&name, self.world, entity) {
+                    return Err(M::Error::custom(format_args!(
+                        "unknown component {:?}",
+                        name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A component type and its name in a [`SerdeRegistry`]
+pub struct Entry<T> {
+    name: &'static str,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+/// The component types registered in a [`SerdeRegistry`]
+///
+/// Implemented for the nested tuples constructed by [`SerdeRegistry::register`].
+pub trait ComponentList {
+    #[doc(hidden)]
+    fn count(&self, entity: &EntityRef<'_>) -> usize;
+
+    #[doc(hidden)]
+    fn serialize<S: SerializeMap>(
+        &self,
+        entity: &EntityRef<'_>,
+        map: &mut S,
+    ) -> Result<(), S::Error>;
+
+    /// Deserialize the value of the component named `name` into `entity`, returning `false` if no
+    /// such component is registered
+    #[doc(hidden)]
+    fn deserialize<'de, M: MapAccess<'de>>(
+        &self,
+        name: &str,
+        map: &mut M,
+        entity: &mut EntityBuilder,
+    ) -> Result<bool, M::Error>;
+
+    /// Name of the component type `ty`, if registered
+    #[doc(hidden)]
+    fn name(&self, ty: TypeId) -> Option<&'static str>;
+
+    /// Serialize the component of type `ty` of `entity`, if registered and present
+    #[doc(hidden)]
+    fn serialize_one<S: SerializeMap>(
+        &self,
+        ty: TypeId,
+        entity: &EntityRef<'_>,
+        map: &mut S,
+    ) -> Result<(), S::Error>;
+
+    /// Remove the component named `name` from `entity`, if present, returning `false` if no such
+    /// component is registered
+    #[doc(hidden)]
+    fn remove(&self, name: &str, world: &mut World, entity: Entity) -> bool;
+}
+
+impl ComponentList for () {
+    fn count(&self, _: &EntityRef<'_>) -> usize {
+        0
+    }
+
+    fn serialize<S: SerializeMap>(&self, _: &EntityRef<'_>, _: &mut S) -> Result<(), S::Error> {
+        Ok(())
+    }
+
+    fn deserialize<'de, M: MapAccess<'de>>(
+        &self,
+        _: &str,
+        _: &mut M,
+        _: &mut EntityBuilder,
+    ) -> Result<bool, M::Error> {
+        Ok(false)
+    }
+
+    fn name(&self, _: TypeId) -> Option<&'static str> {
+        None
+    }
+
+    fn serialize_one<S: SerializeMap>(
+        &self,
+        _: TypeId,
+        _: &EntityRef<'_>,
+        _: &mut S,
+    ) -> Result<(), S::Error> {
+        Ok(())
+    }
+
+    fn remove(&self, _: &str, _: &mut World, _: Entity) -> bool {
+        false
+    }
+}
+
+impl<T, L> ComponentList for (Entry<T>, L)
+where
+    T: Component + Serialize + DeserializeOwned,
+    L: ComponentList,
+{
+    fn count(&self, entity: &EntityRef<'_>) -> usize {
+        usize::from(entity.component_types().any(|x| x == TypeId::of::<T>())) + self.1.count(entity)
+    }
+
+    fn serialize<S: SerializeMap>(
+        &self,
+        entity: &EntityRef<'_>,
+        map: &mut S,
+    ) -> Result<(), S::Error> {
+        // Earlier registrations are further down the list; emit them first
+        self.1.serialize(entity, map)?;
+        row::try_serialize::<T, _, _>(entity, self.0.name, map)
+    }
+
+    fn deserialize<'de, M: MapAccess<'de>>(
+        &self,
+        name: &str,
+        map: &mut M,
+        entity: &mut EntityBuilder,
+    ) -> Result<bool, M::Error> {
+        if name != self.0.name {
+            return self.1.deserialize(name, map, entity);
+        }
+        entity.add::<T>(map.next_value()?);
+        Ok(true)
+    }
+
+    fn name(&self, ty: TypeId) -> Option<&'static str> {
+        if ty != TypeId::of::<T>() {
+            return self.1.name(ty);
+        }
+        Some(self.0.name)
+    }
+
+    fn serialize_one<S: SerializeMap>(
+        &self,
+        ty: TypeId,
+        entity: &EntityRef<'_>,
+        map: &mut S,
+    ) -> Result<(), S::Error> {
+        if ty != TypeId::of::<T>() {
+            return self.1.serialize_one(ty, entity, map);
+        }
+        row::try_serialize::<T, _, _>(entity, self.0.name, map)
+    }
+
+    fn remove(&self, name: &str, world: &mut World, entity: Entity) -> bool {
+        if name != self.0.name {
+            return self.1.remove(name, world, entity);
+        }
+        // Already being absent is as good as being removed
+        let _ = world.remove_one::<T>(entity);
+        true
+    }
+}
+
+struct Context<'a, L>(&'a L);
+
+impl<L: ComponentList> SerializeContext for Context<'_, L> {
+    fn serialize_entity<S>(&mut self, entity: EntityRef<'_>, map: &mut S) -> Result<(), S::Error>
+    where
+        S: SerializeMap,
+    {
+        self.0.serialize(&entity, map)
+    }
+
+    fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+        Some(self.0.count(&entity))
+    }
+}
+
+impl<L: ComponentList> DeserializeContext for Context<'_, L> {
+    fn deserialize_entity<'de, M>(
+        &mut self,
+        mut map: M,
+        entity: &mut EntityBuilder,
+    ) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        while let Some(name) = map.next_key::<String>()? {
+            if !self.0.deserialize(&name, &mut map, entity)? {
+                return Err(M::Error::custom(format_args!(
+                    "unknown component {:?}",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+    use super::*;
+    use crate::*;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+    struct Position([f32; 3]);
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+    struct Health(u32);
+
+    fn registry() -> SerdeRegistry<impl ComponentList> {
+        SerdeRegistry::new()
+            .register::<Position>("position")
+            .register::<Health>("health")
+    }
+
+    /// Bodge into serde_test's very strict interface
+    struct SerWorld(World);
+
+    impl Serialize for SerWorld {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_world(&self.0, &registry(), serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SerWorld {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserialize_world(&registry(), deserializer).map(SerWorld)
+        }
+    }
+
+    impl PartialEq for SerWorld {
+        fn eq(&self, other: &Self) -> bool {
+            fn same_components<T: Component + PartialEq>(x: &EntityRef, y: &EntityRef) -> bool {
+                x.get::<T>().as_deref() == y.get::<T>().as_deref()
+            }
+
+            self.0.len() == other.0.len()
+                && self.0.iter().all(|(id, x)| {
+                    other.0.entity(id).is_ok_and(|y| {
+                        same_components::<Position>(&x, &y) && same_components::<Health>(&x, &y)
+                    })
+                })
+        }
+    }
+
+    impl core::fmt::Debug for SerWorld {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_map()
+                .entries(self.0.iter().map(|(id, ref e)| {
+                    (
+                        id,
+                        (
+                            e.get::<Position>().map(|x| *x),
+                            e.get::<Health>().map(|x| *x),
+                        ),
+                    )
+                }))
+                .finish()
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn roundtrip() {
+        let mut world = World::new();
+        let dead = world.spawn((Health(1),));
+        world.despawn(dead).unwrap();
+        let e0 = world.spawn((Position([1.0, 2.0, 3.0]), Health(10), true));
+        assert_eq!(e0.id(), dead.id());
+        assert_ne!(e0, dead);
+
+        assert_tokens(&SerWorld(world), &[
+            Token::Map { len: Some(1) },
+
+            Token::U64(e0.to_bits()),
+            Token::Map { len: Some(2) },
+
+            Token::Str("position"),
+            Token::NewtypeStruct { name: "Position" },
+            Token::Tuple { len: 3 },
+            Token::F32(1.0),
+            Token::F32(2.0),
+            Token::F32(3.0),
+            Token::TupleEnd,
+
+            Token::Str("health"),
+            Token::NewtypeStruct { name: "Health" },
+            Token::U32(10),
+
+            Token::MapEnd,
+
+            Token::MapEnd,
+        ]);
+    }
+
+    fn initial() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        let a = world.spawn((Position([0.0; 3]), Health(10)));
+        let b = world.spawn((Health(5),));
+        (world, a, b)
+    }
+
+    /// A server world and its changes, which deserialize into the changes applied to `initial`
+    #[derive(Debug)]
+    struct Diff(SerWorld, WorldDiff);
+
+    impl Serialize for Diff {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_diff(&self.0 .0, &self.1, &registry(), serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Diff {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (mut world, _, _) = initial();
+            apply_diff(&mut world, &registry(), deserializer)?;
+            Ok(Diff(SerWorld(world), WorldDiff::default()))
+        }
+    }
+
+    impl PartialEq for Diff {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn diff_roundtrip() {
+        let mut components = ComponentRegistry::new();
+        components.register_copy::<Position>("position");
+        components.register_copy::<Health>("health");
+        components.register_copy::<bool>("bool");
+        let (mut world, a, b) = initial();
+        let baseline = world.snapshot(&components).unwrap();
+        world.clear_changed();
+
+        *world.get_mut::<Health>(a).unwrap() = Health(9);
+        world.remove_one::<Position>(a).unwrap();
+        world.insert_one(a, true).unwrap();
+        world.despawn(b).unwrap();
+        let c = world.spawn((Position([1.0, 2.0, 3.0]),));
+        assert_eq!(c.id(), b.id());
+        let diff = world.diff(&baseline);
+
+        assert_tokens(&Diff(SerWorld(world), diff), &[
+            Token::Tuple { len: 4 },
+
+            Token::Seq { len: Some(1) },
+            Token::U64(b.to_bits()),
+            Token::SeqEnd,
+
+            Token::Map { len: Some(1) },
+            Token::U64(c.to_bits()),
+            Token::Map { len: Some(1) },
+            Token::Str("position"),
+            Token::NewtypeStruct { name: "Position" },
+            Token::Tuple { len: 3 },
+            Token::F32(1.0),
+            Token::F32(2.0),
+            Token::F32(3.0),
+            Token::TupleEnd,
+            Token::MapEnd,
+            Token::MapEnd,
+
+            // The unregistered `bool` is omitted
+            Token::Map { len: Some(1) },
+            Token::U64(a.to_bits()),
+            Token::Map { len: Some(1) },
+            Token::Str("health"),
+            Token::NewtypeStruct { name: "Health" },
+            Token::U32(9),
+            Token::MapEnd,
+            Token::MapEnd,
+
+            Token::Map { len: Some(1) },
+            Token::U64(a.to_bits()),
+            Token::Seq { len: Some(1) },
+            Token::Str("position"),
+            Token::SeqEnd,
+            Token::MapEnd,
+
+            Token::TupleEnd,
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn unknown_component() {
+        assert_de_tokens_error::<SerWorld>(&[
+            Token::Map { len: Some(1) },
+            Token::U64(1 << 32),
+            Token::Map { len: Some(1) },
+            Token::Str("velocity"),
+        ], "unknown component \"velocity\"");
+    }
+}
@@ -209,7 +209,11 @@ where
     }
 }
 
-struct DeserializeComponents<'a, C>(&'a mut C, &'a mut EntityBuilder);
+/// Deserializes one entity's components through a [`DeserializeContext`] into a builder
+pub(crate) struct DeserializeComponents<'a, C>(
+    pub(crate) &'a mut C,
+    pub(crate) &'a mut EntityBuilder,
+);
 
 impl<'de, 'a, C> DeserializeSeed<'de> for DeserializeComponents<'a, C>
 where
@@ -273,7 +277,7 @@ mod tests {
     impl PartialEq for SerWorld {
         fn eq(&self, other: &Self) -> bool {
             fn same_components<T: Component + PartialEq>(x: &EntityRef, y: &EntityRef) -> bool {
-                x.get::<T>().as_ref().map(|x| &**x) == y.get::<T>().as_ref().map(|x| &**x)
+                x.get::<T>().as_deref() == y.get::<T>().as_deref()
             }
 
             for ((x_id, x), (y_id, y)) in self.0.iter().zip(other.0.iter()) {
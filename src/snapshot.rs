@@ -0,0 +1,46 @@
+use crate::alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::{Entity, Query, QueryItem, World};
+
+/// Entities matching a query at some point in time, returned by [`World::iter_snapshot`]
+///
+/// Doesn't borrow the world, so each item is fetched with a fresh `&mut World` that can be used
+/// freely until the next one is requested.
+pub struct Snapshot<Q: Query> {
+    entities: Vec<Entity>,
+    position: usize,
+    _marker: PhantomData<fn() -> Q>,
+}
+
+impl<Q: Query> Snapshot<Q> {
+    pub(crate) fn new(entities: Vec<Entity>) -> Self {
+        Self {
+            entities,
+            position: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Fetch the next captured entity that still exists and matches `Q`
+    pub fn next<'w>(&mut self, world: &'w mut World) -> Option<(Entity, QueryItem<'w, Q>)> {
+        loop {
+            let entity = *self.entities.get(self.position)?;
+            self.position += 1;
+            if world.satisfies::<Q>(entity) {
+                let item = world.query_one_mut::<Q>(entity).ok()?;
+                return Some((entity, item));
+            }
+        }
+    }
+
+    /// Number of captured entities not yet visited, including any that will be skipped
+    pub fn remaining(&self) -> usize {
+        self.entities.len() - self.position
+    }
+
+    /// The captured entities not yet visited, including any that will be skipped
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities[self.position..]
+    }
+}
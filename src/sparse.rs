@@ -0,0 +1,403 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{type_name, Any, TypeId};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::archetype::TypeIdMap;
+use crate::borrow::AtomicBorrow;
+use crate::{CloneError, Component, ComponentRegistry, Entity, EntityMap};
+
+/// Components stored outside of archetypes, keyed by type
+///
+/// See [`World::insert_sparse`](crate::World::insert_sparse).
+#[derive(Default)]
+pub(crate) struct SparseStorage {
+    map: TypeIdMap<Table>,
+}
+
+struct Table {
+    // UnsafeCell allows unique references to be constructed while `borrow` guards access
+    set: UnsafeCell<Box<dyn AnySparseSet>>,
+    borrow: AtomicBorrow,
+}
+
+// Safety: the set is `Send + Sync`, and `borrow` ensures that shared references obtained through
+// `&Table` never coexist with a unique one
+unsafe impl Sync for Table {}
+
+/// Type-erased clone of a component, as registered in a `ComponentRegistry`
+type CloneFn = unsafe fn(*const u8, *mut u8);
+
+/// Operations on a `SparseSet` of unknown component type
+trait AnySparseSet: Send + Sync {
+    fn forget(&mut self, id: u32);
+    fn clear(&mut self);
+    fn is_empty(&self) -> bool;
+    /// Move every component into `storage`, given to the entity `map` gives for its own
+    fn drain_into(&mut self, storage: &mut SparseStorage, map: &EntityMap);
+    /// Move the component of the entity with ID `id`, if any, into `storage`, given to `to`
+    fn move_into(&mut self, id: u32, storage: &mut SparseStorage, to: Entity);
+    /// Clone every component with `clone`, which must clone a value of the set's type
+    unsafe fn clone_with(&self, clone: CloneFn) -> Box<dyn AnySparseSet>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> AnySparseSet for SparseSet<T> {
+    fn forget(&mut self, id: u32) {
+        self.remove_id(id);
+    }
+
+    fn clear(&mut self) {
+        SparseSet::clear(self);
+    }
+
+    fn is_empty(&self) -> bool {
+        SparseSet::is_empty(self)
+    }
+
+    fn drain_into(&mut self, storage: &mut SparseStorage, map: &EntityMap) {
+        let target = storage.register::<T>();
+        for (entity, value) in self.dense.drain(..).zip(self.values.drain(..)) {
+            if let Some(entity) = map.get(entity) {
+                target.insert(entity, value);
+            }
+        }
+        self.sparse.clear();
+    }
+
+    fn move_into(&mut self, id: u32, storage: &mut SparseStorage, to: Entity) {
+        if let Some(value) = self.remove_id(id) {
+            storage.register::<T>().insert(to, value);
+        }
+    }
+
+    unsafe fn clone_with(&self, clone: CloneFn) -> Box<dyn AnySparseSet> {
+        let mut values = Vec::with_capacity(self.values.len());
+        for value in &self.values {
+            let mut copy = MaybeUninit::<T>::uninit();
+            clone((value as *const T).cast(), copy.as_mut_ptr().cast());
+            values.push(copy.assume_init());
+        }
+        Box::new(SparseSet {
+            sparse: self.sparse.clone(),
+            dense: self.dense.clone(),
+            values,
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SparseStorage {
+    pub(crate) fn register<T: Component>(&mut self) -> &mut SparseSet<T> {
+        self.map
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Table {
+                set: UnsafeCell::new(Box::new(SparseSet::<T>::new())),
+                borrow: AtomicBorrow::new(),
+            })
+            .set
+            .get_mut()
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub(crate) fn get_mut<T: Component>(&mut self) -> Option<&mut SparseSet<T>> {
+        let table = self.map.get_mut(&TypeId::of::<T>())?;
+        Some(table.set.get_mut().as_any_mut().downcast_mut().unwrap())
+    }
+
+    pub(crate) fn borrow<T: Component>(&self) -> Option<SparseRef<'_, T>> {
+        let table = self.map.get(&TypeId::of::<T>())?;
+        if !table.borrow.borrow() {
+            panic!("{} already borrowed uniquely", type_name::<T>());
+        }
+        Some(SparseRef {
+            borrow: &table.borrow,
+            target: unsafe { NonNull::from((*table.set.get()).as_any().downcast_ref().unwrap()) },
+        })
+    }
+
+    pub(crate) fn borrow_mut<T: Component>(&self) -> Option<SparseRefMut<'_, T>> {
+        let table = self.map.get(&TypeId::of::<T>())?;
+        if !table.borrow.borrow_mut() {
+            panic!("{} already borrowed", type_name::<T>());
+        }
+        // The unique borrow makes this the only access to the set
+        let target =
+            unsafe { NonNull::from((*table.set.get()).as_any_mut().downcast_mut().unwrap()) };
+        Some(SparseRefMut {
+            borrow: &table.borrow,
+            target,
+        })
+    }
+
+    /// Drop every sparse component of the entity with ID `id`, which was despawned
+    pub(crate) fn forget(&mut self, id: u32) {
+        for table in self.map.values_mut() {
+            table.set.get_mut().forget(id);
+        }
+    }
+
+    /// Drop every sparse component, keeping the registered types
+    pub(crate) fn clear(&mut self) {
+        for table in self.map.values_mut() {
+            table.set.get_mut().clear();
+        }
+    }
+
+    /// Move every sparse component of `other` into this storage, given to the entities `map`
+    /// gives for their own
+    pub(crate) fn absorb(&mut self, other: &mut SparseStorage, map: &EntityMap) {
+        for table in other.map.values_mut() {
+            table.set.get_mut().drain_into(self, map);
+        }
+    }
+
+    /// Move every sparse component of the entity with ID `id` into `target`, given to `to`
+    pub(crate) fn migrate(&mut self, id: u32, target: &mut SparseStorage, to: Entity) {
+        for table in self.map.values_mut() {
+            table.set.get_mut().move_into(id, target, to);
+        }
+    }
+
+    /// Capture clones of every sparse component, which must all be registered as cloneable in
+    /// `registry`
+    pub(crate) fn snapshot(
+        &mut self,
+        registry: &ComponentRegistry,
+    ) -> Result<SparseSnapshot, CloneError> {
+        let mut sets = Vec::new();
+        for (&id, table) in &mut self.map {
+            let set = table.set.get_mut();
+            if set.is_empty() {
+                continue;
+            }
+            let clone = registry
+                .get_by_id(id)
+                .and_then(|x| x.clone_fn())
+                .ok_or(CloneError::Uncloneable(id))?;
+            // Safety: `clone` was registered for the type of `set`
+            sets.push((id, clone, unsafe { set.clone_with(clone) }));
+        }
+        Ok(SparseSnapshot { sets })
+    }
+
+    /// Replace every sparse component with clones of those captured in `snapshot`
+    pub(crate) fn restore(&mut self, snapshot: &SparseSnapshot) {
+        self.clear();
+        for &(id, clone, ref set) in &snapshot.sets {
+            // Safety: `clone` was registered for the type of `set`
+            let set = unsafe { set.clone_with(clone) };
+            self.map.insert(
+                id,
+                Table {
+                    set: UnsafeCell::new(set),
+                    borrow: AtomicBorrow::new(),
+                },
+            );
+        }
+    }
+}
+
+/// Clones of a world's sparse components, captured by `World::snapshot`
+pub(crate) struct SparseSnapshot {
+    /// Type, clone function, and clones of each non-empty set
+    sets: Vec<(TypeId, CloneFn, Box<dyn AnySparseSet>)>,
+}
+
+/// Components of type `T` that live outside of archetypes
+///
+/// Adding or removing a sparse component never moves its entity between archetypes, which makes
+/// them suited to short-lived state, such as a stun timer or a pathfinding request, that would
+/// otherwise churn through archetypes. The trade-off is that sparse components can't be queried,
+/// only looked up individually or iterated on their own. Components are stored densely, so
+/// iteration is as fast as over a `Vec`, and lookups index by `Entity::id` without hashing.
+///
+/// Sparse components are dropped when their entity is despawned or taken by `World::take`. They
+/// follow their entity through `World::migrate` and `World::absorb`, and are captured by
+/// `World::snapshot`, which requires their types to be registered as cloneable.
+///
+/// Obtained from [`World::sparse`](crate::World::sparse) or
+/// [`World::sparse_mut`](crate::World::sparse_mut).
+pub struct SparseSet<T> {
+    /// Position in `dense` of each entity ID's component, or `u32::MAX`
+    sparse: Vec<u32>,
+    dense: Vec<Entity>,
+    values: Vec<T>,
+}
+
+impl<T> SparseSet<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Position of `entity`'s component in `values`, if any
+    #[inline]
+    fn index(&self, entity: Entity) -> Option<usize> {
+        let index = *self.sparse.get(entity.id as usize)? as usize;
+        (self.dense.get(index) == Some(&entity)).then_some(index)
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(index) = self.index(entity) {
+            return Some(core::mem::replace(&mut self.values[index], value));
+        }
+        // Drop the component of any previous entity with the same ID
+        self.remove_id(entity.id);
+        let id = entity.id as usize;
+        if id >= self.sparse.len() {
+            self.sparse.resize(id + 1, u32::MAX);
+        }
+        self.sparse[id] = self.dense.len() as u32;
+        self.dense.push(entity);
+        self.values.push(value);
+        None
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<T> {
+        let index = self.index(entity)?;
+        Some(self.remove_at(index))
+    }
+
+    pub(crate) fn remove_id(&mut self, id: u32) -> Option<T> {
+        let index = *self.sparse.get(id as usize)? as usize;
+        if index >= self.dense.len() || self.dense[index].id != id {
+            return None;
+        }
+        Some(self.remove_at(index))
+    }
+
+    fn remove_at(&mut self, index: usize) -> T {
+        let entity = self.dense.swap_remove(index);
+        self.sparse[entity.id as usize] = u32::MAX;
+        if let Some(moved) = self.dense.get(index) {
+            self.sparse[moved.id as usize] = index as u32;
+        }
+        self.values.swap_remove(index)
+    }
+
+    /// Drop the components of entities for which `f` returns `false`
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(Entity) -> bool) {
+        let mut index = 0;
+        while index < self.dense.len() {
+            if f(self.dense[index]) {
+                index += 1;
+            } else {
+                self.remove_at(index);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.sparse.clear();
+        self.dense.clear();
+        self.values.clear();
+    }
+
+    /// `entity`'s component, if it has one
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        let index = self.index(entity)?;
+        Some(&self.values[index])
+    }
+
+    /// Unique access to `entity`'s component, if it has one
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let index = self.index(entity)?;
+        Some(&mut self.values[index])
+    }
+
+    /// Whether `entity` has a component in this set
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.index(entity).is_some()
+    }
+
+    /// Number of entities with a component in this set
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    /// Whether no entities have a component in this set
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// Iterate over the components and the entities they belong to, in arbitrary order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Entity, &T)> + '_ {
+        self.dense.iter().copied().zip(self.values.iter())
+    }
+
+    /// Iterate uniquely over the components and the entities they belong to, in arbitrary order
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (Entity, &mut T)> + '_ {
+        self.dense.iter().copied().zip(self.values.iter_mut())
+    }
+}
+
+/// Shared borrow of a world's sparse components of one type
+///
+/// See [`World::sparse`](crate::World::sparse).
+pub struct SparseRef<'a, T: Component> {
+    borrow: &'a AtomicBorrow,
+    target: NonNull<SparseSet<T>>,
+}
+
+unsafe impl<T: Component> Send for SparseRef<'_, T> {}
+unsafe impl<T: Component> Sync for SparseRef<'_, T> {}
+
+impl<T: Component> Drop for SparseRef<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.release();
+    }
+}
+
+impl<T: Component> Deref for SparseRef<'_, T> {
+    type Target = SparseSet<T>;
+    fn deref(&self) -> &SparseSet<T> {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+/// Unique borrow of a world's sparse components of one type
+///
+/// See [`World::sparse_mut`](crate::World::sparse_mut).
+pub struct SparseRefMut<'a, T: Component> {
+    borrow: &'a AtomicBorrow,
+    target: NonNull<SparseSet<T>>,
+}
+
+unsafe impl<T: Component> Send for SparseRefMut<'_, T> {}
+unsafe impl<T: Component> Sync for SparseRefMut<'_, T> {}
+
+impl<T: Component> Drop for SparseRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.release_mut();
+    }
+}
+
+impl<T: Component> Deref for SparseRefMut<'_, T> {
+    type Target = SparseSet<T>;
+    fn deref(&self) -> &SparseSet<T> {
+        unsafe { self.target.as_ref() }
+    }
+}
+
+impl<T: Component> DerefMut for SparseRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut SparseSet<T> {
+        unsafe { self.target.as_mut() }
+    }
+}
@@ -0,0 +1,361 @@
+//! Uniform-grid spatial hashing of entities by position
+//!
+//! Enabled by the `spatial` feature. See [`SpatialHash`] and [`GridIndex`].
+
+use crate::alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+
+use crate::{Component, Entity, SecondaryIndex, World};
+
+/// A component locating an entity in space, for use with [`SpatialHash`]
+///
+/// Two-dimensional applications can leave the third coordinate at zero.
+pub trait Position: Component {
+    /// The point in space the entity occupies
+    fn position(&self) -> [f32; 3];
+}
+
+/// Index of entities by their [`Position`] component `P`, for fast proximity queries
+///
+/// Space is divided into cubic cells of a fixed size, each listing the entities within it.
+/// [`update`](Self::update) brings the index up to date with a world, recomputing only the
+/// entities whose `P` was flagged as changed, so it should be called once per tick before
+/// [`World::clear_changed`].
+///
+/// Cells should be about as large as the typical query radius: much smaller cells make queries
+/// visit many cells, and much larger ones make them check many distant entities.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use hecs::spatial::*;
+/// struct Pos([f32; 3]);
+/// impl Position for Pos {
+///     fn position(&self) -> [f32; 3] { self.0 }
+/// }
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Pos([0.0, 0.0, 0.0]),));
+/// let b = world.spawn((Pos([5.0, 0.0, 0.0]),));
+/// let mut index = SpatialHash::<Pos>::new(4.0);
+/// index.update(&world);
+/// world.clear_changed();
+/// assert_eq!(index.entities_within_radius([1.0, 0.0, 0.0], 2.0).collect::<Vec<_>>(), [a]);
+///
+/// world.get_mut::<Pos>(b).unwrap().0 = [2.0, 0.0, 0.0];
+/// index.update(&world);
+/// world.clear_changed();
+/// assert_eq!(index.entities_within_radius([1.0, 0.0, 0.0], 2.0).count(), 2);
+/// ```
+pub struct SpatialHash<P> {
+    grid: Grid,
+    /// Value of `tick` when each entity was last seen
+    seen: HashMap<Entity, u32>,
+    /// Incremented by each `update`, to find entities that have disappeared
+    tick: u32,
+    _marker: PhantomData<fn(&P)>,
+}
+
+impl<P: Position> SpatialHash<P> {
+    /// Create an empty index with cubic cells `cell_size` units across
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            grid: Grid::new(cell_size),
+            seen: HashMap::new(),
+            tick: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bring the index up to date with the `P` components in `world`
+    ///
+    /// Entities are added when first seen with a `P`, moved when their `P` is flagged as changed,
+    /// and removed once they no longer have one.
+    ///
+    /// # Panics
+    /// - if `P` components are uniquely borrowed elsewhere
+    pub fn update(&mut self, world: &World) {
+        self.tick = self.tick.wrapping_add(1);
+        for archetype in world.archetypes() {
+            let column = match archetype.get::<P>() {
+                Some(x) => x,
+                None => continue,
+            };
+            let changed = archetype.get_changed_base::<P>().unwrap();
+            for (index, (&id, value)) in archetype.ids().iter().zip(column.iter()).enumerate() {
+                // Safety: every ID stored in an archetype belongs to a live entity
+                let entity = unsafe { world.find_entity_from_id(id) };
+                let changed = unsafe { *changed.as_ptr().add(index) };
+                let previous = self.seen.insert(entity, self.tick);
+                if previous.is_none() || changed {
+                    self.grid.insert(entity, value.position());
+                }
+            }
+        }
+
+        let tick = self.tick;
+        let grid = &mut self.grid;
+        self.seen.retain(|&entity, &mut seen| {
+            if seen != tick {
+                grid.remove(entity);
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Entities whose position lies within the axis-aligned box spanning `min` to `max`, inclusive
+    ///
+    /// Positions are as of the last `update`. Entities are yielded in arbitrary order.
+    pub fn entities_within_aabb(
+        &self,
+        min: [f32; 3],
+        max: [f32; 3],
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.grid.entities_within_aabb(min, max)
+    }
+
+    /// Entities whose position lies within `radius` of `center`, inclusive
+    ///
+    /// Positions are as of the last `update`. Entities are yielded in arbitrary order.
+    pub fn entities_within_radius(
+        &self,
+        center: [f32; 3],
+        radius: f32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.grid.entities_within_radius(center, radius)
+    }
+
+    /// The position of `entity` as of the last `update`, if indexed
+    pub fn position(&self, entity: Entity) -> Option<[f32; 3]> {
+        self.grid.position(entity)
+    }
+
+    /// Number of indexed entities
+    pub fn len(&self) -> usize {
+        self.grid.entries.len()
+    }
+
+    /// Whether no entities are indexed
+    pub fn is_empty(&self) -> bool {
+        self.grid.entries.is_empty()
+    }
+}
+
+/// A [`SecondaryIndex`] of entities by the position of their `T` component, for fast proximity
+/// queries
+///
+/// Like [`SpatialHash`], but kept up to date by the world it's added to with
+/// [`World::add_index`], so only entities that actually moved need be visited, rather than every
+/// entity with a `T`. Positions are computed from `T` by a function supplied on construction, so
+/// `T` needn't implement [`Position`]. Components modified in place are picked up by
+/// [`World::update_indexes`].
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use hecs::spatial::*;
+/// struct Transform { translation: [f32; 3], scale: f32 }
+///
+/// let mut world = World::new();
+/// world.add_index(GridIndex::new(4.0, |x: &Transform| x.translation));
+/// let a = world.spawn((Transform { translation: [0.0; 3], scale: 1.0 },));
+/// let b = world.spawn((Transform { translation: [5.0, 0.0, 0.0], scale: 1.0 },));
+/// let index = world.index::<GridIndex<Transform>>().unwrap();
+/// assert_eq!(index.entities_within_radius([1.0, 0.0, 0.0], 2.0).collect::<Vec<_>>(), [a]);
+///
+/// world.get_mut::<Transform>(b).unwrap().translation = [2.0, 0.0, 0.0];
+/// world.update_indexes();
+/// world.clear_changed();
+/// let index = world.index::<GridIndex<Transform>>().unwrap();
+/// assert_eq!(index.entities_within_radius([1.0, 0.0, 0.0], 2.0).count(), 2);
+/// ```
+pub struct GridIndex<T> {
+    grid: Grid,
+    position: fn(&T) -> [f32; 3],
+}
+
+impl<T: Component> GridIndex<T> {
+    /// Create an empty index with cubic cells `cell_size` units across, locating entities at
+    /// `position` of their `T`
+    pub fn new(cell_size: f32, position: fn(&T) -> [f32; 3]) -> Self {
+        Self {
+            grid: Grid::new(cell_size),
+            position,
+        }
+    }
+
+    /// Entities whose position lies within the axis-aligned box spanning `min` to `max`, inclusive
+    ///
+    /// Entities are yielded in arbitrary order.
+    pub fn entities_within_aabb(
+        &self,
+        min: [f32; 3],
+        max: [f32; 3],
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.grid.entities_within_aabb(min, max)
+    }
+
+    /// Entities whose position lies within `radius` of `center`, inclusive
+    ///
+    /// Entities are yielded in arbitrary order.
+    pub fn entities_within_radius(
+        &self,
+        center: [f32; 3],
+        radius: f32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.grid.entities_within_radius(center, radius)
+    }
+
+    /// The indexed position of `entity`, if any
+    pub fn position(&self, entity: Entity) -> Option<[f32; 3]> {
+        self.grid.position(entity)
+    }
+
+    /// Number of indexed entities
+    pub fn len(&self) -> usize {
+        self.grid.entries.len()
+    }
+
+    /// Whether no entities are indexed
+    pub fn is_empty(&self) -> bool {
+        self.grid.entries.is_empty()
+    }
+}
+
+impl<T: Component> SecondaryIndex for GridIndex<T> {
+    type Source = T;
+
+    fn insert(&mut self, entity: Entity, source: &T) {
+        self.grid.insert(entity, (self.position)(source));
+    }
+
+    fn remove(&mut self, entity: Entity, _: &T) {
+        self.grid.remove(entity);
+    }
+}
+
+/// Entities bucketed into cubic cells by position
+struct Grid {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<Entity>>,
+    entries: HashMap<Entity, Entry>,
+}
+
+struct Entry {
+    position: [f32; 3],
+    cell: [i32; 3],
+}
+
+impl Grid {
+    fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Record `entity` at `position`, moving it if already recorded
+    fn insert(&mut self, entity: Entity, position: [f32; 3]) {
+        let cell = self.cell(position);
+        match self.entries.get_mut(&entity) {
+            Some(entry) => {
+                entry.position = position;
+                if entry.cell == cell {
+                    return;
+                }
+                remove_from_cell(&mut self.cells, entry.cell, entity);
+                entry.cell = cell;
+            }
+            None => {
+                self.entries.insert(entity, Entry { position, cell });
+            }
+        }
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(entry) = self.entries.remove(&entity) {
+            remove_from_cell(&mut self.cells, entry.cell, entity);
+        }
+    }
+
+    fn entities_within_aabb(
+        &self,
+        min: [f32; 3],
+        max: [f32; 3],
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.candidates(min, max)
+            .filter(move |&(_, p)| (0..3).all(|axis| min[axis] <= p[axis] && p[axis] <= max[axis]))
+            .map(|(entity, _)| entity)
+    }
+
+    fn entities_within_radius(
+        &self,
+        center: [f32; 3],
+        radius: f32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        let min = [center[0] - radius, center[1] - radius, center[2] - radius];
+        let max = [center[0] + radius, center[1] + radius, center[2] + radius];
+        self.candidates(min, max)
+            .filter(move |&(_, p)| {
+                let distance_squared = (0..3)
+                    .map(|axis| (p[axis] - center[axis]) * (p[axis] - center[axis]))
+                    .sum::<f32>();
+                distance_squared <= radius * radius
+            })
+            .map(|(entity, _)| entity)
+    }
+
+    /// Every entity, with its position, in any cell overlapping the box spanning `min` to `max`
+    fn candidates(
+        &self,
+        min: [f32; 3],
+        max: [f32; 3],
+    ) -> impl Iterator<Item = (Entity, [f32; 3])> + '_ {
+        let lo = self.cell(min);
+        let hi = self.cell(max);
+        (lo[0]..=hi[0])
+            .flat_map(move |x| {
+                (lo[1]..=hi[1]).flat_map(move |y| (lo[2]..=hi[2]).map(move |z| [x, y, z]))
+            })
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flat_map(|entities| entities.iter())
+            .map(move |&entity| (entity, self.entries[&entity].position))
+    }
+
+    fn position(&self, entity: Entity) -> Option<[f32; 3]> {
+        Some(self.entries.get(&entity)?.position)
+    }
+
+    fn cell(&self, position: [f32; 3]) -> [i32; 3] {
+        [
+            floor(position[0] / self.cell_size),
+            floor(position[1] / self.cell_size),
+            floor(position[2] / self.cell_size),
+        ]
+    }
+}
+
+fn remove_from_cell(cells: &mut HashMap<[i32; 3], Vec<Entity>>, cell: [i32; 3], entity: Entity) {
+    let entities = cells.get_mut(&cell).unwrap();
+    let index = entities.iter().position(|&x| x == entity).unwrap();
+    entities.swap_remove(index);
+    if entities.is_empty() {
+        cells.remove(&cell);
+    }
+}
+
+/// `f32::floor` without depending on `std`, saturating at the bounds of `i32`
+fn floor(x: f32) -> i32 {
+    let truncated = x as i32;
+    if (truncated as f32) > x {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
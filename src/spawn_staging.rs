@@ -0,0 +1,71 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use hashbrown::HashMap;
+
+use crate::archetype::Archetype;
+use crate::DynamicBundle;
+
+/// Component data for entities to be spawned by [`World::par_extend`](crate::World::par_extend)
+///
+/// Stagings are independent of any `World`, so each thread generating entities can fill its own
+/// without synchronization. Components are stored in private archetypes resolved as entities are
+/// pushed, which `par_extend` splices into the world wholesale.
+#[derive(Default)]
+pub struct SpawnStaging {
+    index: HashMap<Box<[TypeId]>, u32>,
+    pub(crate) archetypes: Vec<Archetype>,
+    /// Index into `archetypes` of each entity, in the order they were pushed
+    pub(crate) order: Vec<u32>,
+}
+
+impl SpawnStaging {
+    /// Create an empty staging
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage an entity with certain components
+    pub fn push(&mut self, components: impl DynamicBundle) {
+        let Self {
+            index, archetypes, ..
+        } = self;
+        let archetype_id = components.with_ids(|ids| {
+            *index.entry(ids.into()).or_insert_with(|| {
+                archetypes.push(Archetype::new(components.type_info()));
+                archetypes.len() as u32 - 1
+            })
+        });
+        let archetype = &mut self.archetypes[archetype_id as usize];
+        unsafe {
+            // The real ID is assigned when the entity is spawned
+            let index = archetype.allocate(u32::MAX);
+            components.put(|ptr, ty| {
+                archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+            });
+        }
+        self.order.push(archetype_id);
+    }
+
+    /// Number of entities staged
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Whether no entities are staged
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl<B: DynamicBundle> Extend<B> for SpawnStaging {
+    fn extend<I: IntoIterator<Item = B>>(&mut self, iter: I) {
+        for components in iter {
+            self.push(components);
+        }
+    }
+}
+
+unsafe impl Send for SpawnStaging {}
+unsafe impl Sync for SpawnStaging {}
@@ -0,0 +1,79 @@
+use crate::alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::entities::{Entities, ReserveEntitiesIterator};
+use crate::{DynamicBundle, Entity, World};
+
+/// The entity ID half of a [`World`] split by [`World::split_entities_and_components`]
+///
+/// Hands out IDs for new entities, whose components can be written concurrently through the
+/// matching [`ComponentStorage`].
+pub struct EntityAllocator {
+    pub(crate) entities: Entities,
+    pub(crate) despawned: Vec<Entity>,
+    pub(crate) token: u64,
+}
+
+impl EntityAllocator {
+    /// Allocate the ID of a new entity
+    ///
+    /// Can be called concurrently through a shared reference.
+    pub fn reserve_entity(&self) -> Entity {
+        self.entities.reserve_entity()
+    }
+
+    /// Allocate the IDs of `count` new entities
+    ///
+    /// Can be called concurrently through a shared reference.
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator<'_> {
+        self.entities.reserve_entities(count)
+    }
+
+    /// Whether `entity` exists, or has been reserved
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Despawn `entity` when the world is rejoined, if it still exists then
+    pub fn despawn(&mut self, entity: Entity) {
+        self.despawned.push(entity);
+    }
+}
+
+/// The component half of a [`World`] split by [`World::split_entities_and_components`]
+///
+/// Stores the components of entities reserved through the matching [`EntityAllocator`].
+pub struct ComponentStorage {
+    /// The world's archetypes, with no entities of its own
+    pub(crate) world: World,
+    /// Entities stored since the split, with their archetype and index
+    pub(crate) placed: Vec<(Entity, u32, u32)>,
+    pub(crate) token: u64,
+}
+
+impl ComponentStorage {
+    /// Store `components` for `entity`, which must have been reserved since the split
+    ///
+    /// Components of entities that weren't reserved through the matching `EntityAllocator`, or
+    /// that were already given components since the split, are dropped when the world is rejoined.
+    pub fn insert(&mut self, entity: Entity, components: impl DynamicBundle) {
+        let (archetype, index) = unsafe { self.world.place(entity, components) };
+        self.placed.push((entity, archetype, index));
+    }
+
+    /// Number of entities stored since the split
+    pub fn len(&self) -> usize {
+        self.placed.len()
+    }
+
+    /// Whether no entities were stored since the split
+    pub fn is_empty(&self) -> bool {
+        self.placed.is_empty()
+    }
+}
+
+/// Identifies the halves of one split, so they cannot be mixed up with those of another
+pub(crate) fn next_token() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
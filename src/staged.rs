@@ -0,0 +1,71 @@
+use crate::alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use crate::{Component, Entity, World};
+
+/// A copy of every `T` component in a [`World`], for double-buffered updates
+///
+/// Obtained from [`World::stage_column`]. Write the next state of each component into the staged
+/// copy while reading the current state from the world, then exchange the two with
+/// [`World::swap_column`].
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1,));
+/// let b = world.spawn((2,));
+/// let mut next = world.stage_column::<i32>();
+/// for (e, &x) in world.query::<&i32>().iter() {
+///     // Every entity reads the *current* value of its neighbor
+///     let neighbor = if e == a { b } else { a };
+///     *next.get_mut(&world, e).unwrap() = x + *world.get::<i32>(neighbor).unwrap();
+/// }
+/// world.swap_column(&mut next).unwrap();
+/// assert_eq!(*world.get::<i32>(a).unwrap(), 3);
+/// assert_eq!(*world.get::<i32>(b).unwrap(), 3);
+/// ```
+pub struct StagedColumn<T> {
+    /// Archetype index, IDs of entities in that archetype when staged, and their components
+    pub(crate) columns: Vec<(u32, Vec<u32>, Vec<T>)>,
+}
+
+impl<T: Component> StagedColumn<T> {
+    /// Access the staged `T` of `entity`, which must be from `world`
+    ///
+    /// Returns `None` if `entity` has moved, been despawned, or had no `T` since staging.
+    pub fn get_mut(&mut self, world: &World, entity: Entity) -> Option<&mut T> {
+        let (archetype, index) = world.locate(entity)?;
+        let i = self
+            .columns
+            .binary_search_by_key(&archetype, |x| x.0)
+            .ok()?;
+        let (_, ids, values) = &mut self.columns[i];
+        if ids.get(index as usize) != Some(&entity.id()) {
+            return None;
+        }
+        values.get_mut(index as usize)
+    }
+
+    /// Iterate over the staged components, one slice per archetype
+    pub fn columns_mut(&mut self) -> impl Iterator<Item = &mut [T]> + '_ {
+        self.columns.iter_mut().map(|x| &mut x.2[..])
+    }
+}
+
+/// Error indicating that entities holding a component changed since it was staged
+///
+/// See [`World::swap_column`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StaleColumn;
+
+impl fmt::Display for StaleColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("entities changed since the column was staged")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for StaleColumn {}
@@ -0,0 +1,76 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts of the work a [`World`](crate::World) has done since its statistics were last reset
+///
+/// Returned by [`World::stats`](crate::World::stats) when the `stats` feature is enabled. Resetting
+/// with [`World::reset_stats`](crate::World::reset_stats) once per tick yields per-tick rates,
+/// making it easy to spot e.g. entities thrashing between archetypes.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct WorldStats {
+    /// Entities spawned, including reserved entities once flushed
+    pub spawns: u64,
+    /// Entities despawned, including those removed by `clear`
+    pub despawns: u64,
+    /// Moves of an entity from one archetype to another, caused by adding or removing components
+    pub archetype_moves: u64,
+    /// Archetypes considered by queries while finding those they match
+    pub archetype_scans: u64,
+    /// Dynamic borrows of a component column, whether shared or unique
+    pub borrows: u64,
+}
+
+/// Number of counters query scans are spread over
+const SHARDS: usize = 16;
+
+/// Counters maintained by the world itself, rather than its entities or archetypes
+#[derive(Default)]
+pub(crate) struct Counters {
+    pub moves: u64,
+    /// Archetype scans, split by thread so that queries started in parallel don't contend for
+    /// one cache line
+    scans: [Shard; SHARDS],
+}
+
+/// A counter on a cache line of its own
+#[derive(Default)]
+#[repr(align(128))]
+struct Shard(AtomicU64);
+
+impl Counters {
+    pub fn scanned(&self, archetypes: usize) {
+        self.scans[shard()]
+            .0
+            .fetch_add(archetypes as u64, Ordering::Relaxed);
+    }
+
+    pub fn scans(&self) -> u64 {
+        self.scans.iter().map(|x| x.0.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn reset(&mut self) {
+        self.moves = 0;
+        for shard in &mut self.scans {
+            *shard.0.get_mut() = 0;
+        }
+    }
+}
+
+/// The scan counter the current thread uses
+#[cfg(feature = "std")]
+fn shard() -> usize {
+    use core::sync::atomic::AtomicUsize;
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::thread_local! {
+        static SHARD: usize = NEXT.fetch_add(1, Ordering::Relaxed) % SHARDS;
+    }
+    SHARD.with(|&x| x)
+}
+
+/// The scan counter the current thread uses
+///
+/// Without `std` there's no portable way to tell threads apart, so all share one.
+#[cfg(not(feature = "std"))]
+fn shard() -> usize {
+    0
+}
@@ -0,0 +1,131 @@
+use core::any::TypeId;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::archetype::Archetype;
+use crate::query::{Access, Fetch, Query};
+use crate::BorrowError;
+
+/// A component packing up to 64 boolean markers into a single bitmask
+///
+/// Adding a marker component moves an entity to a different archetype, which is costly and
+/// fragments storage when many independent markers are in use. Toggling a bit in `Tags` does
+/// neither, while [`WithBits`] keeps such markers queryable.
+///
+/// The meaning of each bit is up to the application, typically assigned by constants.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// const ON_FIRE: u64 = 1 << 0;
+/// const SNEAKING: u64 = 1 << 1;
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Tags(ON_FIRE | SNEAKING), 123));
+/// let b = world.spawn((Tags(ON_FIRE), 456));
+/// world.get_mut::<Tags>(b).unwrap().insert(SNEAKING);
+/// world.get_mut::<Tags>(a).unwrap().remove(ON_FIRE);
+/// let burning = world
+///     .query::<WithBits<ON_FIRE, &i32>>()
+///     .iter()
+///     .map(|(e, &x)| (e, x))
+///     .collect::<Vec<_>>();
+/// assert_eq!(burning, [(b, 456)]);
+/// ```
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Tags(pub u64);
+
+impl Tags {
+    /// Whether every bit in `mask` is set
+    pub fn contains(self, mask: u64) -> bool {
+        self.0 & mask == mask
+    }
+
+    /// Whether any bit in `mask` is set
+    pub fn intersects(self, mask: u64) -> bool {
+        self.0 & mask != 0
+    }
+
+    /// Set the bits in `mask`
+    pub fn insert(&mut self, mask: u64) {
+        self.0 |= mask;
+    }
+
+    /// Clear the bits in `mask`
+    pub fn remove(&mut self, mask: u64) {
+        self.0 &= !mask;
+    }
+
+    /// Set or clear the bits in `mask` according to `value`
+    pub fn set(&mut self, mask: u64, value: bool) {
+        if value {
+            self.insert(mask);
+        } else {
+            self.remove(mask);
+        }
+    }
+}
+
+/// Query transformer skipping entities whose [`Tags`] do not have every bit in `MASK` set
+///
+/// Entities without a `Tags` component are skipped as well. Shares access to `Tags`, so `Q` must
+/// not uniquely borrow it.
+pub struct WithBits<const MASK: u64, Q>(PhantomData<Q>);
+
+impl<const MASK: u64, Q: Query> Query for WithBits<MASK, Q> {
+    type Fetch = FetchWithBits<MASK, Q::Fetch>;
+}
+
+#[doc(hidden)]
+pub struct FetchWithBits<const MASK: u64, F>(F, NonNull<Tags>);
+
+unsafe impl<'a, const MASK: u64, F: Fetch<'a>> Fetch<'a> for FetchWithBits<MASK, F> {
+    type Item = F::Item;
+
+    fn dangling() -> Self {
+        Self(F::dangling(), NonNull::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if !archetype.has::<Tags>() {
+            return None;
+        }
+        Some(F::access(archetype)?.max(Access::Read))
+    }
+
+    fn borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<Tags>()?;
+        F::borrow(archetype).inspect_err(|_| archetype.release::<Tags>())
+    }
+    fn new(archetype: &'a Archetype) -> Option<Self> {
+        Some(Self(F::new(archetype)?, archetype.get_base::<Tags>()?))
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<Tags>();
+        F::release(archetype);
+    }
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<Tags>(), false);
+        F::for_each_borrow(&mut f);
+    }
+    fn for_each_requirement(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<Tags>(), true);
+        F::for_each_requirement(&mut f);
+    }
+
+    unsafe fn get(&self, n: usize) -> F::Item {
+        self.0.get(n)
+    }
+
+    const FILTERS: bool = true;
+
+    #[inline(always)]
+    unsafe fn skip(&self, n: usize) -> bool {
+        !(*self.1.as_ptr().add(n)).contains(MASK) || self.0.skip(n)
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, n: usize) {
+        self.0.prefetch(n);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::archetype::Archetype;
+
+/// A point in a world's timeline, advanced by [`World::advance_tick`](crate::World::advance_tick)
+///
+/// Ticks are ordered, so a component whose [`last_changed`](crate::World::last_changed) tick is
+/// greater than the tick a client last acknowledged has changed since.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Tick(pub u64);
+
+/// The world's current tick
+///
+/// When each component was last changed is stored by its archetype, alongside its change flag.
+/// Flags are folded in whenever they're reset, so a component's recorded tick is only accurate if
+/// it isn't currently flagged. Components never changed since the first tick report tick 0.
+#[derive(Default)]
+pub(crate) struct ChangeTicks {
+    current: Tick,
+}
+
+impl ChangeTicks {
+    pub(crate) fn current(&self) -> Tick {
+        self.current
+    }
+
+    /// Stamp components flagged as changed in `archetypes` with the current tick
+    ///
+    /// Must be called before the flags are reset.
+    pub(crate) fn record(&self, archetypes: &mut [Archetype]) {
+        if self.current == Tick(0) {
+            // Every component changed during tick 0 at the latest
+            return;
+        }
+        for archetype in archetypes {
+            archetype.record_ticks(self.current);
+        }
+    }
+
+    /// Move on to the next tick
+    pub(crate) fn advance(&mut self) -> Tick {
+        self.current.0 += 1;
+        self.current
+    }
+}
@@ -0,0 +1,265 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+
+use crate::archetype::{Archetype, TypeIdMap};
+use crate::entities::EntityMeta;
+use crate::{Component, Entity};
+
+/// Casts from component types to trait objects, registered with `World::register_trait`
+#[derive(Default)]
+pub(crate) struct TraitRegistry {
+    /// `TraitImpls<Dyn>` for each trait object type `Dyn`
+    traits: TypeIdMap<Box<dyn Any + Send + Sync>>,
+}
+
+impl TraitRegistry {
+    pub fn register<T: Component, Dyn: ?Sized + 'static>(
+        &mut self,
+        cast: fn(&T) -> &Dyn,
+        cast_mut: fn(&mut T) -> &mut Dyn,
+    ) {
+        let impls = self
+            .traits
+            .entry(TypeId::of::<Dyn>())
+            .or_insert_with(|| Box::new(TraitImpls::<Dyn> { impls: Vec::new() }))
+            .downcast_mut::<TraitImpls<Dyn>>()
+            .unwrap();
+        let caster = Caster {
+            ty: TypeId::of::<T>(),
+            size: core::mem::size_of::<T>(),
+            cast: Box::new(move |ptr| cast(unsafe { &*ptr.cast::<T>() }) as *const Dyn),
+            cast_mut: Box::new(move |ptr| cast_mut(unsafe { &mut *ptr.cast::<T>() }) as *mut Dyn),
+        };
+        match impls.impls.iter_mut().find(|x| x.ty == caster.ty) {
+            Some(existing) => *existing = caster,
+            None => impls.impls.push(caster),
+        }
+    }
+
+    pub fn get<Dyn: ?Sized + 'static>(&self) -> &[Caster<Dyn>] {
+        self.traits
+            .get(&TypeId::of::<Dyn>())
+            .map_or(&[], |x| &x.downcast_ref::<TraitImpls<Dyn>>().unwrap().impls)
+    }
+}
+
+struct TraitImpls<Dyn: ?Sized> {
+    impls: Vec<Caster<Dyn>>,
+}
+
+pub(crate) struct Caster<Dyn: ?Sized> {
+    ty: TypeId,
+    size: usize,
+    cast: Box<dyn Fn(*const u8) -> *const Dyn + Send + Sync>,
+    cast_mut: Box<dyn Fn(*mut u8) -> *mut Dyn + Send + Sync>,
+}
+
+/// Invoke `f` on every component in `archetypes` that has been registered as implementing `Dyn`
+#[cfg_attr(feature = "track-borrows", track_caller)]
+pub(crate) fn visit<Dyn: ?Sized + 'static>(
+    casters: &[Caster<Dyn>],
+    meta: &[EntityMeta],
+    archetypes: &[Archetype],
+    mut f: impl FnMut(Entity, &Dyn),
+) {
+    for archetype in archetypes.iter().filter(|x| !x.is_empty()) {
+        for caster in casters.iter().filter(|x| archetype.has_dynamic(x.ty)) {
+            archetype.borrow_dynamic(caster.ty);
+            let _guard = ReleaseOnDrop(archetype, caster.ty);
+            for (index, &id) in archetype.ids().iter().enumerate() {
+                let entity = Entity {
+                    id,
+                    generation: meta[id as usize].generation,
+                };
+                unsafe {
+                    let ptr = archetype
+                        .get_dynamic(caster.ty, caster.size, index as u32)
+                        .unwrap();
+                    f(entity, &*(caster.cast)(ptr.as_ptr()));
+                }
+            }
+        }
+    }
+}
+
+/// Like `visit`, but with unique access to each component
+pub(crate) fn visit_mut<Dyn: ?Sized + 'static>(
+    casters: &[Caster<Dyn>],
+    meta: &[EntityMeta],
+    archetypes: &mut [Archetype],
+    mut f: impl FnMut(Entity, &mut Dyn),
+) {
+    for archetype in archetypes.iter_mut().filter(|x| !x.is_empty()) {
+        for caster in casters {
+            if !archetype.has_dynamic(caster.ty) {
+                continue;
+            }
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let entity = Entity {
+                    id,
+                    generation: meta[id as usize].generation,
+                };
+                unsafe {
+                    let ptr = archetype
+                        .get_dynamic(caster.ty, caster.size, index)
+                        .unwrap();
+                    archetype.set_changed(caster.ty, index, true);
+                    f(entity, &mut *(caster.cast_mut)(ptr.as_ptr()));
+                }
+            }
+        }
+    }
+}
+
+/// A borrow of every component registered as implementing `Dyn`
+///
+/// Returned by [`World::query_trait`](crate::World::query_trait). Note that borrows are not
+/// released until this object is dropped.
+///
+/// Components can't be kept once the borrow is dropped:
+/// ```compile_fail
+/// # use hecs::*;
+/// trait Persist {}
+/// impl Persist for i32 {}
+/// let mut world = World::new();
+/// world.register_trait::<i32, dyn Persist>(|x| x, |x| x);
+/// world.spawn((1,));
+/// let kept = world.query_trait::<dyn Persist>().iter().next().unwrap().1;
+/// drop(kept);
+/// ```
+pub struct QueryTrait<'w, Dyn: ?Sized + 'static> {
+    casters: &'w [Caster<Dyn>],
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    /// Each archetype holding a component registered for `Dyn`, and the component's caster,
+    /// borrowed by `iter`
+    columns: Vec<(&'w Archetype, &'w Caster<Dyn>)>,
+    borrowed: bool,
+}
+
+impl<'w, Dyn: ?Sized + 'static> QueryTrait<'w, Dyn> {
+    pub(crate) fn new(
+        casters: &'w [Caster<Dyn>],
+        meta: &'w [EntityMeta],
+        archetypes: &'w [Archetype],
+    ) -> Self {
+        Self {
+            casters,
+            meta,
+            archetypes,
+            columns: Vec::new(),
+            borrowed: false,
+        }
+    }
+
+    /// Execute the query
+    ///
+    /// Must be called only once per query. Panics if a matched component type is already
+    /// uniquely borrowed.
+    // The lifetime narrowing here is required for soundness.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn iter(&mut self) -> TraitIter<'_, Dyn> {
+        self.borrow();
+        TraitIter {
+            meta: self.meta,
+            columns: &self.columns,
+            column: 0,
+            index: 0,
+        }
+    }
+
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    fn borrow(&mut self) {
+        if self.borrowed {
+            panic!(
+                "called QueryTrait::iter twice on the same borrow; construct a new query instead"
+            );
+        }
+        self.borrowed = true;
+        for archetype in self.archetypes.iter().filter(|x| !x.is_empty()) {
+            for caster in self.casters.iter().filter(|x| archetype.has_dynamic(x.ty)) {
+                // Borrows taken so far are released by the destructor if this panics
+                archetype.borrow_dynamic(caster.ty);
+                self.columns.push((archetype, caster));
+            }
+        }
+    }
+}
+
+impl<Dyn: ?Sized + 'static> Drop for QueryTrait<'_, Dyn> {
+    fn drop(&mut self) {
+        for &(archetype, caster) in &self.columns {
+            archetype.release_dynamic(caster.ty);
+        }
+    }
+}
+
+impl<'q, 'w, Dyn: ?Sized + 'static> IntoIterator for &'q mut QueryTrait<'w, Dyn> {
+    type Item = (Entity, &'q Dyn);
+    type IntoIter = TraitIter<'q, Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over every component registered as implementing `Dyn`, along with its entity,
+/// yielded by [`QueryTrait::iter`]
+pub struct TraitIter<'q, Dyn: ?Sized + 'static> {
+    meta: &'q [EntityMeta],
+    columns: &'q [(&'q Archetype, &'q Caster<Dyn>)],
+    /// Position in `columns`
+    column: usize,
+    /// Position in the archetype of the current column
+    index: u32,
+}
+
+impl<'q, Dyn: ?Sized + 'static> Iterator for TraitIter<'q, Dyn> {
+    type Item = (Entity, &'q Dyn);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let &(archetype, caster) = self.columns.get(self.column)?;
+            if self.index == archetype.len() {
+                self.column += 1;
+                self.index = 0;
+                continue;
+            }
+            let id = archetype.entity_id(self.index);
+            let entity = Entity {
+                id,
+                generation: self.meta[id as usize].generation,
+            };
+            let component = unsafe {
+                let ptr = archetype
+                    .get_dynamic(caster.ty, caster.size, self.index)
+                    .unwrap();
+                &*(caster.cast)(ptr.as_ptr())
+            };
+            self.index += 1;
+            return Some((entity, component));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.columns[self.column.min(self.columns.len())..]
+            .iter()
+            .map(|(archetype, _)| archetype.len() as usize)
+            .sum::<usize>()
+            - self.index as usize;
+        (n, Some(n))
+    }
+}
+
+impl<Dyn: ?Sized + 'static> ExactSizeIterator for TraitIter<'_, Dyn> {}
+
+/// Releases a shared borrow even if the visitor panics
+pub(crate) struct ReleaseOnDrop<'a>(pub(crate) &'a Archetype, pub(crate) TypeId);
+
+impl Drop for ReleaseOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.release_dynamic(self.1);
+    }
+}
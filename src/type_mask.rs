@@ -0,0 +1,119 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::archetype::{Archetype, TypeIdMap};
+use crate::{Fetch, Query};
+
+/// Dense indices assigned to the component types stored in a world, in order of first appearance
+#[derive(Default)]
+pub(crate) struct TypeIndices {
+    map: TypeIdMap<u32>,
+}
+
+impl TypeIndices {
+    /// Record the set of component types stored in `archetype`, which was just added to the world
+    pub(crate) fn index_archetype(&mut self, archetype: &mut Archetype) {
+        let mut mask = TypeMask::default();
+        for ty in archetype.types() {
+            let next = self.map.len() as u32;
+            mask.insert(*self.map.entry(ty.id()).or_insert(next));
+        }
+        archetype.set_mask(mask);
+    }
+
+    fn get(&self, ty: TypeId) -> Option<u32> {
+        self.map.get(&ty).copied()
+    }
+}
+
+/// A set of component types, as bits indexed by `TypeIndices`
+#[derive(Default, Clone)]
+pub(crate) struct TypeMask(Vec<u64>);
+
+impl TypeMask {
+    fn insert(&mut self, index: u32) {
+        let word = index as usize / 64;
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << (index % 64);
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self.0.get(i).copied().unwrap_or(0)
+    }
+
+    /// Whether every type in `other` is also in `self`
+    fn contains_all(&self, other: &TypeMask) -> bool {
+        other
+            .0
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| x & !self.word(i) == 0)
+    }
+
+    /// Whether any type in `other` is also in `self`
+    fn contains_any(&self, other: &TypeMask) -> bool {
+        other
+            .0
+            .iter()
+            .enumerate()
+            .any(|(i, &x)| x & self.word(i) != 0)
+    }
+}
+
+/// The component types a query requires and forbids, for ruling out archetypes without hashing
+pub(crate) struct QueryMask {
+    include: TypeMask,
+    exclude: TypeMask,
+    /// Whether a required type has never been stored in the world, so nothing can match
+    impossible: bool,
+}
+
+impl QueryMask {
+    pub(crate) fn new<Q: Query>(indices: &TypeIndices) -> Self {
+        let mut mask = Self {
+            include: TypeMask::default(),
+            exclude: TypeMask::default(),
+            impossible: false,
+        };
+        Q::Fetch::for_each_requirement(|ty, present| match (indices.get(ty), present) {
+            (Some(index), true) => mask.include.insert(index),
+            (Some(index), false) => mask.exclude.insert(index),
+            (None, true) => mask.impossible = true,
+            (None, false) => {}
+        });
+        mask
+    }
+
+    /// Whether `archetype` might be matched by the query
+    ///
+    /// `Fetch::access` has the final say, since fetches needn't report all their requirements.
+    pub(crate) fn admits(&self, archetype: &Archetype) -> bool {
+        !self.impossible
+            && archetype.mask().contains_all(&self.include)
+            && !archetype.mask().contains_any(&self.exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiple_words() {
+        let mut a = TypeMask::default();
+        a.insert(3);
+        a.insert(100);
+        let mut b = TypeMask::default();
+        b.insert(100);
+        assert!(a.contains_all(&b));
+        assert!(a.contains_any(&b));
+        assert!(!b.contains_all(&a));
+        b.insert(64);
+        assert!(!a.contains_all(&b));
+        assert!(a.contains_any(&b));
+        assert!(a.contains_all(&TypeMask::default()));
+        assert!(!a.contains_any(&TypeMask::default()));
+    }
+}
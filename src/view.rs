@@ -0,0 +1,358 @@
+use core::marker::PhantomData;
+
+use crate::query::Copied;
+use crate::{
+    Bundle, Component, ComponentError, Entity, Mut, NoSuchEntity, Query, QueryBorrow, QueryMut,
+    Ref, RefMut, World,
+};
+
+/// A [`World`] restricted to operations on the component types in the tuple `S`
+///
+/// Obtained from [`World::view`]. Useful for handing plugins or subsystems a limited capability
+/// surface over a shared world: attempting to access any component type outside of `S` fails to
+/// compile.
+///
+/// Methods taking a type parameter `I` use it to locate the component type within `S`; it is
+/// always inferred, and can be written as `_`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Position(f32);
+/// struct Velocity(f32);
+/// struct Secret;
+///
+/// fn physics(mut view: View<'_, (Position, Velocity)>) {
+///     for (_, (pos, vel)) in view.query_mut::<(&mut Position, &Velocity), _>() {
+///         pos.0 += vel.0;
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let e = world.spawn((Position(1.0), Velocity(2.0), Secret));
+/// physics(world.view());
+/// assert_eq!(world.get::<Position>(e).unwrap().0, 3.0);
+/// ```
+///
+/// Components outside of `S` are inaccessible:
+/// ```compile_fail
+/// # use hecs::*;
+/// # struct Position(f32);
+/// # struct Secret;
+/// let mut world = World::new();
+/// let e = world.spawn((Position(1.0), Secret));
+/// let view = world.view::<(Position,)>();
+/// view.get::<Secret, _>(e);
+/// ```
+pub struct View<'w, S> {
+    world: &'w mut World,
+    _marker: PhantomData<fn(S)>,
+}
+
+impl<'w, S> View<'w, S> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// See [`World::get`].
+    pub fn get<T: Component, I>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.get::<T>(entity)
+    }
+
+    /// Uniquely borrow the `T` component of `entity`
+    ///
+    /// See [`World::get_mut`].
+    pub fn get_mut<T: Component, I>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.get_mut::<T>(entity)
+    }
+
+    /// Query the entities having the components `Q` requires
+    ///
+    /// See [`World::query`].
+    pub fn query<Q: Query + ViewQuery<S, I>, I>(&self) -> QueryBorrow<'_, Q> {
+        self.world.query::<Q>()
+    }
+
+    /// Query the entities having the components `Q` requires, without dynamic borrow checking
+    ///
+    /// See [`World::query_mut`].
+    pub fn query_mut<Q: Query + ViewQuery<S, I>, I>(&mut self) -> QueryMut<'_, Q> {
+        self.world.query_mut::<Q>()
+    }
+
+    /// Add `component` to `entity`, returning the `T` it replaced, if any
+    ///
+    /// See [`World::insert_one`].
+    pub fn insert_one<T: Component, I>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.insert_one(entity, component)
+    }
+
+    /// Remove the `T` component from `entity`
+    ///
+    /// See [`World::remove_one`].
+    pub fn remove_one<T: Component, I>(&mut self, entity: Entity) -> Result<T, ComponentError>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.remove_one::<T>(entity)
+    }
+
+    /// Divide the view into two views of disjoint sets of its component types
+    ///
+    /// Lets parts of a system be factored into helper functions that each take only the
+    /// components they need, rather than re-querying or passing around one large tuple. Since the
+    /// halves can't access the same components, each can still run unchecked queries.
+    ///
+    /// Panics if `A` and `B` share a component type.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    /// struct Health(u32);
+    ///
+    /// fn integrate(mut view: SubView<'_, (Position, Velocity)>) {
+    ///     for (_, (pos, vel)) in view.query_mut::<(&mut Position, &Velocity), _>() {
+    ///         pos.0 += vel.0;
+    ///     }
+    /// }
+    ///
+    /// fn regenerate(mut view: SubView<'_, (Health,)>) {
+    ///     for (_, health) in view.query_mut::<&mut Health, _>() {
+    ///         health.0 += 1;
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// let e = world.spawn((Position(1.0), Velocity(2.0), Health(10)));
+    /// let mut view = world.view::<(Position, Velocity, Health)>();
+    /// let (physics, health) = view.split::<(Position, Velocity), (Health,), _, _>();
+    /// integrate(physics);
+    /// regenerate(health);
+    /// assert_eq!(world.get::<Position>(e).unwrap().0, 3.0);
+    /// assert_eq!(world.get::<Health>(e).unwrap().0, 11);
+    /// ```
+    pub fn split<A, B, IA, IB>(&mut self) -> (SubView<'_, A>, SubView<'_, B>)
+    where
+        A: Bundle + ViewSubset<S, IA>,
+        B: Bundle + ViewSubset<S, IB>,
+    {
+        A::with_static_ids(|a| {
+            B::with_static_ids(|b| {
+                assert!(
+                    a.iter().all(|x| !b.contains(x)),
+                    "split views must not share component types"
+                );
+            })
+        });
+        // Unchecked queries assume there are no pending entities
+        self.world.flush();
+        let world = &*self.world;
+        (
+            SubView {
+                world,
+                _marker: PhantomData,
+            },
+            SubView {
+                world,
+                _marker: PhantomData,
+            },
+        )
+    }
+}
+
+/// A share of a [`View`] restricted to the component types in the tuple `S`, obtained from
+/// [`View::split`]
+///
+/// Unlike a `View`, can't add or remove components, as that would move entities, and with them
+/// components accessible through other `SubView`s.
+pub struct SubView<'w, S> {
+    world: &'w World,
+    _marker: PhantomData<fn(S)>,
+}
+
+impl<'w, S> SubView<'w, S> {
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// See [`World::get`].
+    pub fn get<T: Component, I>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.get::<T>(entity)
+    }
+
+    /// Uniquely borrow the `T` component of `entity`
+    ///
+    /// See [`World::get_mut`].
+    pub fn get_mut<T: Component, I>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError>
+    where
+        S: Includes<T, I>,
+    {
+        self.world.get_mut::<T>(entity)
+    }
+
+    /// Query the entities having the components `Q` requires
+    ///
+    /// See [`World::query`].
+    pub fn query<Q: Query + ViewQuery<S, I>, I>(&self) -> QueryBorrow<'_, Q> {
+        self.world.query::<Q>()
+    }
+
+    /// Query the entities having the components `Q` requires, without dynamic borrow checking
+    ///
+    /// See [`World::query_mut`].
+    pub fn query_mut<Q: Query + ViewQuery<S, I>, I>(&mut self) -> QueryMut<'_, Q> {
+        // Safety: other `SubView`s split from the same `View` can't access `S`, and this one is
+        // uniquely borrowed
+        unsafe { self.world.query_mut_shared::<Q>() }
+    }
+}
+
+/// Implemented by tuples of component types that include `T`
+///
+/// `I` identifies the position of `T` in the tuple, so that no two implementations overlap.
+pub trait Includes<T, I> {}
+
+/// Implemented by query types accessing only component types in the tuple `S`
+///
+/// See [`View`].
+pub trait ViewQuery<S, I> {}
+
+impl<S: Includes<T, I>, T: Component, I> ViewQuery<S, I> for &'_ T {}
+impl<S: Includes<T, I>, T: Component, I> ViewQuery<S, I> for &'_ mut T {}
+impl<S: Includes<T, I>, T: Component, I> ViewQuery<S, I> for Mut<'_, T> {}
+impl<S: Includes<T, I>, T: Component + Copy, I> ViewQuery<S, I> for Copied<T> {}
+impl<S, Q: ViewQuery<S, I>, I> ViewQuery<S, I> for Option<Q> {}
+
+/// Implemented by tuples of component types that are all in the tuple `S`
+///
+/// See [`View::split`].
+pub trait ViewSubset<S, I> {}
+
+/// Markers for positions within a tuple
+#[doc(hidden)]
+pub mod index {
+    pub enum P0 {}
+    pub enum P1 {}
+    pub enum P2 {}
+    pub enum P3 {}
+    pub enum P4 {}
+    pub enum P5 {}
+    pub enum P6 {}
+    pub enum P7 {}
+}
+
+macro_rules! includes_impl {
+    ($($name: ident),*) => {
+        includes_impl!(@at [$($name),*] [$($name),*] [P0, P1, P2, P3, P4, P5, P6, P7]);
+    };
+    (@at [$($all: ident),*] [$head: ident $(, $tail: ident)*] [$index: ident $(, $rest: ident)*]) => {
+        impl<$($all),*> Includes<$head, index::$index> for ($($all,)*) {}
+        includes_impl!(@at [$($all),*] [$($tail),*] [$($rest),*]);
+    };
+    (@at [$($all: ident),*] [] [$($rest: ident),*]) => {};
+}
+
+smaller_tuples_too!(includes_impl, H, G, F, E, D, C, B, A);
+
+macro_rules! view_query_tuple {
+    ($(($name: ident, $index: ident)),*) => {
+        impl<S, $($name: ViewQuery<S, $index>, $index),*> ViewQuery<S, ($($index,)*)>
+            for ($($name,)*)
+        {
+        }
+    };
+}
+
+view_query_tuple!();
+view_query_tuple!((A, IA));
+view_query_tuple!((A, IA), (B, IB));
+view_query_tuple!((A, IA), (B, IB), (C, IC));
+view_query_tuple!((A, IA), (B, IB), (C, IC), (D, ID));
+view_query_tuple!((A, IA), (B, IB), (C, IC), (D, ID), (E, IE));
+view_query_tuple!((A, IA), (B, IB), (C, IC), (D, ID), (E, IE), (F, IF));
+view_query_tuple!(
+    (A, IA),
+    (B, IB),
+    (C, IC),
+    (D, ID),
+    (E, IE),
+    (F, IF),
+    (G, IG)
+);
+view_query_tuple!(
+    (A, IA),
+    (B, IB),
+    (C, IC),
+    (D, ID),
+    (E, IE),
+    (F, IF),
+    (G, IG),
+    (H, IH)
+);
+
+macro_rules! view_subset_tuple {
+    ($(($name: ident, $index: ident)),*) => {
+        impl<S, $($name, $index),*> ViewSubset<S, ($($index,)*)> for ($($name,)*)
+        where
+            $(S: Includes<$name, $index>),*
+        {
+        }
+    };
+}
+
+view_subset_tuple!((A, IA));
+view_subset_tuple!((A, IA), (B, IB));
+view_subset_tuple!((A, IA), (B, IB), (C, IC));
+view_subset_tuple!((A, IA), (B, IB), (C, IC), (D, ID));
+view_subset_tuple!((A, IA), (B, IB), (C, IC), (D, ID), (E, IE));
+view_subset_tuple!((A, IA), (B, IB), (C, IC), (D, ID), (E, IE), (F, IF));
+view_subset_tuple!(
+    (A, IA),
+    (B, IB),
+    (C, IC),
+    (D, ID),
+    (E, IE),
+    (F, IF),
+    (G, IG)
+);
+view_subset_tuple!(
+    (A, IA),
+    (B, IB),
+    (C, IC),
+    (D, ID),
+    (E, IE),
+    (F, IF),
+    (G, IG),
+    (H, IH)
+);
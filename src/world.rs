@@ -5,22 +5,53 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use crate::{alloc::vec::Vec, DynamicQuery, DynamicQueryTypes};
+use crate::alloc::borrow::Cow;
+use crate::alloc::{vec, vec::Vec};
+use crate::{DynamicQuery, DynamicQueryTypes};
 use core::any::TypeId;
 use core::convert::TryFrom;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::{fmt, mem, ptr};
 
 #[cfg(feature = "std")]
 use std::error::Error;
 
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 
 use crate::alloc::boxed::Box;
+use crate::alloc::sync::Arc;
 use crate::archetype::Archetype;
-use crate::entities::{Entities, Location, ReserveEntitiesIterator};
+use crate::bundle::RawComponent;
+use crate::dense_map::DenseIndices;
+use crate::entities::{Entities, EntityMeta, Location, ReserveEntitiesIterator};
+use crate::events::Events;
+use crate::group::Groups;
+use crate::hierarchy::Hierarchy;
+#[cfg(feature = "debug-history")]
+use crate::history::{History, Transition};
+use crate::hooks::Hooks;
+use crate::names::Names;
+use crate::prefab::{self, Prefab};
+use crate::query::{assert_borrow, ChunkFetch};
+use crate::resources::Resources;
+use crate::sparse::SparseStorage;
+use crate::spawn_staging::SpawnStaging;
+use crate::split;
+#[cfg(feature = "stats")]
+use crate::stats::{Counters, WorldStats};
+use crate::ticks::ChangeTicks;
+use crate::traits::{self, QueryTrait, TraitRegistry};
+use crate::type_mask::{QueryMask, TypeIndices};
+use crate::world_snapshot::{ArchetypeSnapshot, WorldSnapshot};
+use crate::world_view::WorldView;
 use crate::{
-    Bundle, ColumnBatch, DynamicBundle, Entity, EntityRef, Fetch, MissingComponent, NoSuchEntity,
-    Query, QueryBorrow, QueryItem, QueryMut, QueryOne, Ref, RefMut,
+    Blackboard, BorrowError, Bundle, CloneError, ColumnBatch, CommandBuffer, ComponentRegistry,
+    ComponentStorage, DynamicBundle, Entity, EntityAllocator, EntityBuilder, EntityHandle,
+    EntityMap, EntityRef, Fetch, GroupChunks, IdNamespace, IdRangeUnavailable, MissingComponent,
+    NoSuchEntity, OwnedRef, OwnedRefMut, Query, QueryBorrow, QueryItem, QueryMut, QueryOne, Ref,
+    RefMut, ResourceRef, ResourceRefMut, Scope, SecondaryIndex, Snapshot, SparseRef, SparseRefMut,
+    StagedColumn, StaleColumn, TakenEntity, Tick, TypeInfo, View, WorldConfig, WorldDiff,
 };
 
 /// An unordered collection of entities, each having any number of distinctly typed components
@@ -41,29 +72,103 @@ use crate::{
 /// following spawns and despawns, that handle may, in rare circumstances, collide with a
 /// newly-allocated `Entity` handle. Very long-lived applications should therefore limit the period
 /// over which they may retain handles of despawned entities.
+///
+/// ### Thread safety
+///
+/// `World` is `Send + Sync`, since components must be. Methods taking `&self` may be called from
+/// any number of threads at once: queries, `get`, `entity`, resources, and sparse components
+/// acquire dynamic borrows through atomic flags, so conflicting accesses, such as two threads
+/// running `&mut T` queries, panic instead of racing, while shared ones, such as read-only queries
+/// or `get` calls alongside an `EntityRef`, proceed freely. `reserve_entity` is likewise atomic,
+/// and `stage` only queues its entity behind a lock. Everything that changes the set of entities or
+/// components takes `&mut self`, so the compiler rules out running it concurrently with anything
+/// else.
 pub struct World {
+    /// Distinguishes this world from every other, for `PreparedQuery`
+    id: usize,
     entities: Entities,
     index: HashMap<Box<[TypeId]>, u32>,
     archetypes: Vec<Archetype>,
     archetype_generation: u64,
+    type_indices: TypeIndices,
+    traits: TraitRegistry,
+    hooks: Hooks,
+    /// Component types that no entity has held since they were last drained
+    vanished: Vec<TypeId>,
+    /// Entities despawned with a reason since they were last drained
+    despawns: Vec<(Entity, DespawnReason)>,
+    resources: Resources,
+    events: Events,
+    hierarchy: Hierarchy,
+    names: Names,
+    dense_indices: DenseIndices,
+    sparse: SparseStorage,
+    groups: Groups,
+    ticks: ChangeTicks,
+    config: WorldConfig,
+    /// Archetype at which `maintain` resumes
+    maintenance_cursor: u32,
+    /// Entities staged by `stage`, awaiting `apply_staged`
+    #[cfg(feature = "std")]
+    staged: std::sync::Mutex<Vec<(Entity, EntityBuilder)>>,
+    #[cfg(feature = "debug-history")]
+    history: History,
+    #[cfg(feature = "stats")]
+    counters: Counters,
 }
 
+/// Source of `World::id`, starting from 1 so that 0 can represent no world
+static NEXT_WORLD_ID: AtomicUsize = AtomicUsize::new(1);
+
 impl World {
     /// Create an empty world
     pub fn new() -> Self {
+        Self::from_config(WorldConfig::default())
+    }
+
+    /// Create an empty world with a specific configuration
+    pub fn from_config(config: WorldConfig) -> Self {
         // `flush` assumes archetype 0 always exists, representing entities with no components.
-        let mut archetypes = Vec::new();
-        archetypes.push(Archetype::new(Vec::new()));
+        let archetypes = vec![Archetype::with_config(Vec::new(), &config)];
         let mut index = HashMap::default();
         index.insert(Box::default(), 0);
+        let mut entities = Entities::default();
+        entities.set_recycling(config.fifo_id_reuse, config.id_reuse_delay);
         Self {
-            entities: Entities::default(),
+            id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+            entities,
             index,
             archetypes,
             archetype_generation: 0,
+            type_indices: TypeIndices::default(),
+            traits: TraitRegistry::default(),
+            hooks: Hooks::default(),
+            vanished: Vec::new(),
+            despawns: Vec::new(),
+            resources: Resources::default(),
+            events: Events::default(),
+            hierarchy: Hierarchy::default(),
+            names: Names::default(),
+            dense_indices: DenseIndices::default(),
+            sparse: SparseStorage::default(),
+            groups: Groups::default(),
+            ticks: ChangeTicks::default(),
+            config,
+            maintenance_cursor: 0,
+            #[cfg(feature = "std")]
+            staged: std::sync::Mutex::new(Vec::new()),
+            #[cfg(feature = "debug-history")]
+            history: History::default(),
+            #[cfg(feature = "stats")]
+            counters: Counters::default(),
         }
     }
 
+    /// The configuration this world was created with
+    pub fn config(&self) -> &WorldConfig {
+        &self.config
+    }
+
     /// Create an entity with certain components
     ///
     /// Returns the ID of the newly created entity.
@@ -94,15 +199,66 @@ impl World {
         entity
     }
 
+    /// Set aside `ids` for entities spawned with `spawn_in`
+    ///
+    /// IDs in the range are never handed out by `spawn` or other general-purpose allocation, and
+    /// despawning an entity in the range returns its ID to the namespace. Useful when a subsystem
+    /// needs its entities' IDs to index a fixed-size array, such as player slots managed by a
+    /// connection layer.
+    ///
+    /// Fails if any ID in `ids` belongs to an existing entity or namespace, or if `ids` is empty.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let players = world.reserve_id_range(0..4).unwrap();
+    /// let a = world.spawn(("monster",));
+    /// assert!(a.id() >= 4);
+    /// let b = world.spawn_in(players, ("player",)).unwrap();
+    /// assert!(world.namespace_ids(players).contains(&b.id()));
+    /// ```
+    pub fn reserve_id_range(&mut self, ids: Range<u32>) -> Result<IdNamespace, IdRangeUnavailable> {
+        self.flush();
+        self.entities
+            .reserve_namespace(ids)
+            .map(IdNamespace)
+            .ok_or(IdRangeUnavailable)
+    }
+
+    /// The IDs set aside for `namespace`
+    pub fn namespace_ids(&self, namespace: IdNamespace) -> Range<u32> {
+        self.entities.namespace_ids(namespace.0)
+    }
+
+    /// Create an entity with certain components and an ID from `namespace`
+    ///
+    /// Returns `None`, dropping `components`, if every ID in the namespace is in use. IDs are
+    /// reused lowest first. Specific IDs in a namespace can be claimed with `spawn_at`.
+    pub fn spawn_in(
+        &mut self,
+        namespace: IdNamespace,
+        components: impl DynamicBundle,
+    ) -> Option<Entity> {
+        self.flush();
+        let entity = self.entities.alloc_in(namespace.0)?;
+        self.spawn_inner(entity, components);
+        Some(entity)
+    }
+
     /// Create an entity with certain components and a specific `Entity` handle.
     ///
     /// See `spawn`.
     ///
-    /// Despawns any existing entity with the same `Entity::id`.
+    /// Despawns any existing entity with the same `Entity::id`, along with its descendants, as
+    /// `despawn` would.
     ///
     /// Useful for easy handle-preserving deserialization. Be cautious resurrecting old `Entity`
     /// handles in already-populated worlds as it vastly increases the likelihood of collisions.
     ///
+    /// Panics if `handle` has the maximum generation, such as `Entity::DANGLING`, which is reserved
+    /// for IDs that have been despawned so often that reusing them could alias stale handles.
+    ///
     /// # Example
     /// ```
     /// # use hecs::*;
@@ -120,45 +276,140 @@ impl World {
         // necessary
         self.flush();
 
-        let loc = self.entities.alloc_at(handle);
-        if let Some(loc) = loc {
-            if let Some(moved) =
-                unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) }
-            {
-                self.entities.meta[moved as usize].location.index = loc.index;
-            }
+        let replaced = self.entities.alloc_at(handle);
+        if let Some((entity, loc)) = replaced {
+            self.vacate(entity, loc, None);
         }
 
         self.spawn_inner(handle, components);
+        if let Some((entity, loc)) = replaced {
+            self.note_vacated(loc.archetype);
+            self.despawn_children(entity, None);
+        }
     }
 
     fn spawn_inner(&mut self, entity: Entity, components: impl DynamicBundle) {
-        let archetype_id = components.with_ids(|ids| {
+        let archetype_id = self.archetype_for(&components);
+        unsafe {
+            self.spawn_into(entity, archetype_id, components);
+        }
+    }
+
+    /// Find or create the archetype storing exactly the component types of `components`
+    fn archetype_for(&mut self, components: &impl DynamicBundle) -> u32 {
+        components.with_ids(|ids| {
             self.index.get(ids).copied().unwrap_or_else(|| {
                 let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(components.type_info()));
+                self.archetypes
+                    .push(Archetype::with_config(components.type_info(), &self.config));
                 self.index.insert(ids.into(), x);
+                self.type_indices
+                    .index_archetype(self.archetypes.last_mut().unwrap());
                 self.archetype_generation += 1;
+                self.hooks.archetype_created(&self.archetypes);
+                self.groups.archetype_created(&self.archetypes);
                 x
             })
-        });
+        })
+    }
+
+    /// Find or create the archetype storing exactly the sorted component types `info`
+    fn archetype_for_types(&mut self, info: Vec<TypeInfo>) -> u32 {
+        use hashbrown::hash_map::Entry;
+
+        let elements = info.iter().map(|x| x.id()).collect();
+        match self.index.entry(elements) {
+            Entry::Occupied(x) => *x.get(),
+            Entry::Vacant(x) => {
+                let index = self.archetypes.len() as u32;
+                self.archetypes
+                    .push(Archetype::with_config(info, &self.config));
+                x.insert(index);
+                self.type_indices
+                    .index_archetype(self.archetypes.last_mut().unwrap());
+                self.archetype_generation += 1;
+                self.hooks.archetype_created(&self.archetypes);
+                self.groups.archetype_created(&self.archetypes);
+                index
+            }
+        }
+    }
 
+    /// Store `components` for `entity` without recording its location
+    ///
+    /// Returns the archetype and index the components were stored at.
+    ///
+    /// # Safety
+    ///
+    /// The location must be recorded before the world is otherwise used.
+    pub(crate) unsafe fn place(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> (u32, u32) {
+        let archetype_id = self.archetype_for(&components);
         let archetype = &mut self.archetypes[archetype_id as usize];
-        unsafe {
-            let index = archetype.allocate(entity.id);
-            components.put(|ptr, ty| {
-                archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
-            });
-            self.entities.meta[entity.id as usize].location = Location {
-                archetype: archetype_id,
-                index,
-            };
+        let index = archetype.allocate(entity.id);
+        components.put(|ptr, ty| {
+            archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+        });
+        (archetype_id, index)
+    }
+
+    /// Spawn a sequence of bundles which all have identical component types
+    ///
+    /// Looks up the target archetype and reserves storage only once for the whole sequence.
+    ///
+    /// # Safety
+    ///
+    /// Every bundle yielded by `bundles` must have the same component types.
+    pub(crate) unsafe fn spawn_same_archetype<B: DynamicBundle>(
+        &mut self,
+        mut bundles: impl ExactSizeIterator<Item = B>,
+    ) {
+        self.flush();
+        let first = match bundles.next() {
+            Some(x) => x,
+            None => return,
+        };
+        let archetype_id = self.archetype_for(&first);
+        let additional = u32::try_from(bundles.len() + 1).expect("too many entities");
+        self.entities.reserve(additional);
+        self.archetypes[archetype_id as usize].reserve(additional);
+        for components in core::iter::once(first).chain(bundles) {
+            let entity = self.entities.alloc();
+            self.spawn_into(entity, archetype_id, components);
         }
     }
 
+    /// # Safety
+    ///
+    /// `archetype_id` must identify the archetype storing exactly the component types of
+    /// `components`, and `entity` must have been freshly allocated.
+    unsafe fn spawn_into(
+        &mut self,
+        entity: Entity,
+        archetype_id: u32,
+        components: impl DynamicBundle,
+    ) {
+        let archetype = &mut self.archetypes[archetype_id as usize];
+        let index = archetype.allocate(entity.id);
+        components.put(|ptr, ty| {
+            archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+        });
+        self.entities.meta[entity.id as usize].location = Location {
+            archetype: archetype_id,
+            index,
+        };
+        self.hooks.spawned(entity, archetype, index);
+    }
+
     /// Efficiently spawn a large number of entities with the same components
     ///
-    /// Faster than calling `spawn` repeatedly with the same components.
+    /// Faster than calling `spawn` repeatedly with the same components: the target archetype is
+    /// looked up only once, and storage for as many entities as `iter`'s size hint allows is
+    /// reserved up front. Entities are spawned as the returned iterator is advanced, and any
+    /// remaining entities are spawned when it's dropped.
     ///
     /// # Example
     /// ```
@@ -187,6 +438,7 @@ impl World {
         SpawnBatchIter {
             inner: iter,
             entities: &mut self.entities,
+            hooks: &mut self.hooks,
             archetype_id,
             archetype: &mut self.archetypes[archetype_id as usize],
         }
@@ -214,6 +466,7 @@ impl World {
             archetype.set_entity_id(index, id);
             index += 1;
         }
+        self.spawned_rows(archetype_id, base..base + entity_count);
 
         // Return iterator over new IDs
         SpawnColumnBatchIter {
@@ -235,25 +488,105 @@ impl World {
         );
 
         // Drop components of entities that will be replaced
+        let mut replaced = Vec::new();
         for &handle in handles {
-            let loc = self.entities.alloc_at(handle);
-            if let Some(loc) = loc {
-                if let Some(moved) =
-                    unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) }
-                {
-                    self.entities.meta[moved as usize].location.index = loc.index;
-                }
+            if let Some((entity, loc)) = self.entities.alloc_at(handle) {
+                self.vacate(entity, loc, None);
+                replaced.push((entity, loc.archetype));
             }
         }
 
         // Store components
         let (archetype_id, base) = self.insert_archetype(archetype);
+        for &(_, id) in &replaced {
+            self.note_vacated(id);
+        }
 
-        // Fix up entity IDs
+        // Fix up entity IDs and locations
         let archetype = &mut self.archetypes[archetype_id as usize];
-        for (&handle, index) in handles.iter().zip(base as usize..) {
-            archetype.set_entity_id(index, handle.id());
+        for (&handle, index) in handles.iter().zip(base..) {
+            archetype.set_entity_id(index as usize, handle.id());
+            self.entities.meta[handle.id() as usize].location = Location {
+                archetype: archetype_id,
+                index,
+            };
+        }
+        self.spawned_rows(archetype_id, base..base + handles.len() as u32);
+
+        if self.hierarchy.is_empty() {
+            return;
         }
+        for (entity, _) in replaced {
+            for child in self.hierarchy.forget(entity) {
+                // Children replaced by this batch were cleaned up along with their parent
+                if !handles.contains(&child) {
+                    let _ = self.despawn_inner(child, None);
+                }
+            }
+        }
+    }
+
+    /// Spawn the entities staged in each of `stagings`, returning their handles in the order they
+    /// were pushed
+    ///
+    /// A parallel complement to `spawn_batch`: threads generating entities can each fill a
+    /// [`SpawnStaging`] independently, leaving only this sync point to run serially. Staged
+    /// component data is moved into the world one archetype at a time rather than entity by
+    /// entity, and archetypes new to the world are adopted without copying.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let stagings = (0..4)
+    ///     .map(|chunk| {
+    ///         std::thread::spawn(move || {
+    ///             let mut staging = SpawnStaging::new();
+    ///             staging.extend((0..100).map(|i| (chunk * 100 + i, true)));
+    ///             staging
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>()
+    ///     .into_iter()
+    ///     .map(|thread| thread.join().unwrap());
+    /// let entities = world.par_extend(stagings);
+    /// assert_eq!(entities.len(), 400);
+    /// assert_eq!(*world.get::<i32>(entities[123]).unwrap(), 123);
+    /// ```
+    pub fn par_extend<I>(&mut self, stagings: I) -> Vec<Entity>
+    where
+        I: IntoIterator<Item = SpawnStaging>,
+    {
+        self.flush();
+
+        let mut entities = Vec::new();
+        for staging in stagings {
+            let SpawnStaging {
+                archetypes, order, ..
+            } = staging;
+            // World archetype ID and next index of the entities from each staged archetype
+            let mut cursors = archetypes
+                .into_iter()
+                .map(|archetype| self.insert_archetype(archetype))
+                .collect::<Vec<_>>();
+            let bases = cursors.iter().map(|&(_, base)| base).collect::<Vec<_>>();
+            entities.reserve(order.len());
+            for staged in order {
+                let (archetype, index) = &mut cursors[staged as usize];
+                let entity = self.entities.alloc();
+                self.entities.meta[entity.id as usize].location = Location {
+                    archetype: *archetype,
+                    index: *index,
+                };
+                self.archetypes[*archetype as usize].set_entity_id(*index as usize, entity.id);
+                *index += 1;
+                entities.push(entity);
+            }
+            for ((archetype, end), base) in cursors.into_iter().zip(bases) {
+                self.spawned_rows(archetype, base..end);
+            }
+        }
+        entities
     }
 
     /// Returns archetype ID and starting location index
@@ -279,8 +612,15 @@ impl World {
             Entry::Vacant(x) => {
                 // Brand new archetype
                 let id = self.archetypes.len() as u32;
+                let mut archetype = archetype;
+                archetype.configure(&self.config);
                 self.archetypes.push(archetype);
                 x.insert(id);
+                self.type_indices
+                    .index_archetype(self.archetypes.last_mut().unwrap());
+                self.archetype_generation += 1;
+                self.hooks.archetype_created(&self.archetypes);
+                self.groups.archetype_created(&self.archetypes);
                 (id, 0)
             }
         }
@@ -296,131 +636,1680 @@ impl World {
     /// explicitly by calling `flush`.
     ///
     /// Useful for reserving an ID that will later have components attached to it with `insert`.
-    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator {
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator<'_> {
         self.entities.reserve_entities(count)
     }
 
     /// Allocate an entity ID concurrently
     ///
-    /// See `reserve_entities`.
+    /// Lock-free, so systems running in parallel over a shared `&World` can create entities. The
+    /// entity is materialized without components by the next `flush`, or any other operation
+    /// requiring `&mut World`. See `reserve_entities`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let reserved = std::thread::scope(|s| {
+    ///     let world = &world;
+    ///     let threads = (0..4)
+    ///         .map(|_| s.spawn(move || world.reserve_entity()))
+    ///         .collect::<Vec<_>>();
+    ///     threads.into_iter().map(|x| x.join().unwrap()).collect::<Vec<_>>()
+    /// });
+    /// world.flush();
+    /// assert_eq!(world.len(), 4);
+    /// for entity in reserved {
+    ///     world.insert_one(entity, "materialized").unwrap();
+    /// }
+    /// ```
     pub fn reserve_entity(&self) -> Entity {
         self.entities.reserve_entity()
     }
 
+    /// Queue an entity built off-thread to be spawned by the next `apply_staged`, returning its
+    /// handle
+    ///
+    /// Unlike `spawn`, only needs `&self`, so e.g. asynchronous chunk loading tasks can hand their
+    /// entities to a shared world directly instead of funnelling them through a channel to the
+    /// thread that owns it. Like those of `reserve_entity`, the returned handle can be stored in
+    /// other components right away, and any operation taking `&mut self` makes it a real, but
+    /// empty, entity. `apply_staged` then gives it the components of `builder`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let staged = std::thread::scope(|s| {
+    ///     let world = &world;
+    ///     let tasks = (0..4)
+    ///         .map(|chunk| {
+    ///             s.spawn(move || {
+    ///                 let mut builder = EntityBuilder::new();
+    ///                 builder.add(chunk).add("tree");
+    ///                 world.stage(builder)
+    ///             })
+    ///         })
+    ///         .collect::<Vec<_>>();
+    ///     tasks.into_iter().map(|x| x.join().unwrap()).collect::<Vec<_>>()
+    /// });
+    /// assert_eq!(world.apply_staged(), 4);
+    /// assert_eq!(*world.get::<i32>(staged[2]).unwrap(), 2);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn stage(&self, builder: EntityBuilder) -> Entity {
+        let entity = self.entities.reserve_entity();
+        self.staged
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push((entity, builder));
+        entity
+    }
+
+    /// Spawn every entity queued by `stage`, in the order they were staged, returning how many
+    ///
+    /// Call at a point where the world isn't otherwise in use, e.g. once per tick. Entities
+    /// despawned through their handles before being applied are skipped, discarding their
+    /// components. Insertion hooks run as for `insert`.
+    #[cfg(feature = "std")]
+    #[doc(alias = "flush_deferred")]
+    pub fn apply_staged(&mut self) -> usize {
+        let staged = self
+            .staged
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut staged = mem::take(staged);
+        let mut count = 0;
+        for (entity, mut builder) in staged.drain(..) {
+            if self.insert(entity, builder.build()).is_ok() {
+                count += 1;
+            }
+        }
+        // Keep the allocation for next time
+        *self
+            .staged
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = staged;
+        count
+    }
+
+    /// Borrow the world such that entities spawned through the returned [`Scope`] are despawned
+    /// when it is dropped
+    ///
+    /// Useful for temporary entities, such as simulation scaffolding in tests, preview entities
+    /// in tools, or an explosion's short-lived debris. Since cleanup happens in `Drop`, early
+    /// returns and `?` can't skip it. Individual entities can be kept alive past the end of the
+    /// scope with [`Scope::promote`]. Entities outliving the borrow can be cleaned up in bulk with
+    /// [`despawn_batch`](Self::despawn_batch).
+    #[doc(alias = "spawn_scoped")]
+    pub fn scope(&mut self) -> Scope<'_> {
+        Scope::new(self)
+    }
+
     /// Destroy an entity and all its components
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.despawn_inner(entity, None)
+    }
+
+    /// Destroy `entity` and its descendants, recording `reason` for each
+    fn despawn_inner(
+        &mut self,
+        entity: Entity,
+        reason: Option<DespawnReason>,
+    ) -> Result<(), NoSuchEntity> {
         self.flush();
         let loc = self.entities.free(entity)?;
-        if let Some(moved) = unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) } {
-            self.entities.meta[moved as usize].location.index = loc.index;
-        }
+        self.vacate(entity, loc, reason);
+        self.note_vacated(loc.archetype);
+        self.despawn_children(entity, reason);
         Ok(())
     }
 
-    /// Ensure `additional` entities with exact components `T` can be spawned without reallocating
-    pub fn reserve<T: Bundle>(&mut self, additional: u32) {
-        self.reserve_inner::<T>(additional);
+    /// Run despawn hooks for `entity`, which was just freed from `loc`, forget it everywhere but
+    /// the hierarchy, and drop its components
+    fn vacate(&mut self, entity: Entity, loc: Location, reason: Option<DespawnReason>) {
+        self.hooks.despawned(
+            entity,
+            &self.archetypes[loc.archetype as usize],
+            loc.index,
+            reason,
+        );
+        if let Some(reason) = reason {
+            self.despawns.push((entity, reason));
+        }
+        self.forget_side_tables(entity);
+        if let Some(moved) = unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) } {
+            self.entities.meta[moved as usize].location.index = loc.index;
+        }
     }
 
-    fn reserve_inner<T: Bundle>(&mut self, additional: u32) -> u32 {
-        self.flush();
-        self.entities.reserve(additional);
-
-        let archetype_id = T::with_static_ids(|ids| {
-            self.index.get(ids).copied().unwrap_or_else(|| {
-                let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(T::static_type_info()));
-                self.index.insert(ids.into(), x);
-                self.archetype_generation += 1;
-                x
-            })
-        });
-
-        self.archetypes[archetype_id as usize].reserve(additional);
-        archetype_id
+    /// Forget `entity`, which was just freed, in every table keyed by entity ID
+    fn forget_side_tables(&mut self, entity: Entity) {
+        self.names.forget(entity.id);
+        self.dense_indices.forget(entity.id);
+        self.sparse.forget(entity.id);
+        #[cfg(feature = "debug-history")]
+        self.history.forget(entity.id);
     }
 
-    /// Despawn all entities
-    ///
-    /// Preserves allocated storage for reuse.
-    pub fn clear(&mut self) {
-        for x in &mut self.archetypes {
-            x.clear();
+    /// Despawn the children of `entity`, which was just despawned for `reason`, and their
+    /// descendants
+    fn despawn_children(&mut self, entity: Entity, reason: Option<DespawnReason>) {
+        if self.hierarchy.is_empty() {
+            return;
+        }
+        for child in self.hierarchy.forget(entity) {
+            // Fails only for children despawned in the same batch as their parent
+            let _ = self.despawn_inner(child, reason);
         }
-        self.entities.clear();
-    }
-
-    /// Whether `entity` still exists
-    pub fn contains(&self, entity: Entity) -> bool {
-        self.entities.contains(entity)
     }
 
-    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
-    /// checking
-    ///
-    /// Prefer `query_mut` when concurrent access to the `World` is not required.
-    ///
-    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
-    /// type. A query type is `&T`, `&mut T`, a tuple of query types, or an `Option` wrapping a
-    /// query type, where `T` is any component type. Components queried with `&mut` must only appear
-    /// once. Entities which do not have a component type referenced outside of an `Option` will be
-    /// skipped.
-    ///
-    /// Entities are yielded in arbitrary order.
-    ///
-    /// The returned `QueryBorrow` can be further transformed with combinator methods; see its
-    /// documentation for details.
-    ///
-    /// Iterating a query will panic if it would violate an existing unique reference or construct
-    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
-    /// the same entity. Simultaneous queries can access the same component type if and only if the
-    /// world contains no entities that have all components required by both queries, assuming no
-    /// other component borrows are outstanding.
+    /// Despawn an entity, returning its components
     ///
-    /// Iterating a query yields references with lifetimes bound to the `QueryBorrow` returned
-    /// here. To ensure those are invalidated, the return value of this method must be dropped for
-    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
-    /// references obtained from a query cannot outlive the `QueryBorrow`.
+    /// Useful for stashing entities away, e.g. when they wander into an unloaded chunk. The result
+    /// can be spawned into this or another world later, restoring the same components under a new
+    /// handle. Only components stored in archetypes are taken: sparse components are dropped, the
+    /// entity's name and dense index are forgotten, and its children are despawned.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<(&i32, &bool)>()
-    ///     .iter()
-    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities.len(), 2);
-    /// assert!(entities.contains(&(a, 123, true)));
-    /// assert!(entities.contains(&(b, 456, false)));
+    /// let a = world.spawn((123, "abc"));
+    /// let taken = world.take(a).unwrap();
+    /// assert!(!world.contains(a));
+    /// assert_eq!(taken.get::<i32>(), Some(&123));
+    ///
+    /// let mut other = World::new();
+    /// let b = other.spawn(taken);
+    /// assert_eq!(*other.get::<&str>(b).unwrap(), "abc");
     /// ```
-    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
-        QueryBorrow::new(&self.entities.meta, &self.archetypes)
+    pub fn take(&mut self, entity: Entity) -> Result<TakenEntity, NoSuchEntity> {
+        self.flush();
+        let loc = self.entities.free(entity)?;
+        self.hooks.despawned(
+            entity,
+            &self.archetypes[loc.archetype as usize],
+            loc.index,
+            None,
+        );
+        self.forget_side_tables(entity);
+        let mut builder = EntityBuilder::new();
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        let types = archetype.types().to_vec();
+        unsafe {
+            let moved = archetype.move_to(loc.index, |ptr, ty, _, _, _, _| {
+                let ty = *types.iter().find(|x| x.id() == ty).unwrap();
+                builder.add_bundle(RawComponent { ty, ptr });
+            });
+            if let Some(moved) = moved {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+        }
+        self.note_vacated(loc.archetype);
+        self.despawn_children(entity, None);
+        Ok(TakenEntity::new(builder))
     }
 
-    /// Query a uniquely borrowed world
+    /// Create a new entity with clones of all of `entity`'s components
     ///
-    /// Like `query`, but faster because dynamic borrow checks can be skipped. Note that, unlike
-    /// `query`, this returns an `IntoIterator` which can be passed directly to a `for` loop.
-    pub fn query_mut<Q: Query>(&mut self) -> QueryMut<'_, Q> {
-        QueryMut::new(&self.entities.meta, &mut self.archetypes)
+    /// Fails if any component isn't registered as cloneable in `registry`, in which case nothing is
+    /// spawned. To stamp out many copies of a template, see [`Prefab`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut registry = ComponentRegistry::new();
+    /// register_component!(registry, i32, clone);
+    /// register_component!(registry, String, clone);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, String::from("zombie")));
+    /// let b = world.clone_entity(a, &registry).unwrap();
+    /// assert_eq!(*world.get::<String>(b).unwrap(), "zombie");
+    ///
+    /// let c = world.spawn((true,));
+    /// assert!(world.clone_entity(c, &registry).is_err());
+    /// ```
+    pub fn clone_entity(
+        &mut self,
+        entity: Entity,
+        registry: &ComponentRegistry,
+    ) -> Result<Entity, CloneError> {
+        let mut builder = EntityBuilder::new();
+        prefab::clone_entity(self, entity, registry, &mut builder)?;
+        Ok(self.spawn(builder.build()))
     }
 
-    /// Perform a dynamic query.
-    pub fn query_dynamic<'q>(&'q self, types: DynamicQueryTypes<'q>) -> DynamicQuery<'q> {
-        DynamicQuery::new(types, &self.archetypes, &self.entities.meta)
+    /// Create a new entity with clones of `prefab`'s components
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) -> Entity {
+        let mut builder = EntityBuilder::new();
+        self.spawn(prefab.clone_into(&mut builder).build())
     }
 
-    /// Prepare a query against a single entity, using dynamic borrow checking
-    ///
-    /// Prefer `query_one_mut` when concurrent access to the `World` is not required.
+    /// Move an entity and all its components into `target`, returning its handle there
     ///
-    /// Call `get` on the resulting `QueryOne` to actually execute the query. The `QueryOne` value
-    /// is responsible for releasing the dynamically-checked borrow made by `get`, so it can't be
-    /// dropped while references returned by `get` are live.
+    /// The entity is despawned from this world. Unlike `take` followed by `spawn`, components are
+    /// copied directly between the two worlds' storage, and no knowledge of their types is needed,
+    /// e.g. when a player crosses into a region managed by another world. Hooks and indexes run as
+    /// for a despawn from this world and a spawn into `target`. Sparse components and user data
+    /// come along, while the entity's name, dense index, and parent/child relations are forgotten.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut a = World::new();
+    /// let mut b = World::new();
+    /// let player = a.spawn(("steve", 20u32));
+    /// let moved = a.migrate(player, &mut b).unwrap();
+    /// assert!(!a.contains(player));
+    /// assert_eq!(*b.get::<&str>(moved).unwrap(), "steve");
+    /// assert_eq!(*b.get::<u32>(moved).unwrap(), 20);
+    /// ```
+    pub fn migrate(&mut self, entity: Entity, target: &mut World) -> Result<Entity, NoSuchEntity> {
+        self.flush();
+        target.flush();
+        let user_data = self.user_data(entity)?;
+        let loc = self.entities.free(entity)?;
+        self.hooks.despawned(
+            entity,
+            &self.archetypes[loc.archetype as usize],
+            loc.index,
+            None,
+        );
+        let migrated = target.entities.alloc();
+        target.entities.meta[migrated.id as usize].user_data = user_data;
+        self.sparse.migrate(entity.id, &mut target.sparse, migrated);
+        self.forget_side_tables(entity);
+        let source = &mut self.archetypes[loc.archetype as usize];
+        let archetype_id = target.archetype_for_types(source.types().to_vec());
+        let dst = &mut target.archetypes[archetype_id as usize];
+        let index = unsafe {
+            let index = dst.allocate(migrated.id);
+            let moved = source.move_to(loc.index, |ptr, ty, size, _, _, _| {
+                dst.put_dynamic(ptr, ty, size, index);
+            });
+            if let Some(moved) = moved {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+            target.entities.meta[migrated.id as usize].location = Location {
+                archetype: archetype_id,
+                index,
+            };
+            index
+        };
+        target
+            .hooks
+            .spawned(migrated, &target.archetypes[archetype_id as usize], index);
+        self.note_vacated(loc.archetype);
+        self.hierarchy.forget(entity);
+        Ok(migrated)
+    }
+
+    /// Move every entity in `other` into this world, returning a map from their handles in `other`
+    /// to their handles here
+    ///
+    /// Useful for building entities away from the main world, e.g. while loading a chunk on a
+    /// worker thread, then merging them in one step. Component data is moved an archetype at a
+    /// time: archetypes new to this world are adopted without copying, and the rest are appended
+    /// to their counterparts here. Parent/child relations, names, sparse components, and user data
+    /// come along, though an absorbed name replaces that of any entity here that had it. Dense
+    /// indices don't, since they index the other world's arrays; absorbed entities are given new
+    /// ones on demand. Insertion hooks and indexes registered with this world see each absorbed
+    /// entity as spawned, as for `spawn`. `other` is left empty, but keeps everything besides its
+    /// entities, such as resources.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let player = world.spawn(("steve", 20u32));
+    ///
+    /// let mut chunk = World::new();
+    /// let tree = chunk.spawn(("oak", 5u32));
+    /// let sapling = chunk.spawn((1.5f32,));
+    /// chunk.attach(sapling, tree).unwrap();
+    ///
+    /// let map = world.absorb(&mut chunk);
+    /// assert!(chunk.is_empty());
+    /// let tree = map.get(tree).unwrap();
+    /// let sapling = map.get(sapling).unwrap();
+    /// assert_eq!(*world.get::<&str>(tree).unwrap(), "oak");
+    /// assert_eq!(world.parent(sapling), Some(tree));
+    /// assert!(world.contains(player));
+    /// ```
+    pub fn absorb(&mut self, other: &mut World) -> EntityMap {
+        self.flush();
+        other.flush();
+        let mut map = EntityMap::with_capacity(other.entities.meta.len());
+        let mut new_ids = Vec::new();
+        for archetype in &mut other.archetypes {
+            if archetype.is_empty() {
+                continue;
+            }
+            // Ticks count from when `other` was created, so mean nothing here
+            archetype.reset_ticks();
+            new_ids.clear();
+            for &id in archetype.ids() {
+                let meta = &other.entities.meta[id as usize];
+                let new = self.entities.alloc();
+                self.entities.meta[new.id as usize].user_data = meta.user_data;
+                map.insert(
+                    Entity {
+                        id,
+                        generation: meta.generation,
+                    },
+                    new,
+                );
+                new_ids.push(new.id);
+            }
+            let empty = Archetype::with_config(archetype.types().to_vec(), &other.config);
+            let (archetype_id, base) = self.insert_archetype(mem::replace(archetype, empty));
+            let archetype = &mut self.archetypes[archetype_id as usize];
+            for (&id, index) in new_ids.iter().zip(base..) {
+                self.entities.meta[id as usize].location = Location {
+                    archetype: archetype_id,
+                    index,
+                };
+                archetype.set_entity_id(index as usize, id);
+            }
+            self.spawned_rows(archetype_id, base..base + new_ids.len() as u32);
+        }
+        for (parent, children) in other.hierarchy.iter() {
+            for &child in children {
+                self.hierarchy
+                    .attach(map.get(child).unwrap(), map.get(parent).unwrap());
+            }
+        }
+        for (id, name) in other.names.iter() {
+            let old = Entity {
+                id,
+                generation: other.entities.meta[id as usize].generation,
+            };
+            self.names.set(map.get(old).unwrap().id, name.clone());
+        }
+        self.sparse.absorb(&mut other.sparse, &map);
+        other.clear();
+        map
+    }
+
+    /// Run insertion hooks for the entities at `rows` in archetype `archetype_id`, which were just
+    /// spawned
+    fn spawned_rows(&mut self, archetype_id: u32, rows: Range<u32>) {
+        if !self.hooks.watches_inserts() {
+            return;
+        }
+        let archetype = &self.archetypes[archetype_id as usize];
+        for index in rows {
+            let id = archetype.entity_id(index);
+            let entity = Entity {
+                id,
+                generation: self.entities.meta[id as usize].generation,
+            };
+            self.hooks.spawned(entity, archetype, index);
+        }
+    }
+
+    /// Destroy many entities and all their components, returning the number that existed
+    ///
+    /// Faster than calling `despawn` for each entity when many share an archetype, such as when
+    /// unloading a chunk: each archetype is compacted from the back, so that as few remaining
+    /// entities as possible are moved to fill the gaps. Entities that don't exist are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let wave = (0..10).map(|i| world.spawn((i, true))).collect::<Vec<_>>();
+    /// let survivor = world.spawn((10, true));
+    /// assert_eq!(world.despawn_batch(&wave), 10);
+    /// assert_eq!(world.despawn_batch(&wave), 0);
+    /// assert_eq!(world.len(), 1);
+    /// assert!(world.contains(survivor));
+    /// ```
+    pub fn despawn_batch(&mut self, entities: &[Entity]) -> usize {
+        self.flush();
+        let mut locations = Vec::with_capacity(entities.len());
+        for &entity in entities {
+            if let Ok(loc) = self.entities.free(entity) {
+                self.hooks.despawned(
+                    entity,
+                    &self.archetypes[loc.archetype as usize],
+                    loc.index,
+                    None,
+                );
+                self.forget_side_tables(entity);
+                locations.push(loc);
+            }
+        }
+        // Removing the highest indices first ensures that entities moved to fill gaps are never
+        // themselves due for removal
+        locations.sort_unstable_by_key(|loc| (loc.archetype, core::cmp::Reverse(loc.index)));
+        for (i, loc) in locations.iter().enumerate() {
+            let archetype = &mut self.archetypes[loc.archetype as usize];
+            if let Some(moved) = unsafe { archetype.remove(loc.index) } {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+            if locations
+                .get(i + 1)
+                .map_or(true, |next| next.archetype != loc.archetype)
+            {
+                self.note_vacated(loc.archetype);
+            }
+        }
+        for &entity in entities {
+            self.despawn_children(entity, None);
+        }
+        locations.len()
+    }
+
+    /// Destroy every entity matched by `Q` and all their components, returning how many there were
+    ///
+    /// Much faster than collecting the matching entities and despawning each: archetypes matched
+    /// in their entirety are emptied in one pass, without moving any entities to fill gaps. Only
+    /// queries that filter individual entities, such as `Changed`, fall back to `despawn_batch`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Projectile;
+    ///
+    /// let mut world = World::new();
+    /// for i in 0..10 {
+    ///     world.spawn((Projectile, i));
+    ///     world.spawn((Projectile, "arrow"));
+    /// }
+    /// let player = world.spawn(("steve", 20));
+    /// assert_eq!(world.despawn_all::<&Projectile>(), 20);
+    /// assert_eq!(world.len(), 1);
+    /// assert!(world.contains(player));
+    /// ```
+    pub fn despawn_all<Q: Query>(&mut self) -> usize {
+        self.flush();
+        let mut count = 0;
+        let mut vacated = Vec::new();
+        let mut filtered = Vec::new();
+        let mut despawned = Vec::new();
+        let mask = self.query_mask::<Q>();
+        for archetype_id in 0..self.archetypes.len() {
+            let archetype = &self.archetypes[archetype_id];
+            if archetype.is_empty()
+                || !mask.admits(archetype)
+                || Q::Fetch::access(archetype).is_none()
+            {
+                continue;
+            }
+            if Q::Fetch::FILTERS {
+                if let Some(fetch) = Q::Fetch::new(archetype) {
+                    for (index, &id) in archetype.ids().iter().enumerate() {
+                        if unsafe { !fetch.skip(index) } {
+                            filtered.push(Entity {
+                                id,
+                                generation: self.entities.meta[id as usize].generation,
+                            });
+                        }
+                    }
+                }
+                continue;
+            }
+            for index in 0..archetype.len() {
+                let archetype = &self.archetypes[archetype_id];
+                let id = archetype.entity_id(index);
+                let entity = Entity {
+                    id,
+                    generation: self.entities.meta[id as usize].generation,
+                };
+                self.hooks.despawned(entity, archetype, index, None);
+                self.entities.free(entity).unwrap();
+                if !self.hierarchy.is_empty() {
+                    despawned.push(entity);
+                }
+                self.forget_side_tables(entity);
+            }
+            let archetype = &mut self.archetypes[archetype_id];
+            count += archetype.len() as usize;
+            archetype.clear();
+            vacated.push(archetype_id as u32);
+        }
+        for archetype in vacated {
+            self.note_vacated(archetype);
+        }
+        for entity in despawned {
+            self.despawn_children(entity, None);
+        }
+        count + self.despawn_batch(&filtered)
+    }
+
+    /// Destroy an entity and all its components, recording why for [`drain_despawns`] and
+    /// [`on_despawn`] hooks
+    ///
+    /// Lets listeners distinguish e.g. entities unloaded with their chunk from those that died,
+    /// without tagging them with marker components first. Descendants despawned along with the
+    /// entity (see [`attach`](Self::attach)) are recorded with the same reason, after
+    /// their parent.
+    ///
+    /// [`drain_despawns`]: Self::drain_despawns
+    /// [`on_despawn`]: Self::on_despawn
+    pub fn despawn_with_reason(
+        &mut self,
+        entity: Entity,
+        reason: DespawnReason,
+    ) -> Result<(), NoSuchEntity> {
+        self.despawn_inner(entity, Some(reason))
+    }
+
+    /// Iterate over the entities despawned by `despawn_with_reason` since the last call, in the
+    /// order they were despawned
+    ///
+    /// Entities despawned without a reason are not recorded. The log grows until drained.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let b = world.spawn((456,));
+    /// let c = world.spawn((789,));
+    /// world.despawn_with_reason(a, DespawnReason::Killed).unwrap();
+    /// world.despawn(b).unwrap();
+    /// world.despawn_with_reason(c, DespawnReason::Custom(7)).unwrap();
+    /// assert_eq!(
+    ///     world.drain_despawns().collect::<Vec<_>>(),
+    ///     [(a, DespawnReason::Killed), (c, DespawnReason::Custom(7))]
+    /// );
+    /// assert_eq!(world.drain_despawns().count(), 0);
+    /// ```
+    pub fn drain_despawns(
+        &mut self,
+    ) -> impl ExactSizeIterator<Item = (Entity, DespawnReason)> + '_ {
+        self.despawns.drain(..)
+    }
+
+    /// Capture clones of every entity and component, to be reinstated later by `restore`
+    ///
+    /// Useful for rollback networking, where the simulation is checkpointed every tick and rewound
+    /// when late input arrives. Every component type present must be registered in `registry` as
+    /// cloneable, or better, copyable: columns of copyable components are captured with a single
+    /// bulk copy each, and the same goes for sparse components. Parent/child relations are captured
+    /// too, but resources and events aren't.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut registry = ComponentRegistry::new();
+    /// register_component!(registry, i32, copy);
+    /// register_component!(registry, String, clone);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, String::from("steve")));
+    /// let snapshot = world.snapshot(&registry).unwrap();
+    /// *world.get_mut::<i32>(a).unwrap() = 2;
+    /// let b = world.spawn((3,));
+    /// world.despawn(a).unwrap();
+    ///
+    /// world.restore(&snapshot);
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    /// assert_eq!(*world.get::<String>(a).unwrap(), "steve");
+    /// assert!(!world.contains(b));
+    ///
+    /// world.spawn((true,));
+    /// assert!(world.snapshot(&registry).is_err());
+    /// ```
+    pub fn snapshot(&mut self, registry: &ComponentRegistry) -> Result<WorldSnapshot, CloneError> {
+        self.flush();
+        let archetypes = self
+            .archetypes
+            .iter()
+            // Safety: the world is uniquely borrowed, so no components are
+            .map(|x| unsafe { ArchetypeSnapshot::new(x, registry) })
+            .collect::<Result<_, _>>()?;
+        let sparse = self.sparse.snapshot(registry)?;
+        Ok(WorldSnapshot {
+            world_id: self.id,
+            entities: self.entities.clone(),
+            hierarchy: self.hierarchy.clone(),
+            names: self.names.clone(),
+            dense_indices: self.dense_indices.clone(),
+            sparse,
+            archetypes,
+        })
+    }
+
+    /// Reinstate the entities and components captured by `snapshot`, discarding all others
+    ///
+    /// Entities regain the handles they had when the snapshot was taken, and entities spawned
+    /// since are forgotten. Handles to forgotten entities must be discarded, since the same
+    /// handles are issued again to entities spawned after the restore. Restored components are
+    /// flagged as added and changed. Hooks aren't run, so secondary indexes must be rebuilt by
+    /// the caller if needed. Storage is reused where possible, so restoring a snapshot of a world
+    /// that hasn't grown since doesn't allocate beyond what cloning components requires.
+    ///
+    /// # Panics
+    /// - if `snapshot` was taken of a different world
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        assert_eq!(
+            snapshot.world_id, self.id,
+            "snapshot was taken of a different world"
+        );
+        self.flush();
+
+        /// Leaves the world empty, rather than inconsistent, if a component's clone panics
+        struct ClearOnUnwind<'a>(&'a mut World);
+        impl Drop for ClearOnUnwind<'_> {
+            fn drop(&mut self) {
+                for archetype in &mut self.0.archetypes {
+                    archetype.clear();
+                }
+                self.0.entities.clear();
+                self.0.hierarchy.clear();
+                self.0.names.clear();
+                self.0.dense_indices.clear();
+                self.0.sparse.clear();
+            }
+        }
+
+        let guard = ClearOnUnwind(self);
+        for (index, archetype) in guard.0.archetypes.iter_mut().enumerate() {
+            archetype.clear();
+            if let Some(x) = snapshot.archetypes.get(index) {
+                // Safety: archetypes are never removed, so the snapshot of the archetype at the
+                // same index has the same component types
+                unsafe {
+                    x.restore(archetype);
+                }
+            }
+        }
+        mem::forget(guard);
+        self.entities.clone_from(&snapshot.entities);
+        self.hierarchy.clone_from(&snapshot.hierarchy);
+        self.names.clone_from(&snapshot.names);
+        self.dense_indices.clone_from(&snapshot.dense_indices);
+        self.sparse.restore(&snapshot.sparse);
+        #[cfg(feature = "debug-history")]
+        self.history.clear();
+    }
+
+    /// List the entities spawned and despawned, and the components changed and removed, since
+    /// `baseline` was taken
+    ///
+    /// Components count as changed if they were added since the baseline or are flagged as
+    /// changed (see `is_changed`), so change flags should be cleared whenever a baseline is
+    /// taken. Entities reserved but not yet flushed are ignored.
+    ///
+    /// # Panics
+    /// - if `baseline` was taken of a different world
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut registry = ComponentRegistry::new();
+    /// registry.register_copy::<i32>("i32");
+    /// registry.register_copy::<bool>("bool");
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let baseline = world.snapshot(&registry).unwrap();
+    /// world.clear_changed();
+    ///
+    /// *world.get_mut::<i32>(a).unwrap() = 3;
+    /// world.remove_one::<bool>(a).unwrap();
+    /// world.despawn(b).unwrap();
+    /// let c = world.spawn((4,));
+    ///
+    /// let diff = world.diff(&baseline);
+    /// assert_eq!(diff.spawned(), [c]);
+    /// assert_eq!(diff.despawned(), [b]);
+    /// assert_eq!(diff.changed(), [(a, std::any::TypeId::of::<i32>())]);
+    /// assert_eq!(diff.removed(), [(a, std::any::TypeId::of::<bool>())]);
+    /// ```
+    pub fn diff(&self, baseline: &WorldSnapshot) -> WorldDiff {
+        assert_eq!(
+            baseline.world_id, self.id,
+            "snapshot was taken of a different world"
+        );
+        WorldDiff::new(baseline, &self.entities.meta, &self.archetypes)
+    }
+
+    /// Make `child` a child of `parent`, detaching it from any previous parent
+    ///
+    /// Children are despawned along with their parent, recursively, by every method that
+    /// despawns entities, such as `despawn`, `despawn_batch`, and `clear`. Relations are otherwise
+    /// invisible to queries, and aren't carried over by `migrate`.
+    ///
+    /// # Panics
+    /// - if `parent` is `child` or one of its descendants
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let boat = world.spawn(("boat",));
+    /// let steve = world.spawn(("steve",));
+    /// let sword = world.spawn(("sword",));
+    /// world.attach(steve, boat).unwrap();
+    /// world.attach(sword, steve).unwrap();
+    /// assert_eq!(world.children(boat).collect::<Vec<_>>(), [steve]);
+    /// assert_eq!(world.parent(sword), Some(steve));
+    ///
+    /// world.despawn(boat).unwrap();
+    /// assert!(!world.contains(steve));
+    /// assert!(!world.contains(sword));
+    /// ```
+    pub fn attach(&mut self, child: Entity, parent: Entity) -> Result<(), NoSuchEntity> {
+        if !self.contains(child) || !self.contains(parent) {
+            return Err(NoSuchEntity);
+        }
+        self.hierarchy.attach(child, parent);
+        Ok(())
+    }
+
+    /// Detach `child` from its parent, returning the former parent if it had one
+    ///
+    /// The child and its own descendants will no longer be despawned along with the former parent.
+    pub fn detach(&mut self, child: Entity) -> Option<Entity> {
+        self.hierarchy.detach(child)
+    }
+
+    /// The entity `child` is attached to, if any
+    pub fn parent(&self, child: Entity) -> Option<Entity> {
+        self.hierarchy.parent(child)
+    }
+
+    /// Iterate over the entities attached to `parent`, in the order they were attached
+    pub fn children(&self, parent: Entity) -> impl ExactSizeIterator<Item = Entity> + '_ {
+        self.hierarchy.children(parent).iter().copied()
+    }
+
+    /// Name `entity`, returning the name it replaced, if any
+    ///
+    /// Names are unique: if another entity already has `name`, it loses it. Names are forgotten
+    /// when their entity is despawned, so [`entity_by_name`](Self::entity_by_name) never finds a
+    /// dead entity.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// world.set_name(a, "player").unwrap();
+    /// assert_eq!(world.entity_by_name("player"), Some(a));
+    /// assert_eq!(world.name(a), Some("player"));
+    /// assert_eq!(format!("{:?}", world.describe(a)), format!("{:?} \"player\"", a));
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(world.entity_by_name("player"), None);
+    /// ```
+    pub fn set_name(
+        &mut self,
+        entity: Entity,
+        name: impl Into<Cow<'static, str>>,
+    ) -> Result<Option<Cow<'static, str>>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        let old = self.names.forget(entity.id);
+        self.names.set(entity.id, name.into());
+        Ok(old)
+    }
+
+    /// Remove `entity`'s name, returning it
+    pub fn clear_name(&mut self, entity: Entity) -> Option<Cow<'static, str>> {
+        if !self.contains(entity) {
+            return None;
+        }
+        self.names.forget(entity.id)
+    }
+
+    /// The name given to `entity` with [`set_name`](Self::set_name), if any
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        if !self.contains(entity) {
+            return None;
+        }
+        self.names.get(entity.id)
+    }
+
+    /// The entity named `name`, if any
+    pub fn entity_by_name(&self, name: &str) -> Option<Entity> {
+        let id = self.names.find(name)?;
+        // Safety: names are forgotten on despawn, so `id` belongs to a live entity
+        Some(unsafe { self.entities.resolve_unknown_gen(id) })
+    }
+
+    /// Format `entity` for debugging, along with its name if it has one
+    pub fn describe(&self, entity: Entity) -> impl fmt::Debug + '_ {
+        struct Describe<'a>(Entity, Option<&'a str>);
+        impl fmt::Debug for Describe<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.1 {
+                    Some(name) => write!(f, "{:?} {:?}", self.0, name),
+                    None => write!(f, "{:?}", self.0),
+                }
+            }
+        }
+        Describe(entity, self.name(entity))
+    }
+
+    /// A small index for `entity` that stays the same for as long as it lives
+    ///
+    /// Unlike an entity's location, which changes as it moves between archetypes, or its ID, which
+    /// may be large, dense indices are handed out only to entities passed to this method and are
+    /// recycled when those entities are despawned or passed to
+    /// [`release_dense_index`](Self::release_dense_index). They therefore stay below
+    /// [`dense_index_bound`](Self::dense_index_bound), which is at most the peak number of entities
+    /// holding one at once, making them suitable for indexing flat arrays kept parallel to a subset
+    /// of entities, such as a renderer's instance buffers.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1.5f32,));
+    /// let b = world.spawn((2.5f32,));
+    /// let mut sizes = Vec::new();
+    /// for e in [a, b] {
+    ///     let index = world.dense_index(e).unwrap() as usize;
+    ///     sizes.resize(world.dense_index_bound() as usize, 0.0);
+    ///     sizes[index] = *world.get::<f32>(e).unwrap();
+    /// }
+    /// assert_eq!(sizes, [1.5, 2.5]);
+    ///
+    /// world.despawn(a).unwrap();
+    /// let c = world.spawn(());
+    /// assert_eq!(world.dense_index(c), Ok(0));
+    /// ```
+    pub fn dense_index(&mut self, entity: Entity) -> Result<u32, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.dense_indices.get_or_alloc(entity.id))
+    }
+
+    /// The dense index previously given to `entity` by `dense_index`, if any
+    pub fn get_dense_index(&self, entity: Entity) -> Option<u32> {
+        if !self.contains(entity) {
+            return None;
+        }
+        self.dense_indices.get(entity.id)
+    }
+
+    /// Free `entity`'s dense index for reuse by another entity, returning it
+    pub fn release_dense_index(&mut self, entity: Entity) -> Option<u32> {
+        if !self.contains(entity) {
+            return None;
+        }
+        self.dense_indices.forget(entity.id)
+    }
+
+    /// One past the largest dense index ever handed out, suitable for sizing arrays indexed by them
+    ///
+    /// Reset only by `clear`.
+    pub fn dense_index_bound(&self) -> u32 {
+        self.dense_indices.bound()
+    }
+
+    /// Store an arbitrary word alongside `entity`, such as a network ID or a set of flags
+    ///
+    /// The word lives in the entity's bookkeeping rather than in an archetype, so setting it never
+    /// moves the entity, and reading it with [`user_data`](Self::user_data) costs no more than
+    /// [`contains`](Self::contains). Every entity's word starts out as 0, and is reset to 0 when
+    /// the entity is despawned.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// assert_eq!(world.user_data(a), Ok(0));
+    /// world.set_user_data(a, 42).unwrap();
+    /// assert_eq!(world.user_data(a), Ok(42));
+    /// ```
+    pub fn set_user_data(&mut self, entity: Entity, data: u32) -> Result<(), NoSuchEntity> {
+        self.flush();
+        if !self.entities.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        self.entities.meta[entity.id as usize].user_data = data;
+        Ok(())
+    }
+
+    /// The word last stored alongside `entity` with `set_user_data`, or 0
+    pub fn user_data(&self, entity: Entity) -> Result<u32, NoSuchEntity> {
+        if !self.entities.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        // Reserved entities may not have metadata yet
+        Ok(self
+            .entities
+            .meta
+            .get(entity.id as usize)
+            .map_or(0, |x| x.user_data))
+    }
+
+    /// Invoke `f` whenever a `T` component is added to an entity or replaced
+    ///
+    /// Runs after the component is stored by `spawn`, `insert`, and the methods built on them,
+    /// such as `CommandBuffer::run_on`, as well as by every other way of spawning entities, from
+    /// `spawn_batch` and `spawn_column_batch` to `par_extend`, `absorb`, `migrate`, and
+    /// `join_entities_and_components`. Hooks can't access the world, so external indexes such as
+    /// a network view should be updated through state shared with the hook. Only `restore`, which
+    /// reinstates entities wholesale, doesn't run hooks.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Position(i32, i32);
+    ///
+    /// let moved = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let log = moved.clone();
+    /// world.on_insert(move |entity, pos: &Position| log.lock().unwrap().push((entity, pos.0)));
+    /// let a = world.spawn((Position(1, 2),));
+    /// world.insert_one(a, Position(3, 4)).unwrap();
+    /// world.spawn((true,));
+    /// assert_eq!(*moved.lock().unwrap(), [(a, 1), (a, 3)]);
+    /// ```
+    pub fn on_insert<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.hooks.on_insert(f);
+    }
+
+    /// Invoke `f` whenever a `T` component is removed from an entity, including when the entity is
+    /// despawned
+    ///
+    /// Runs before the component is removed by `remove`, `despawn`, `despawn_batch`,
+    /// `despawn_all`, `take`, `clear`, and the methods built on them, but not when it's replaced
+    /// by `insert`. See `on_insert`.
+    pub fn on_remove<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.hooks.on_remove(f);
+    }
+
+    /// Invoke `f` whenever an entity is despawned
+    ///
+    /// Runs after the entity's `on_remove` hooks, in the same circumstances. See `on_insert`.
+    pub fn on_despawn(&mut self, mut f: impl FnMut(Entity) + Send + Sync + 'static) {
+        self.hooks.on_despawn(move |entity, _| f(entity));
+    }
+
+    /// Invoke `f` whenever an entity is despawned, along with the reason passed to
+    /// [`despawn_with_reason`](Self::despawn_with_reason), if any
+    ///
+    /// Runs alongside `on_despawn` hooks. Unlike [`drain_despawns`](Self::drain_despawns), sees
+    /// entities despawned without a reason too, as `None`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let sink = log.clone();
+    /// world.on_despawn_with_reason(move |entity, reason| sink.lock().unwrap().push((entity, reason)));
+    /// let chunk = world.spawn(());
+    /// let tree = world.spawn(());
+    /// world.attach(tree, chunk).unwrap();
+    /// let player = world.spawn(());
+    /// world.despawn_with_reason(chunk, DespawnReason::Unloaded).unwrap();
+    /// world.despawn(player).unwrap();
+    /// assert_eq!(
+    ///     *log.lock().unwrap(),
+    ///     [
+    ///         (chunk, Some(DespawnReason::Unloaded)),
+    ///         (tree, Some(DespawnReason::Unloaded)),
+    ///         (player, None),
+    ///     ]
+    /// );
+    /// ```
+    pub fn on_despawn_with_reason(
+        &mut self,
+        f: impl FnMut(Entity, Option<DespawnReason>) + Send + Sync + 'static,
+    ) {
+        self.hooks.on_despawn(f);
+    }
+
+    /// Invoke `f` whenever a new archetype is created
+    ///
+    /// `f` is passed the archetype and its position in [`archetypes`](Self::archetypes), which
+    /// never changes as archetypes are never removed. Runs as soon as the archetype is created, so
+    /// external per-archetype state such as cached query matches can be kept in lockstep without
+    /// polling `archetypes_generation`. Archetypes are created on demand by any operation that may
+    /// give an entity a new combination of components, such as `spawn`, `insert`, and `remove`,
+    /// and are empty when `f` runs, except those adopted whole along with their entities by
+    /// `spawn_column_batch`, `par_extend`, and `absorb`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let created = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let log = created.clone();
+    /// world.on_archetype_created(move |index, archetype| {
+    ///     log.lock().unwrap().push((index, archetype.has::<bool>()))
+    /// });
+    /// let a = world.spawn((123,));
+    /// world.spawn((456,));
+    /// world.insert_one(a, true).unwrap();
+    /// assert_eq!(*created.lock().unwrap(), [(1, false), (2, true)]);
+    /// ```
+    pub fn on_archetype_created(
+        &mut self,
+        f: impl FnMut(usize, &Archetype) + Send + Sync + 'static,
+    ) {
+        self.hooks.on_archetype_created(f);
+    }
+
+    /// Keep `index` in sync with this world's `I::Source` components
+    ///
+    /// `index` is first populated with the existing components. Panics if an index of type `I` was
+    /// already added. See [`SecondaryIndex`](crate::SecondaryIndex).
+    ///
+    /// Indexes are notified along with `on_insert` and `on_remove` hooks, so every way of spawning
+    /// entities keeps them up to date. The one exception is `restore`, after which indexes must be
+    /// rebuilt by the caller.
+    pub fn add_index<I: SecondaryIndex>(&mut self, mut index: I) {
+        for archetype in &self.archetypes {
+            let column = match archetype.get::<I::Source>() {
+                Some(x) => x,
+                None => continue,
+            };
+            for (&id, source) in archetype.ids().iter().zip(column.iter()) {
+                let entity = Entity {
+                    id,
+                    generation: self.entities.meta[id as usize].generation,
+                };
+                index.insert(entity, source);
+            }
+        }
+        self.hooks.add_index(index);
+    }
+
+    /// Access the index of type `I` added by `add_index`, if any
+    pub fn index<I: SecondaryIndex>(&self) -> Option<&I> {
+        self.hooks.index::<I>()
+    }
+
+    /// Notify indexes of their source components that were modified in place
+    ///
+    /// Finds the modified components through their change flags, so should be called before
+    /// `clear_changed`, e.g. once per tick.
+    pub fn update_indexes(&mut self) {
+        self.hooks
+            .update_indexes(&self.entities.meta, &self.archetypes);
+    }
+
+    /// Record component types of the archetype `id` that are no longer held by any entity
+    fn note_vacated(&mut self, id: u32) {
+        let archetype = &self.archetypes[id as usize];
+        if !archetype.is_empty() {
+            return;
+        }
+        for ty in archetype.types() {
+            let id = ty.id();
+            if self.vanished.contains(&id)
+                || self
+                    .archetypes
+                    .iter()
+                    .any(|x| !x.is_empty() && x.has_dynamic(id))
+            {
+                continue;
+            }
+            self.vanished.push(id);
+        }
+    }
+
+    /// Iterate over the component types that have vanished from the world since the last call
+    ///
+    /// A component type vanishes when the last entity holding it is despawned, has it removed, or
+    /// is cleared. Lets systems tied to the presence of some component go dormant without counting
+    /// entities every tick. A type is reported once per call even if it vanished several times, and
+    /// may have reappeared since; check with `query` if that matters.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456,));
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(world.drain_vanished().collect::<Vec<_>>(), [TypeId::of::<bool>()]);
+    /// world.remove_one::<i32>(b).unwrap();
+    /// assert_eq!(world.drain_vanished().collect::<Vec<_>>(), [TypeId::of::<i32>()]);
+    /// assert_eq!(world.drain_vanished().count(), 0);
+    /// ```
+    pub fn drain_vanished(&mut self) -> impl ExactSizeIterator<Item = TypeId> + '_ {
+        self.vanished.drain(..)
+    }
+
+    /// Perform deferred housekeeping for up to about `budget`, returning whether all of it is done
+    ///
+    /// Currently releases memory held by archetypes that have shrunk far below their peak size.
+    /// Work is done one archetype at a time, resuming where the previous call left off, so calling
+    /// this once per tick with whatever time is left over keeps memory use in check without ever
+    /// causing a spike. At least one unit of work is done per call, regardless of `budget`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::time::Duration;
+    /// let mut world = World::new();
+    /// let mobs = (0..1000).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// world.despawn_batch(&mobs);
+    /// while !world.maintain(Duration::from_micros(100)) {}
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn maintain(&mut self, budget: core::time::Duration) -> bool {
+        let start = std::time::Instant::now();
+        self.maintain_while(|| start.elapsed() < budget)
+    }
+
+    /// Like `maintain`, but stops once `proceed` returns `false` rather than after a duration
+    ///
+    /// `proceed` is called after each unit of work. Useful where `std::time::Instant` isn't
+    /// available, or to bound the work done by something other than time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mobs = (0..1000).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// world.despawn_batch(&mobs);
+    /// let mut steps = 0;
+    /// while !world.maintain_while(|| {
+    ///     steps += 1;
+    ///     steps % 4 != 0
+    /// }) {}
+    /// ```
+    pub fn maintain_while(&mut self, mut proceed: impl FnMut() -> bool) -> bool {
+        loop {
+            if let Some(archetype) = self.archetypes.get_mut(self.maintenance_cursor as usize) {
+                archetype.shrink();
+                self.maintenance_cursor += 1;
+            }
+            if self.maintenance_cursor as usize >= self.archetypes.len() {
+                self.maintenance_cursor = 0;
+                return true;
+            }
+            if !proceed() {
+                return false;
+            }
+        }
+    }
+
+    /// Release all memory not needed by the entities currently stored
+    ///
+    /// Unlike `maintain`, which leaves room to grow back into, shrinks every archetype's storage to
+    /// exactly its number of entities, so is best reserved for after exceptional events such as
+    /// despawning most of the world. Archetypes are always densely packed, so no other compaction
+    /// is needed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mobs = world.spawn_batch((0..10_000).map(|i| (i, true))).collect::<Vec<_>>();
+    /// let capacity = |world: &World| world.archetypes().map(|x| x.capacity()).sum::<u32>();
+    /// world.despawn_batch(&mobs[1..]);
+    /// assert!(capacity(&world) >= 10_000);
+    /// world.shrink_to_fit();
+    /// assert_eq!(capacity(&world), 1);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.flush();
+        self.entities.shrink_to_fit();
+        for archetype in &mut self.archetypes {
+            archetype.shrink_to_fit();
+        }
+    }
+
+    /// Release all memory not needed by the entities with exact components `T`
+    ///
+    /// Like `shrink_to_fit`, but leaves other archetypes and entity metadata untouched.
+    pub fn shrink_archetype<T: Bundle>(&mut self) {
+        if let Some(id) = T::with_static_ids(|ids| self.index.get(ids).copied()) {
+            self.archetypes[id as usize].shrink_to_fit();
+        }
+    }
+
+    /// Ensure `additional` entities with exact components `T` can be spawned without reallocating
+    pub fn reserve<T: Bundle>(&mut self, additional: u32) {
+        self.reserve_inner::<T>(additional);
+    }
+
+    /// Ensure `additional` entities can be spawned without reallocating entity metadata
+    ///
+    /// Unlike `reserve`, makes no room for components, for when the makeup of the entities about to
+    /// be spawned isn't known in advance. Combine with `reserve_archetype` for the bundles that are,
+    /// e.g. when loading a saved region.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.reserve_entity_capacity(1000);
+    /// world.reserve_archetype::<(i32, bool)>(600);
+    /// world.reserve_archetype::<(i32,)>(400);
+    /// let capacity = |world: &World| world.archetypes().map(|x| x.capacity()).sum::<u32>();
+    /// assert!(capacity(&world) >= 1000);
+    /// ```
+    pub fn reserve_entity_capacity(&mut self, additional: u32) {
+        self.flush();
+        self.entities.reserve(additional);
+    }
+
+    /// Ensure `additional` entities with exact components `T` can be stored without reallocating
+    /// their archetype, creating it if necessary
+    ///
+    /// Unlike `reserve`, makes no room for entity metadata, so that many archetypes can be sized
+    /// individually after a single `reserve_entity_capacity` for their total.
+    pub fn reserve_archetype<T: Bundle>(&mut self, additional: u32) {
+        let archetype_id = self.archetype_for_bundle::<T>();
+        self.archetypes[archetype_id as usize].reserve(additional);
+    }
+
+    fn reserve_inner<T: Bundle>(&mut self, additional: u32) -> u32 {
+        self.reserve_entity_capacity(additional);
+        let archetype_id = self.archetype_for_bundle::<T>();
+        self.archetypes[archetype_id as usize].reserve(additional);
+        archetype_id
+    }
+
+    /// Find or create the archetype storing exactly the component types of `T`
+    fn archetype_for_bundle<T: Bundle>(&mut self) -> u32 {
+        T::with_static_ids(|ids| {
+            self.index.get(ids).copied().unwrap_or_else(|| {
+                let x = self.archetypes.len() as u32;
+                self.archetypes
+                    .push(Archetype::with_config(T::static_type_info(), &self.config));
+                self.index.insert(ids.into(), x);
+                self.type_indices
+                    .index_archetype(self.archetypes.last_mut().unwrap());
+                self.archetype_generation += 1;
+                self.hooks.archetype_created(&self.archetypes);
+                self.groups.archetype_created(&self.archetypes);
+                x
+            })
+        })
+    }
+
+    /// Despawn all entities
+    ///
+    /// Faster than despawning entities individually, as component data is dropped in bulk rather
+    /// than being removed from archetypes one row at a time. Handles to despawned entities are
+    /// invalidated as usual, and their IDs are reused by future spawns. Preserves allocated
+    /// storage, including archetypes, for reuse.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, "abc"));
+    /// world.clear();
+    /// assert!(!world.contains(a));
+    /// let b = world.spawn((456, "def"));
+    /// assert_eq!(a.id(), b.id());
+    /// assert!(world.get::<i32>(a).is_err());
+    /// ```
+    pub fn clear(&mut self) {
+        self.flush();
+        for x in &mut self.archetypes {
+            if self.hooks.watches_despawns() {
+                for (index, &id) in x.ids().iter().enumerate() {
+                    let entity = Entity {
+                        id,
+                        generation: self.entities.meta[id as usize].generation,
+                    };
+                    self.hooks.despawned(entity, x, index as u32, None);
+                }
+            }
+            if !x.is_empty() {
+                for ty in x.types() {
+                    if !self.vanished.contains(&ty.id()) {
+                        self.vanished.push(ty.id());
+                    }
+                }
+            }
+            x.clear();
+        }
+        self.entities.clear();
+        self.hierarchy.clear();
+        self.names.clear();
+        self.dense_indices.clear();
+        self.sparse.clear();
+        #[cfg(feature = "debug-history")]
+        self.history.clear();
+    }
+
+    /// Split the world into an entity ID allocator and component storage
+    ///
+    /// The halves can be sent to different threads to pipeline allocating entities with storing
+    /// their components, e.g. during world generation, then recombined with
+    /// `join_entities_and_components`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let world = World::new();
+    /// let (alloc, mut storage) = world.split_entities_and_components();
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for i in 0..100 {
+    ///             tx.send((alloc.reserve_entity(), i)).unwrap();
+    ///         }
+    ///         drop(tx);
+    ///     });
+    ///     for (entity, i) in rx {
+    ///         storage.insert(entity, (i,));
+    ///     }
+    /// });
+    /// let world = World::join_entities_and_components(alloc, storage);
+    /// assert_eq!(world.len(), 100);
+    /// ```
+    pub fn split_entities_and_components(mut self) -> (EntityAllocator, ComponentStorage) {
+        self.flush();
+        let token = split::next_token();
+        let allocator = EntityAllocator {
+            entities: mem::take(&mut self.entities),
+            despawned: Vec::new(),
+            token,
+        };
+        let storage = ComponentStorage {
+            world: self,
+            placed: Vec::new(),
+            token,
+        };
+        (allocator, storage)
+    }
+
+    /// Recombine the halves produced by `split_entities_and_components`
+    ///
+    /// Reserved entities that weren't given components are left empty, and despawns recorded by
+    /// the allocator are applied.
+    ///
+    /// Panics if the halves came from different splits.
+    pub fn join_entities_and_components(
+        allocator: EntityAllocator,
+        storage: ComponentStorage,
+    ) -> World {
+        assert_eq!(
+            allocator.token, storage.token,
+            "entity allocator and component storage must come from the same split"
+        );
+        let EntityAllocator {
+            entities,
+            despawned,
+            ..
+        } = allocator;
+        let ComponentStorage {
+            world: mut this,
+            placed,
+            ..
+        } = storage;
+        this.entities = entities;
+
+        // Record the locations of entities given components, discarding any that were not
+        // reserved since the split or were given components twice.
+        let mut locations = HashMap::<u32, (u32, u32)>::new();
+        let mut orphans = Vec::new();
+        let mut spawned = Vec::new();
+        for (entity, archetype, index) in placed {
+            if !this.entities.contains(entity) || locations.contains_key(&entity.id) {
+                orphans.push((archetype, index));
+                continue;
+            }
+            locations.insert(entity.id, (archetype, index));
+            spawned.push(entity);
+        }
+        let empty = &mut this.archetypes[0];
+        this.entities.flush(|id, location| {
+            *location = match locations.remove(&id) {
+                Some((archetype, index)) => Location { archetype, index },
+                None => Location {
+                    archetype: 0,
+                    index: unsafe { empty.allocate(id) },
+                },
+            };
+        });
+        orphans.extend(locations.into_iter().map(|(_, x)| x));
+
+        // Remove from the back so that rows moved into the gaps belong to live entities
+        orphans.sort_unstable_by(|x, y| y.cmp(x));
+        for (archetype, index) in orphans {
+            let archetype_ref = &mut this.archetypes[archetype as usize];
+            unsafe {
+                if let Some(moved) = archetype_ref.remove(index) {
+                    this.entities.meta[moved as usize].location.index = index;
+                }
+            }
+        }
+
+        if this.hooks.watches_inserts() {
+            for entity in spawned {
+                let loc = this.entities.meta[entity.id as usize].location;
+                this.hooks
+                    .spawned(entity, &this.archetypes[loc.archetype as usize], loc.index);
+            }
+        }
+
+        for entity in despawned {
+            let _ = this.despawn(entity);
+        }
+        this
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
+    /// checking
+    ///
+    /// Prefer `query_mut` when concurrent access to the `World` is not required.
+    ///
+    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
+    /// type. A query type is `&T`, `&mut T`, a tuple of query types, or an `Option` wrapping a
+    /// query type, where `T` is any component type. Components queried with `&mut` must only appear
+    /// once. Entities which do not have a component type referenced outside of an `Option` will be
+    /// skipped.
+    ///
+    /// Entities are yielded in arbitrary order.
+    ///
+    /// The returned `QueryBorrow` can be further transformed with combinator methods; see its
+    /// documentation for details.
+    ///
+    /// Iterating a query will panic if it would violate an existing unique reference or construct
+    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
+    /// the same entity. Simultaneous queries can access the same component type if and only if the
+    /// world contains no entities that have all components required by both queries, assuming no
+    /// other component borrows are outstanding.
+    ///
+    /// Iterating a query yields references with lifetimes bound to the `QueryBorrow` returned
+    /// here. To ensure those are invalidated, the return value of this method must be dropped for
+    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
+    /// references obtained from a query cannot outlive the `QueryBorrow`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<(&i32, &bool)>()
+    ///     .iter()
+    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities.len(), 2);
+    /// assert!(entities.contains(&(a, 123, true)));
+    /// assert!(entities.contains(&(b, 456, false)));
+    /// ```
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
+        #[cfg(feature = "stats")]
+        self.count_scans(self.archetypes.len());
+        QueryBorrow::new(&self.entities.meta, &self.archetypes)
+    }
+
+    /// Query a uniquely borrowed world
+    ///
+    /// Like `query`, but faster because dynamic borrow checks can be skipped. Note that, unlike
+    /// `query`, this returns an `IntoIterator` which can be passed directly to a `for` loop.
+    ///
+    /// Panics if `Q` uniquely borrows a component it also borrows elsewhere, e.g.
+    /// `(&mut T, &T)`. The check depends only on `Q`, so it's optimized out of valid queries.
+    pub fn query_mut<Q: Query>(&mut self) -> QueryMut<'_, Q> {
+        #[cfg(feature = "stats")]
+        self.count_scans(self.archetypes.len());
+        QueryMut::new(&self.entities.meta, &mut self.archetypes)
+    }
+
+    /// Like `query_mut`, for callers that have established that nothing else accesses the
+    /// components `Q` borrows
+    ///
+    /// # Safety
+    ///
+    /// No components `Q` borrows may be accessed elsewhere while the result is live, and the
+    /// world must have no pending reserved entities.
+    pub(crate) unsafe fn query_mut_shared<Q: Query>(&self) -> QueryMut<'_, Q> {
+        #[cfg(feature = "stats")]
+        self.count_scans(self.archetypes.len());
+        QueryMut::new_shared(&self.entities.meta, &self.archetypes)
+    }
+
+    /// Keep track of the archetypes storing every component type in `G`, for `query_group`
+    ///
+    /// Suited to component sets such as a position and a velocity that a hot loop reads together.
+    /// Each archetype stores its entities' components in columns that are packed without gaps and
+    /// sorted alike, so a group's components form one contiguous, co-sorted run per member
+    /// archetype, and only one if every member of the group has the same other components.
+    /// Members are found once at registration and then as archetypes are created, so sweeping a
+    /// group never scans unrelated archetypes. Registering a group twice has no effect.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::new();
+    /// world.register_group::<(Position, Velocity)>();
+    /// world.spawn_batch((0..100).map(|i| (Position(i as f32), Velocity(1.0))));
+    /// world.spawn((Position(0.0),));
+    /// for (ids, (pos, vel)) in world.query_group::<(&mut Position, &Velocity)>() {
+    ///     assert_eq!(ids.len(), 100);
+    ///     for (p, v) in pos.iter_mut().zip(vel) {
+    ///         p.0 += v.0;
+    ///     }
+    /// }
+    /// ```
+    pub fn register_group<G: Bundle>(&mut self) {
+        G::with_static_ids(|ids| self.groups.register(ids, &self.archetypes));
+    }
+
+    /// Borrow the components of the group registered for exactly the component types `Q`
+    /// borrows, as one slice per component type per member archetype, along with the IDs of the
+    /// archetype's entities
+    ///
+    /// Only available for queries that don't filter individual entities. Empty archetypes are
+    /// skipped. Panics if no such group was registered with `register_group`, or if `Q` uniquely
+    /// borrows a component it also borrows elsewhere.
+    pub fn query_group<'q, Q: Query>(&'q mut self) -> GroupChunks<'q, Q>
+    where
+        Q::Fetch: ChunkFetch<'q>,
+    {
+        assert_borrow::<Q>();
+        let matching = self
+            .groups
+            .of::<Q>()
+            .expect("no group registered for the queried components");
+        // Safety: `Q` doesn't alias, and the world is uniquely borrowed
+        unsafe { GroupChunks::new(&self.archetypes, matching) }
+    }
+
+    /// Query a uniquely borrowed world for its only entity matching `Q`, such as the entity with
+    /// a `PlayerController`
+    ///
+    /// Returns the first match, if any, without visiting the rest. Debug builds panic if more
+    /// than one entity matches, catching singletons that have been duplicated by mistake.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Player;
+    ///
+    /// let mut world = World::new();
+    /// world.spawn((1,));
+    /// let player = world.spawn((Player, 2));
+    /// let (entity, (_, number)) = world.find_one::<(&Player, &mut i32)>().unwrap();
+    /// assert_eq!(entity, player);
+    /// *number += 1;
+    /// assert!(world.find_one::<&bool>().is_none());
+    /// ```
+    pub fn find_one<Q: Query>(&mut self) -> Option<(Entity, QueryItem<'_, Q>)> {
+        debug_assert!(
+            self.query::<Q>().at_most(2) < 2,
+            "more than one entity matches {}",
+            core::any::type_name::<Q>()
+        );
+        self.query_mut::<Q>().into_iter().next()
+    }
+
+    /// Append the entities matching `Q` to `out`, in the order `query` would visit them
+    ///
+    /// Sizes `out` from archetype lengths before filling it, and, unless `Q` filters individual
+    /// entities as [`Changed`](crate::Changed) does, reads neither components nor their flags,
+    /// so no borrows are taken. Reusing `out` across calls avoids allocating every tick.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// world.spawn((2,));
+    /// let mut targets = Vec::new();
+    /// for _ in 0..3 {
+    ///     targets.clear();
+    ///     world.collect_entities::<(&i32, &bool)>(&mut targets);
+    ///     assert_eq!(targets, [a]);
+    /// }
+    /// ```
+    pub fn collect_entities<Q: Query>(&self, out: &mut Vec<Entity>) {
+        if Q::Fetch::FILTERS {
+            let mut query = self.query::<Q>();
+            let iter = query.iter();
+            out.reserve(iter.len());
+            out.extend(iter.map(|(entity, _)| entity));
+            return;
+        }
+        let matching = || {
+            self.archetypes
+                .iter()
+                .filter(|x| !x.is_empty() && Q::Fetch::access(x).is_some())
+        };
+        out.reserve(matching().map(|x| x.len() as usize).sum());
+        for archetype in matching() {
+            out.extend(archetype.ids().iter().map(|&id| Entity {
+                id,
+                generation: self.entities.meta[id as usize].generation,
+            }));
+        }
+    }
+
+    /// Perform a dynamic query.
+    pub fn query_dynamic<'q>(&'q self, types: DynamicQueryTypes<'q>) -> DynamicQuery<'q> {
+        DynamicQuery::new(types, &self.archetypes, &self.entities.meta)
+    }
+
+    /// Prepare a query against a single entity, using dynamic borrow checking
+    ///
+    /// Prefer `query_one_mut` when concurrent access to the `World` is not required.
+    ///
+    /// Call `get` on the resulting `QueryOne` to actually execute the query. The `QueryOne` value
+    /// is responsible for releasing the dynamically-checked borrow made by `get`, so it can't be
+    /// dropped while references returned by `get` are live.
     ///
     /// Handy for accessing multiple components simultaneously.
     ///
@@ -428,55 +2317,827 @@ impl World {
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// // The returned query must outlive the borrow made by `get`
-    /// let mut query = world.query_one::<(&mut i32, &bool)>(a).unwrap();
-    /// let (number, flag) = query.get().unwrap();
-    /// if *flag { *number *= 2; }
-    /// assert_eq!(*number, 246);
+    /// let a = world.spawn((123, true, "abc"));
+    /// // The returned query must outlive the borrow made by `get`
+    /// let mut query = world.query_one::<(&mut i32, &bool)>(a).unwrap();
+    /// let (number, flag) = query.get().unwrap();
+    /// if *flag { *number *= 2; }
+    /// assert_eq!(*number, 246);
+    /// ```
+    pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
+        let loc = self.entities.get(entity)?;
+        Ok(unsafe { QueryOne::new(&self.archetypes[loc.archetype as usize], loc.index) })
+    }
+
+    /// Query a single entity in a uniquely borrow world
+    ///
+    /// Like `query_one`, but faster because dynamic borrow checks can be skipped. Note that, unlike
+    /// `query_one`, on success this returns the query's results directly.
+    ///
+    /// Fetching several components this way validates the entity only once, making it cheaper than
+    /// an equivalent series of `get` calls.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let (number, flag, name) = world.query_one_mut::<(&mut i32, &bool, Option<&&str>)>(a).unwrap();
+    /// assert!(*flag && name.is_none());
+    /// *number += 1;
+    /// assert!(world.query_one_mut::<&&str>(a).is_err());
+    /// ```
+    pub fn query_one_mut<Q: Query>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<QueryItem<'_, Q>, QueryOneError> {
+        assert_borrow::<Q>();
+        let loc = self.entities.get(entity)?;
+        unsafe {
+            let fetch = Q::Fetch::new(&self.archetypes[loc.archetype as usize])
+                .ok_or(QueryOneError::Unsatisfied)?;
+            if Q::Fetch::FILTERS && fetch.skip(loc.index as usize) {
+                return Err(QueryOneError::Unsatisfied);
+            }
+            Ok(fetch.get(loc.index as usize))
+        }
+    }
+
+    /// Whether `entity` exists and would be visited by the query `Q`
+    pub(crate) fn satisfies<Q: Query>(&self, entity: Entity) -> bool {
+        let loc = match self.entities.get(entity) {
+            Ok(x) => x,
+            Err(NoSuchEntity) => return false,
+        };
+        match Q::Fetch::new(&self.archetypes[loc.archetype as usize]) {
+            None => false,
+            Some(fetch) => !Q::Fetch::FILTERS || unsafe { !fetch.skip(loc.index as usize) },
+        }
+    }
+
+    /// Capture the entities currently matching `Q`, to be visited later with `&mut World` access
+    ///
+    /// Unlike `query`, the world remains free to be modified between items, at the cost of
+    /// collecting the matching entities up front and looking each one up again when it's reached.
+    /// Entities that have been despawned or no longer match `Q` by the time they're reached are
+    /// skipped. Entities spawned after the snapshot was taken are not visited.
+    ///
+    /// Panics if `Q` filters entities by components that are already uniquely borrowed, as
+    /// `query` would.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// let mut snapshot = world.iter_snapshot::<&mut i32>();
+    /// while let Some((e, x)) = snapshot.next(&mut world) {
+    ///     *x += 10;
+    ///     // Structural changes are fine mid-iteration
+    ///     world.spawn((0,));
+    ///     world.despawn(if e == a { b } else { a }).ok();
+    /// }
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 11);
+    /// assert!(!world.contains(b));
+    /// ```
+    pub fn iter_snapshot<Q: Query>(&self) -> Snapshot<Q> {
+        let mut entities = Vec::new();
+        self.collect_entities::<Q>(&mut entities);
+        Snapshot::new(entities)
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// A missing entity or component is reported as a `ComponentError` rather than a panic, leaving
+    /// the policy to the caller: code relying on engine invariants can simply `unwrap`, while code
+    /// handling untrusted input can recover.
+    ///
+    /// Panics if the component is already uniquely borrowed from another entity with the same
+    /// components.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(unsafe { Ref::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+    }
+
+    /// Uniquely borrow the `T` component of `entity`
+    ///
+    /// A missing entity or component is reported as a `ComponentError`; see `get`.
+    ///
+    /// Panics if the component is already borrowed from another entity with the same components.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(unsafe { RefMut::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+    }
+
+    /// Like `get`, but reports a conflicting borrow as [`ComponentError::Borrowed`] rather than
+    /// panicking
+    ///
+    /// Useful when a conflict is recoverable, e.g. by retrying later, or to report which system
+    /// holds the borrow; see [`with_borrow_label`](crate::with_borrow_label).
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn try_get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe { Ref::try_new(&self.archetypes[loc.archetype as usize], loc.index) }
+    }
+
+    /// Like `get_mut`, but reports a conflicting borrow as [`ComponentError::Borrowed`] rather
+    /// than panicking
+    ///
+    /// See `try_get`.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn try_get_mut<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<RefMut<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe { RefMut::try_new(&self.archetypes[loc.archetype as usize], loc.index) }
+    }
+
+    /// Borrow the `T` component of `entity` from a shared world, without borrowing the `Arc`
+    ///
+    /// The guard owns a reference to the world, so it can be held across `.await` points or sent
+    /// to other tasks, as when network code must read components while waiting on I/O. The world
+    /// cannot be modified while any such guard exists, as `Arc::get_mut` will fail.
+    ///
+    /// Panics if the component is already uniquely borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::sync::Arc;
+    /// let mut world = World::new();
+    /// let e = world.spawn((42,));
+    /// let world = Arc::new(world);
+    /// let guard = World::get_owned::<i32>(&world, e).unwrap();
+    /// let handle = std::thread::spawn(move || *guard + 1);
+    /// assert_eq!(handle.join().unwrap(), 43);
+    /// ```
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn get_owned<T: Component>(
+        this: &Arc<Self>,
+        entity: Entity,
+    ) -> Result<OwnedRef<T>, ComponentError> {
+        let loc = this.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe {
+            let target = Ref::<T>::new(&this.archetypes[loc.archetype as usize], loc.index)?.leak();
+            Ok(OwnedRef::new(this.clone(), loc.archetype, target))
+        }
+    }
+
+    /// Uniquely borrow the `T` component of `entity` from a shared world
+    ///
+    /// See `get_owned`.
+    ///
+    /// Panics if the component is already borrowed.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn get_owned_mut<T: Component>(
+        this: &Arc<Self>,
+        entity: Entity,
+    ) -> Result<OwnedRefMut<T>, ComponentError> {
+        let loc = this.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe {
+            let ptrs =
+                RefMut::<T>::new(&this.archetypes[loc.archetype as usize], loc.index)?.leak();
+            Ok(OwnedRefMut::new(this.clone(), loc.archetype, ptrs))
+        }
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    pub(crate) fn query_mask<Q: Query>(&self) -> QueryMask {
+        QueryMask::new::<Q>(&self.type_indices)
+    }
+
+    pub(crate) fn storage(&self) -> (&[EntityMeta], &[Archetype]) {
+        (&self.entities.meta, &self.archetypes)
+    }
+
+    pub(crate) fn storage_mut(&mut self) -> (&[EntityMeta], &mut [Archetype]) {
+        (&self.entities.meta, &mut self.archetypes)
+    }
+
+    pub(crate) fn archetype_at(&self, id: u32) -> &Archetype {
+        &self.archetypes[id as usize]
+    }
+
+    /// Uniquely borrow the [`Blackboard`] of `entity`, adding an empty one first if necessary
+    ///
+    /// Only the first call for a given entity moves it to a new archetype; values stored in the
+    /// blackboard afterwards never cause further archetype changes.
+    pub fn blackboard_mut(&mut self, entity: Entity) -> Result<&mut Blackboard, NoSuchEntity> {
+        let loc = self.entities.get(entity)?;
+        if !self.archetypes[loc.archetype as usize].has::<Blackboard>() {
+            self.insert_one(entity, Blackboard::new())?;
+        }
+        Ok(self
+            .query_one_mut::<&mut Blackboard>(entity)
+            .expect("blackboard was just ensured to exist"))
+    }
+
+    /// Whether the `T` component of `entity` has been flagged as changed since the last call to
+    /// `clear_changed`
+    ///
+    /// Components are flagged when they're added to an entity, when they're replaced by `insert`,
+    /// when they're visited by a query for `&mut T`, and when they're written through a `RefMut` or
+    /// [`Mut`](crate::Mut). Moving an entity between archetypes preserves its flags.
+    pub fn is_changed<T: Component>(&self, entity: Entity) -> Result<bool, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe {
+            Ok(self.archetypes[loc.archetype as usize]
+                .is_changed::<T>(loc.index)
+                .ok_or_else(MissingComponent::new::<T>)?)
+        }
+    }
+
+    /// Reset the change flags of every component in the world
+    ///
+    /// Typically called once per tick, after all interested parties have observed changes. See
+    /// `is_changed`.
+    pub fn clear_changed(&mut self) {
+        self.ticks.record(&mut self.archetypes);
+        for archetype in &mut self.archetypes {
+            archetype.clear_changed();
+        }
+        #[cfg(feature = "debug-history")]
+        self.history.advance();
+        self.end_frame();
+        self.auto_validate();
+    }
+
+    /// The world's current tick, which starts at 0
+    pub fn tick(&self) -> Tick {
+        self.ticks.current()
+    }
+
+    /// Move on to the next tick, returning it
+    ///
+    /// Components flagged as changed are recorded as last changed during the tick that's ending,
+    /// and their flags are reset as if by `clear_changed`. See [`last_changed`](Self::last_changed).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// world.advance_tick();
+    /// *world.get_mut::<i32>(a).unwrap() += 1;
+    /// assert_eq!(world.advance_tick(), Tick(2));
+    /// assert_eq!(world.last_changed::<i32>(a), Some(Tick(1)));
+    /// assert_eq!(world.last_changed::<bool>(a), Some(Tick(0)));
+    /// ```
+    pub fn advance_tick(&mut self) -> Tick {
+        self.clear_changed();
+        self.ticks.advance()
+    }
+
+    /// The tick during which the `T` component of `entity` was last changed, if it exists
+    ///
+    /// Components count as changed in the same circumstances as for `is_changed`. A component
+    /// that's currently flagged as changed reports the current tick, even if the flag was set
+    /// during an earlier tick and not reset since, so ticks are only precise if the world's change
+    /// flags are reset through `advance_tick`, `clear_changed`, or `clear_trackers`.
+    pub fn last_changed<T: Component>(&self, entity: Entity) -> Option<Tick> {
+        if self.is_changed::<T>(entity).ok()? {
+            Some(self.ticks.current())
+        } else {
+            let loc = self.entities.get(entity).ok()?;
+            self.archetypes[loc.archetype as usize].tick(TypeId::of::<T>(), loc.index)
+        }
+    }
+
+    /// Reset both the change and addition flags of every component in the world
+    ///
+    /// Typically called once per tick, after all interested parties have observed changes through
+    /// [`Changed`](crate::Changed) and [`Added`](crate::Added) queries.
+    pub fn clear_trackers(&mut self) {
+        self.ticks.record(&mut self.archetypes);
+        for archetype in &mut self.archetypes {
+            archetype.clear_changed();
+            archetype.clear_added();
+            archetype.clear_membership();
+        }
+        #[cfg(feature = "debug-history")]
+        self.history.advance();
+        self.end_frame();
+        self.auto_validate();
+    }
+
+    /// Check the world's internal bookkeeping for consistency
+    ///
+    /// Verifies that every live entity's location points to an archetype row holding it, that
+    /// every archetype row's entity points back, that no ID awaiting reuse is held by a row, and
+    /// that no component column is borrowed. Intended for tracking down corruption caused by
+    /// unsafe code, such as misuse of `query_unchecked` or `ComponentStorage`, close to its source.
+    /// Takes time linear in the number of entities.
+    ///
+    /// Since outstanding borrows are reported, call this between frames rather than while queries
+    /// or component references are alive. See also
+    /// [`WorldConfig::validate`](crate::WorldConfig::validate) for validating automatically.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// world.spawn(("abc",));
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(world.validate(), Ok(()));
+    /// let mut query = world.query::<&&str>();
+    /// let _iter = query.iter();
+    /// assert_eq!(world.validate(), Err(IntegrityError::Borrowed("&str")));
+    /// ```
+    pub fn validate(&self) -> Result<(), IntegrityError> {
+        let meta = &self.entities.meta;
+        let mut seen = vec![false; meta.len()];
+        for (archetype_index, archetype) in self.archetypes.iter().enumerate() {
+            if let Some(ty) = archetype.borrowed_type() {
+                return Err(IntegrityError::Borrowed(ty.type_name()));
+            }
+            for (index, &id) in archetype.ids().iter().enumerate() {
+                let misplaced = IntegrityError::MisplacedEntity {
+                    id,
+                    archetype: archetype_index as u32,
+                    index: index as u32,
+                };
+                let loc = match meta.get(id as usize) {
+                    Some(x) => x.location,
+                    None => return Err(misplaced),
+                };
+                if loc.archetype as usize != archetype_index
+                    || loc.index as usize != index
+                    || mem::replace(&mut seen[id as usize], true)
+                {
+                    return Err(misplaced);
+                }
+            }
+        }
+        let mut idle = vec![false; meta.len()];
+        for id in self.entities.idle_ids() {
+            if seen[id as usize] {
+                return Err(IntegrityError::IdleEntityInUse(id));
+            }
+            idle[id as usize] = true;
+        }
+        for (id, x) in meta.iter().enumerate() {
+            if !seen[id] && !idle[id] {
+                return Err(IntegrityError::DanglingEntity(Entity {
+                    id: id as u32,
+                    generation: x.generation,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a frame towards [`WorldConfig::id_reuse_delay`], making IDs freed long enough ago
+    /// available for reuse
+    ///
+    /// Called by `clear_changed`, `clear_trackers`, and `advance_tick`, so worlds reset with one of
+    /// those once per frame needn't call it. Worlds that don't track changes can call this instead
+    /// to end a frame without touching change flags. Flushes reserved entities if a reuse delay or
+    /// `fifo_id_reuse` is configured, and otherwise does nothing.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut config = WorldConfig::default();
+    /// config.id_reuse_delay = 1;
+    /// let mut world = World::from_config(config);
+    /// let a = world.spawn(());
+    /// world.despawn(a).unwrap();
+    /// assert_ne!(world.spawn(()).id(), a.id());
+    /// world.end_frame();
+    /// assert_eq!(world.spawn(()).id(), a.id());
+    /// ```
+    pub fn end_frame(&mut self) {
+        if self.config.fifo_id_reuse || self.config.id_reuse_delay != 0 {
+            // Queued IDs are released into the freelist, which reservations mustn't be using
+            self.flush();
+            self.entities.end_frame();
+        }
+    }
+
+    /// Validate the world if `WorldConfig::validate` asks for it, panicking on failure
+    fn auto_validate(&self) {
+        #[cfg(debug_assertions)]
+        if self.config.validate {
+            if let Err(e) = self.validate() {
+                panic!("world failed validation: {}", e);
+            }
+        }
+    }
+
+    /// The most recent changes to the set of component types `entity` has, oldest first
+    ///
+    /// Up to 16 transitions made by `insert` and `remove` are retained per entity, each stamped
+    /// with the number of calls to `clear_changed` or `clear_trackers` made before it. Useful for
+    /// diagnosing where an entity unexpectedly gains or loses components; pair with a
+    /// [`ComponentRegistry`](crate::ComponentRegistry) to name the recorded types.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// world.insert_one(e, true).unwrap();
+    /// world.clear_trackers();
+    /// world.remove_one::<i32>(e).unwrap();
+    /// let history = world.debug_history(e).unwrap().collect::<Vec<_>>();
+    /// assert_eq!(history[0].added(), [TypeId::of::<bool>()]);
+    /// assert_eq!((history[1].tick(), history[1].removed()), (1, &[TypeId::of::<i32>()][..]));
+    /// ```
+    #[cfg(feature = "debug-history")]
+    pub fn debug_history(
+        &self,
+        entity: Entity,
+    ) -> Result<impl DoubleEndedIterator<Item = &Transition> + ExactSizeIterator + '_, NoSuchEntity>
+    {
+        self.entities.get(entity)?;
+        Ok(self.history.get(entity.id))
+    }
+
+    /// Counts of the work done by this world since the last `reset_stats`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// world.insert_one(e, true).unwrap();
+    /// let stats = world.stats();
+    /// assert_eq!((stats.spawns, stats.archetype_moves), (1, 1));
+    /// world.reset_stats();
+    /// assert_eq!(world.stats(), WorldStats::default());
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> WorldStats {
+        WorldStats {
+            spawns: self.entities.spawned,
+            despawns: self.entities.despawned,
+            archetype_moves: self.counters.moves,
+            archetype_scans: self.counters.scans(),
+            borrows: self.archetypes.iter().map(|x| x.borrow_count()).sum(),
+        }
+    }
+
+    /// Zero the counts reported by `stats`, e.g. at the start of each tick
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.entities.spawned = 0;
+        self.entities.despawned = 0;
+        self.counters.reset();
+        for archetype in &mut self.archetypes {
+            archetype.reset_borrow_count();
+        }
+    }
+
+    /// Record that a query considered `archetypes` archetypes
+    #[cfg(feature = "stats")]
+    pub(crate) fn count_scans(&self, archetypes: usize) {
+        self.counters.scanned(archetypes);
+    }
+
+    /// A shareable handle that can access components and resources, but not change the world's
+    /// structure
+    ///
+    /// Queries through the handle may still borrow components uniquely. See [`WorldView`].
+    pub fn as_view(&self) -> WorldView<'_> {
+        WorldView::from(self)
+    }
+
+    /// Restrict access to the component types in the tuple `S`
+    ///
+    /// See [`View`].
+    pub fn view<S>(&mut self) -> View<'_, S> {
+        View::new(self)
+    }
+
+    /// Store `value` as the world's `T` resource, returning the previous one, if any
+    ///
+    /// Resources are world-global values of distinct types, such as a tick counter, RNG, or block
+    /// registry, which aren't associated with any entity. They are unaffected by `clear`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Tick(u64);
+    ///
+    /// let mut world = World::new();
+    /// world.insert_resource(Tick(0));
+    /// world.resource_mut::<Tick>().unwrap().0 += 1;
+    /// assert_eq!(world.resource::<Tick>().unwrap().0, 1);
+    /// ```
+    pub fn insert_resource<T: Component>(&mut self, value: T) -> Option<T> {
+        self.resources.insert(value)
+    }
+
+    /// Remove and return the world's `T` resource, if any
+    pub fn remove_resource<T: Component>(&mut self) -> Option<T> {
+        self.resources.remove::<T>()
+    }
+
+    /// Whether the world has a `T` resource
+    pub fn contains_resource<T: Component>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Borrow the world's `T` resource, if any
+    ///
+    /// Panics if the resource is already uniquely borrowed.
+    pub fn resource<T: Component>(&self) -> Option<ResourceRef<'_, T>> {
+        self.resources.get::<T>()
+    }
+
+    /// Uniquely borrow the world's `T` resource, if any
+    ///
+    /// Panics if the resource is already borrowed.
+    pub fn resource_mut<T: Component>(&self) -> Option<ResourceRefMut<'_, T>> {
+        self.resources.get_mut::<T>()
+    }
+
+    /// Give `entity` a sparse `T` component, returning the one it replaced, if any
+    ///
+    /// Sparse components live in a side table rather than in the entity's archetype, so adding
+    /// and removing them never moves the entity, which suits components that come and go often,
+    /// such as status effects. They aren't visible to queries or `get`, and are accessed through
+    /// [`sparse`](Self::sparse) and [`sparse_mut`](Self::sparse_mut) instead. They are dropped
+    /// when their entity is despawned, taken, or migrated, but follow it into another world through
+    /// `absorb`, and are captured by `snapshot` like any other component.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Stunned(u32);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let archetypes = world.archetypes_generation();
+    /// world.insert_sparse(a, Stunned(3)).unwrap();
+    /// assert_eq!(world.archetypes_generation(), archetypes);
+    /// world.sparse_mut::<Stunned>().unwrap().get_mut(a).unwrap().0 -= 1;
+    /// assert_eq!(world.sparse::<Stunned>().unwrap().get(a).unwrap().0, 2);
+    /// assert!(world.remove_sparse::<Stunned>(a).is_some());
+    /// assert!(world.sparse::<Stunned>().unwrap().is_empty());
+    /// ```
+    pub fn insert_sparse<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.sparse.register::<T>().insert(entity, component))
+    }
+
+    /// Remove and return `entity`'s sparse `T` component, if any
+    pub fn remove_sparse<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.sparse.get_mut::<T>()?.remove(entity)
+    }
+
+    /// Set aside storage for sparse `T` components, so that `sparse` and `sparse_mut` find it
+    /// even before any are inserted
+    pub fn register_sparse<T: Component>(&mut self) {
+        self.sparse.register::<T>();
+    }
+
+    /// Borrow every sparse `T` component, or `None` if none were ever inserted or registered
+    ///
+    /// Panics if the components are already uniquely borrowed.
+    pub fn sparse<T: Component>(&self) -> Option<SparseRef<'_, T>> {
+        self.sparse.borrow::<T>()
+    }
+
+    /// Uniquely borrow every sparse `T` component, or `None` if none were ever inserted or
+    /// registered
+    ///
+    /// Panics if the components are already borrowed.
+    pub fn sparse_mut<T: Component>(&self) -> Option<SparseRefMut<'_, T>> {
+        self.sparse.borrow_mut::<T>()
+    }
+
+    /// Send `event` to readers of `E` events
+    ///
+    /// Lets gameplay systems communicate, e.g. about broken blocks or dealt damage, without
+    /// attaching marker components to entities and thereby moving them between archetypes. The
+    /// event can be read with `events` until the second call to `update_events` after it was sent.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Damage { target: Entity, amount: u32 }
+    ///
+    /// let mut world = World::new();
+    /// let zombie = world.spawn((20u32,));
+    /// world.send_event(Damage { target: zombie, amount: 5 });
+    /// // Later in the same tick, or early in the next
+    /// let hits = world.events::<Damage>().map(|x| (x.target, x.amount)).collect::<Vec<_>>();
+    /// for (target, amount) in hits {
+    ///     *world.get_mut::<u32>(target).unwrap() -= amount;
+    /// }
+    /// assert_eq!(*world.get::<u32>(zombie).unwrap(), 15);
+    /// world.update_events();
+    /// world.update_events();
+    /// assert_eq!(world.events::<Damage>().count(), 0);
+    /// ```
+    pub fn send_event<E: Component>(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    /// Iterate over the `E` events sent since the second-to-last call to `update_events`, in the
+    /// order they were sent
+    pub fn events<E: Component>(&self) -> impl Iterator<Item = &E> + '_ {
+        self.events.iter::<E>()
+    }
+
+    /// Discard events sent before the last call, and retain those sent since for another cycle
+    ///
+    /// Should be called once per tick. Keeping each event for two ticks ensures that it's seen by
+    /// every reader that runs once per tick, whether the reader runs before or after the sender.
+    pub fn update_events(&mut self) {
+        self.events.update();
+    }
+
+    /// Register `T` as implementing the trait represented by the trait object type `Dyn`
+    ///
+    /// Enables `visit_trait` and `visit_trait_mut` to find `T` components when visiting `Dyn`. The
+    /// casts are usually trivial closures relying on unsizing coercion, as in the example below.
+    /// Registering the same `T` for the same `Dyn` again replaces the previous casts.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// trait Describe {
+    ///     fn describe(&self) -> String;
+    /// }
+    /// impl Describe for i32 {
+    ///     fn describe(&self) -> String { format!("the number {}", self) }
+    /// }
+    /// impl Describe for bool {
+    ///     fn describe(&self) -> String { format!("the flag {}", self) }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.register_trait::<i32, dyn Describe>(|x| x, |x| x);
+    /// world.register_trait::<bool, dyn Describe>(|x| x, |x| x);
+    /// let a = world.spawn((42, true));
+    /// let mut descriptions = Vec::new();
+    /// world.visit_trait::<dyn Describe>(|e, x| descriptions.push((e, x.describe())));
+    /// descriptions.sort();
+    /// assert_eq!(descriptions, [
+    ///     (a, "the flag true".to_string()),
+    ///     (a, "the number 42".to_string()),
+    /// ]);
     /// ```
-    pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
-        let loc = self.entities.get(entity)?;
-        Ok(unsafe { QueryOne::new(&self.archetypes[loc.archetype as usize], loc.index) })
+    pub fn register_trait<T: Component, Dyn: ?Sized + 'static>(
+        &mut self,
+        cast: fn(&T) -> &Dyn,
+        cast_mut: fn(&mut T) -> &mut Dyn,
+    ) {
+        self.traits.register(cast, cast_mut);
     }
 
-    /// Query a single entity in a uniquely borrow world
+    /// Invoke `f` on every component registered as implementing `Dyn`, using dynamic borrow
+    /// checking
     ///
-    /// Like `query_one`, but faster because dynamic borrow checks can be skipped. Note that, unlike
-    /// `query_one`, on success this returns the query's results directly.
-    pub fn query_one_mut<Q: Query>(
-        &mut self,
-        entity: Entity,
-    ) -> Result<QueryItem<'_, Q>, QueryOneError> {
-        let loc = self.entities.get(entity)?;
-        unsafe {
-            let fetch = Q::Fetch::new(&self.archetypes[loc.archetype as usize])
-                .ok_or(QueryOneError::Unsatisfied)?;
-            Ok(fetch.get(loc.index as usize))
-        }
+    /// An entity is visited once for each of its components registered for `Dyn`. Components are
+    /// visited in arbitrary order. See `register_trait`.
+    ///
+    /// Panics if a visited component type is already uniquely borrowed.
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn visit_trait<Dyn: ?Sized + 'static>(&self, f: impl FnMut(Entity, &Dyn)) {
+        traits::visit(
+            self.traits.get::<Dyn>(),
+            &self.entities.meta,
+            &self.archetypes,
+            f,
+        );
     }
 
-    /// Borrow the `T` component of `entity`
+    /// Iterate over every component registered as implementing `Dyn`, using dynamic borrow
+    /// checking
     ///
-    /// Panics if the component is already uniquely borrowed from another entity with the same
-    /// components.
-    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
-        let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
-        Ok(unsafe { Ref::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+    /// An iterator counterpart to `visit_trait`, e.g. for walking every `dyn Persist` component
+    /// regardless of its concrete type. An entity is yielded once for each of its components
+    /// registered for `Dyn`, in arbitrary order. Matched component types are borrowed by
+    /// [`QueryTrait::iter`] until the returned `QueryTrait` is dropped, so yielded components can't
+    /// outlive their borrows. See `register_trait`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// trait Persist {
+    ///     fn key(&self) -> u32;
+    /// }
+    /// struct Chest(u32);
+    /// impl Persist for Chest {
+    ///     fn key(&self) -> u32 { self.0 }
+    /// }
+    /// struct Sign(u32);
+    /// impl Persist for Sign {
+    ///     fn key(&self) -> u32 { self.0 }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.register_trait::<Chest, dyn Persist>(|x| x, |x| x);
+    /// world.register_trait::<Sign, dyn Persist>(|x| x, |x| x);
+    /// let a = world.spawn((Chest(1),));
+    /// let b = world.spawn((Sign(2), true));
+    /// world.spawn((3,));
+    /// let mut keys = world
+    ///     .query_trait::<dyn Persist>()
+    ///     .iter()
+    ///     .map(|(e, x)| (e, x.key()))
+    ///     .collect::<Vec<_>>();
+    /// keys.sort();
+    /// assert_eq!(keys, [(a, 1), (b, 2)]);
+    /// ```
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn query_trait<Dyn: ?Sized + 'static>(&self) -> QueryTrait<'_, Dyn> {
+        QueryTrait::new(
+            self.traits.get::<Dyn>(),
+            &self.entities.meta,
+            &self.archetypes,
+        )
     }
 
-    /// Uniquely borrow the `T` component of `entity`
+    /// Invoke `f` with unique access to every component registered as implementing `Dyn`
     ///
-    /// Panics if the component is already borrowed from another entity with the same components.
-    pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError> {
+    /// Visited components are flagged as changed. See `visit_trait`.
+    pub fn visit_trait_mut<Dyn: ?Sized + 'static>(&mut self, f: impl FnMut(Entity, &mut Dyn)) {
+        traits::visit_mut(
+            self.traits.get::<Dyn>(),
+            &self.entities.meta,
+            &mut self.archetypes,
+            f,
+        );
+    }
+
+    /// Invoke `visitor` on every component of `entity`, using dynamic borrow checking
+    ///
+    /// Each component is identified only by its [`TypeInfo`] and address, allowing generic tooling
+    /// such as hashing, size accounting, or custom serializers to inspect entities without
+    /// knowing their component types. Components are visited in arbitrary order.
+    ///
+    /// Panics if a component of `entity` is already uniquely borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((42u32, 1.0f64));
+    /// let mut bytes = 0;
+    /// world
+    ///     .visit_entity(e, &mut |ty: TypeInfo, _: *const u8| bytes += ty.layout().size())
+    ///     .unwrap();
+    /// assert_eq!(bytes, 12);
+    /// ```
+    #[cfg_attr(feature = "track-borrows", track_caller)]
+    pub fn visit_entity(
+        &self,
+        entity: Entity,
+        visitor: &mut dyn ComponentVisitor,
+    ) -> Result<(), NoSuchEntity> {
         let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
+        let archetype = &self.archetypes[loc.archetype as usize];
+        for &ty in archetype.types() {
+            archetype.borrow_dynamic(ty.id());
+            let _guard = traits::ReleaseOnDrop(archetype, ty.id());
+            unsafe {
+                let ptr = archetype
+                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                    .unwrap();
+                visitor.visit(ty, ptr.as_ptr());
+            }
         }
-        Ok(unsafe { RefMut::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+        Ok(())
     }
 
     /// Access an entity regardless of its component types
@@ -489,6 +3150,17 @@ impl World {
         })
     }
 
+    /// A handle to `entity` that checks its liveness on every use, if it's currently alive
+    ///
+    /// To also learn of despawns as they happen, e.g. to clear references held outside of any
+    /// handle, see [`drain_despawns`](Self::drain_despawns).
+    pub fn handle(&self, entity: Entity) -> Option<EntityHandle> {
+        if !self.contains(entity) {
+            return None;
+        }
+        Some(EntityHandle::new(entity, self.id))
+    }
+
     /// Given an id obtained from `Entity::id`, reconstruct the still-live `Entity`.
     ///
     /// # Safety
@@ -499,114 +3171,597 @@ impl World {
 
     /// Iterate over all entities in the world
     ///
-    /// Entities are yielded in arbitrary order. Prefer `World::query` for better performance when
-    /// components will be accessed in predictable patterns.
+    /// Entities are yielded in arbitrary order. Prefer `World::query` for better performance when
+    /// components will be accessed in predictable patterns.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(());
+    /// let ids = world.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    /// assert_eq!(ids.len(), 2);
+    /// assert!(ids.contains(&a));
+    /// assert!(ids.contains(&b));
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.archetypes, &self.entities)
+    }
+
+    /// Like `iter`, but returns child iterators of at most `batch_size` entities
+    ///
+    /// Each batch covers entities sharing the same components. Useful for distributing
+    /// whole-world passes, such as autosaving, over a threadpool.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// world.spawn((true,));
+    /// let batches = world.iter_batched(4).map(|batch| batch.len()).collect::<Vec<_>>();
+    /// assert_eq!(batches.iter().sum::<usize>(), 11);
+    /// assert_eq!(batches.len(), 4);
+    /// ```
+    pub fn iter_batched(&self, batch_size: u32) -> EntityBatches<'_> {
+        assert!(batch_size > 0, "batch size must be nonzero");
+        EntityBatches {
+            archetypes: self.archetypes.iter(),
+            entities: &self.entities,
+            current: None,
+            batch_size,
+            offset: 0,
+        }
+    }
+
+    /// Add `components` to `entity`
+    ///
+    /// Computational cost is proportional to the number of components `entity` has. If an entity
+    /// already has a component of a certain type, it is dropped and replaced. However many
+    /// components are added, the entity's data is moved to its new archetype only once, so prefer
+    /// a single `insert` of a bundle over several `insert_one` calls.
+    ///
+    /// When inserting a single component, see `insert_one` for convenience and for recovering the
+    /// replaced value.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// world.insert(e, (456, true));
+    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
+    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// ```
+    pub fn insert(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), NoSuchEntity> {
+        if !self.hooks.watches_inserts() {
+            return self.insert_inner(entity, components);
+        }
+        let types = components.with_ids(|ids| ids.to_vec());
+        self.insert_inner(entity, components)?;
+        let loc = self.entities.get(entity)?;
+        self.hooks.inserted(
+            entity,
+            &self.archetypes[loc.archetype as usize],
+            loc.index,
+            &types,
+        );
+        Ok(())
+    }
+
+    fn insert_inner(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), NoSuchEntity> {
+        use hashbrown::hash_map::Entry;
+
+        self.flush();
+        let loc = self.entities.get_mut(entity)?;
+        unsafe {
+            let key = components.key();
+            let arch = &mut self.archetypes[loc.archetype as usize];
+            let target = match key.and_then(|key| arch.insert_edge(key)) {
+                Some(target) => {
+                    // Drop components that will be replaced
+                    components.with_ids(|ids| {
+                        for ty in arch.types() {
+                            if ids.contains(&ty.id()) {
+                                let ptr = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index);
+                                ty.drop(ptr.unwrap().as_ptr());
+                            }
+                        }
+                    });
+                    target
+                }
+                None => {
+                    // Assemble Vec<TypeInfo> for the final entity
+                    let mut info = arch.types().to_vec();
+                    for ty in components.type_info() {
+                        if let Some(ptr) = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                        {
+                            ty.drop(ptr.as_ptr());
+                        } else {
+                            info.push(ty);
+                        }
+                    }
+                    info.sort();
+
+                    // Find the archetype it'll live in
+                    let elements = info.iter().map(|x| x.id()).collect();
+                    let target = match self.index.entry(elements) {
+                        Entry::Occupied(x) => *x.get(),
+                        Entry::Vacant(x) => {
+                            let index = self.archetypes.len() as u32;
+                            self.archetypes
+                                .push(Archetype::with_config(info, &self.config));
+                            x.insert(index);
+                            self.type_indices
+                                .index_archetype(self.archetypes.last_mut().unwrap());
+                            self.archetype_generation += 1;
+                            self.hooks.archetype_created(&self.archetypes);
+                            self.groups.archetype_created(&self.archetypes);
+                            index
+                        }
+                    };
+                    if let Some(key) = key {
+                        self.archetypes[loc.archetype as usize].set_insert_edge(key, target);
+                    }
+                    target
+                }
+            };
+
+            if target == loc.archetype {
+                // Update components in the current archetype
+                let arch = &mut self.archetypes[loc.archetype as usize];
+                components.put(|ptr, ty| {
+                    arch.put_dynamic(ptr, ty.id(), ty.layout().size(), loc.index);
+                });
+                return Ok(());
+            }
+
+            // Move into a new archetype
+            let (source_arch, target_arch) = index2(
+                &mut self.archetypes,
+                loc.archetype as usize,
+                target as usize,
+            );
+            let target_index = target_arch.allocate(entity.id);
+            #[cfg(feature = "debug-history")]
+            self.history
+                .record(entity.id, source_arch.types(), target_arch.types());
+            #[cfg(feature = "stats")]
+            {
+                self.counters.moves += 1;
+            }
+            loc.archetype = target;
+            let old_index = mem::replace(&mut loc.index, target_index);
+            if let Some(moved) =
+                source_arch.move_to(old_index, |ptr, ty, size, changed, added, tick| {
+                    target_arch.put_dynamic(ptr, ty, size, target_index);
+                    target_arch.set_changed(ty, target_index, changed);
+                    target_arch.set_added(ty, target_index, added);
+                    target_arch.set_tick(ty, target_index, tick);
+                })
+            {
+                self.entities.meta[moved as usize].location.index = old_index;
+            }
+            components.put(|ptr, ty| {
+                target_arch.put_dynamic(ptr, ty.id(), ty.layout().size(), target_index);
+            });
+        }
+        Ok(())
+    }
+
+    /// Add `component` to `entity`, returning the `T` it replaced, if any
+    ///
+    /// Like `HashMap::insert`, lets callers detect replacement and recover the previous value.
+    /// Replacing a component doesn't move the entity between archetypes. See `insert`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// assert_eq!(world.insert_one(e, true), Ok(None));
+    /// assert_eq!(world.insert_one(e, 456), Ok(Some(123)));
+    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
+    /// ```
+    pub fn insert_one<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        // Entities without components have no index
+        let existing = match loc.index {
+            u32::MAX => None,
+            index => unsafe {
+                archetype.get_dynamic(TypeId::of::<T>(), mem::size_of::<T>(), index)
+            },
+        };
+        let ptr = match existing {
+            Some(x) => x.as_ptr().cast::<T>(),
+            None => {
+                self.insert(entity, (component,))?;
+                return Ok(None);
+            }
+        };
+        let old = unsafe {
+            let old = ptr::replace(ptr, component);
+            archetype.set_changed(TypeId::of::<T>(), loc.index, true);
+            old
+        };
+        if self.hooks.watches_inserts() {
+            self.hooks.inserted(
+                entity,
+                &self.archetypes[loc.archetype as usize],
+                loc.index,
+                &[TypeId::of::<T>()],
+            );
+        }
+        Ok(Some(old))
+    }
+
+    /// Uniquely borrow `entity`'s `T`, first adding `T::default()` if it has none
+    ///
+    /// Replaces checking for a component, inserting it, then borrowing it with `get_mut`: the
+    /// entity's archetype is consulted once, and an entity that already has a `T` is left in place.
+    /// Adding the component moves the entity and runs insertion hooks, as for `insert`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Default)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// world.ensure::<Velocity>(e).unwrap().0 += 1.0;
+    /// world.ensure::<Velocity>(e).unwrap().0 += 1.0;
+    /// assert_eq!(world.get::<Velocity>(e).unwrap().0, 2.0);
+    /// ```
+    pub fn ensure<T: Component + Default>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<RefMut<'_, T>, NoSuchEntity> {
+        self.flush();
+        let mut loc = self.entities.get(entity)?;
+        if !self.archetypes[loc.archetype as usize].has::<T>() {
+            self.insert(entity, (T::default(),))?;
+            loc = self.entities.get(entity)?;
+        }
+        // Can't conflict with other borrows, since the world is uniquely borrowed
+        Ok(unsafe { RefMut::new(&self.archetypes[loc.archetype as usize], loc.index).unwrap() })
+    }
+
+    /// Add a clone of `component` to each of `entities`, returning the number of distinct entities
+    /// that exist
+    ///
+    /// Faster than calling `insert_one` for each entity when many share an archetype, such as when
+    /// tagging every entity in a freshly loaded chunk: entities are grouped by archetype, so the
+    /// destination is looked up and its storage grown once per group. Like `insert`, any existing
+    /// `T` is dropped and replaced. Entities that don't exist, and repeats of an entity, are
+    /// ignored.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Clone)]
+    /// struct Loaded;
+    ///
+    /// let mut world = World::new();
+    /// let chunk = (0..10).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// assert_eq!(world.insert_batch(&chunk, Loaded), 10);
+    /// assert_eq!(world.query::<&Loaded>().iter().count(), 10);
+    /// ```
+    pub fn insert_batch<T: Component + Clone>(
+        &mut self,
+        entities: &[Entity],
+        component: T,
+    ) -> usize {
+        self.flush();
+        let mut locations = entities
+            .iter()
+            .filter_map(|&entity| Some((self.entities.get(entity).ok()?.archetype, entity)))
+            .collect::<Vec<_>>();
+        locations.sort_unstable();
+        locations.dedup_by_key(|x| x.1);
+        let mut start = 0;
+        while start < locations.len() {
+            let source = locations[start].0;
+            let end = locations[start..]
+                .iter()
+                .position(|x| x.0 != source)
+                .map_or(locations.len(), |x| start + x);
+            let target = if self.archetypes[source as usize].has::<T>() {
+                source
+            } else {
+                let mut info = self.archetypes[source as usize].types().to_vec();
+                info.push(TypeInfo::of::<T>());
+                info.sort();
+                let target = self.archetype_for_types(info);
+                self.archetypes[target as usize].reserve((end - start) as u32);
+                target
+            };
+            for &(_, entity) in &locations[start..end] {
+                if target == source {
+                    let _ = self.insert_one(entity, component.clone());
+                    continue;
+                }
+                unsafe {
+                    self.move_with(entity, target, component.clone());
+                }
+            }
+            start = end;
+        }
+        locations.len()
+    }
+
+    /// Move `entity` into the archetype `target`, which must have exactly its component types
+    /// plus `T`, which it mustn't have
+    unsafe fn move_with<T: Component>(&mut self, entity: Entity, target: u32, component: T) {
+        let loc = self.entities.get_mut(entity).unwrap();
+        let (source_arch, target_arch) = index2(
+            &mut self.archetypes,
+            loc.archetype as usize,
+            target as usize,
+        );
+        let target_index = target_arch.allocate(entity.id);
+        #[cfg(feature = "debug-history")]
+        self.history
+            .record(entity.id, source_arch.types(), target_arch.types());
+        #[cfg(feature = "stats")]
+        {
+            self.counters.moves += 1;
+        }
+        loc.archetype = target;
+        let old_index = mem::replace(&mut loc.index, target_index);
+        if let Some(moved) =
+            source_arch.move_to(old_index, |ptr, ty, size, changed, added, tick| {
+                target_arch.put_dynamic(ptr, ty, size, target_index);
+                target_arch.set_changed(ty, target_index, changed);
+                target_arch.set_added(ty, target_index, added);
+                target_arch.set_tick(ty, target_index, tick);
+            })
+        {
+            self.entities.meta[moved as usize].location.index = old_index;
+        }
+        let mut component = mem::ManuallyDrop::new(component);
+        target_arch.put_dynamic(
+            (&mut *component as *mut T).cast(),
+            TypeId::of::<T>(),
+            mem::size_of::<T>(),
+            target_index,
+        );
+        if self.hooks.watches_inserts() {
+            self.hooks
+                .inserted(entity, target_arch, target_index, &[TypeId::of::<T>()]);
+        }
+    }
+
+    /// Add a component of a dynamically specified type to `entity`
+    ///
+    /// Useful for scripting layers that only know components as type-erased data. Like `insert`,
+    /// any existing component of the same type is dropped and replaced. See
+    /// [`TypeInfo::from_parts`] for defining component types at runtime.
+    ///
+    /// # Safety
+    ///
+    /// `component` must point to a valid value of the type described by `ty`, which is moved into
+    /// the world even on failure; the caller must not use or drop it afterwards.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn(());
-    /// let b = world.spawn(());
-    /// let ids = world.iter().map(|(id, _)| id).collect::<Vec<_>>();
-    /// assert_eq!(ids.len(), 2);
-    /// assert!(ids.contains(&a));
-    /// assert!(ids.contains(&b));
+    /// let e = world.spawn(());
+    /// let value = std::mem::ManuallyDrop::new(String::from("abc"));
+    /// unsafe {
+    ///     world.insert_dynamic(e, TypeInfo::of::<String>(), &*value as *const String as *const u8)
+    ///         .unwrap();
+    /// }
+    /// assert_eq!(*world.get::<String>(e).unwrap(), "abc");
     /// ```
-    pub fn iter(&self) -> Iter<'_> {
-        Iter::new(&self.archetypes, &self.entities)
+    pub unsafe fn insert_dynamic(
+        &mut self,
+        entity: Entity,
+        ty: TypeInfo,
+        component: *const u8,
+    ) -> Result<(), NoSuchEntity> {
+        if !self.contains(entity) {
+            ty.drop(component as *mut u8);
+            return Err(NoSuchEntity);
+        }
+        self.insert(
+            entity,
+            RawComponent {
+                ty,
+                ptr: component as *mut u8,
+            },
+        )
     }
 
-    /// Add `components` to `entity`
+    /// Locate the component of type `ty` of `entity`, if both exist
     ///
-    /// Computational cost is proportional to the number of components `entity` has. If an entity
-    /// already has a component of a certain type, it is dropped and replaced.
+    /// Type-erased counterpart to `get` for scripting layers. Access through the returned pointer
+    /// is not borrow-checked, and writes through it are not flagged as changes. The pointer is
+    /// invalidated when `entity` gains or loses components or is despawned, or when another entity
+    /// with the same components is despawned.
+    pub fn get_dynamic(&self, entity: Entity, ty: TypeId) -> Option<ptr::NonNull<u8>> {
+        let loc = self.entities.get(entity).ok()?;
+        let archetype = &self.archetypes[loc.archetype as usize];
+        let size = archetype.component_layout(ty)?.size();
+        unsafe { archetype.get_dynamic(ty, size, loc.index) }
+    }
+
+    /// Overwrite the existing `T` components of many entities at once
+    ///
+    /// Much cheaper than calling `get_mut` for each entity, since values are grouped by archetype
+    /// and written without dynamic borrow checks. Values for entities that no longer exist or lack
+    /// a `T` component are dropped. Returns the number of components written.
     ///
-    /// When inserting a single component, see `insert_one` for convenience.
+    /// Values are written in the order given, so if an entity appears more than once, the last
+    /// value for it wins. Each appearance counts towards the number returned.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let e = world.spawn((123, "abc"));
-    /// world.insert(e, (456, true));
-    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
-    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn((false,));
+    /// assert_eq!(world.set_batch(vec![(b, 20), (a, 10), (c, 30)]), 2);
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    /// assert_eq!(*world.get::<i32>(b).unwrap(), 20);
     /// ```
-    pub fn insert(
+    pub fn set_batch<T: Component>(
         &mut self,
-        entity: Entity,
-        components: impl DynamicBundle,
-    ) -> Result<(), NoSuchEntity> {
-        use hashbrown::hash_map::Entry;
+        values: impl IntoIterator<Item = (Entity, T)>,
+    ) -> usize {
+        let mut located = values
+            .into_iter()
+            .filter_map(|(entity, value)| Some((self.entities.get(entity).ok()?, value)))
+            .collect::<Vec<_>>();
+        // Stable, so duplicates are written in order
+        located.sort_by_key(|(loc, _)| loc.archetype);
 
-        self.flush();
-        let loc = self.entities.get_mut(entity)?;
-        unsafe {
-            // Assemble Vec<TypeInfo> for the final entity
-            let arch = &mut self.archetypes[loc.archetype as usize];
-            let mut info = arch.types().to_vec();
-            for ty in components.type_info() {
-                if let Some(ptr) = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index) {
-                    ty.drop(ptr.as_ptr());
-                } else {
-                    info.push(ty);
-                }
+        let mut written = 0;
+        let mut column = None;
+        let mut current = u32::MAX;
+        for (loc, value) in located {
+            if loc.archetype != current {
+                current = loc.archetype;
+                let archetype = &self.archetypes[current as usize];
+                column = archetype
+                    .get_base::<T>()
+                    .map(|base| (base, archetype.get_changed_base::<T>().unwrap()));
             }
-            info.sort();
-
-            // Find the archetype it'll live in
-            let elements = info.iter().map(|x| x.id()).collect();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    let index = self.archetypes.len() as u32;
-                    self.archetypes.push(Archetype::new(info));
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
+            if let Some((base, changed)) = column {
+                unsafe {
+                    *base.as_ptr().add(loc.index as usize) = value;
+                    *changed.as_ptr().add(loc.index as usize) = true;
                 }
-            };
+                written += 1;
+            }
+        }
+        written
+    }
 
-            if target == loc.archetype {
-                // Update components in the current archetype
-                let arch = &mut self.archetypes[loc.archetype as usize];
-                components.put(|ptr, ty| {
-                    arch.put_dynamic(ptr, ty.id(), ty.layout().size(), loc.index);
-                });
-                return Ok(());
+    /// The archetype and index of `entity`, if it exists and has been flushed
+    pub(crate) fn locate(&self, entity: Entity) -> Option<(u32, u32)> {
+        let loc = self.entities.get(entity).ok()?;
+        if loc.index == u32::MAX {
+            return None;
+        }
+        Some((loc.archetype, loc.index))
+    }
+
+    /// Copy every `T` component in the world, to be modified and swapped back in with
+    /// `swap_column`
+    pub fn stage_column<T: Component + Clone>(&self) -> StagedColumn<T> {
+        let columns = self
+            .archetypes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, archetype)| {
+                let column = archetype.get::<T>()?;
+                Some((i as u32, archetype.ids().to_vec(), column.to_vec()))
+            })
+            .collect();
+        StagedColumn { columns }
+    }
+
+    /// Exchange every `T` component in the world with the corresponding value in `staged`
+    ///
+    /// Work is proportional to the number of archetypes holding `T`, plus a bulk copy of the
+    /// data, rather than a lookup per entity. All swapped components are flagged as changed.
+    ///
+    /// Fails without modifying anything if any entity holding `T` has been spawned, despawned,
+    /// or moved between archetypes since `staged` was created.
+    pub fn swap_column<T: Component>(
+        &mut self,
+        staged: &mut StagedColumn<T>,
+    ) -> Result<(), StaleColumn> {
+        let mut expected = staged.columns.iter();
+        for (i, archetype) in self.archetypes.iter().enumerate() {
+            // Matches `stage_column`, which skips archetypes whose storage was never allocated
+            if archetype.get_base::<T>().is_none() {
+                continue;
             }
+            match expected.next() {
+                Some((id, ids, _)) if *id == i as u32 && ids[..] == *archetype.ids() => {}
+                None if archetype.is_empty() => {}
+                _ => return Err(StaleColumn),
+            }
+        }
+        if expected.next().is_some() {
+            return Err(StaleColumn);
+        }
 
-            // Move into a new archetype
-            let (source_arch, target_arch) = index2(
-                &mut self.archetypes,
-                loc.archetype as usize,
-                target as usize,
-            );
-            let target_index = target_arch.allocate(entity.id);
-            loc.archetype = target;
-            let old_index = mem::replace(&mut loc.index, target_index);
-            if let Some(moved) = source_arch.move_to(old_index, |ptr, ty, size| {
-                target_arch.put_dynamic(ptr, ty, size, target_index);
-            }) {
-                self.entities.meta[moved as usize].location.index = old_index;
+        for (id, _, values) in &mut staged.columns {
+            let archetype = &mut self.archetypes[*id as usize];
+            let len = archetype.len() as usize;
+            unsafe {
+                let base = archetype.get_base::<T>().unwrap().as_ptr();
+                core::slice::from_raw_parts_mut(base, len).swap_with_slice(values);
+                let changed = archetype.get_changed_base::<T>().unwrap().as_ptr();
+                core::slice::from_raw_parts_mut(changed, len).fill(true);
             }
-            components.put(|ptr, ty| {
-                target_arch.put_dynamic(ptr, ty.id(), ty.layout().size(), target_index);
-            });
         }
         Ok(())
     }
 
-    /// Add `component` to `entity`
+    /// Exchange the `A` and `B` components of every entity that has both
     ///
-    /// See `insert`.
-    pub fn insert_one(
-        &mut self,
-        entity: Entity,
-        component: impl Component,
-    ) -> Result<(), NoSuchEntity> {
-        self.insert(entity, (component,))
+    /// Useful for double buffering, e.g. of `Position` and `PreviousPosition`: rather than copying
+    /// each entity's components, the storage of the two columns is swapped, so work is
+    /// proportional to the number of archetypes. Both components of every affected entity are
+    /// flagged as changed.
+    ///
+    /// Panics if `A` and `B` have different layouts.
+    ///
+    /// # Safety
+    ///
+    /// Every `A` must be a valid `B` and vice versa, as when both are `#[repr(transparent)]`
+    /// wrappers of the same type with no invariants of their own.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[repr(transparent)]
+    /// struct Position([f32; 3]);
+    /// #[repr(transparent)]
+    /// struct PreviousPosition([f32; 3]);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((Position([1.0; 3]), PreviousPosition([0.0; 3])));
+    /// // Start the tick by making the current position the previous one
+    /// unsafe {
+    ///     world.swap_columns::<Position, PreviousPosition>();
+    /// }
+    /// assert_eq!(world.get::<PreviousPosition>(a).unwrap().0, [1.0; 3]);
+    /// world.get_mut::<Position>(a).unwrap().0 = [2.0; 3];
+    /// ```
+    pub unsafe fn swap_columns<A: Component, B: Component>(&mut self) {
+        assert_eq!(
+            core::alloc::Layout::new::<A>(),
+            core::alloc::Layout::new::<B>(),
+            "swapped components must have the same layout"
+        );
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            return;
+        }
+        for archetype in &mut self.archetypes {
+            archetype.swap_columns(TypeId::of::<A>(), TypeId::of::<B>());
+        }
     }
 
     /// Remove components from `entity`
@@ -614,7 +3769,7 @@ impl World {
     /// Computational cost is proportional to the number of components `entity` has. The entity
     /// itself is not removed, even if no components remain; use `despawn` for that. If any
     /// component in `T` is not present in `entity`, no components are removed and an error is
-    /// returned.
+    /// returned. As with `insert`, the entity's remaining data is moved only once.
     ///
     /// When removing a single component, see `remove_one` for convenience.
     ///
@@ -629,51 +3784,163 @@ impl World {
     /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
     /// ```
     pub fn remove<T: Bundle>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        T::with_static_ids(|removed| unsafe {
+            self.remove_inner(entity, T::static_key(), removed, |archetype, index| {
+                T::get(|ty| archetype.get_dynamic(ty.id(), ty.layout().size(), index))
+            })
+        })
+    }
+
+    /// Remove and drop the component of type `ty` from `entity`
+    #[cfg(feature = "abi")]
+    pub(crate) fn remove_dynamic(
+        &mut self,
+        entity: Entity,
+        ty: TypeInfo,
+    ) -> Result<(), ComponentError> {
+        unsafe {
+            self.remove_inner(entity, None, &[ty.id()], |archetype, index| {
+                let ptr = archetype
+                    .get_dynamic(ty.id(), ty.layout().size(), index)
+                    .ok_or_else(|| MissingComponent::dynamic(ty))?;
+                ty.drop(ptr.as_ptr());
+                Ok(())
+            })
+        }
+    }
+
+    /// Pointer to the component of type `ty` of `entity`, which is flagged as changed
+    #[cfg(feature = "abi")]
+    pub(crate) fn get_dynamic_mut(
+        &mut self,
+        entity: Entity,
+        ty: TypeInfo,
+    ) -> Option<ptr::NonNull<u8>> {
+        let loc = self.entities.get(entity).ok()?;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        unsafe {
+            let ptr = archetype.get_dynamic(ty.id(), ty.layout().size(), loc.index)?;
+            archetype.set_changed(ty.id(), loc.index, true);
+            Some(ptr)
+        }
+    }
+
+    /// Invoke `f` on every entity with a component of type `ty`, which is flagged as changed
+    #[cfg(feature = "abi")]
+    pub(crate) fn for_each_dynamic(
+        &mut self,
+        ty: TypeInfo,
+        mut f: impl FnMut(Entity, ptr::NonNull<u8>),
+    ) {
+        for archetype in &mut self.archetypes {
+            if !archetype.has_dynamic(ty.id()) {
+                continue;
+            }
+            for index in 0..archetype.len() {
+                unsafe {
+                    let id = archetype.ids()[index as usize];
+                    let entity = self.entities.resolve_unknown_gen(id);
+                    let ptr = archetype
+                        .get_dynamic(ty.id(), ty.layout().size(), index)
+                        .unwrap();
+                    archetype.set_changed(ty.id(), index, true);
+                    f(entity, ptr);
+                }
+            }
+        }
+    }
+
+    /// Move `entity` to the archetype without the `removed` types, after `extract` takes ownership
+    /// of the removed components from the original archetype at the given index
+    ///
+    /// `key` identifies the set of `removed` types as in `DynamicBundle::key`.
+    ///
+    /// # Safety
+    ///
+    /// `extract` must take ownership of exactly the components in `removed`, or fail without taking
+    /// ownership of any.
+    unsafe fn remove_inner<R>(
+        &mut self,
+        entity: Entity,
+        key: Option<TypeId>,
+        removed: &[TypeId],
+        extract: impl FnOnce(&Archetype, u32) -> Result<R, MissingComponent>,
+    ) -> Result<R, ComponentError> {
         use hashbrown::hash_map::Entry;
 
         self.flush();
         let loc = self.entities.get_mut(entity)?;
-        unsafe {
-            let removed = T::with_static_ids(|ids| ids.iter().copied().collect::<HashSet<_>>());
-            let info = self.archetypes[loc.archetype as usize]
-                .types()
-                .iter()
-                .cloned()
-                .filter(|x| !removed.contains(&x.id()))
-                .collect::<Vec<_>>();
-            let elements = info.iter().map(|x| x.id()).collect();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    self.archetypes.push(Archetype::new(info));
-                    let index = (self.archetypes.len() - 1) as u32;
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
+        {
+            let source_arch = &self.archetypes[loc.archetype as usize];
+            let target = match key.and_then(|key| source_arch.remove_edge(key)) {
+                Some(target) => target,
+                None => {
+                    let info = source_arch
+                        .types()
+                        .iter()
+                        .cloned()
+                        .filter(|x| !removed.contains(&x.id()))
+                        .collect::<Vec<_>>();
+                    let elements = info.iter().map(|x| x.id()).collect();
+                    let target = match self.index.entry(elements) {
+                        Entry::Occupied(x) => *x.get(),
+                        Entry::Vacant(x) => {
+                            self.archetypes
+                                .push(Archetype::with_config(info, &self.config));
+                            let index = (self.archetypes.len() - 1) as u32;
+                            x.insert(index);
+                            self.type_indices
+                                .index_archetype(self.archetypes.last_mut().unwrap());
+                            self.archetype_generation += 1;
+                            self.hooks.archetype_created(&self.archetypes);
+                            self.groups.archetype_created(&self.archetypes);
+                            index
+                        }
+                    };
+                    if let Some(key) = key {
+                        self.archetypes[loc.archetype as usize].set_remove_edge(key, target);
+                    }
+                    target
                 }
             };
             let old_index = loc.index;
             let source_arch = &self.archetypes[loc.archetype as usize];
-            let bundle =
-                T::get(|ty| source_arch.get_dynamic(ty.id(), ty.layout().size(), old_index))?;
+            if removed.iter().all(|&ty| source_arch.has_dynamic(ty)) {
+                self.hooks.removed(entity, source_arch, old_index, removed);
+            }
+            let bundle = extract(source_arch, old_index)?;
             // If we actually removed any components, the entity needs to be moved into a new archetype
-            if loc.archetype != target {
+            let source = loc.archetype;
+            if source != target {
                 let (source_arch, target_arch) = index2(
                     &mut self.archetypes,
                     loc.archetype as usize,
                     target as usize,
                 );
                 let target_index = target_arch.allocate(entity.id);
+                #[cfg(feature = "debug-history")]
+                self.history
+                    .record(entity.id, source_arch.types(), target_arch.types());
+                #[cfg(feature = "stats")]
+                {
+                    self.counters.moves += 1;
+                }
                 loc.archetype = target;
                 loc.index = target_index;
-                if let Some(moved) = source_arch.move_to(old_index, |src, ty, size| {
-                    // Only move the components present in the target archetype, i.e. the non-removed ones.
-                    if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
-                        ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
-                    }
-                }) {
+                if let Some(moved) =
+                    source_arch.move_to(old_index, |src, ty, size, changed, added, tick| {
+                        // Only move the components present in the target archetype, i.e. the non-removed ones.
+                        if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
+                            ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
+                            target_arch.set_changed(ty, target_index, changed);
+                            target_arch.set_added(ty, target_index, added);
+                            target_arch.set_tick(ty, target_index, tick);
+                        }
+                    })
+                {
                     self.entities.meta[moved as usize].location.index = old_index;
                 }
+                self.note_vacated(source);
             }
             Ok(bundle)
         }
@@ -688,12 +3955,17 @@ impl World {
 
     /// Borrow the `T` component of `entity` without safety checks
     ///
-    /// Should only be used as a building block for safe abstractions.
+    /// Should only be used as a building block for safe abstractions, or in hot paths where
+    /// aliasing is ruled out statically. No dynamic borrow is acquired or released, and no guard
+    /// is returned. The entity's generation is still compared against the handle's, since it's
+    /// read along with the location that must be looked up anyway, so stale handles are reported
+    /// as `NoSuchEntity` rather than aliasing the components of whichever entity reused the ID.
     ///
     /// # Safety
     ///
     /// `entity` must have been previously obtained from this `World`, and no unique borrow of the
     /// same component of `entity` may be live simultaneous to the returned reference.
+    #[inline]
     pub unsafe fn get_unchecked<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
         let loc = self.entities.get(entity)?;
         if loc.archetype == 0 {
@@ -708,12 +3980,30 @@ impl World {
 
     /// Uniquely borrow the `T` component of `entity` without safety checks
     ///
-    /// Should only be used as a building block for safe abstractions.
+    /// Flags the component as changed, but otherwise behaves like `get_unchecked`.
     ///
     /// # Safety
     ///
     /// `entity` must have been previously obtained from this `World`, and no borrow of the same
     /// component of `entity` may be live simultaneous to the returned reference.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::new();
+    /// let entities = (0..4)
+    ///     .map(|i| world.spawn((Position(i as f32),)))
+    ///     .collect::<Vec<_>>();
+    /// for &e in &entities {
+    ///     // Safety: each entity is visited once, and nothing else borrows `Position`
+    ///     unsafe { world.get_unchecked_mut::<Position>(e).unwrap().0 += 1.0 };
+    /// }
+    /// assert_eq!(world.get::<Position>(entities[3]).unwrap().0, 4.0);
+    /// ```
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
     pub unsafe fn get_unchecked_mut<T: Component>(
         &self,
         entity: Entity,
@@ -722,22 +4012,82 @@ impl World {
         if loc.archetype == 0 {
             return Err(MissingComponent::new::<T>().into());
         }
-        Ok(&mut *self.archetypes[loc.archetype as usize]
+        let archetype = &self.archetypes[loc.archetype as usize];
+        let base = archetype
             .get_base::<T>()
-            .ok_or_else(MissingComponent::new::<T>)?
+            .ok_or_else(MissingComponent::new::<T>)?;
+        *archetype
+            .get_changed_base::<T>()
+            .unwrap()
             .as_ptr()
-            .add(loc.index as usize))
+            .add(loc.index as usize) = true;
+        Ok(&mut *base.as_ptr().add(loc.index as usize))
     }
 
     /// Convert all reserved entities into empty entities that can be iterated and accessed
     ///
-    /// Invoked implicitly by `spawn`, `despawn`, `insert`, and `remove`.
+    /// Invoked implicitly by `spawn`, `despawn`, `insert`, and `remove`. To apply structural
+    /// changes recorded while iterating a query, see [`CommandBuffer::run_on`].
     pub fn flush(&mut self) {
         let arch = &mut self.archetypes[0];
         self.entities
             .flush(|id, location| location.index = unsafe { arch.allocate(id) });
     }
 
+    /// Apply the operations recorded in each of `buffers`, leaving them empty
+    ///
+    /// Equivalent to calling [`CommandBuffer::run_on`] on each buffer in turn, except that all
+    /// spawns across every buffer are grouped by component types so that each target archetype is
+    /// looked up and grown only once. Useful for applying buffers recorded by many threads at the
+    /// end of a tick without paying for a separate pass per buffer.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut buffers = (0..4).map(|_| CommandBuffer::new()).collect::<Vec<_>>();
+    /// for (i, cmd) in buffers.iter_mut().enumerate() {
+    ///     cmd.spawn((i as i32, true));
+    /// }
+    /// world.apply_all(&mut buffers);
+    /// assert_eq!(world.query::<(&i32, &bool)>().iter().count(), 4);
+    /// assert!(buffers.iter().all(|x| x.is_empty()));
+    /// ```
+    pub fn apply_all(&mut self, buffers: &mut [CommandBuffer]) {
+        crate::command_buffer::apply_all(self, buffers);
+    }
+
+    /// The [`ArchetypeId`] of `entity`, identifying its set of component types
+    ///
+    /// As cheap as `contains`. Useful for bucketing entities by signature, e.g. to reuse one
+    /// serializer for every entity with the same components rather than dispatching per component
+    /// per entity.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((789,));
+    /// let signature = world.signature(a).unwrap();
+    /// assert_eq!(signature, world.signature(b).unwrap());
+    /// assert_ne!(signature, world.signature(c).unwrap());
+    /// assert!(signature.contains::<bool>(&world));
+    /// assert!(!signature.contains::<&str>(&world));
+    /// assert_eq!(world.archetype(signature).len(), 2);
+    /// ```
+    pub fn signature(&self, entity: Entity) -> Result<ArchetypeId, NoSuchEntity> {
+        Ok(ArchetypeId(self.entities.get(entity)?.archetype))
+    }
+
+    /// The archetype identified by `id`
+    ///
+    /// Panics if `id` came from a different world with more archetypes.
+    pub fn archetype(&self, id: ArchetypeId) -> &Archetype {
+        &self.archetypes[id.0 as usize]
+    }
+
     /// Inspect the archetypes that entities are organized into
     ///
     /// Useful for dynamically scheduling concurrent queries by checking borrows in advance, and for
@@ -746,6 +4096,29 @@ impl World {
         self.archetypes.iter()
     }
 
+    /// Approximate number of bytes allocated for storing entities and their components
+    ///
+    /// Includes unused capacity. Compare with the [`memory_usage`](Archetype::memory_usage) of each
+    /// of `archetypes` to find where memory is going, e.g. when diagnosing bloat caused by
+    /// fragmentation into many small archetypes on a long-running server.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let before = world.memory_usage();
+    /// world.spawn_batch((0..1000).map(|i| (i, i as f64)));
+    /// assert!(world.memory_usage() >= before + 1000 * 12);
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        self.entities.memory_usage()
+            + self
+                .archetypes
+                .iter()
+                .map(Archetype::memory_usage)
+                .sum::<usize>()
+    }
+
     /// Returns a distinct value after `archetypes` is changed
     ///
     /// Store the current value after deriving information from `archetypes`, then check whether the
@@ -769,6 +4142,24 @@ impl World {
     }
 
     /// Number of currently live entities
+    ///
+    /// Maintained as entities are spawned and despawned, so this does not iterate. Entities
+    /// reserved with `reserve_entity` or `reserve_entities` are counted once flushed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// world.spawn(());
+    /// assert_eq!(world.len(), 2);
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(world.len(), 1);
+    /// world.reserve_entity();
+    /// assert_eq!(world.len(), 1);
+    /// world.flush();
+    /// assert_eq!(world.len(), 2);
+    /// ```
     #[inline]
     pub fn len(&self) -> u32 {
         self.entities.len()
@@ -781,8 +4172,12 @@ impl World {
     }
 }
 
-unsafe impl Send for World {}
-unsafe impl Sync for World {}
+// `World` is `Send + Sync` automatically: the storage it's built from justifies that locally.
+// Keep it that way, so that adding a field that isn't thread-safe fails to compile.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<World>();
+};
 
 impl Default for World {
     fn default() -> Self {
@@ -813,6 +4208,11 @@ pub enum ComponentError {
     NoSuchEntity,
     /// The entity did not have a requested component
     MissingComponent(MissingComponent),
+    /// The component was already borrowed incompatibly
+    ///
+    /// Only reported by accessors that don't panic on conflicting borrows, such as
+    /// `World::try_get`.
+    Borrowed(BorrowError),
 }
 
 #[cfg(feature = "std")]
@@ -824,6 +4224,7 @@ impl fmt::Display for ComponentError {
         match *self {
             NoSuchEntity => f.write_str("no such entity"),
             MissingComponent(ref x) => x.fmt(f),
+            Borrowed(ref x) => x.fmt(f),
         }
     }
 }
@@ -840,6 +4241,49 @@ impl From<MissingComponent> for ComponentError {
     }
 }
 
+/// Inconsistency found by [`World::validate`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum IntegrityError {
+    /// An archetype row holds an entity ID whose location doesn't point back to that row
+    MisplacedEntity {
+        /// ID held by the row
+        id: u32,
+        /// Index of the archetype
+        archetype: u32,
+        /// Index of the row within the archetype
+        index: u32,
+    },
+    /// A live entity isn't held by any archetype row
+    DanglingEntity(Entity),
+    /// An entity ID that's free, reserved, or retired is held by an archetype row
+    IdleEntityInUse(u32),
+    /// A column of components of the named type is borrowed
+    Borrowed(&'static str),
+}
+
+#[cfg(feature = "std")]
+impl Error for IntegrityError {}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IntegrityError::*;
+        match *self {
+            MisplacedEntity {
+                id,
+                archetype,
+                index,
+            } => write!(
+                f,
+                "row {} of archetype {} holds entity ID {}, which is located elsewhere",
+                index, archetype, id
+            ),
+            DanglingEntity(entity) => write!(f, "entity {:?} has no archetype row", entity),
+            IdleEntityInUse(id) => write!(f, "unused entity ID {} is held by a row", id),
+            Borrowed(name) => write!(f, "{} components are borrowed", name),
+        }
+    }
+}
+
 /// Errors that arise when querying a single entity
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum QueryOneError {
@@ -868,6 +4312,22 @@ impl From<NoSuchEntity> for QueryOneError {
     }
 }
 
+/// Receives the components of an entity from [`World::visit_entity`]
+///
+/// Implemented for closures accepting the same arguments as `visit`.
+pub trait ComponentVisitor {
+    /// Inspect a component of type `ty` stored at `ptr`
+    ///
+    /// `ptr` is valid for reads of `ty.layout().size()` bytes for the duration of the call.
+    fn visit(&mut self, ty: TypeInfo, ptr: *const u8);
+}
+
+impl<F: FnMut(TypeInfo, *const u8)> ComponentVisitor for F {
+    fn visit(&mut self, ty: TypeInfo, ptr: *const u8) {
+        self(ty, ptr)
+    }
+}
+
 /// Types that can be components, implemented automatically for all `Send + Sync + 'static` types
 ///
 /// This is just a convenient shorthand for `Send + Sync + 'static`, and never needs to be
@@ -881,6 +4341,7 @@ pub struct Iter<'a> {
     entities: &'a Entities,
     current: Option<&'a Archetype>,
     index: u32,
+    remaining: usize,
 }
 
 impl<'a> Iter<'a> {
@@ -890,6 +4351,7 @@ impl<'a> Iter<'a> {
             entities,
             current: None,
             index: 0,
+            remaining: archetypes.iter().map(|x| x.len() as usize).sum(),
         }
     }
 }
@@ -907,12 +4369,13 @@ impl<'a> Iterator for Iter<'a> {
                     self.index = 0;
                 }
                 Some(current) => {
-                    if self.index == current.len() as u32 {
+                    if self.index == current.len() {
                         self.current = None;
                         continue;
                     }
                     let index = self.index;
                     self.index += 1;
+                    self.remaining -= 1;
                     let id = current.entity_id(index);
                     return Some((
                         Entity {
@@ -934,7 +4397,84 @@ impl<'a> Iterator for Iter<'a> {
 impl ExactSizeIterator for Iter<'_> {
     #[inline]
     fn len(&self) -> usize {
-        self.entities.len() as usize
+        self.remaining
+    }
+}
+
+/// Iterator over batches of entities returned by `World::iter_batched`
+pub struct EntityBatches<'a> {
+    archetypes: core::slice::Iter<'a, Archetype>,
+    entities: &'a Entities,
+    current: Option<&'a Archetype>,
+    batch_size: u32,
+    offset: u32,
+}
+
+unsafe impl Send for EntityBatches<'_> {}
+unsafe impl Sync for EntityBatches<'_> {}
+
+impl<'a> Iterator for EntityBatches<'a> {
+    type Item = EntityBatch<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = match self.current {
+                Some(x) if self.offset < x.len() => x,
+                _ => {
+                    self.current = Some(self.archetypes.next()?);
+                    self.offset = 0;
+                    continue;
+                }
+            };
+            let start = self.offset;
+            self.offset += self.batch_size.min(current.len() - start);
+            return Some(EntityBatch {
+                archetype: current,
+                entities: self.entities,
+                index: start,
+                end: self.offset,
+            });
+        }
+    }
+}
+
+/// A sequence of entities sharing the same components, yielded by `World::iter_batched`
+pub struct EntityBatch<'a> {
+    archetype: &'a Archetype,
+    entities: &'a Entities,
+    index: u32,
+    end: u32,
+}
+
+unsafe impl Send for EntityBatch<'_> {}
+unsafe impl Sync for EntityBatch<'_> {}
+
+impl<'a> Iterator for EntityBatch<'a> {
+    type Item = (Entity, EntityRef<'a>);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.end {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        let id = self.archetype.entity_id(index);
+        Some((
+            Entity {
+                id,
+                generation: self.entities.meta[id as usize].generation,
+            },
+            unsafe { EntityRef::new(self.archetype, index) },
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl ExactSizeIterator for EntityBatch<'_> {
+    #[inline]
+    fn len(&self) -> usize {
+        (self.end - self.index) as usize
     }
 }
 
@@ -957,6 +4497,40 @@ impl<A: DynamicBundle> core::iter::FromIterator<A> for World {
     }
 }
 
+/// Why an entity was despawned, as recorded by [`World::despawn_with_reason`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum DespawnReason {
+    /// The entity was destroyed within the simulation, e.g. by dying
+    Killed,
+    /// The entity was removed from the simulation but may be loaded again, e.g. with its chunk
+    Unloaded,
+    /// The entity was folded into another entity
+    Merged,
+    /// An application-defined reason
+    Custom(u32),
+}
+
+/// Identifies the archetype of the entities having a particular set of component types
+///
+/// Obtained from [`World::signature`]. Within a world, every entity with exactly the same component
+/// types has the same `ArchetypeId`, and IDs are never reused for other sets of types, so they
+/// can key per-signature state such as serializers. IDs are cheap to copy, compare, and hash, but
+/// aren't meaningful across worlds.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ArchetypeId(u32);
+
+impl ArchetypeId {
+    /// Position of the archetype in [`World::archetypes`]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Whether entities with this signature in `world` have a `T` component
+    pub fn contains<T: Component>(self, world: &World) -> bool {
+        world.archetypes[self.0 as usize].has::<T>()
+    }
+}
+
 /// Determines freshness of information derived from `World::archetypes`
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct ArchetypesGeneration(u64);
@@ -969,6 +4543,7 @@ where
 {
     inner: I,
     entities: &'a mut Entities,
+    hooks: &'a mut Hooks,
     archetype_id: u32,
     archetype: &'a mut Archetype,
 }
@@ -1003,6 +4578,7 @@ where
                 archetype: self.archetype_id,
                 index,
             };
+            self.hooks.spawned(entity, self.archetype, index);
         }
         Some(entity)
     }
@@ -1033,7 +4609,7 @@ impl Iterator for SpawnColumnBatchIter<'_> {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Entity> {
-        let id = self.id_alloc.next(&self.entities)?;
+        let id = self.id_alloc.next(self.entities)?;
         Some(unsafe { self.entities.resolve_unknown_gen(id) })
     }
 
@@ -1044,7 +4620,7 @@ impl Iterator for SpawnColumnBatchIter<'_> {
 
 impl ExactSizeIterator for SpawnColumnBatchIter<'_> {
     fn len(&self) -> usize {
-        self.id_alloc.len(&self.entities)
+        self.id_alloc.len(self.entities)
     }
 }
 
@@ -0,0 +1,119 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::archetype::Archetype;
+use crate::entities::{EntityMeta, Location};
+use crate::world_snapshot::WorldSnapshot;
+use crate::Entity;
+
+/// The differences between a [`WorldSnapshot`] and the current state of its world, computed by
+/// [`World::diff`](crate::World::diff)
+///
+/// Lists are ordered by archetype, then by position within the archetype, so a diff of the same
+/// states always comes out the same. With the `serde` feature, a diff can be sent to another world
+/// with `serialize::registry::serialize_diff` and applied there with
+/// `serialize::registry::apply_diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorldDiff {
+    pub(crate) spawned: Vec<Entity>,
+    pub(crate) despawned: Vec<Entity>,
+    pub(crate) changed: Vec<(Entity, TypeId)>,
+    pub(crate) removed: Vec<(Entity, TypeId)>,
+}
+
+impl WorldDiff {
+    /// Compare the entities captured by `baseline` with those in `meta` and `archetypes`, the
+    /// storage of the world `baseline` was taken from
+    pub(crate) fn new(
+        baseline: &WorldSnapshot,
+        meta: &[EntityMeta],
+        archetypes: &[Archetype],
+    ) -> Self {
+        let mut diff = Self::default();
+        for (index, snapshot) in baseline.archetypes.iter().enumerate() {
+            // Archetypes are never removed, so the one at the same index has the baseline's types
+            let old = &archetypes[index];
+            for &id in snapshot.ids() {
+                let entity = Entity {
+                    id,
+                    generation: baseline.entities.meta[id as usize].generation,
+                };
+                let loc = match locate(meta, entity) {
+                    Some(x) => x,
+                    None => {
+                        diff.despawned.push(entity);
+                        continue;
+                    }
+                };
+                let new = &archetypes[loc.archetype as usize];
+                for ty in new.types() {
+                    // Components added since the baseline count as changed
+                    let changed = !old.has_dynamic(ty.id())
+                        || unsafe { *new.changed_dynamic(ty.id(), loc.index).unwrap().as_ptr() };
+                    if changed {
+                        diff.changed.push((entity, ty.id()));
+                    }
+                }
+                for ty in old.types() {
+                    if !new.has_dynamic(ty.id()) {
+                        diff.removed.push((entity, ty.id()));
+                    }
+                }
+            }
+        }
+        for archetype in archetypes {
+            for &id in archetype.ids() {
+                let entity = Entity {
+                    id,
+                    generation: meta[id as usize].generation,
+                };
+                if locate(&baseline.entities.meta, entity).is_none() {
+                    diff.spawned.push(entity);
+                }
+            }
+        }
+        diff
+    }
+
+    /// Entities that exist now but didn't in the baseline
+    pub fn spawned(&self) -> &[Entity] {
+        &self.spawned
+    }
+
+    /// Entities that existed in the baseline but don't now
+    ///
+    /// Includes entities whose IDs have since been reused, which also appear in `spawned` under
+    /// their new handles.
+    pub fn despawned(&self) -> &[Entity] {
+        &self.despawned
+    }
+
+    /// Components of surviving entities that were added since the baseline, or that are flagged
+    /// as changed
+    ///
+    /// Change flags aren't tied to snapshots, so `World::clear_changed` should be called when the
+    /// baseline is taken for this to include only components changed since.
+    pub fn changed(&self) -> &[(Entity, TypeId)] {
+        &self.changed
+    }
+
+    /// Components of surviving entities that were removed since the baseline
+    pub fn removed(&self) -> &[(Entity, TypeId)] {
+        &self.removed
+    }
+
+    /// Whether the world appears unchanged since the baseline
+    pub fn is_empty(&self) -> bool {
+        self.spawned.is_empty()
+            && self.despawned.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+    }
+}
+
+/// Where `entity` is stored according to `meta`, if it's alive
+fn locate(meta: &[EntityMeta], entity: Entity) -> Option<Location> {
+    let x = meta.get(entity.id as usize)?;
+    // Freed IDs keep an invalid index until reused
+    (x.generation == entity.generation && x.location.index != u32::MAX).then_some(x.location)
+}
@@ -0,0 +1,191 @@
+use crate::alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use crate::alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+
+use crate::archetype::{Archetype, TypeInfo};
+use crate::dense_map::DenseIndices;
+use crate::entities::Entities;
+use crate::hierarchy::Hierarchy;
+use crate::names::Names;
+use crate::sparse::SparseSnapshot;
+use crate::{CloneError, ComponentRegistry};
+
+/// A copy of every entity and component of a `World`, taken by [`World::snapshot`] and reinstated
+/// by [`World::restore`]
+///
+/// [`World::snapshot`]: crate::World::snapshot
+/// [`World::restore`]: crate::World::restore
+pub struct WorldSnapshot {
+    pub(crate) world_id: usize,
+    pub(crate) entities: Entities,
+    pub(crate) hierarchy: Hierarchy,
+    pub(crate) names: Names,
+    pub(crate) dense_indices: DenseIndices,
+    pub(crate) sparse: SparseSnapshot,
+    /// Contents of each of the world's archetypes, by index
+    pub(crate) archetypes: Vec<ArchetypeSnapshot>,
+}
+
+impl WorldSnapshot {
+    /// Number of entities captured
+    pub fn len(&self) -> u32 {
+        self.entities.len()
+    }
+
+    /// Whether no entities were captured
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Safety: components are `Send + Sync`, and are only accessed through `&self` to be cloned
+unsafe impl Send for WorldSnapshot {}
+unsafe impl Sync for WorldSnapshot {}
+
+pub(crate) struct ArchetypeSnapshot {
+    ids: Vec<u32>,
+    columns: Vec<Column>,
+}
+
+impl ArchetypeSnapshot {
+    /// Capture clones of every component in `archetype`
+    ///
+    /// # Safety
+    ///
+    /// No components in `archetype` may be uniquely borrowed.
+    pub(crate) unsafe fn new(
+        archetype: &Archetype,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, CloneError> {
+        if archetype.is_empty() {
+            // Types of past entities needn't be cloneable
+            return Ok(Self {
+                ids: Vec::new(),
+                columns: Vec::new(),
+            });
+        }
+        // Check every type before cloning any, so a failure needn't unwind partial work
+        let mut columns = Vec::with_capacity(archetype.types().len());
+        for &ty in archetype.types() {
+            let meta = registry.get_by_id(ty.id());
+            let clone = meta
+                .and_then(|x| x.clone_fn())
+                .ok_or(CloneError::Uncloneable(ty.id()))?;
+            columns.push((ty, (!meta.unwrap().is_copy()).then_some(clone)));
+        }
+        let len = archetype.len() as usize;
+        Ok(Self {
+            ids: archetype.ids().to_vec(),
+            columns: columns
+                .into_iter()
+                .map(|(ty, clone)| {
+                    let src = archetype
+                        .get_dynamic(ty.id(), ty.layout().size(), 0)
+                        .unwrap();
+                    Column::new(ty, clone, src.as_ptr(), len)
+                })
+                .collect(),
+        })
+    }
+
+    /// IDs of the captured entities, in archetype order
+    pub(crate) fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// Append clones of the captured components to `archetype`, which must have the same types
+    pub(crate) unsafe fn restore(&self, archetype: &mut Archetype) {
+        archetype.reserve(self.ids.len() as u32);
+        for (index, &id) in self.ids.iter().enumerate() {
+            // Write into the slot just past the end before claiming it, so that a panicking clone
+            // can only leak
+            for column in &self.columns {
+                let dst = archetype
+                    .get_dynamic(column.ty.id(), column.ty.layout().size(), archetype.len())
+                    .unwrap();
+                column.clone_to(index, dst.as_ptr());
+            }
+            archetype.allocate(id);
+        }
+    }
+}
+
+/// Clones of the components of one type from an archetype
+struct Column {
+    ty: TypeInfo,
+    /// How to clone a component, or `None` if a bitwise copy suffices
+    clone: Option<unsafe fn(*const u8, *mut u8)>,
+    data: NonNull<u8>,
+    /// Number of initialized components
+    len: usize,
+    capacity: usize,
+}
+
+impl Column {
+    /// Clone the `len` components of type `ty` at `src`
+    unsafe fn new(
+        ty: TypeInfo,
+        clone: Option<unsafe fn(*const u8, *mut u8)>,
+        src: *const u8,
+        len: usize,
+    ) -> Self {
+        let size = ty.layout().size();
+        let data = if size == 0 || len == 0 {
+            NonNull::new_unchecked(ty.layout().align() as *mut u8)
+        } else {
+            let layout = Self::layout(ty, len);
+            NonNull::new(alloc(layout)).unwrap_or_else(|| handle_alloc_error(layout))
+        };
+        let mut column = Self {
+            ty,
+            clone,
+            data,
+            len: 0,
+            capacity: len,
+        };
+        match clone {
+            None => {
+                ptr::copy_nonoverlapping(src, data.as_ptr(), size * len);
+                column.len = len;
+            }
+            Some(clone) => {
+                for i in 0..len {
+                    clone(src.add(i * size), data.as_ptr().add(i * size));
+                    // Incremented as we go so that a panicking clone drops only initialized values
+                    column.len += 1;
+                }
+            }
+        }
+        column
+    }
+
+    fn layout(ty: TypeInfo, capacity: usize) -> Layout {
+        Layout::from_size_align(ty.layout().size() * capacity, ty.layout().align()).unwrap()
+    }
+
+    /// Clone the `index`th component into the uninitialized memory at `dst`
+    unsafe fn clone_to(&self, index: usize, dst: *mut u8) {
+        let size = self.ty.layout().size();
+        let src = self.data.as_ptr().add(index * size);
+        match self.clone {
+            None => ptr::copy_nonoverlapping(src, dst, size),
+            Some(clone) => clone(src, dst),
+        }
+    }
+}
+
+impl Drop for Column {
+    fn drop(&mut self) {
+        let size = self.ty.layout().size();
+        unsafe {
+            if self.clone.is_some() {
+                for i in 0..self.len {
+                    self.ty.drop(self.data.as_ptr().add(i * size));
+                }
+            }
+            if size != 0 && self.capacity != 0 {
+                dealloc(self.data.as_ptr(), Self::layout(self.ty, self.capacity));
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::query::Query;
+use crate::{
+    Component, ComponentError, Entity, EntityRef, Iter, NoSuchEntity, QueryBorrow, QueryOne, Ref,
+    ResourceRef, World,
+};
+
+/// Shared access to a [`World`] that can't change which entities exist or what components they
+/// have
+///
+/// A `WorldView` is `Copy` and `Send`, so a frame's parallel systems can each be handed one
+/// without any of them being able to spawn, despawn, insert, or remove. Components and resources
+/// can still be read, and queries may borrow components uniquely, subject to the usual dynamic
+/// borrow checks. Obtained from [`World::as_view`] or `From<&World>`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let e = world.spawn((123, true));
+/// let view = world.as_view();
+/// let total = std::thread::scope(|s| {
+///     let worker = s.spawn(move || view.query::<&i32>().iter().map(|(_, &x)| x).sum::<i32>());
+///     assert!(*view.get::<bool>(e).unwrap());
+///     worker.join().unwrap()
+/// });
+/// assert_eq!(total, 123);
+/// ```
+#[derive(Copy, Clone)]
+pub struct WorldView<'a> {
+    world: &'a World,
+}
+
+impl<'a> WorldView<'a> {
+    /// See [`World::query`]
+    pub fn query<Q: Query>(self) -> QueryBorrow<'a, Q> {
+        self.world.query()
+    }
+
+    /// See [`World::query_one`]
+    pub fn query_one<Q: Query>(self, entity: Entity) -> Result<QueryOne<'a, Q>, NoSuchEntity> {
+        self.world.query_one(entity)
+    }
+
+    /// See [`World::get`]
+    pub fn get<T: Component>(self, entity: Entity) -> Result<Ref<'a, T>, ComponentError> {
+        self.world.get(entity)
+    }
+
+    /// See [`World::entity`]
+    pub fn entity(self, entity: Entity) -> Result<EntityRef<'a>, NoSuchEntity> {
+        self.world.entity(entity)
+    }
+
+    /// See [`World::iter`]
+    pub fn iter(self) -> Iter<'a> {
+        self.world.iter()
+    }
+
+    /// See [`World::contains`]
+    pub fn contains(self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// See [`World::len`]
+    pub fn len(self) -> u32 {
+        self.world.len()
+    }
+
+    /// See [`World::is_empty`]
+    pub fn is_empty(self) -> bool {
+        self.world.is_empty()
+    }
+
+    /// See [`World::resource`]
+    pub fn resource<T: Component>(self) -> Option<ResourceRef<'a, T>> {
+        self.world.resource()
+    }
+
+    /// See [`World::contains_resource`]
+    pub fn contains_resource<T: Component>(self) -> bool {
+        self.world.contains_resource::<T>()
+    }
+}
+
+impl<'a> From<&'a World> for WorldView<'a> {
+    fn from(world: &'a World) -> Self {
+        Self { world }
+    }
+}
@@ -0,0 +1,87 @@
+//! Checks that hot paths don't touch the heap
+//!
+//! Lives in its own test binary so that the counting allocator doesn't slow down other tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use hecs::*;
+
+struct Counter;
+
+thread_local! {
+    /// Allocations made by each thread, so that concurrently running tests don't interfere
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|x| x.set(x.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counter = Counter;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let start = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - start
+}
+
+#[test]
+fn spawn_into_existing_archetype() {
+    struct Particle([f32; 3]);
+
+    let mut world = World::new();
+    // Creates the archetype
+    world.spawn((Particle([0.0; 3]), 0u8, true));
+    world.reserve::<(Particle, u8, bool)>(1000);
+
+    let n = allocations(|| {
+        for i in 0..1000 {
+            // Differently ordered tuples share an archetype
+            if i % 2 == 0 {
+                world.spawn((Particle([0.0; 3]), i as u8, true));
+            } else {
+                world.spawn((false, i as u8, Particle([1.0; 3])));
+            }
+        }
+    });
+    assert_eq!(n, 0);
+    assert_eq!(
+        world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, p)| p.0[0] == 1.0)
+            .count(),
+        500
+    );
+}
+
+#[test]
+// Recording transitions allocates
+#[cfg(not(feature = "debug-history"))]
+fn toggle_component() {
+    let mut world = World::new();
+    let entities = (0..100).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    // Discover the transitions
+    world.insert_one(entities[0], true).unwrap();
+    world.remove_one::<bool>(entities[0]).unwrap();
+    world.reserve::<(i32, bool)>(100);
+
+    let n = allocations(|| {
+        for &e in &entities {
+            world.insert_one(e, true).unwrap();
+        }
+        for &e in &entities {
+            world.remove_one::<bool>(e).unwrap();
+        }
+    });
+    assert_eq!(n, 0);
+}
@@ -75,6 +75,34 @@ fn derived_query() {
     );
 }
 
+#[test]
+#[cfg(feature = "macros")]
+fn derived_query_iter() {
+    #[derive(Query)]
+    struct Mover<'a> {
+        pos: &'a mut i32,
+        vel: &'a i64,
+        grounded: Option<&'a bool>,
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, 2i64, true));
+    let b = world.spawn((10, 20i64));
+    world.spawn((100,));
+
+    let mut query = world.query::<Mover>();
+    let iter = query.iter();
+    assert_eq!(iter.len(), 2);
+    for (_, m) in iter {
+        if m.grounded.is_none() {
+            *m.pos += *m.vel as i32;
+        }
+    }
+    drop(query);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 30);
+}
+
 #[test]
 fn query_single_component() {
     let mut world = World::new();
@@ -184,6 +212,64 @@ fn build_entity_bundle() {
     assert_eq!(*world.get::<i32>(f).unwrap(), 789);
 }
 
+#[test]
+fn builder_remove() {
+    use std::sync::Arc;
+
+    let tracker = Arc::new(());
+    let mut builder = EntityBuilder::new();
+    builder.add_bundle((Arc::clone(&tracker), 1u8, "abc", 2.0f64));
+    let taken = builder.remove::<Arc<()>>().unwrap();
+    assert_eq!(Arc::strong_count(&tracker), 2);
+    drop(taken);
+    assert_eq!(builder.remove::<u8>(), Some(1));
+    assert_eq!(builder.get::<&str>(), Some(&"abc"));
+    builder.add(3u8).add(Arc::clone(&tracker));
+    *builder.get_mut::<f64>().unwrap() += 1.0;
+
+    let mut world = World::new();
+    let e = world.spawn(builder.build());
+    let entity = world.entity(e).unwrap();
+    assert_eq!(entity.component_types().count(), 4);
+    assert_eq!(*entity.get::<u8>().unwrap(), 3);
+    assert_eq!(*entity.get::<f64>().unwrap(), 3.0);
+    assert_eq!(*entity.get::<&str>().unwrap(), "abc");
+    world.clear();
+    assert_eq!(Arc::strong_count(&tracker), 1);
+}
+
+#[test]
+fn reuse_builder() {
+    use std::sync::Arc;
+
+    let tracker = Arc::new(());
+    let mut world = World::new();
+    let mut entity = EntityBuilder::new();
+
+    entity.add(Arc::clone(&tracker)).add(1);
+    entity.clear();
+    assert_eq!(Arc::strong_count(&tracker), 1);
+    assert!(!entity.has::<i32>());
+
+    // Built entities that are never spawned don't leak
+    entity.add(Arc::clone(&tracker));
+    drop(entity.build());
+    assert_eq!(Arc::strong_count(&tracker), 1);
+
+    for i in 0..100 {
+        entity.add(i).add(Arc::clone(&tracker));
+        world.spawn(entity.build());
+        assert!(!entity.has::<i32>());
+    }
+    assert_eq!(Arc::strong_count(&tracker), 101);
+    let e = world.spawn(entity.build());
+    entity.add("abc");
+    world.insert(e, entity.build()).unwrap();
+    assert_eq!(*world.get::<&str>(e).unwrap(), "abc");
+    world.clear();
+    assert_eq!(Arc::strong_count(&tracker), 1);
+}
+
 #[test]
 fn dynamic_components() {
     let mut world = World::new();
@@ -279,6 +365,44 @@ fn derived_bundle() {
     assert_eq!(*world.get::<char>(e).unwrap(), 'a');
 }
 
+#[test]
+#[cfg(feature = "macros")]
+fn nested_bundle() {
+    #[derive(Bundle, Debug, PartialEq)]
+    struct Inner(i32, char);
+    #[derive(Bundle, Debug, PartialEq)]
+    struct Generic<T> {
+        value: T,
+    }
+    #[derive(Bundle, Debug, PartialEq)]
+    struct Outer {
+        #[bundle]
+        inner: Inner,
+        #[bundle]
+        generic: Generic<&'static str>,
+        flag: bool,
+    }
+
+    let outer = || Outer {
+        inner: Inner(42, 'a'),
+        generic: Generic { value: "abc" },
+        flag: true,
+    };
+    let mut world = World::new();
+    let e = world.spawn(outer());
+    assert_eq!(*world.get::<i32>(e).unwrap(), 42);
+    assert_eq!(*world.get::<&str>(e).unwrap(), "abc");
+    assert_eq!(world.query::<(&i32, &char, &bool)>().iter().count(), 1);
+    let f = world.spawn(outer());
+    assert_eq!(world.archetypes().filter(|x| !x.is_empty()).count(), 1);
+
+    world.remove_one::<&str>(f).unwrap();
+    assert!(world.remove::<Outer>(f).is_err());
+    assert_eq!(*world.get::<char>(f).unwrap(), 'a');
+    assert_eq!(world.remove::<Outer>(e).unwrap(), outer());
+    assert!(!world.entity(e).unwrap().has::<i32>());
+}
+
 #[test]
 #[cfg(feature = "macros")]
 #[cfg_attr(
@@ -324,6 +448,27 @@ fn clear() {
     assert_eq!(world.iter().count(), 0);
 }
 
+#[test]
+fn clear_invalidates_handles() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123));
+    let b = world.spawn(("def", 456, true));
+    world.despawn(a).unwrap();
+    let reserved = world.reserve_entity();
+    world.clear();
+    assert_eq!(world.len(), 0);
+    for &e in &[a, b, reserved] {
+        assert!(!world.contains(e));
+    }
+    let c = world.spawn(("ghi", 789));
+    let d = world.spawn(("jkl", 12, false));
+    assert!(c != a && c != b && c != reserved);
+    assert!(d != a && d != b && d != reserved);
+    assert_eq!(world.len(), 2);
+    assert_eq!(*world.get::<i32>(d).unwrap(), 12);
+    assert!(world.get::<i32>(b).is_err());
+}
+
 #[test]
 #[should_panic(expected = "twice on the same borrow")]
 fn alias() {
@@ -503,3 +648,3956 @@ fn columnar_access() {
     assert_eq!(b.ids(), &[f.id(), g.id()]);
     assert_eq!(*b.get::<i32>().unwrap(), [456, 789]);
 }
+
+#[test]
+fn command_buffer() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123));
+    let b = world.spawn(("def", 456));
+    let c = world.reserve_entity();
+
+    let mut cmd = CommandBuffer::new();
+    cmd.insert(a, (true, 4.5f32));
+    cmd.insert(c, (789,));
+    cmd.remove_one::<&str>(b);
+    cmd.despawn(a);
+    cmd.spawn((42, false));
+    assert_eq!(cmd.len(), 5);
+    cmd.run_on(&mut world);
+    assert!(cmd.is_empty());
+
+    assert!(!world.contains(a));
+    assert!(world.get::<&str>(b).is_err());
+    assert_eq!(*world.get::<i32>(c).unwrap(), 789);
+    assert_eq!(
+        world
+            .query::<(&i32, &bool)>()
+            .iter()
+            .map(|(_, (&i, &b))| (i, b))
+            .collect::<Vec<_>>(),
+        &[(42, false)]
+    );
+}
+
+#[test]
+fn command_buffer_merge() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let mut first = CommandBuffer::new();
+    first.insert_one(a, "abc");
+    first.spawn((2, true));
+    let mut second = CommandBuffer::new();
+    second.spawn((3, true));
+    second.insert_one(a, "def");
+    first.merge(&mut second);
+    assert!(second.is_empty());
+    assert_eq!(first.len(), 4);
+
+    let mut third = CommandBuffer::new();
+    third.spawn((4, true));
+    third.spawn((5,));
+    let mut buffers = [first, second, third];
+    world.apply_all(&mut buffers);
+    assert!(buffers.iter().all(|x| x.is_empty()));
+
+    assert_eq!(*world.get::<&str>(a).unwrap(), "def");
+    let mut spawned = world
+        .query::<&i32>()
+        .with::<bool>()
+        .iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    spawned.sort_unstable();
+    assert_eq!(spawned, [2, 3, 4]);
+    assert_eq!(world.len(), 5);
+}
+
+#[test]
+fn command_buffer_drops_unapplied() {
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let a = world.spawn(());
+    world.despawn(a).unwrap();
+    let counter = Arc::new(());
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_one(a, counter.clone());
+    cmd.run_on(&mut world);
+    cmd.spawn((counter.clone(),));
+    cmd.clear();
+    cmd.insert_one(a, counter.clone());
+    drop(cmd);
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+fn scope() {
+    let mut world = World::new();
+    let outside = world.spawn((1,));
+    let (a, b, c);
+    {
+        let mut scope = world.scope();
+        a = scope.spawn((2,));
+        b = scope.spawn((3,));
+        c = scope.spawn((4,));
+        assert!(scope.promote(b));
+        assert!(!scope.promote(b));
+        assert!(!scope.promote(outside));
+        scope.despawn(c).unwrap();
+        assert_eq!(scope.query::<&i32>().iter().count(), 3);
+    }
+    assert!(world.contains(outside));
+    assert!(!world.contains(a));
+    assert!(world.contains(b));
+    assert!(!world.contains(c));
+    assert_eq!(world.len(), 2);
+}
+
+#[test]
+fn blackboard() {
+    let mut world = World::new();
+    let e = world.spawn((123,));
+    let generation = world.archetypes_generation();
+    {
+        let board = world.blackboard_mut(e).unwrap();
+        assert!(board.is_empty());
+        assert_eq!(board.insert(true), None);
+        assert_eq!(board.insert("abc"), None);
+        assert_eq!(board.insert(false), Some(true));
+    }
+    let generation2 = world.archetypes_generation();
+    assert_ne!(generation, generation2);
+    {
+        let board = world.blackboard_mut(e).unwrap();
+        *board.get_mut::<&str>().unwrap() = "def";
+        board.insert(4.5f32);
+        assert_eq!(board.remove::<bool>(), Some(false));
+        assert!(!board.contains::<bool>());
+    }
+    assert_eq!(generation2, world.archetypes_generation());
+    let board = world.get::<Blackboard>(e).unwrap();
+    assert_eq!(board.len(), 2);
+    assert_eq!(board.get::<&str>(), Some(&"def"));
+    assert_eq!(*world.get::<i32>(e).unwrap(), 123);
+    drop(board);
+
+    world.despawn(e).unwrap();
+    assert!(world.blackboard_mut(e).is_err());
+}
+
+#[test]
+fn change_flags() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, false));
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(world.is_changed::<bool>(b).unwrap());
+    assert!(world.is_changed::<&str>(a).is_err());
+    world.clear_changed();
+    assert!(!world.is_changed::<i32>(a).unwrap());
+
+    // Reading through `Mut` doesn't flag
+    for (_, x) in world.query_mut::<Mut<i32>>() {
+        assert!(!x.is_changed());
+        assert!(*x > 0);
+    }
+    assert!(!world.is_changed::<i32>(a).unwrap());
+    for (_, (mut x, &flag)) in world.query::<(Mut<i32>, &bool)>().iter() {
+        if flag {
+            *x += 1;
+        }
+    }
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(!world.is_changed::<i32>(b).unwrap());
+    assert!(!world.is_changed::<bool>(a).unwrap());
+
+    // Flags survive archetype changes
+    world.insert_one(a, "abc").unwrap();
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(!world.is_changed::<bool>(a).unwrap());
+    assert!(world.is_changed::<&str>(a).unwrap());
+    world.remove_one::<&str>(a).unwrap();
+    assert!(world.is_changed::<i32>(a).unwrap());
+
+    world.clear_changed();
+    let _ = world.get_mut::<i32>(b).unwrap();
+    assert!(!world.is_changed::<i32>(b).unwrap());
+    *world.get_mut::<i32>(b).unwrap() = 3;
+    assert!(world.is_changed::<i32>(b).unwrap());
+    for (_, _) in world.query_mut::<&mut bool>() {}
+    assert!(world.is_changed::<bool>(a).unwrap());
+}
+
+#[test]
+fn visit_trait() {
+    trait Scale {
+        fn scale(&mut self, factor: i32);
+        fn value(&self) -> i32;
+    }
+    impl Scale for i32 {
+        fn scale(&mut self, factor: i32) {
+            *self *= factor;
+        }
+        fn value(&self) -> i32 {
+            *self
+        }
+    }
+    impl Scale for i64 {
+        fn scale(&mut self, factor: i32) {
+            *self *= i64::from(factor);
+        }
+        fn value(&self) -> i32 {
+            *self as i32
+        }
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, "abc"));
+    let b = world.spawn((2i64,));
+    let c = world.spawn((3, 4i64));
+    world.spawn((true,));
+
+    let mut seen = 0;
+    world.visit_trait::<dyn Scale>(|_, _| seen += 1);
+    assert_eq!(seen, 0);
+
+    world.register_trait::<i32, dyn Scale>(|x| x, |x| x);
+    world.register_trait::<i64, dyn Scale>(|x| x, |x| x);
+    world.clear_changed();
+    world.visit_trait_mut::<dyn Scale>(|_, x| x.scale(10));
+    assert!(world.is_changed::<i64>(c).unwrap());
+    let mut values = Vec::new();
+    world.visit_trait::<dyn Scale>(|e, x| values.push((e, x.value())));
+    values.sort_unstable();
+    assert_eq!(values, [(a, 10), (b, 20), (c, 30), (c, 40)]);
+}
+
+#[test]
+fn component_registry() {
+    #[derive(Clone)]
+    struct Health(u32);
+    struct Position;
+
+    let mut registry = ComponentRegistry::new();
+    register_component!(registry, Health, "health", clone);
+    register_component!(registry, Position);
+    assert_eq!(registry.len(), 2);
+    assert!(registry.get_by_name("health").is_some());
+    assert!(registry
+        .get::<Position>()
+        .unwrap()
+        .name()
+        .ends_with("::Position"));
+
+    let meta = *registry.get::<Health>().unwrap();
+    let src = Health(42);
+    let mut dst = core::mem::MaybeUninit::<Health>::uninit();
+    unsafe {
+        assert!(meta.clone_raw(&src as *const Health as *const u8, dst.as_mut_ptr().cast()));
+        assert_eq!(dst.assume_init().0, 42);
+    }
+
+    // Re-registering under a new name replaces the old one
+    register_component!(registry, Health, "hp");
+    assert!(registry.get_by_name("health").is_none());
+    assert!(!registry.get_by_name("hp").unwrap().is_clone());
+    assert_eq!(registry.len(), 2);
+}
+
+#[test]
+fn query_copied() {
+    let mut world = World::new();
+    let a = world.spawn((1u32, 2.0f32));
+    let b = world.spawn((3u32,));
+    world.clear_changed();
+    for (_, (id, x)) in world.query_mut::<(Copied<u32>, &mut f32)>() {
+        *x += id as f32;
+    }
+    assert_eq!(*world.get::<f32>(a).unwrap(), 3.0);
+    let mut ids = world
+        .query::<Copied<u32>>()
+        .iter()
+        .map(|(_, id)| id)
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert_eq!(ids, [1, 3]);
+    assert!(!world.is_changed::<u32>(b).unwrap());
+}
+
+#[test]
+fn vanished_components() {
+    use core::any::TypeId;
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "abc"));
+    let c = world.spawn((3,));
+    assert_eq!(world.drain_vanished().len(), 0);
+
+    world.despawn(c).unwrap();
+    // Other entities still hold an i32
+    assert_eq!(world.drain_vanished().len(), 0);
+
+    world.remove_one::<bool>(a).unwrap();
+    world.despawn(b).unwrap();
+    let mut vanished = world.drain_vanished().collect::<Vec<_>>();
+    vanished.sort();
+    let mut expected = vec![TypeId::of::<bool>(), TypeId::of::<&str>()];
+    expected.sort();
+    assert_eq!(vanished, expected);
+
+    // Replacing the only holder with the same types is not a disappearance
+    world.spawn_at(a, (4,));
+    assert_eq!(world.drain_vanished().len(), 0);
+
+    world.clear();
+    assert_eq!(
+        world.drain_vanished().collect::<Vec<_>>(),
+        [TypeId::of::<i32>()]
+    );
+}
+
+#[test]
+fn tag_bits() {
+    const A: u64 = 1;
+    const B: u64 = 2;
+
+    let mut world = World::new();
+    let ab = world.spawn((Tags(A | B), 1));
+    let a = world.spawn((Tags(A), 2));
+    let none = world.spawn((Tags::default(), 3));
+    world.spawn((4,));
+
+    let mut query = world.query::<WithBits<A, &i32>>();
+    let iter = query.iter();
+    assert_eq!(iter.len(), 2);
+    let mut xs = iter.map(|(_, &x)| x).collect::<Vec<_>>();
+    xs.sort_unstable();
+    assert_eq!(xs, [1, 2]);
+    drop(query);
+
+    assert_eq!(
+        world
+            .query_mut::<WithBits<{ A | B }, ()>>()
+            .into_iter()
+            .map(|(e, ())| e)
+            .collect::<Vec<_>>(),
+        [ab]
+    );
+    let batched = world
+        .query::<WithBits<B, &i32>>()
+        .iter_batched(1)
+        .flatten()
+        .count();
+    assert_eq!(batched, 1);
+
+    assert!(world.query_one_mut::<WithBits<A, &i32>>(none).is_err());
+    assert_eq!(*world.query_one_mut::<WithBits<A, &i32>>(a).unwrap(), 2);
+    assert!(world
+        .query_one::<WithBits<B, ()>>(a)
+        .unwrap()
+        .get()
+        .is_none());
+    assert_eq!(
+        world.query_one_mut::<Option<WithBits<B, &i32>>>(a).unwrap(),
+        None
+    );
+
+    world.get_mut::<Tags>(none).unwrap().set(B, true);
+    assert_eq!(world.query::<WithBits<B, ()>>().iter().len(), 2);
+}
+
+#[test]
+fn iter_batched_world() {
+    let mut world = World::new();
+    let entities = world
+        .spawn_batch((0..5).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    world.spawn(());
+    let batches = world.iter_batched(2).collect::<Vec<_>>();
+    assert_eq!(
+        batches.iter().map(|x| x.len()).collect::<Vec<_>>(),
+        [1, 2, 2, 1]
+    );
+    let mut seen = Vec::new();
+    for batch in batches {
+        for (e, r) in batch {
+            if let Some(x) = r.get::<i32>() {
+                assert_eq!(entities[*x as usize], e);
+                seen.push(e);
+            }
+        }
+    }
+    assert_eq!(seen.len(), 5);
+}
+
+#[test]
+#[cfg(feature = "track-borrows")]
+#[should_panic(expected = "(most recently at tests/tests.rs")]
+fn borrow_conflict_sites() {
+    let mut world = World::new();
+    let e = world.spawn((123,));
+    let _held = world.get_mut::<i32>(e).unwrap();
+    for _ in world.query::<&i32>().iter() {}
+}
+
+#[test]
+fn query_prefetch() {
+    let mut world = World::new();
+    world.spawn_batch((0..100).map(|i| (i, i as f32)));
+    world.spawn_batch((0..10).map(|i| (i,)));
+    let mut count = 0;
+    for (_, (x, y)) in world.query::<(&i32, Option<&mut f32>)>().iter().prefetch(4) {
+        if let Some(y) = y {
+            assert_eq!(*x as f32, *y);
+        }
+        count += 1;
+    }
+    assert_eq!(count, 110);
+}
+
+#[test]
+fn split_entities_and_components() {
+    let mut world = World::new();
+    let old = world.spawn((0, "old"));
+    let doomed = world.spawn((1,));
+
+    let (mut alloc, mut storage) = world.split_entities_and_components();
+    let a = alloc.reserve_entity();
+    let b = alloc.reserve_entity();
+    let c = alloc.reserve_entity();
+    let empty = alloc.reserve_entity();
+    storage.insert(a, (10,));
+    storage.insert(b, (20, "b"));
+    // Not reserved since the split, so dropped on join
+    storage.insert(old, (30,));
+    storage.insert(c, (40,));
+    // Already given components, so dropped on join
+    storage.insert(a, (50,));
+    alloc.despawn(doomed);
+    assert_eq!(storage.len(), 5);
+
+    let mut world = World::join_entities_and_components(alloc, storage);
+    assert_eq!(world.len(), 5);
+    assert!(!world.contains(doomed));
+    assert_eq!(*world.get::<i32>(old).unwrap(), 0);
+    assert_eq!(*world.get::<&str>(old).unwrap(), "old");
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 20);
+    assert_eq!(*world.get::<&str>(b).unwrap(), "b");
+    assert_eq!(*world.get::<i32>(c).unwrap(), 40);
+    assert_eq!(world.entity(empty).unwrap().len(), 0);
+    let mut ints = world
+        .query::<&i32>()
+        .iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    ints.sort_unstable();
+    assert_eq!(ints, [0, 10, 20, 40]);
+
+    world.despawn(a).unwrap();
+    assert_eq!(*world.get::<i32>(c).unwrap(), 40);
+}
+
+#[test]
+#[should_panic(expected = "same split")]
+fn join_mismatched_split() {
+    let (alloc, _) = World::new().split_entities_and_components();
+    let (_, storage) = World::new().split_entities_and_components();
+    World::join_entities_and_components(alloc, storage);
+}
+
+#[test]
+fn iter_snapshot() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3, true));
+    world.spawn((4,));
+
+    let mut snapshot = world.iter_snapshot::<(&mut i32, &bool)>();
+    assert_eq!(snapshot.remaining(), 3);
+    let mut visited = Vec::new();
+    while let Some((e, (x, _))) = snapshot.next(&mut world) {
+        *x *= 10;
+        visited.push(e);
+        if e == a {
+            world.despawn(b).unwrap();
+            world.remove_one::<bool>(c).unwrap();
+            world.spawn((5, true));
+        }
+    }
+    assert_eq!(visited, [a]);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn iter_snapshot_borrows_filters() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let _held = world.get_mut::<i32>(a).unwrap();
+    world.iter_snapshot::<Changed<i32, ()>>();
+}
+
+#[test]
+fn set_batch() {
+    let mut world = World::new();
+    let entities = (0..10)
+        .map(|i| {
+            if i % 2 == 0 {
+                world.spawn((i, true))
+            } else {
+                world.spawn((i,))
+            }
+        })
+        .collect::<Vec<_>>();
+    let missing = world.spawn(("abc",));
+    let dead = world.spawn((42,));
+    world.despawn(dead).unwrap();
+    world.clear_changed();
+
+    let updates = entities
+        .iter()
+        .rev()
+        .map(|&e| (e, -1))
+        .chain(vec![(missing, -2), (dead, -3)]);
+    assert_eq!(world.set_batch(updates), 10);
+    for &e in &entities {
+        assert_eq!(*world.get::<i32>(e).unwrap(), -1);
+        assert!(world.is_changed::<i32>(e).unwrap());
+        assert!(!world.is_changed::<bool>(e).unwrap_or(false));
+    }
+    assert!(world.get::<i32>(missing).is_err());
+}
+
+#[test]
+fn set_batch_last_write_wins() {
+    let mut world = World::new();
+    let entities = (0..64)
+        .map(|i| match i % 3 {
+            0 => world.spawn((i,)),
+            1 => world.spawn((i, true)),
+            _ => world.spawn((i, "abc")),
+        })
+        .collect::<Vec<_>>();
+    let updates = (0..4)
+        .flat_map(|round| entities.iter().map(move |&e| (e, round)))
+        .collect::<Vec<_>>();
+    assert_eq!(world.set_batch(updates), 256);
+    for &e in &entities {
+        assert_eq!(*world.get::<i32>(e).unwrap(), 3);
+    }
+}
+
+#[test]
+fn query_any_at_most() {
+    let mut world = World::new();
+    assert!(!world.query::<()>().any());
+    world.spawn_batch((0..5).map(|i| (i,)));
+    world.spawn_batch((0..5).map(|i| (i, true)));
+    world.spawn((Tags(1), 0));
+
+    assert!(world.query::<&i32>().any());
+    assert_eq!(world.query::<&i32>().at_most(3), 3);
+    assert_eq!(world.query::<&i32>().at_most(100), 11);
+    assert_eq!(world.query::<&bool>().at_most(100), 5);
+    assert_eq!(world.query::<WithBits<1, ()>>().at_most(100), 1);
+    assert!(!world.query::<WithBits<2, ()>>().any());
+    assert_eq!(world.query_mut::<&mut i32>().at_most(7), 7);
+    assert!(!world.query_mut::<&mut &str>().any());
+
+    // Existence checks don't conflict with outstanding borrows of unfiltered queries
+    let mut borrow = world.query::<&mut i32>();
+    let _iter = borrow.iter();
+    assert!(world.query::<&i32>().any());
+}
+
+#[test]
+fn swap_column() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    world.spawn(("abc",));
+    world.clear_changed();
+
+    let mut staged = world.stage_column::<i32>();
+    for column in staged.columns_mut() {
+        for x in column {
+            *x *= 10;
+        }
+    }
+    assert_eq!(*staged.get_mut(&world, b).unwrap(), 20);
+    world.swap_column(&mut staged).unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 20);
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(!world.is_changed::<bool>(a).unwrap());
+    // The staged copy now holds the previous values
+    assert_eq!(*staged.get_mut(&world, a).unwrap(), 1);
+
+    // Swapping back restores them
+    world.swap_column(&mut staged).unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+
+    world.spawn((3,));
+    assert_eq!(world.swap_column(&mut staged), Err(StaleColumn));
+    let mut staged = world.stage_column::<i32>();
+    world.despawn(a).unwrap();
+    assert!(staged.get_mut(&world, a).is_none());
+    assert_eq!(world.swap_column(&mut staged), Err(StaleColumn));
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+}
+
+#[test]
+fn batched_archetype_order() {
+    let mut world = World::new();
+    world.spawn_batch((0..3).map(|i| (i,)));
+    world.spawn_batch((0..7).map(|i| (i, true)));
+    world.spawn_batch((0..5).map(|i| (i, "abc")));
+
+    let sizes = world
+        .query::<&i32>()
+        .iter_batched(4)
+        .largest_first()
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [4, 3, 4, 1, 3]);
+
+    let sizes = world
+        .query::<&i32>()
+        .iter_batched(4)
+        .sort_archetypes_by(|x, y| x.len().cmp(&y.len()))
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [3, 4, 1, 4, 3]);
+
+    // Sorting mid-way finishes the current archetype first
+    let mut query = world.query::<&i32>();
+    let mut iter = query.iter_batched(2);
+    assert_eq!(iter.next().unwrap().count(), 2);
+    let sizes = iter
+        .largest_first()
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [1, 2, 2, 2, 1, 2, 2, 1]);
+}
+
+#[test]
+#[cfg(feature = "spatial")]
+fn spatial_hash() {
+    use hecs::spatial::{Position, SpatialHash};
+
+    struct Pos([f32; 3]);
+    impl Position for Pos {
+        fn position(&self) -> [f32; 3] {
+            self.0
+        }
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((Pos([-0.5, -0.5, 0.0]),));
+    let b = world.spawn((Pos([3.0, 3.0, 0.0]), true));
+    let c = world.spawn((Pos([10.0, 0.0, 0.0]),));
+    let mut index = SpatialHash::<Pos>::new(2.0);
+    index.update(&world);
+    world.clear_changed();
+    assert_eq!(index.len(), 3);
+
+    let mut found = index
+        .entities_within_aabb([-1.0, -1.0, -1.0], [3.0, 3.0, 1.0])
+        .collect::<Vec<_>>();
+    found.sort();
+    let mut expected = [a, b];
+    expected.sort();
+    assert_eq!(found, expected);
+    assert_eq!(
+        index
+            .entities_within_radius([0.0, 0.0, 0.0], 4.0)
+            .collect::<Vec<_>>(),
+        [a]
+    );
+
+    world.get_mut::<Pos>(c).unwrap().0 = [0.5, 0.5, 0.0];
+    world.despawn(a).unwrap();
+    world.remove_one::<Pos>(b).unwrap();
+    index.update(&world);
+    assert_eq!(index.len(), 1);
+    assert_eq!(index.position(c), Some([0.5, 0.5, 0.0]));
+    assert_eq!(
+        index
+            .entities_within_radius([0.0, 0.0, 0.0], 1.0)
+            .collect::<Vec<_>>(),
+        [c]
+    );
+}
+
+#[test]
+fn visit_entity() {
+    let mut world = World::new();
+    let e = world.spawn((7u8, 42i32));
+    let mut seen = Vec::new();
+    world
+        .visit_entity(e, &mut |ty: TypeInfo, ptr: *const u8| {
+            if ty.id() == std::any::TypeId::of::<i32>() {
+                seen.push(unsafe { *ptr.cast::<i32>() });
+            } else {
+                assert_eq!(ty.id(), std::any::TypeId::of::<u8>());
+                seen.push(unsafe { *ptr } as i32);
+            }
+        })
+        .unwrap();
+    seen.sort_unstable();
+    assert_eq!(seen, [7, 42]);
+
+    // Borrows are released after each component
+    drop(world.get_mut::<i32>(e).unwrap());
+    world.despawn(e).unwrap();
+    assert!(world
+        .visit_entity(e, &mut |_: TypeInfo, _: *const u8| {})
+        .is_err());
+}
+
+#[test]
+fn changed_added_filters() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2, "b"));
+    assert_eq!(world.query::<Added<i32, ()>>().iter().count(), 2);
+    world.clear_trackers();
+    assert_eq!(world.query::<Added<i32, ()>>().iter().count(), 0);
+    assert_eq!(world.query::<Changed<i32, ()>>().iter().count(), 0);
+
+    // Moving between archetypes preserves flags; only the new component counts as added
+    world.insert_one(a, true).unwrap();
+    world.remove_one::<&str>(b).unwrap();
+    assert_eq!(world.query::<Added<i32, ()>>().iter().count(), 0);
+    let added = world
+        .query::<Added<bool, ()>>()
+        .iter()
+        .map(|(e, ())| e)
+        .collect::<Vec<_>>();
+    assert_eq!(added, [a]);
+
+    // Replacing a component changes it without adding it
+    world.clear_trackers();
+    world.insert_one(b, 5).unwrap();
+    assert_eq!(world.query::<Added<i32, ()>>().iter().count(), 0);
+    let changed = world
+        .query::<Changed<i32, &i32>>()
+        .iter()
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(b, 5)]);
+
+    // `clear_changed` leaves addition flags alone
+    let c = world.spawn((3,));
+    world.clear_changed();
+    assert_eq!(world.query::<Changed<i32, ()>>().iter().count(), 0);
+    let added = world
+        .query::<Added<i32, ()>>()
+        .iter()
+        .map(|(e, ())| e)
+        .collect::<Vec<_>>();
+    assert_eq!(added, [c]);
+}
+
+#[test]
+#[cfg(feature = "debug-history")]
+fn debug_history() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    assert_eq!(world.debug_history(e).unwrap().len(), 0);
+    for i in 0..20u32 {
+        world.insert_one(e, i).unwrap();
+        world.remove_one::<u32>(e).unwrap();
+        world.clear_changed();
+    }
+    let history = world.debug_history(e).unwrap().collect::<Vec<_>>();
+    assert_eq!(history.len(), 16);
+    let last = history.last().unwrap();
+    assert_eq!(last.tick(), 19);
+    assert_eq!(last.removed(), [TypeId::of::<u32>()]);
+    assert!(last.added().is_empty());
+
+    world.despawn(e).unwrap();
+    assert!(world.debug_history(e).is_err());
+    let f = world.spawn((1,));
+    assert_eq!(f.id(), e.id());
+    assert_eq!(world.debug_history(f).unwrap().len(), 0);
+}
+
+#[test]
+fn dense_index() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    let mut map = EntityDenseMap::new();
+    assert_eq!(map.insert(a, 5), None);
+    assert_eq!(map.insert(a, 0), Some(5));
+    map.insert(c, 1);
+    assert_eq!(map.len(), 2);
+    let mut seen = world
+        .query::<&i32>()
+        .iter_with_dense_index(&map)
+        .map(|(e, i, &x)| (e, i, x))
+        .collect::<Vec<_>>();
+    seen.sort();
+    assert_eq!(seen, [(a, Some(0), 1), (b, None, 2), (c, Some(1), 3)]);
+
+    // Stale handles sharing an ID are not confused with live ones
+    world.despawn(a).unwrap();
+    let d = world.spawn((4,));
+    assert_eq!(d.id(), a.id());
+    assert_eq!(map.get(d), None);
+    assert_eq!(map.insert(d, 2), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(a), None);
+    assert_eq!(map.remove(d), Some(2));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn resources() {
+    let mut world = World::new();
+    assert!(world.resource::<u64>().is_none());
+    assert_eq!(world.insert_resource(1u64), None);
+    assert_eq!(world.insert_resource(2u64), Some(1));
+    {
+        let a = world.resource::<u64>().unwrap();
+        let b = world.resource::<u64>().unwrap();
+        assert_eq!(*a + *b, 4);
+        assert!(world.resource_mut::<&str>().is_none());
+    }
+    *world.resource_mut::<u64>().unwrap() += 1;
+    world.spawn((1,));
+    world.clear();
+    assert!(world.contains_resource::<u64>());
+    assert_eq!(world.remove_resource::<u64>(), Some(3));
+    assert!(!world.contains_resource::<u64>());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn resource_borrow_conflict() {
+    let mut world = World::new();
+    world.insert_resource(0u32);
+    let _a = world.resource::<u32>().unwrap();
+    let _b = world.resource_mut::<u32>().unwrap();
+}
+
+#[test]
+fn foreign_entity() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let mut other = World::new();
+    for _ in 0..10 {
+        other.spawn(());
+    }
+    let foreign = other.spawn((2,));
+    let forged = Entity::from_bits(u64::MAX).unwrap();
+    for e in [foreign, forged] {
+        assert!(!world.contains(e));
+        assert!(matches!(
+            world.get::<i32>(e),
+            Err(ComponentError::NoSuchEntity)
+        ));
+        assert!(world.get_mut::<i32>(e).is_err());
+        assert!(world.entity(e).is_err());
+        assert!(world.query_one::<&i32>(e).is_err());
+        assert!(world.is_changed::<i32>(e).is_err());
+        assert!(world.insert_one(e, true).is_err());
+        assert!(world.remove_one::<i32>(e).is_err());
+        assert!(world.despawn(e).is_err());
+    }
+    assert!(world.contains(a));
+
+    // Reserved entities are still recognized before they're flushed
+    let reserved = world.reserve_entity();
+    assert!(world.contains(reserved));
+    assert!(world.entity(reserved).is_ok());
+}
+
+#[test]
+fn missing_component_name() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    match world.get::<bool>(e) {
+        Err(ComponentError::MissingComponent(x)) => assert_eq!(x.type_name(), "bool"),
+        _ => panic!("expected a missing component"),
+    }
+    world.despawn(e).unwrap();
+    assert!(matches!(
+        world.get_mut::<i32>(e),
+        Err(ComponentError::NoSuchEntity)
+    ));
+}
+
+#[test]
+fn restricted_view() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, true, "secret"));
+    let b = world.spawn((2i32,));
+    let mut view = world.view::<(i32, bool, u8)>();
+    assert_eq!(*view.get::<i32, _>(a).unwrap(), 1);
+    *view.get_mut::<i32, _>(b).unwrap() = 3;
+    view.insert_one(b, 7u8).unwrap();
+    assert!(view.remove_one::<bool, _>(a).unwrap());
+    let mut seen = view
+        .query::<(&i32, Option<&u8>), _>()
+        .iter()
+        .map(|(e, (&x, y))| (e, x, y.copied()))
+        .collect::<Vec<_>>();
+    seen.sort();
+    assert_eq!(seen, [(a, 1, None), (b, 3, Some(7))]);
+    assert!(view.contains(a));
+    assert_eq!(*world.get::<&str>(a).unwrap(), "secret");
+}
+
+#[test]
+fn owned_guards() {
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let e = world.spawn((1, true));
+    world.clear_changed();
+    let mut world = Arc::new(world);
+    let a = World::get_owned::<i32>(&world, e).unwrap();
+    let b = World::get_owned::<i32>(&world, e).unwrap();
+    assert_eq!(*a + *b, 2);
+    assert!(Arc::get_mut(&mut world).is_none());
+    drop((a, b));
+
+    let mut c = World::get_owned_mut::<i32>(&world, e).unwrap();
+    let worker = std::thread::spawn(move || {
+        *c += 1;
+    });
+    worker.join().unwrap();
+    assert!(World::get_owned::<&str>(&world, e).is_err());
+
+    let world = Arc::get_mut(&mut world).unwrap();
+    assert_eq!(*world.get::<i32>(e).unwrap(), 2);
+    assert!(world.is_changed::<i32>(e).unwrap());
+    assert!(!world.is_changed::<bool>(e).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn owned_guard_conflict() {
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    let world = Arc::new(world);
+    let _a = World::get_owned_mut::<i32>(&world, e).unwrap();
+    let _b = world.get::<i32>(e).unwrap();
+}
+
+#[test]
+fn par_extend() {
+    let mut world = World::new();
+    let existing = world.spawn((0, "zero"));
+    world.clear_trackers();
+
+    let mut a = SpawnStaging::new();
+    a.push((1, "one"));
+    a.push((2, true));
+    a.push((3, "three"));
+    let mut b = SpawnStaging::new();
+    b.extend(vec![(4, false), (5, true)]);
+    assert_eq!(a.len(), 3);
+
+    let entities = world.par_extend(vec![a, SpawnStaging::new(), b]);
+    assert_eq!(entities.len(), 5);
+    assert_eq!(world.len(), 6);
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<i32>(e).unwrap(), i as i32 + 1);
+    }
+    assert_eq!(*world.get::<&str>(entities[2]).unwrap(), "three");
+    assert!(*world.get::<bool>(entities[4]).unwrap());
+    assert_eq!(*world.get::<&str>(existing).unwrap(), "zero");
+
+    let mut added = world
+        .query::<Added<i32, &i32>>()
+        .iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    added.sort_unstable();
+    assert_eq!(added, [1, 2, 3, 4, 5]);
+
+    world.despawn(entities[0]).unwrap();
+    assert_eq!(*world.get::<&str>(entities[2]).unwrap(), "three");
+}
+
+#[test]
+fn id_namespaces() {
+    let mut world = World::new();
+    let early = world.spawn(("early",));
+    assert_eq!(early.id(), 0);
+    assert_eq!(world.reserve_id_range(0..4), Err(IdRangeUnavailable));
+    let players = world.reserve_id_range(2..5).unwrap();
+    assert_eq!(world.namespace_ids(players), 2..5);
+    assert_eq!(world.reserve_id_range(4..8), Err(IdRangeUnavailable));
+
+    // General allocation avoids the namespace, even for IDs below it
+    let others = (0..4).map(|_| world.spawn(())).collect::<Vec<_>>();
+    assert_eq!(
+        others.iter().map(|e| e.id()).collect::<Vec<_>>(),
+        [1, 5, 6, 7]
+    );
+
+    let a = world.spawn_in(players, (0,)).unwrap();
+    let b = world.spawn_in(players, (1,)).unwrap();
+    let c = world.spawn_in(players, (2,)).unwrap();
+    assert_eq!([a.id(), b.id(), c.id()], [2, 3, 4]);
+    assert_eq!(world.spawn_in(players, (3,)), None);
+
+    // Freed IDs return to their namespace
+    world.despawn(b).unwrap();
+    world.despawn(others[0]).unwrap();
+    let d = world.spawn(());
+    assert_eq!(d.id(), 1);
+    let e = world.spawn_in(players, (4,)).unwrap();
+    assert_eq!(e.id(), 3);
+    assert!(!world.contains(b));
+    assert_eq!(*world.get::<i32>(e).unwrap(), 4);
+
+    world.clear();
+    assert_eq!(world.spawn(()).id(), 0);
+    let f = world.spawn_in(players, ()).unwrap();
+    assert_eq!(f.id(), 2);
+}
+
+#[test]
+fn component_gc() {
+    let mut gc = ComponentGc::new();
+    gc.root::<&str, &i32>().root::<&str, (&bool, &u8)>();
+    gc.root::<u64, &u16>();
+
+    let mut world = World::new();
+    let a = world.spawn(("a", 1));
+    let b = world.spawn(("b", true, 2u8));
+    let c = world.spawn(("c", true, 7u64));
+    let d = world.spawn((8u64, 3u16));
+    let e = world.spawn((9u64, 'x'));
+    assert_eq!(gc.collect(&mut world), 3);
+    assert!(world.get::<&str>(a).is_ok());
+    assert!(world.get::<&str>(b).is_ok());
+    assert!(world.get::<&str>(c).is_err());
+    assert!(world.get::<u64>(c).is_err());
+    assert!(world.get::<u64>(d).is_ok());
+    assert!(world.get::<u64>(e).is_err());
+    assert_eq!(*world.get::<char>(e).unwrap(), 'x');
+    assert_eq!(gc.collect(&mut world), 0);
+}
+
+#[test]
+fn archetype_columns() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2, "b"));
+    world.clear_changed();
+
+    let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    {
+        let names = archetype.get::<&str>().unwrap();
+        let mut numbers = archetype.get_mut::<i32>().unwrap();
+        for ((&id, name), number) in archetype
+            .ids()
+            .iter()
+            .zip(names.iter())
+            .zip(numbers.iter_mut())
+        {
+            *number += if id == a.id() { 10 } else { 20 };
+            assert!(*name == "a" || *name == "b");
+        }
+        assert!(archetype.get_mut::<bool>().is_none());
+    }
+    assert_eq!(*world.get::<i32>(a).unwrap(), 11);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 22);
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(!world.is_changed::<&str>(a).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn archetype_column_conflict() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    let _shared = archetype.get::<i32>().unwrap();
+    let _unique = archetype.get_mut::<i32>().unwrap();
+}
+
+#[test]
+fn spawn_at_replication() {
+    let mut server = World::new();
+    let mut handles = (0..8).map(|i| server.spawn((i,))).collect::<Vec<_>>();
+    for &e in &handles[..4] {
+        server.despawn(e).unwrap();
+    }
+    handles.drain(..4);
+    handles.push(server.spawn((8,)));
+
+    let mut client = World::new();
+    for &e in handles.iter().rev() {
+        client.spawn_at(e, (*server.get::<i32>(e).unwrap(),));
+    }
+    assert_eq!(client.len(), server.len());
+    for &e in &handles {
+        assert_eq!(
+            *client.get::<i32>(e).unwrap(),
+            *server.get::<i32>(e).unwrap()
+        );
+    }
+
+    // Locally spawned entities never collide with replicated ones
+    let local = client.spawn(("local",));
+    assert!(!handles.iter().any(|e| e.id() == local.id()));
+}
+
+#[test]
+fn spawn_at_replaces_like_despawn() {
+    use std::sync::{Arc, Mutex};
+
+    let despawned = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let sink = despawned.clone();
+    world.on_despawn(move |e| sink.lock().unwrap().push(e));
+    let parent = world.spawn((1,));
+    let child = world.spawn((2,));
+    let other = world.spawn((3,));
+    let other_child = world.spawn((4,));
+    world.set_name(parent, "parent").unwrap();
+    world.set_name(other, "other").unwrap();
+    world.attach(child, parent).unwrap();
+    world.attach(other_child, other).unwrap();
+    world.insert_sparse(parent, "sparse").unwrap();
+
+    world.spawn_at(parent, (5,));
+    assert_eq!(*world.get::<i32>(parent).unwrap(), 5);
+    assert_eq!(world.name(parent), None);
+    assert_eq!(world.entity_by_name("parent"), None);
+    assert!(world.sparse::<&str>().unwrap().is_empty());
+    assert!(!world.contains(child));
+    assert_eq!(world.children(parent).count(), 0);
+    assert_eq!(*despawned.lock().unwrap(), [parent, child]);
+
+    let mut batch = ColumnBatchType::new();
+    batch.add::<i32>();
+    let mut batch = batch.into_batch(2);
+    let mut ints = batch.writer::<i32>().unwrap();
+    ints.push(6).unwrap();
+    ints.push(7).unwrap();
+    world.spawn_column_batch_at(&[other, other_child], batch.build().unwrap());
+    assert_eq!(world.entity_by_name("other"), None);
+    assert_eq!(world.parent(other_child), None);
+    assert_eq!(*world.get::<i32>(other_child).unwrap(), 7);
+    assert_eq!(
+        *despawned.lock().unwrap(),
+        [parent, child, other, other_child]
+    );
+    assert_eq!(world.len(), 3);
+}
+
+#[test]
+#[cfg(feature = "abi")]
+fn abi_world() {
+    use hecs::abi::*;
+
+    const NAME: StableId = StableId(0);
+    const SCORE: StableId = StableId(1);
+
+    let mut registry = AbiRegistry::new();
+    registry.register::<String>(NAME).register::<u32>(SCORE);
+    let mut world = World::new();
+    let a = world.spawn(("a".to_string(), 1u32));
+    let b = world.spawn((2u32,));
+    let spawned;
+    {
+        let mut host = AbiHost::new(&mut world, &registry);
+        let mut handle = host.handle();
+        unsafe {
+            assert_eq!(handle.get::<String>(a, NAME).unwrap(), "a");
+            assert!(handle.get::<String>(b, NAME).is_none());
+            let mut total = 0;
+            handle.for_each::<u32, _>(SCORE, |_, x| {
+                *x *= 10;
+                total += *x;
+            });
+            assert_eq!(total, 30);
+            assert!(handle.insert(b, NAME, "b".to_string()));
+            spawned = handle.spawn();
+            assert!(handle.insert(spawned, SCORE, 7u32));
+        }
+        assert!(handle.remove(a, SCORE));
+        assert!(!handle.remove(a, SCORE));
+        handle.despawn_deferred(b);
+        assert!(handle.contains(b));
+    }
+    assert!(!world.contains(b));
+    assert!(world.get::<u32>(a).is_err());
+    assert_eq!(*world.get::<u32>(spawned).unwrap(), 7);
+}
+
+#[test]
+fn runtime_components() {
+    use std::alloc::Layout;
+    use std::any::TypeId;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A component type known only at runtime, e.g. defined by a script
+    enum ScriptComponent {}
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    unsafe fn drop_script(_: *mut u8) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+    let ty = TypeInfo::from_parts(
+        TypeId::of::<ScriptComponent>(),
+        Layout::new::<[u8; 12]>(),
+        drop_script,
+    );
+
+    let mut world = World::new();
+    let e = world.spawn((42,));
+    let data = [7u8; 12];
+    unsafe {
+        world.insert_dynamic(e, ty, data.as_ptr()).unwrap();
+    }
+    let ptr = world.get_dynamic(e, ty.id()).unwrap();
+    assert_eq!(unsafe { *ptr.as_ptr().add(11) }, 7);
+    assert!(world.get_dynamic(e, TypeId::of::<bool>()).is_none());
+    assert_eq!(*world.get::<i32>(e).unwrap(), 42);
+
+    // Replacing drops the old value
+    unsafe {
+        world.insert_dynamic(e, ty, [8u8; 12].as_ptr()).unwrap();
+    }
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    world.despawn(e).unwrap();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+
+    // Failed insertion still takes ownership
+    unsafe {
+        assert!(world.insert_dynamic(e, ty, data.as_ptr()).is_err());
+    }
+    assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn query_map() {
+    let mut world = World::new();
+    world.spawn_batch((0..100).map(|i| (i, i as f32)));
+    world.spawn_batch((0..50).map(|i| (i,)));
+
+    let mut query = world
+        .query::<(&i32, Option<&f32>)>()
+        .map(|_, (&i, f)| (i, f.is_some()));
+    let iter = query.iter();
+    assert_eq!(iter.len(), 150);
+    assert_eq!(iter.filter(|&(_, f)| f).count(), 100);
+
+    // Batches are distributable across threads
+    let mut query = world.query::<&i32>().map(|_, &i| i);
+    let total = std::sync::atomic::AtomicI32::new(0);
+    std::thread::scope(|s| {
+        for batch in query.iter_batched(16) {
+            let total = &total;
+            s.spawn(move || {
+                total.fetch_add(batch.sum(), std::sync::atomic::Ordering::Relaxed);
+            });
+        }
+    });
+    assert_eq!(total.into_inner(), 4950 + 1225);
+
+    let mut query = world
+        .query::<&i32>()
+        .without::<f32>()
+        .flat_map(|e, &i| std::iter::repeat_n(e, i as usize % 2));
+    assert_eq!(query.iter().count(), 25);
+    let mut query = world.query::<&i32>().flat_map(|_, &i| vec![i; 2]);
+    assert_eq!(query.iter_batched(7).flatten().count(), 300);
+}
+
+#[test]
+fn despawn_reasons() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    let mut cmd = CommandBuffer::new();
+    cmd.despawn_with_reason(a, DespawnReason::Unloaded);
+    cmd.despawn(b);
+    cmd.despawn_with_reason(c, DespawnReason::Merged);
+    cmd.despawn_with_reason(c, DespawnReason::Killed);
+    cmd.run_on(&mut world);
+    assert!(world.is_empty());
+    assert_eq!(
+        world.drain_despawns().collect::<Vec<_>>(),
+        [(a, DespawnReason::Unloaded), (c, DespawnReason::Merged)]
+    );
+    assert_eq!(
+        world.despawn_with_reason(a, DespawnReason::Killed),
+        Err(NoSuchEntity)
+    );
+    assert_eq!(world.drain_despawns().len(), 0);
+}
+
+#[test]
+fn world_config() {
+    let mut config = WorldConfig::default();
+    config.archetype_min_capacity = 1;
+    let mut world = World::from_config(config);
+    assert_eq!(*world.config(), config);
+    let entities = (0..100).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    world.insert_one(entities[0], true).unwrap();
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+    }
+    assert_eq!(World::new().config(), &WorldConfig::default());
+}
+
+#[test]
+fn archetype_membership() {
+    fn deltas(world: &World) -> Vec<(Vec<u32>, Vec<u32>)> {
+        world
+            .archetypes()
+            .filter(|x| x.has::<i32>())
+            .map(|x| (x.entered().unwrap().to_vec(), x.exited().unwrap().to_vec()))
+            .collect()
+    }
+
+    assert!(World::new().archetypes().all(|x| x.entered().is_none()));
+
+    let mut config = WorldConfig::default();
+    config.track_membership = true;
+    let mut world = World::from_config(config);
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    assert_eq!(deltas(&world), [(vec![a.id(), b.id()], vec![])]);
+
+    world.clear_trackers();
+    assert_eq!(deltas(&world), [(vec![], vec![])]);
+
+    world.insert_one(a, true).unwrap();
+    world.despawn(b).unwrap();
+    let c = world.spawn_batch(Some((3, false))).next().unwrap();
+    assert_eq!(
+        deltas(&world),
+        [
+            (vec![], vec![a.id(), b.id()]),
+            (vec![a.id(), c.id()], vec![])
+        ]
+    );
+    world.clear_trackers();
+
+    // Entities spawned through staging and column batches are tracked too
+    let mut staging = SpawnStaging::new();
+    staging.push((4,));
+    let d = world.par_extend(vec![staging])[0];
+    let mut batch = ColumnBatchType::new();
+    batch.add::<i32>();
+    let mut batch = batch.into_batch(1);
+    batch.writer::<i32>().unwrap().push(5).unwrap();
+    let e = world
+        .spawn_column_batch(batch.build().unwrap())
+        .next()
+        .unwrap();
+    assert_eq!(deltas(&world)[0], (vec![d.id(), e.id()], vec![]));
+}
+
+#[test]
+fn cached_transitions() {
+    use std::sync::Arc;
+
+    let tracker = Arc::new(());
+    let mut world = World::new();
+    let a = world.spawn((1, Arc::clone(&tracker)));
+    let b = world.spawn((2, Arc::clone(&tracker)));
+    for &e in &[a, b, a] {
+        // Replaces the existing `Arc` while moving to a new archetype
+        world.insert(e, (true, Arc::clone(&tracker))).unwrap();
+        assert_eq!(Arc::strong_count(&tracker), 3);
+        // Replaces without moving
+        world.insert(e, (false, Arc::clone(&tracker))).unwrap();
+        assert_eq!(Arc::strong_count(&tracker), 3);
+        assert!(!*world.get::<bool>(e).unwrap());
+        assert!(!world.remove::<(bool, i32)>(e).unwrap().0);
+        assert!(world.remove::<(bool, i32)>(e).is_err());
+        world.insert_one(e, 3).unwrap();
+    }
+    assert_eq!(world.query::<(&i32, &Arc<()>)>().iter().count(), 2);
+}
+
+#[test]
+fn despawn_batch() {
+    let mut world = World::new();
+    let entities = (0..100)
+        .map(|i| {
+            if i % 3 == 0 {
+                world.spawn((i, true))
+            } else {
+                world.spawn((i,))
+            }
+        })
+        .collect::<Vec<_>>();
+    let doomed = entities
+        .iter()
+        .copied()
+        .filter(|e| e.id() % 2 == 0)
+        .chain(Some(entities[0]))
+        .collect::<Vec<_>>();
+    assert_eq!(world.despawn_batch(&doomed), 50);
+    assert_eq!(world.len(), 50);
+    for (i, &e) in entities.iter().enumerate() {
+        if e.id() % 2 == 0 {
+            assert!(!world.contains(e));
+        } else {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+        }
+    }
+    let survivors = entities
+        .iter()
+        .copied()
+        .filter(|e| world.contains(*e))
+        .collect::<Vec<_>>();
+    assert_eq!(world.despawn_batch(&survivors), 50);
+    assert!(world.is_empty());
+    assert_eq!(world.drain_vanished().count(), 2);
+}
+
+#[test]
+fn take_entity() {
+    use std::sync::Arc;
+
+    let tracker = Arc::new(());
+    let mut world = World::new();
+    let a = world.spawn((1, Arc::clone(&tracker), "abc"));
+    let b = world.spawn((2, Arc::clone(&tracker), "def"));
+    let empty = world.spawn(());
+
+    let mut taken = world.take(a).unwrap();
+    assert!(world.take(a).is_err());
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert_eq!(taken.component_types().count(), 3);
+    *taken.get_mut::<i32>().unwrap() = 3;
+    let c = world.spawn(taken);
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+    assert_eq!(Arc::strong_count(&tracker), 3);
+
+    // Dropping unrestored components
+    drop(world.take(b).unwrap());
+    assert_eq!(Arc::strong_count(&tracker), 2);
+
+    let taken = world.take(empty).unwrap();
+    assert_eq!(taken.component_types().count(), 0);
+    let mut builder = world.take(c).unwrap().into_builder();
+    builder.add(true);
+    let d = world.spawn(builder.build());
+    assert_eq!(world.entity(d).unwrap().len(), 4);
+}
+
+#[test]
+fn registration_conflicts() {
+    use std::alloc::Layout;
+    use std::any::TypeId;
+
+    struct Health;
+    struct Mana;
+    enum ScriptComponent {}
+    unsafe fn drop_script(_: *mut u8) {}
+
+    let mut registry = ComponentRegistry::new();
+    registry.register::<Health>("health");
+    match registry.try_register::<Mana>("health") {
+        Err(RegistrationConflict::Name {
+            name,
+            existing,
+            new,
+        }) => {
+            assert_eq!(name, "health");
+            assert!(existing.ends_with("Health"));
+            assert!(new.ends_with("Mana"));
+        }
+        x => panic!("unexpected result {:?}", x),
+    }
+    // The existing registration is untouched
+    assert_eq!(
+        registry.get_by_name("health").unwrap().type_id(),
+        TypeId::of::<Health>()
+    );
+    assert!(registry.get::<Mana>().is_none());
+
+    let script =
+        |layout| TypeInfo::from_parts(TypeId::of::<ScriptComponent>(), layout, drop_script);
+    registry
+        .try_register_dynamic("script", "Script", script(Layout::new::<[u8; 12]>()))
+        .unwrap();
+    // Same layout is a harmless re-registration
+    registry
+        .try_register_dynamic("script", "Script", script(Layout::new::<[u8; 12]>()))
+        .unwrap();
+    let err = registry
+        .try_register_dynamic("script2", "Script", script(Layout::new::<[u8; 16]>()))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "component Script is registered with size 12 and alignment 1, not size 16 and alignment 1"
+    );
+    assert!(registry.get_by_name("script").is_some());
+    assert!(registry.get_by_name("script2").is_none());
+    assert_eq!(registry.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "component name \"health\" is registered to")]
+fn registration_conflict_panics() {
+    struct Health;
+    struct Mana;
+
+    let mut registry = ComponentRegistry::new();
+    registry.register::<Health>("health");
+    registry.register::<Mana>("health");
+}
+
+#[test]
+fn migrate_entity() {
+    let mut a = World::new();
+    let mut b = World::new();
+    let x = a.spawn((1, "x"));
+    let y = a.spawn((2, "y"));
+    let z = b.spawn((3, "z"));
+
+    let x2 = a.migrate(x, &mut b).unwrap();
+    assert!(!a.contains(x));
+    assert_eq!(a.migrate(x, &mut b), Err(NoSuchEntity));
+    // The entity moved to fill the gap is still found
+    assert_eq!(*a.get::<i32>(y).unwrap(), 2);
+    assert_eq!(*b.get::<&str>(x2).unwrap(), "x");
+    assert_eq!(*b.get::<i32>(z).unwrap(), 3);
+    assert_eq!(b.len(), 2);
+
+    // Types the target world hasn't seen get a new archetype
+    let w = a.spawn((String::from("heap"), 4u8));
+    let w2 = a.migrate(w, &mut b).unwrap();
+    assert_eq!(*b.get::<String>(w2).unwrap(), "heap");
+    assert_eq!(*b.get::<u8>(w2).unwrap(), 4);
+    assert_eq!(a.len(), 1);
+}
+
+#[test]
+fn migrate_sparse_components_and_user_data() {
+    let mut a = World::new();
+    let mut b = World::new();
+    let x = a.spawn((1,));
+    let y = a.spawn((2,));
+    a.insert_sparse(x, "stunned").unwrap();
+    a.insert_sparse(y, "slowed").unwrap();
+    a.insert_sparse(x, 1.5f32).unwrap();
+    a.set_user_data(x, 42).unwrap();
+
+    let x2 = b.spawn(());
+    b.despawn(x2).unwrap();
+    let x2 = a.migrate(x, &mut b).unwrap();
+    assert_eq!(b.sparse::<&str>().unwrap().get(x2), Some(&"stunned"));
+    assert_eq!(b.sparse::<f32>().unwrap().get(x2), Some(&1.5));
+    assert_eq!(b.user_data(x2), Ok(42));
+    assert!(a.sparse::<f32>().unwrap().is_empty());
+    assert_eq!(a.sparse::<&str>().unwrap().len(), 1);
+    assert_eq!(a.sparse::<&str>().unwrap().get(y), Some(&"slowed"));
+}
+
+#[test]
+fn migrate_updates_indexes() {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct ByValue(BTreeMap<i32, Entity>);
+    impl SecondaryIndex for ByValue {
+        type Source = i32;
+        fn insert(&mut self, entity: Entity, &value: &i32) {
+            self.0.insert(value, entity);
+        }
+        fn remove(&mut self, _: Entity, value: &i32) {
+            self.0.remove(value);
+        }
+    }
+
+    let mut a = World::new();
+    let mut b = World::new();
+    a.add_index(ByValue::default());
+    b.add_index(ByValue::default());
+    let despawned = Arc::new(AtomicUsize::new(0));
+    let counter = despawned.clone();
+    a.on_despawn(move |_| {
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+    let x = a.spawn((1, "x"));
+    let y = a.spawn((2, "y"));
+
+    let x2 = a.migrate(x, &mut b).unwrap();
+    assert_eq!(despawned.load(Ordering::Relaxed), 1);
+    let index = &a.index::<ByValue>().unwrap().0;
+    assert_eq!(index.len(), 1);
+    assert_eq!(index[&2], y);
+    let index = &b.index::<ByValue>().unwrap().0;
+    assert_eq!(index.len(), 1);
+    assert_eq!(index[&1], x2);
+}
+
+#[test]
+fn clone_entity_and_prefab() {
+    use std::any::TypeId;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Loot(String);
+
+    let mut registry = ComponentRegistry::new();
+    register_component!(registry, Loot, clone);
+    register_component!(registry, i32, clone);
+    register_component!(registry, bool);
+
+    let mut world = World::new();
+    let a = world.spawn((Loot("apple".into()), 3));
+    let b = world.clone_entity(a, &registry).unwrap();
+    assert_ne!(a, b);
+    assert_eq!(*world.get::<Loot>(b).unwrap(), Loot("apple".into()));
+    assert_eq!(*world.get::<i32>(b).unwrap(), 3);
+
+    let c = world.spawn((Loot("pear".into()), true));
+    assert_eq!(
+        world.clone_entity(c, &registry),
+        Err(CloneError::Uncloneable(TypeId::of::<bool>()))
+    );
+    assert_eq!(world.len(), 3);
+    world.despawn(c).unwrap();
+    assert_eq!(
+        world.clone_entity(c, &registry),
+        Err(CloneError::NoSuchEntity)
+    );
+
+    let mut prefab = Prefab::from_entity(&world, a, &registry).unwrap();
+    world.despawn(a).unwrap();
+    *prefab.get_mut::<i32>().unwrap() = 7;
+    let mut builder = EntityBuilder::new();
+    let instances = (0..10)
+        .map(|_| world.spawn(prefab.clone_into(&mut builder).build()))
+        .collect::<Vec<_>>();
+    for &e in &instances {
+        assert_eq!(*world.get::<i32>(e).unwrap(), 7);
+        assert_eq!(*world.get::<Loot>(e).unwrap(), Loot("apple".into()));
+    }
+    assert_eq!(prefab.get::<Loot>(), Some(&Loot("apple".into())));
+
+    let mut template = EntityBuilder::new();
+    template.add(1).add(false);
+    assert!(Prefab::new(template, &registry).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn maintain_shrinks_archetypes() {
+    use std::time::Duration;
+
+    let mut world = World::new();
+    let entities = (0..1000)
+        .map(|i| world.spawn((i, true)))
+        .collect::<Vec<_>>();
+    let capacity = |world: &World| {
+        world
+            .archetypes()
+            .find(|x| x.has::<bool>())
+            .unwrap()
+            .capacity()
+    };
+    assert!(capacity(&world) >= 1000);
+    assert!(world.maintain(Duration::from_secs(1)));
+    assert!(capacity(&world) >= 1000);
+
+    world.despawn_batch(&entities[10..]);
+    // A zero budget still makes progress
+    while !world.maintain(Duration::from_secs(0)) {}
+    assert!(capacity(&world) < 1000);
+    assert!(capacity(&world) >= 10);
+    for (i, &e) in entities[..10].iter().enumerate() {
+        assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+    }
+    let e = world.spawn((42, false));
+    assert!(!*world.get::<bool>(e).unwrap());
+
+    world.despawn_batch(&entities[..10]);
+    world.despawn(e).unwrap();
+    assert!(world.maintain(Duration::from_secs(1)));
+    assert_eq!(capacity(&world), 0);
+    let e = world.spawn((7, true));
+    assert_eq!(*world.get::<i32>(e).unwrap(), 7);
+}
+
+#[test]
+fn query_chunks() {
+    let mut world = World::new();
+    let a = world.spawn((1, 1.0f32));
+    let b = world.spawn((2, 2.0f32, true));
+    let c = world.spawn((3, 3.0f32, true));
+    world.spawn((4,));
+    let empty = world.spawn((5, 5.0f32, "gone"));
+    world.despawn(empty).unwrap();
+    world.clear_changed();
+
+    let mut chunks = world
+        .query::<(&mut i32, &f32, Option<&bool>)>()
+        .iter_chunks()
+        .map(|(ids, (ints, floats, flags))| {
+            for (i, f) in ints.iter_mut().zip(floats) {
+                *i += *f as i32;
+            }
+            (ids.to_vec(), ints.to_vec(), flags.map(<[bool]>::len))
+        })
+        .collect::<Vec<_>>();
+    chunks.sort();
+    assert_eq!(
+        chunks,
+        [
+            (vec![a.id()], vec![2], None),
+            (vec![b.id(), c.id()], vec![4, 6], Some(2)),
+        ]
+    );
+    assert!(world.is_changed::<i32>(c).unwrap());
+    assert!(!world.is_changed::<f32>(c).unwrap());
+
+    let sum = world
+        .query::<Without<bool, Copied<i32>>>()
+        .iter_chunks()
+        .flat_map(|(_, x)| x.iter().copied())
+        .sum::<i32>();
+    assert_eq!(sum, 6);
+}
+
+#[test]
+fn query_group() {
+    let mut world = World::new();
+    let a = world.spawn((1, 1.0f32));
+    world.spawn((2,));
+    world.register_group::<(i32, f32)>();
+    world.register_group::<(f32, i32)>();
+    let b = world.spawn((3, 3.0f32, true));
+    world.spawn((4, "other"));
+    world.clear_changed();
+
+    let mut chunks = world
+        .query_group::<(&mut i32, &f32)>()
+        .map(|(ids, (ints, floats))| {
+            for (i, f) in ints.iter_mut().zip(floats) {
+                *i += *f as i32;
+            }
+            (ids.to_vec(), ints.to_vec())
+        })
+        .collect::<Vec<_>>();
+    chunks.sort();
+    assert_eq!(chunks, [(vec![a.id()], vec![2]), (vec![b.id()], vec![6])]);
+    assert!(world.is_changed::<i32>(b).unwrap());
+
+    world.despawn(a).unwrap();
+    assert_eq!(world.query_group::<(&i32, &f32)>().count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "no group registered")]
+fn query_unregistered_group() {
+    let mut world = World::new();
+    world.register_group::<(i32, f32)>();
+    world.query_group::<&i32>().count();
+}
+
+#[test]
+fn borrow_errors() {
+    let mut world = World::new();
+    world.spawn((true, 1));
+    let e = world.spawn((false, 2, "held"));
+    let held = world.get_mut::<i32>(e).unwrap();
+
+    let mut query = world.query::<(&mut bool, &i32)>();
+    let err = query.try_iter().err().unwrap();
+    assert_eq!(err.type_name(), "i32");
+    assert!(err.to_string().starts_with("i32 already borrowed uniquely"));
+    // Borrows acquired before the failure were released, in every archetype
+    assert_eq!(world.query::<&mut bool>().iter().count(), 2);
+    assert!(world.query_one::<&i32>(e).unwrap().try_get().is_err());
+
+    drop(held);
+    assert_eq!(query.try_iter().unwrap().count(), 2);
+    drop(query);
+    assert_eq!(
+        world.query_one::<&i32>(e).unwrap().try_get().unwrap(),
+        Some(&2)
+    );
+}
+
+#[test]
+fn entity_ref_has() {
+    let mut world = World::new();
+    let a = world.spawn((123, "abc"));
+    let b = world.spawn(());
+    let _held = world.get_mut::<i32>(a).unwrap();
+    let entity = world.entity(a).unwrap();
+    // Probing doesn't borrow, so doesn't conflict with `_held`
+    assert!(entity.has::<i32>());
+    assert!(entity.has::<&str>());
+    assert!(!entity.has::<bool>());
+    assert!(entity.get::<bool>().is_none());
+    assert_eq!(*entity.get::<&str>().unwrap(), "abc");
+    assert!(!world.entity(b).unwrap().has::<i32>());
+}
+
+#[test]
+fn memory_usage() {
+    let mut world = World::new();
+    let empty = world.memory_usage();
+    let entities = world
+        .spawn_batch((0..1000).map(|i| (i, [0u8; 16])))
+        .collect::<Vec<_>>();
+    let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    assert_eq!(archetype.len(), 1000);
+    assert!(archetype.capacity() >= 1000);
+    let per_entity = 4 + 16 + 4 + 2 * 2 + 2 * 8; // components, ID, flags, and ticks
+    assert!(archetype.memory_usage() >= 1000 * per_entity);
+    let full = world.memory_usage();
+    assert!(full >= empty + 1000 * per_entity);
+
+    world.despawn_batch(&entities);
+    // Storage is retained for reuse until maintenance
+    assert!(world.memory_usage() >= full);
+    assert!(world.maintain_while(|| true));
+    assert!(world.memory_usage() < full);
+    let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    assert_eq!(archetype.memory_usage(), 0);
+}
+
+#[test]
+fn exact_size_after_partial_iteration() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2, true));
+    world.spawn((3, "abc"));
+    world.spawn((true,));
+
+    let mut query = world.query::<&i32>();
+    let mut iter = query.iter();
+    assert_eq!(iter.len(), 3);
+    iter.next().unwrap();
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.size_hint(), (2, Some(2)));
+    iter.next().unwrap();
+    iter.next().unwrap();
+    assert_eq!(iter.len(), 0);
+    assert!(iter.next().is_none());
+    drop(query);
+
+    let mut iter = world.iter();
+    assert_eq!(iter.len(), 4);
+    iter.next().unwrap();
+    iter.next().unwrap();
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "query violates a unique borrow")]
+fn query_mut_rejects_aliasing() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.query_mut::<(&mut i32, &i32)>();
+}
+
+#[test]
+#[should_panic(expected = "query violates a unique borrow")]
+fn query_one_mut_rejects_aliasing() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    world.query_one_mut::<(&mut i32, Option<&mut i32>)>(e).ok();
+}
+
+#[test]
+fn query_mut_disjoint_borrows() {
+    let mut world = World::new();
+    let e = world.spawn((1, true));
+    for (_, (x, y)) in world.query_mut::<(&mut i32, &mut bool)>() {
+        *x += 1;
+        *y = false;
+    }
+    assert_eq!(*world.get::<i32>(e).unwrap(), 2);
+    assert_eq!(
+        world
+            .query_mut::<Without<bool, &mut i32>>()
+            .into_iter()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn entity_niche() {
+    assert_eq!(
+        core::mem::size_of::<Option<Entity>>(),
+        core::mem::size_of::<Entity>()
+    );
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    assert_ne!(e, Entity::DANGLING);
+    assert!(!world.contains(Entity::DANGLING));
+    assert!(world.despawn(Entity::DANGLING).is_err());
+    assert_eq!(Entity::from_bits(e.id().into()), None);
+}
+
+#[test]
+fn exhausted_ids_are_retired() {
+    let mut world = World::new();
+    let last = Entity::from_bits(u64::from(u32::MAX - 1) << 32).unwrap();
+    world.spawn_at(last, (1,));
+    world.despawn(last).unwrap();
+    // The ID can't be reused without aliasing `last` after its generation wraps around
+    let e = world.spawn((2,));
+    assert_ne!(e.id(), last.id());
+    assert!(!world.contains(last));
+    for _ in 0..10 {
+        let e = world.spawn(());
+        assert_ne!(e.id(), last.id());
+        world.despawn(e).unwrap();
+    }
+
+    // Retired IDs are also skipped by `clear`, but may be explicitly spawned at
+    world.clear();
+    assert!(world.spawn(()).id() != last.id());
+    let revived = Entity::from_bits(u64::from(last.id()) | 1 << 32).unwrap();
+    world.spawn_at(revived, (3,));
+    assert_eq!(*world.get::<i32>(revived).unwrap(), 3);
+    assert_eq!(world.len(), 2);
+}
+
+#[test]
+fn retired_generation_is_never_alive() {
+    let mut world = World::new();
+    let last = Entity::from_bits(u64::from(u32::MAX - 1) << 32).unwrap();
+    world.spawn_at(last, (1,));
+    world.despawn(last).unwrap();
+    let ghost = Entity::from_bits(u64::from(u32::MAX) << 32 | u64::from(last.id())).unwrap();
+    assert!(!world.contains(ghost));
+    assert!(world.entity(ghost).is_err());
+    assert!(world.get::<i32>(ghost).is_err());
+    assert!(world.insert_one(ghost, true).is_err());
+    assert!(world.despawn(ghost).is_err());
+    world.validate().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "maximum generation is reserved")]
+fn spawn_at_dangling() {
+    World::new().spawn_at(Entity::DANGLING, ());
+}
+
+#[test]
+fn despawn_all() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "abc"));
+    let c = world.spawn(("def",));
+    let d = world.spawn((3, 4.5f32));
+    assert_eq!(world.despawn_all::<Without<f32, &i32>>(), 2);
+    assert!(!world.contains(a) && !world.contains(b));
+    assert!(world.contains(c) && world.contains(d));
+    assert_eq!(world.len(), 2);
+    assert_eq!(world.despawn_all::<&i32>(), 1);
+    assert_eq!(world.despawn_all::<&i32>(), 0);
+    assert_eq!(*world.get::<&str>(c).unwrap(), "def");
+    assert!(world
+        .drain_vanished()
+        .any(|x| x == std::any::TypeId::of::<i32>()));
+
+    // IDs are recycled
+    let e = world.spawn((5,));
+    assert!([a.id(), b.id(), d.id()].contains(&e.id()));
+
+    // Queries filtering individual entities
+    world.clear_trackers();
+    let f = world.spawn((6,));
+    assert_eq!(world.despawn_all::<Added<i32, ()>>(), 1);
+    assert!(!world.contains(f));
+    assert!(world.contains(e));
+}
+
+#[test]
+fn hooks() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Insert(Entity, i32),
+        Remove(Entity, i32),
+        Despawn(Entity),
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let log = events.clone();
+    world.on_insert(move |e, &x: &i32| log.lock().unwrap().push(Event::Insert(e, x)));
+    let log = events.clone();
+    world.on_remove(move |e, &x: &i32| log.lock().unwrap().push(Event::Remove(e, x)));
+    let log = events.clone();
+    world.on_despawn(move |e| log.lock().unwrap().push(Event::Despawn(e)));
+    let take = || core::mem::take(&mut *events.lock().unwrap());
+
+    let a = world.spawn((1, "abc"));
+    let b = world.spawn_batch(vec![(2,)]).next().unwrap();
+    assert_eq!(take(), [Event::Insert(a, 1), Event::Insert(b, 2)]);
+
+    world.insert(a, (3, true)).unwrap();
+    world.insert_one(a, 4.5f32).unwrap();
+    assert_eq!(take(), [Event::Insert(a, 3)]);
+
+    assert_eq!(world.remove_one::<i32>(a), Ok(3));
+    assert!(world.remove_one::<i32>(a).is_err());
+    assert!(world.remove::<(bool, i32)>(a).is_err());
+    world.remove_one::<bool>(a).unwrap();
+    assert_eq!(take(), [Event::Remove(a, 3)]);
+
+    world.despawn(a).unwrap();
+    world.despawn(b).unwrap();
+    assert_eq!(
+        take(),
+        [Event::Despawn(a), Event::Remove(b, 2), Event::Despawn(b)]
+    );
+
+    let c = world.spawn((5,));
+    let d = world.spawn((6, true));
+    take();
+    assert_eq!(world.despawn_all::<&bool>(), 1);
+    assert_eq!(take(), [Event::Remove(d, 6), Event::Despawn(d)]);
+    world.clear();
+    assert_eq!(take(), [Event::Remove(c, 5), Event::Despawn(c)]);
+}
+
+#[test]
+fn events() {
+    let mut world = World::new();
+    assert_eq!(world.events::<i32>().count(), 0);
+    world.send_event(1);
+    world.send_event(2);
+    world.send_event("abc");
+    assert_eq!(world.events::<i32>().copied().collect::<Vec<_>>(), [1, 2]);
+    world.update_events();
+    world.send_event(3);
+    assert_eq!(
+        world.events::<i32>().copied().collect::<Vec<_>>(),
+        [1, 2, 3]
+    );
+    assert_eq!(world.events::<&str>().count(), 1);
+    world.update_events();
+    assert_eq!(world.events::<i32>().copied().collect::<Vec<_>>(), [3]);
+    assert_eq!(world.events::<&str>().count(), 0);
+    world.update_events();
+    assert_eq!(world.events::<i32>().count(), 0);
+}
+
+#[test]
+fn secondary_index() {
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct ByValue(BTreeMap<i32, Entity>);
+    impl SecondaryIndex for ByValue {
+        type Source = i32;
+        fn insert(&mut self, entity: Entity, &value: &i32) {
+            self.0.retain(|_, &mut x| x != entity);
+            self.0.insert(value, entity);
+        }
+        fn remove(&mut self, _: Entity, value: &i32) {
+            self.0.remove(value);
+        }
+    }
+    let values = |world: &World| {
+        world
+            .index::<ByValue>()
+            .unwrap()
+            .0
+            .iter()
+            .map(|(&k, &v)| (k, v))
+            .collect::<Vec<_>>()
+    };
+
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    assert!(world.index::<ByValue>().is_none());
+    world.add_index(ByValue::default());
+    assert_eq!(values(&world), [(1, a)]);
+    let b = world.spawn((2,));
+    let c = world.spawn(("c",));
+    world.insert_one(c, 3).unwrap();
+    assert_eq!(values(&world), [(1, a), (2, b), (3, c)]);
+    world.insert_one(a, 4).unwrap();
+    world.remove_one::<i32>(b).unwrap();
+    world.despawn(c).unwrap();
+    assert_eq!(values(&world), [(4, a)]);
+
+    world.clear_changed();
+    *world.get_mut::<i32>(a).unwrap() = 5;
+    assert_eq!(values(&world), [(4, a)]);
+    world.update_indexes();
+    assert_eq!(values(&world), [(5, a)]);
+}
+
+#[test]
+#[cfg(feature = "spatial")]
+fn grid_index() {
+    use hecs::spatial::GridIndex;
+
+    struct Pos([f32; 3]);
+
+    let mut world = World::new();
+    let a = world.spawn((Pos([-0.5, -0.5, 0.0]),));
+    world.add_index(GridIndex::new(2.0, |x: &Pos| x.0));
+    let b = world.spawn((Pos([3.0, 3.0, 0.0]), true));
+    let c = world.spawn((Pos([10.0, 0.0, 0.0]),));
+    world.clear_changed();
+    let index = world.index::<GridIndex<Pos>>().unwrap();
+    assert_eq!(index.len(), 3);
+    let mut found = index
+        .entities_within_aabb([-1.0, -1.0, -1.0], [3.0, 3.0, 1.0])
+        .collect::<Vec<_>>();
+    found.sort();
+    let mut expected = [a, b];
+    expected.sort();
+    assert_eq!(found, expected);
+
+    world.get_mut::<Pos>(c).unwrap().0 = [0.5, 0.5, 0.0];
+    world.despawn(a).unwrap();
+    world.remove_one::<Pos>(b).unwrap();
+    world.update_indexes();
+    let index = world.index::<GridIndex<Pos>>().unwrap();
+    assert_eq!(index.len(), 1);
+    assert_eq!(index.position(c), Some([0.5, 0.5, 0.0]));
+    assert_eq!(
+        index
+            .entities_within_radius([0.0, 0.0, 0.0], 1.0)
+            .collect::<Vec<_>>(),
+        [c]
+    );
+}
+
+#[test]
+fn hierarchy() {
+    let mut world = World::new();
+    let root = world.spawn((0,));
+    let a = world.spawn((1,));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3,));
+    world.attach(a, root).unwrap();
+    world.attach(b, root).unwrap();
+    world.attach(c, a).unwrap();
+    assert_eq!(world.children(root).collect::<Vec<_>>(), [a, b]);
+    assert_eq!(world.parent(c), Some(a));
+    assert_eq!(world.parent(root), None);
+
+    world.attach(c, b).unwrap();
+    assert_eq!(world.children(a).count(), 0);
+    assert_eq!(world.detach(b), Some(root));
+    assert_eq!(world.detach(b), None);
+    world.despawn(root).unwrap();
+    assert!(!world.contains(a));
+    assert!(world.contains(b));
+    assert!(world.contains(c));
+
+    assert_eq!(world.despawn_all::<&bool>(), 1);
+    assert!(!world.contains(c));
+    assert_eq!(world.parent(c), None);
+
+    let d = world.spawn(());
+    let e = world.spawn(());
+    let f = world.spawn(());
+    world.attach(e, d).unwrap();
+    world.attach(f, e).unwrap();
+    assert_eq!(world.despawn_batch(&[e, d]), 2);
+    assert!(!world.contains(f));
+    assert_eq!(world.attach(f, d), Err(NoSuchEntity));
+    assert!(world.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "entity cannot be attached to itself or its descendants")]
+fn hierarchy_cycle() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn(());
+    world.attach(b, a).unwrap();
+    world.attach(a, b).unwrap();
+}
+
+#[test]
+fn prepared_query() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    world.spawn(("abc",));
+    let mut query = PreparedQuery::<(&i32, &mut bool)>::new();
+    {
+        let mut borrow = query.query(&world);
+        let mut iter = borrow.iter();
+        assert_eq!(iter.len(), 1);
+        let (e, (&i, b)) = iter.next().unwrap();
+        assert_eq!((e, i), (a, 1));
+        *b = false;
+    }
+    assert!(!*world.get::<bool>(a).unwrap());
+
+    let b = world.spawn((2, false, "def"));
+    let mut found = query
+        .query_mut(&mut world)
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, [a, b]);
+
+    let mut other = World::new();
+    let c = other.spawn((3, true));
+    assert_eq!(
+        query
+            .query(&other)
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [c]
+    );
+    assert_eq!(query.query_mut(&mut world).len(), 2);
+}
+
+#[test]
+fn prepared_query_masks() {
+    let mut world = World::new();
+    for i in 0..70u32 {
+        let mut builder = EntityBuilder::new();
+        builder.add(i);
+        match i % 3 {
+            0 => builder.add(i as i32),
+            1 => builder.add(i as i32).add(true),
+            _ => builder.add(i as u64),
+        };
+        world.spawn(builder.build());
+    }
+    let a = world.spawn((-1, "abc"));
+    let b = world.spawn((-2, 'c'));
+
+    let mut query = PreparedQuery::<Without<bool, &i32>>::new();
+    assert_eq!(query.query_mut(&mut world).len(), 26);
+    let mut query = PreparedQuery::<With<&str, Changed<i32, &i32>>>::new();
+    assert_eq!(
+        query
+            .query(&world)
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [a]
+    );
+    let mut query = PreparedQuery::<(&i32, &f64)>::new();
+    assert_eq!(query.query_mut(&mut world).len(), 0);
+    world.spawn((1, 2.0f64));
+    assert_eq!(query.query_mut(&mut world).len(), 1);
+    let mut query = PreparedQuery::<(&i32, Option<&bool>)>::new();
+    assert_eq!(query.query_mut(&mut world).len(), 50);
+    assert_eq!(world.despawn_all::<Without<u32, &char>>(), 1);
+    assert!(!world.contains(b));
+}
+
+#[test]
+fn reserve_capacity() {
+    let mut world = World::new();
+    world.reserve_entity_capacity(100);
+    world.reserve_archetype::<(i32, bool)>(60);
+    world.reserve_archetype::<(i32, bool)>(10);
+    assert_eq!(world.len(), 0);
+    let archetype = world
+        .archetypes()
+        .find(|x| x.has::<i32>() && x.has::<bool>())
+        .unwrap();
+    assert!(archetype.capacity() >= 60);
+    assert!(archetype.capacity() < 70);
+    let capacity = archetype.capacity();
+    for i in 0..60 {
+        world.spawn((i, true));
+    }
+    let archetype = world.archetypes().find(|x| x.has::<bool>()).unwrap();
+    assert_eq!(archetype.capacity(), capacity);
+}
+
+#[test]
+fn shrink_to_fit() {
+    let mut world = World::new();
+    let a = world
+        .spawn_batch((0..100).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let b = world
+        .spawn_batch((0..100).map(|i| (i,)))
+        .collect::<Vec<_>>();
+    world.despawn_batch(&a[10..]);
+    world.despawn_batch(&b[10..]);
+    let capacity = |world: &World, with_bool| {
+        world
+            .archetypes()
+            .find(|x| x.has::<i32>() && x.has::<bool>() == with_bool)
+            .unwrap()
+            .capacity()
+    };
+    assert!(capacity(&world, true) >= 100);
+
+    world.shrink_archetype::<(i32, bool)>();
+    world.shrink_archetype::<(f32,)>();
+    assert_eq!(capacity(&world, true), 10);
+    assert!(capacity(&world, false) >= 100);
+    world.shrink_to_fit();
+    assert_eq!(capacity(&world, false), 10);
+    world.despawn_batch(&a);
+    world.shrink_to_fit();
+    assert_eq!(capacity(&world, true), 0);
+    assert_eq!(
+        world
+            .query::<&i32>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        b[..10]
+    );
+}
+
+#[test]
+fn sorted_iteration() {
+    let mut world = World::new();
+    let entities = (0..10)
+        .map(|i| {
+            if i % 2 == 0 {
+                world.spawn((i, true))
+            } else {
+                world.spawn((i, 'x'))
+            }
+        })
+        .collect::<Vec<_>>();
+    world.despawn(entities[2]).unwrap();
+    world.remove_one::<bool>(entities[4]).unwrap();
+    world.insert_one(entities[1], true).unwrap();
+    let mut query = world.query::<&i32>();
+    let iter = query.iter_sorted();
+    assert_eq!(iter.len(), 9);
+    assert_eq!(
+        iter.map(|(_, &i)| i).collect::<Vec<_>>(),
+        [0, 1, 3, 4, 5, 6, 7, 8, 9]
+    );
+    drop(query);
+
+    world.clear_trackers();
+    *world.get_mut::<i32>(entities[9]).unwrap() = 90;
+    *world.get_mut::<i32>(entities[3]).unwrap() = 30;
+    assert_eq!(
+        world
+            .query::<Changed<i32, ()>>()
+            .iter_sorted()
+            .map(|(e, ())| e)
+            .collect::<Vec<_>>(),
+        [entities[3], entities[9]]
+    );
+    for (_, x) in world
+        .query_mut::<&mut i32>()
+        .with::<bool>()
+        .into_iter_sorted()
+    {
+        *x += 100;
+    }
+    assert_eq!(*world.get::<i32>(entities[1]).unwrap(), 101);
+    assert_eq!(*world.get::<i32>(entities[4]).unwrap(), 4);
+}
+
+#[test]
+fn snapshot_restore() {
+    use std::sync::Arc;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Position([f32; 3]);
+    struct Marker;
+
+    let mut registry = ComponentRegistry::new();
+    register_component!(registry, Position, copy);
+    register_component!(registry, Arc<i32>, clone);
+    register_component!(registry, Marker, "marker");
+    assert!(registry.get::<Position>().unwrap().is_copy());
+    assert!(!registry.get::<Arc<i32>>().unwrap().is_copy());
+
+    let shared = Arc::new(7);
+    let mut world = World::new();
+    let a = world.spawn((Position([1.0; 3]), shared.clone()));
+    let b = world.spawn((Position([2.0; 3]),));
+    let c = world.spawn((shared.clone(),));
+    world.attach(c, a).unwrap();
+    world.despawn(b).unwrap();
+    let snapshot = world.snapshot(&registry).unwrap();
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(Arc::strong_count(&shared), 5);
+
+    world.get_mut::<Position>(a).unwrap().0[0] = 10.0;
+    world.remove_one::<Arc<i32>>(a).unwrap();
+    world.despawn(c).unwrap();
+    world.spawn((Position([3.0; 3]), true));
+    world.spawn(());
+    world.restore(&snapshot);
+    assert_eq!(world.len(), 2);
+    assert_eq!(*world.get::<Position>(a).unwrap(), Position([1.0; 3]));
+    assert_eq!(**world.get::<Arc<i32>>(c).unwrap(), 7);
+    assert_eq!(world.parent(c), Some(a));
+    assert!(!world.contains(b));
+    assert_eq!(world.query::<&Position>().iter().count(), 1);
+    assert!(world.query::<&bool>().iter().next().is_none());
+    assert_eq!(Arc::strong_count(&shared), 5);
+    assert!(world.is_changed::<Position>(a).unwrap());
+
+    // Handles are reissued deterministically after a rollback
+    let d2 = world.spawn((Position([3.0; 3]), true));
+    world.restore(&snapshot);
+    world.restore(&snapshot);
+    assert_eq!(world.spawn((Position([3.0; 3]), true)), d2);
+    world.restore(&snapshot);
+    assert_eq!(world.len(), 2);
+    drop(snapshot);
+    assert_eq!(Arc::strong_count(&shared), 3);
+    world.clear();
+    assert_eq!(Arc::strong_count(&shared), 1);
+
+    world.spawn((Marker,));
+    assert_eq!(
+        world.snapshot(&registry).err(),
+        Some(CloneError::Uncloneable(std::any::TypeId::of::<Marker>()))
+    );
+}
+
+#[test]
+#[should_panic(expected = "snapshot was taken of a different world")]
+fn restore_foreign_snapshot() {
+    let registry = ComponentRegistry::new();
+    let snapshot = World::new().snapshot(&registry).unwrap();
+    World::new().restore(&snapshot);
+}
+
+#[test]
+fn world_diff() {
+    use std::any::TypeId;
+
+    let mut registry = ComponentRegistry::new();
+    register_component!(registry, i32, copy);
+    register_component!(registry, bool, copy);
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn(());
+    let baseline = world.snapshot(&registry).unwrap();
+    // Components are flagged as changed when spawned
+    assert_eq!(world.diff(&baseline).changed().len(), 3);
+    world.clear_changed();
+    assert!(world.diff(&baseline).is_empty());
+
+    // Moving between archetypes doesn't flag untouched components
+    world.insert_one(b, false).unwrap();
+    world.despawn(c).unwrap();
+    let d = world.spawn(());
+    assert_eq!(d.id(), c.id());
+    world.query_mut::<&mut i32>().into_iter().count();
+    let diff = world.diff(&baseline);
+    assert_eq!(diff.spawned(), [d]);
+    assert_eq!(diff.despawned(), [c]);
+    assert_eq!(
+        diff.changed(),
+        [
+            (a, TypeId::of::<i32>()),
+            (b, TypeId::of::<i32>()),
+            (b, TypeId::of::<bool>())
+        ]
+    );
+    assert!(diff.removed().is_empty());
+
+    world.clear_changed();
+    let diff = world.diff(&baseline);
+    assert_eq!(diff.changed(), [(b, TypeId::of::<bool>())]);
+    world.clear();
+    let diff = world.diff(&baseline);
+    assert!(diff.spawned().is_empty());
+    assert_eq!(diff.despawned(), [c, a, b]);
+}
+
+#[test]
+fn dynamic_query_filters() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "abc"));
+    let c = world.spawn((3, true, "def"));
+    let entities = |query: &mut QueryBorrow<'_, &i32>| {
+        let mut xs = query.iter().map(|(e, _)| e).collect::<Vec<_>>();
+        xs.sort();
+        xs
+    };
+
+    let mut query = world.query::<&i32>().with_dynamic(TypeId::of::<bool>());
+    assert_eq!(query.iter().len(), 2);
+    drop(query);
+    let mut query = world
+        .query::<&i32>()
+        .with_dynamic(TypeId::of::<bool>())
+        .without_dynamic(TypeId::of::<&str>());
+    assert_eq!(entities(&mut query), [a]);
+    drop(query);
+    assert_eq!(
+        world
+            .query::<&i32>()
+            .without_dynamic(TypeId::of::<bool>())
+            .iter_batched(1)
+            .flatten()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [b]
+    );
+    assert!(!world
+        .query::<&i32>()
+        .with_dynamic(TypeId::of::<f32>())
+        .any());
+    assert_eq!(
+        world
+            .query::<&i32>()
+            .with::<&str>()
+            .with_dynamic(TypeId::of::<bool>())
+            .iter_sorted()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [c]
+    );
+
+    // Components in pruned archetypes aren't borrowed
+    let held = world.get_mut::<i32>(b).unwrap();
+    let mut query = world.query::<&mut i32>().with_dynamic(TypeId::of::<bool>());
+    for (_, x) in query.iter() {
+        *x += 10;
+    }
+    drop(query);
+    drop(held);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 11);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+}
+
+#[test]
+#[should_panic(expected = "query filters must be added before the query is executed")]
+fn dynamic_query_filter_after_iter() {
+    let world = World::new();
+    let mut query = world.query::<&i32>();
+    query.iter();
+    query.with_dynamic(std::any::TypeId::of::<bool>());
+}
+
+#[test]
+fn insert_one_returns_replaced() {
+    use std::sync::{Arc, Mutex};
+
+    let inserted = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let log = inserted.clone();
+    world.on_insert(move |_, &x: &i32| log.lock().unwrap().push(x));
+    let e = world.spawn(("abc",));
+    assert_eq!(world.insert_one(e, 1), Ok(None));
+    world.clear_changed();
+    assert_eq!(world.insert_one(e, 2), Ok(Some(1)));
+    assert!(world.is_changed::<i32>(e).unwrap());
+    assert_eq!(*world.get::<i32>(e).unwrap(), 2);
+    assert_eq!(*inserted.lock().unwrap(), [1, 2]);
+
+    world.despawn(e).unwrap();
+    assert_eq!(world.insert_one(e, 3), Err(NoSuchEntity));
+}
+
+#[test]
+fn zero_sized_components() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Marker;
+    impl Drop for Marker {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    #[repr(align(16))]
+    struct Aligned;
+
+    let mut world = World::new();
+    let entities = (0..4)
+        .map(|i| world.spawn((i, Marker, Aligned)))
+        .collect::<Vec<_>>();
+    world.clear_changed();
+    world.insert_one(entities[0], true).unwrap();
+    assert!(!world.is_changed::<Marker>(entities[0]).unwrap());
+    let removed = world.remove_one::<Marker>(entities[1]).unwrap();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    drop(removed);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    world.despawn(entities[2]).unwrap();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+    let aligned = world.get::<Aligned>(entities[3]).unwrap();
+    assert_eq!(&*aligned as *const Aligned as usize % 16, 0);
+    drop(aligned);
+    assert_eq!(*world.get::<i32>(entities[3]).unwrap(), 3);
+    assert_eq!(world.query::<&Marker>().iter().count(), 2);
+    world.clear();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn sparse_components() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let generation = world.archetypes_generation();
+    assert_eq!(world.insert_sparse(a, "a"), Ok(None));
+    assert_eq!(world.insert_sparse(b, "b"), Ok(None));
+    assert_eq!(world.insert_sparse(a, "A"), Ok(Some("a")));
+    assert_eq!(world.archetypes_generation(), generation);
+    assert_eq!(world.entity(a).unwrap().len(), 1);
+    {
+        let set = world.sparse::<&str>().unwrap();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(a), Some(&"A"));
+        let mut all = set.iter().collect::<Vec<_>>();
+        all.sort_by_key(|x| x.0);
+        assert_eq!(all, [(a, &"A"), (b, &"b")]);
+    }
+
+    assert_eq!(world.remove_sparse::<&str>(a), Some("A"));
+    assert_eq!(world.remove_sparse::<&str>(a), None);
+    assert_eq!(world.sparse::<&str>().unwrap().get(b), Some(&"b"));
+
+    world.despawn(b).unwrap();
+    assert!(world.sparse::<&str>().unwrap().is_empty());
+    assert_eq!(world.insert_sparse(b, "b"), Err(NoSuchEntity));
+
+    // A reused ID doesn't inherit the despawned entity's components
+    world.insert_sparse(a, "a").unwrap();
+    world.despawn(a).unwrap();
+    let c = world.spawn((3,));
+    assert!(!world.sparse::<&str>().unwrap().contains(c));
+    assert!(world.sparse::<bool>().is_none());
+}
+
+#[test]
+fn take_drops_sparse_components() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.insert_sparse(a, String::from("a")).unwrap();
+    let taken = world.take(a).unwrap();
+    assert_eq!(taken.component_types().count(), 1);
+    assert!(world.sparse::<String>().unwrap().is_empty());
+    let b = world.spawn(taken);
+    assert!(!world.sparse::<String>().unwrap().contains(b));
+}
+
+#[test]
+fn absorb_sparse_components() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.insert_sparse(a, "a").unwrap();
+
+    let mut other = World::new();
+    let b = other.spawn((2,));
+    let c = other.spawn((3,));
+    other.insert_sparse(b, "b").unwrap();
+    other.insert_sparse(c, 3u8).unwrap();
+    let map = world.absorb(&mut other);
+    assert!(other.sparse::<&str>().unwrap().is_empty());
+    let strs = world.sparse::<&str>().unwrap();
+    assert_eq!(strs.len(), 2);
+    assert_eq!(strs.get(a), Some(&"a"));
+    assert_eq!(strs.get(map.get(b).unwrap()), Some(&"b"));
+    assert_eq!(
+        world.sparse::<u8>().unwrap().get(map.get(c).unwrap()),
+        Some(&3)
+    );
+}
+
+#[test]
+fn snapshot_sparse_components() {
+    let mut registry = ComponentRegistry::new();
+    register_component!(registry, i32, copy);
+    register_component!(registry, String, clone);
+
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    world.insert_sparse(a, String::from("a")).unwrap();
+    world.register_sparse::<bool>();
+    let snapshot = world.snapshot(&registry).unwrap();
+
+    world.insert_sparse(a, String::from("changed")).unwrap();
+    world.insert_sparse(b, String::from("b")).unwrap();
+    world.insert_sparse(b, true).unwrap();
+    world.restore(&snapshot);
+    let strings = world.sparse::<String>().unwrap();
+    assert_eq!(strings.len(), 1);
+    assert_eq!(strings.get(a).unwrap(), "a");
+    drop(strings);
+    assert!(world.sparse::<bool>().unwrap().is_empty());
+
+    // Sparse component types must be cloneable too, unless none are present
+    world.insert_sparse(a, 1.5f32).unwrap();
+    assert!(world.snapshot(&registry).is_err());
+    world.remove_sparse::<f32>(a);
+    assert!(world.snapshot(&registry).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn sparse_borrow_conflict() {
+    let mut world = World::new();
+    world.register_sparse::<i32>();
+    let _a = world.sparse::<i32>().unwrap();
+    let _b = world.sparse_mut::<i32>().unwrap();
+}
+
+#[test]
+#[cfg(feature = "executor")]
+fn schedule_stages() {
+    use hecs::schedule::{Schedule, System};
+
+    let mut world = World::new();
+    let e = world.spawn((1i32, 1u32, true));
+    let mut schedule = Schedule::new();
+    schedule
+        .add(System::new::<&mut i32, _>("double", |world| {
+            for (_, x) in world.query::<&mut i32>().iter() {
+                *x *= 2;
+            }
+        }))
+        .add(System::new::<&mut u32, _>("increment", |world| {
+            for (_, x) in world.query::<&mut u32>().iter() {
+                *x += 1;
+            }
+        }))
+        .add(System::new::<(&i32, &mut bool), _>("check", |world| {
+            for (_, (&x, flag)) in world.query::<(&i32, &mut bool)>().iter() {
+                *flag = x > 2;
+            }
+        }))
+        .add(System::new::<(), _>("flags", |_| {}).reads::<bool>());
+    assert_eq!(
+        schedule.stages().collect::<Vec<_>>(),
+        [vec!["double", "increment"], vec!["check"], vec!["flags"]]
+    );
+    schedule.run(&world);
+    assert!(!*world.get::<bool>(e).unwrap());
+    schedule.run(&world);
+    assert_eq!(*world.get::<i32>(e).unwrap(), 4);
+    assert_eq!(*world.get::<u32>(e).unwrap(), 3);
+    assert!(*world.get::<bool>(e).unwrap());
+}
+
+#[test]
+fn query_access() {
+    use std::any::TypeId;
+
+    let access = <(&mut i32, Option<&bool>, With<u8, &u16>)>::access();
+    let mut borrows = access.iter().collect::<Vec<_>>();
+    borrows.sort();
+    let mut expected = vec![
+        (TypeId::of::<i32>(), true),
+        (TypeId::of::<bool>(), false),
+        (TypeId::of::<u16>(), false),
+    ];
+    expected.sort();
+    assert_eq!(borrows, expected);
+    assert!(!access.reads::<u8>());
+    assert!(!access.is_read_only());
+    assert!(<(&i32, &bool)>::access().is_read_only());
+
+    let mut combined = <&i32>::access();
+    assert!(!combined.conflicts_with(&<&i32>::access()));
+    combined.extend(&<&mut i32>::access());
+    assert!(combined.writes::<i32>());
+    assert!(combined.conflicts_with(&<&i32>::access()));
+}
+
+#[test]
+fn change_ticks() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    assert_eq!(world.tick(), Tick(0));
+    assert_eq!(world.last_changed::<i32>(a), Some(Tick(0)));
+    assert_eq!(world.last_changed::<&str>(a), None);
+    assert_eq!(world.advance_tick(), Tick(1));
+
+    for (_, x) in world.query_mut::<&mut i32>() {
+        *x += 1;
+    }
+    // Flagged components report the current tick until the flags are reset
+    assert_eq!(world.last_changed::<i32>(b), Some(Tick(1)));
+    world.advance_tick();
+    world.remove_one::<bool>(a).unwrap();
+    world.insert_one(a, false).unwrap();
+    world.clear_changed();
+    world.advance_tick();
+    assert_eq!(world.tick(), Tick(3));
+    assert_eq!(world.last_changed::<i32>(a), Some(Tick(1)));
+    assert_eq!(world.last_changed::<i32>(b), Some(Tick(1)));
+    assert_eq!(world.last_changed::<bool>(a), Some(Tick(2)));
+
+    // A reused ID doesn't inherit the despawned entity's ticks
+    world.despawn(b).unwrap();
+    assert_eq!(world.last_changed::<i32>(b), None);
+    world.advance_tick();
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), b.id());
+    world.clear_changed();
+    assert_eq!(world.last_changed::<i32>(c), Some(Tick(4)));
+}
+
+#[test]
+fn change_ticks_follow_moved_rows() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3, true));
+    world.advance_tick();
+    *world.get_mut::<i32>(c).unwrap() += 1;
+    world.advance_tick();
+    *world.get_mut::<bool>(b).unwrap() = false;
+    world.advance_tick();
+
+    // `c` fills the row `a` vacates
+    world.despawn(a).unwrap();
+    assert_eq!(world.last_changed::<i32>(c), Some(Tick(1)));
+    assert_eq!(world.last_changed::<bool>(c), Some(Tick(0)));
+    // `b` moves to another archetype
+    world.insert_one(b, "b").unwrap();
+    world.clear_changed();
+    assert_eq!(world.last_changed::<bool>(b), Some(Tick(2)));
+    assert_eq!(world.last_changed::<i32>(b), Some(Tick(0)));
+    assert_eq!(world.last_changed::<&str>(b), Some(Tick(3)));
+}
+
+#[test]
+fn entity_components_reflection() {
+    struct Marker;
+
+    let mut world = World::new();
+    let a = world.spawn((1u64, "abc", Marker));
+    let entity = world.entity(a).unwrap();
+    let mut components = entity.components().collect::<Vec<_>>();
+    components.sort_by_key(|x| x.type_name());
+    assert_eq!(components.len(), 3);
+    assert_eq!(components[0].type_name(), "&str");
+    assert_eq!(components[0].layout(), std::alloc::Layout::new::<&str>());
+    assert_eq!(unsafe { *components[0].as_ptr().cast::<&str>() }, "abc");
+    assert!(components[1].type_name().ends_with("Marker"));
+    assert_eq!(components[1].layout().size(), 0);
+    assert_eq!(components[2].id(), std::any::TypeId::of::<u64>());
+    assert_eq!(unsafe { *components[2].as_ptr().cast::<u64>() }, 1);
+
+    let empty = world.spawn(());
+    assert_eq!(world.entity(empty).unwrap().components().count(), 0);
+}
+
+#[test]
+fn registry_raw_clone_and_drop() {
+    use std::mem::MaybeUninit;
+
+    let mut registry = ComponentRegistry::new();
+    registry.register_clone::<String>("name");
+    let meta = registry.get_by_name("name").unwrap();
+    let src = String::from("steve");
+    let mut dst = MaybeUninit::<String>::uninit();
+    unsafe {
+        assert!(meta.clone_raw((&src as *const String).cast(), dst.as_mut_ptr().cast()));
+        assert_eq!(*dst.as_ptr(), "steve");
+        meta.drop_raw(dst.as_mut_ptr().cast());
+    }
+}
+
+#[test]
+fn find_one() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    world.spawn((2,));
+    let (entity, (x, flag)) = world.find_one::<(&mut i32, &bool)>().unwrap();
+    assert_eq!(entity, a);
+    assert!(*flag);
+    *x = 10;
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    assert!(world.find_one::<&&str>().is_none());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "more than one entity matches")]
+fn find_one_ambiguous() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.spawn((2, true));
+    world.find_one::<&i32>();
+}
+
+#[test]
+fn insert_batch() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3,));
+    let d = world.spawn((4, "old"));
+    let empty = world.spawn(());
+    let dead = world.spawn((5,));
+    world.despawn(dead).unwrap();
+
+    let count = world.insert_batch(&[a, b, c, d, empty, dead, a], "new");
+    assert_eq!(count, 5);
+    for entity in [a, b, c, d, empty] {
+        assert_eq!(*world.get::<&str>(entity).unwrap(), "new");
+    }
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+    assert!(*world.get::<bool>(b).unwrap());
+    assert_eq!(world.entity(a).unwrap().len(), 2);
+    assert_eq!(world.entity(empty).unwrap().len(), 1);
+    assert_eq!(world.query::<&&str>().iter().count(), 5);
+}
+
+#[test]
+fn local_components() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let drops = Rc::new(Cell::new(0));
+    struct Tracked(Rc<Cell<u32>>);
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let mut world = LocalWorld::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn(());
+    for &entity in &[a, b, c] {
+        assert!(world
+            .insert_local(entity, Tracked(drops.clone()))
+            .unwrap()
+            .is_none());
+    }
+    assert!(world.get_local::<Tracked>(a).is_some());
+    assert!(world.get_local::<Rc<u32>>(a).is_none());
+
+    world.despawn(a).unwrap();
+    assert_eq!(drops.get(), 1);
+    assert_eq!(world.insert_local(a, 0u8), Err(NoSuchEntity));
+
+    // Despawned through the wrapped world, so only dropped on `prune`
+    world.despawn_batch(&[b]);
+    assert!(world.get_local::<Tracked>(b).is_none());
+    assert_eq!(world.local::<Tracked>().unwrap().len(), 2);
+    world.prune();
+    assert_eq!(drops.get(), 2);
+
+    drop(world.remove_local::<Tracked>(c));
+    assert_eq!(drops.get(), 3);
+    world.insert_local(c, Tracked(drops.clone())).unwrap();
+    drop(world.into_inner());
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+fn split_view() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, 1u32, true));
+    world.spawn((2i32, false));
+    let mut view = world.view::<(i32, u32, bool)>();
+    let (mut numbers, flags) = view.split::<(i32, u32), (bool,), _, _>();
+    let flag = flags.get::<bool, _>(a).unwrap();
+    for (_, (x, y)) in numbers.query_mut::<(&mut i32, Option<&mut u32>), _>() {
+        *x += 10;
+        if let Some(y) = y {
+            *y += 1;
+        }
+    }
+    assert!(*flag);
+    drop(flag);
+    assert_eq!(flags.query::<&bool, _>().iter().count(), 2);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 11);
+    assert_eq!(*world.get::<u32>(a).unwrap(), 2);
+}
+
+#[test]
+#[should_panic(expected = "split views must not share component types")]
+fn split_view_overlap() {
+    let mut world = World::new();
+    let mut view = world.view::<(i32, bool)>();
+    view.split::<(i32,), (bool, i32), _, _>();
+}
+
+#[test]
+fn entity_names() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    assert_eq!(world.set_name(a, "a").unwrap(), None);
+    assert_eq!(world.set_name(b, String::from("b")).unwrap(), None);
+    assert_eq!(world.entity_by_name("a"), Some(a));
+    assert_eq!(world.entity_by_name("b"), Some(b));
+
+    // Renaming frees the old name
+    assert_eq!(world.set_name(a, "c").unwrap().as_deref(), Some("a"));
+    assert_eq!(world.entity_by_name("a"), None);
+    assert_eq!(world.entity_by_name("c"), Some(a));
+
+    // Names are unique
+    world.set_name(b, "c").unwrap();
+    assert_eq!(world.entity_by_name("c"), Some(b));
+    assert_eq!(world.name(a), None);
+    assert_eq!(format!("{:?}", world.describe(a)), format!("{:?}", a));
+
+    world.despawn(b).unwrap();
+    assert_eq!(world.entity_by_name("c"), None);
+    assert!(world.set_name(b, "d").is_err());
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), b.id());
+    assert_eq!(world.name(c), None);
+
+    world.set_name(c, "c").unwrap();
+    assert_eq!(world.clear_name(c).as_deref(), Some("c"));
+    assert_eq!(world.entity_by_name("c"), None);
+
+    world.set_name(a, "a").unwrap();
+    world.clear();
+    assert_eq!(world.entity_by_name("a"), None);
+}
+
+#[test]
+fn validate_world() {
+    let mut config = WorldConfig::default();
+    config.validate = true;
+    let mut world = World::from_config(config);
+    assert_eq!(world.validate(), Ok(()));
+    let entities = (0..10)
+        .map(|i| world.spawn((i, i as f32)))
+        .collect::<Vec<_>>();
+    world.spawn(());
+    for &e in entities.iter().step_by(3) {
+        world.despawn(e).unwrap();
+    }
+    world.remove_one::<f32>(entities[1]).unwrap();
+    world.insert_one(entities[2], "abc").unwrap();
+    let reserved = world.reserve_entity();
+    assert_eq!(world.validate(), Ok(()));
+    world.spawn_at(reserved, (true,));
+    world.clear_trackers();
+
+    {
+        let mut query = world.query::<&mut f32>();
+        let _iter = query.iter();
+        assert_eq!(world.validate(), Err(IntegrityError::Borrowed("f32")));
+    }
+    world.clear();
+    world.clear_changed();
+    assert_eq!(world.validate(), Ok(()));
+}
+
+#[test]
+fn query_components_only() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2,));
+    world.spawn((3, "abc"));
+    let mut query = world.query::<&i32>();
+    let iter = query.iter_components();
+    assert_eq!(iter.len(), 3);
+    let mut values = iter.copied().collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, [1, 2, 3]);
+    drop(query);
+
+    for x in world.query_mut::<&mut i32>().into_iter().components() {
+        *x *= 10;
+    }
+    let mut values = world
+        .query::<&i32>()
+        .without::<bool>()
+        .iter_components()
+        .copied()
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, [20, 30]);
+}
+
+#[test]
+fn get_unchecked() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn(());
+    world.clear_changed();
+    unsafe {
+        *world.get_unchecked_mut::<i32>(a).unwrap() += 1;
+        assert_eq!(*world.get_unchecked::<i32>(a).unwrap(), 2);
+        assert!(world.get_unchecked::<&str>(a).is_err());
+        assert!(world.get_unchecked::<i32>(b).is_err());
+    }
+    assert!(world.is_changed::<i32>(a).unwrap());
+    assert!(!world.is_changed::<bool>(a).unwrap());
+    world.despawn(a).unwrap();
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), a.id());
+    unsafe {
+        assert_eq!(
+            world.get_unchecked::<i32>(a).err(),
+            Some(ComponentError::NoSuchEntity)
+        );
+    }
+}
+
+#[test]
+fn dense_indices() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    assert_eq!(world.get_dense_index(a), None);
+    assert_eq!(world.dense_index(c), Ok(0));
+    assert_eq!(world.dense_index(a), Ok(1));
+    assert_eq!(world.dense_index(c), Ok(0));
+    assert_eq!(world.dense_index_bound(), 2);
+
+    // Indices survive archetype moves
+    world.insert_one(c, true).unwrap();
+    world.remove_one::<i32>(c).unwrap();
+    assert_eq!(world.get_dense_index(c), Some(0));
+
+    world.despawn(c).unwrap();
+    assert_eq!(world.get_dense_index(c), None);
+    assert_eq!(world.dense_index(c), Err(NoSuchEntity));
+    assert_eq!(world.dense_index(b), Ok(0));
+    assert_eq!(world.release_dense_index(a), Some(1));
+    let d = world.spawn(());
+    assert_eq!(world.dense_index(d), Ok(1));
+    assert_eq!(world.dense_index_bound(), 2);
+
+    world.clear();
+    assert_eq!(world.dense_index_bound(), 0);
+}
+
+#[test]
+fn query_split() {
+    let mut world = World::new();
+    assert_eq!(world.query::<&i32>().iter_split(3).count(), 0);
+    world.spawn_batch((0..10).map(|i| (i,)));
+    let sizes = world
+        .query::<&i32>()
+        .iter_split(3)
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [4, 4, 2]);
+
+    world.spawn_batch((0..10).map(|i| (i, true)));
+    let sizes = world
+        .query::<&i32>()
+        .iter_split(4)
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [5, 5, 5, 5]);
+    let sizes = world
+        .query::<&i32>()
+        .with::<bool>()
+        .iter_split(2)
+        .map(|batch| batch.count())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [5, 5]);
+}
+
+#[test]
+fn entity_handles() {
+    let mut world = World::new();
+    let other = World::new();
+    let a = world.spawn((1,));
+    let handle = world.handle(a).unwrap();
+    assert_eq!(handle.entity(), a);
+    assert!(handle.is_alive(&world));
+    assert!(!handle.is_alive(&other));
+    assert!(handle.get(&other).is_none());
+    assert_eq!(*handle.get(&world).unwrap().get::<i32>().unwrap(), 1);
+
+    let mut slot = Some(handle);
+    assert_eq!(EntityHandle::resolve(&mut slot, &world), Some(a));
+    assert_eq!(slot, Some(handle));
+
+    world.despawn(a).unwrap();
+    assert!(world.handle(a).is_none());
+    let b = world.spawn((2,));
+    assert_eq!(b.id(), a.id());
+    assert!(!handle.is_alive(&world));
+    assert!(handle.get(&world).is_none());
+    assert_eq!(EntityHandle::resolve(&mut slot, &world), None);
+    assert_eq!(slot, None);
+}
+
+#[test]
+fn scope_early_return() {
+    fn explode(world: &mut World, fail: bool) -> Result<(), ()> {
+        let mut scope = world.scope();
+        scope.spawn(("debris",));
+        scope.spawn(("debris",));
+        if fail {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    let mut world = World::new();
+    let survivor = world.spawn(("player",));
+    assert!(explode(&mut world, true).is_err());
+    assert!(explode(&mut world, false).is_ok());
+    assert_eq!(world.len(), 1);
+    assert!(world.contains(survivor));
+}
+
+#[test]
+fn prepared_query_initially_empty() {
+    struct Boss;
+    let mut world = World::new();
+    world.spawn((1,));
+    let mut query = PreparedQuery::<&Boss>::new();
+    for _ in 0..2 {
+        assert_eq!(query.query(&world).iter().count(), 0);
+    }
+    let boss = world.spawn((Boss, 2));
+    let found = query
+        .query(&world)
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(found, [boss]);
+}
+
+#[test]
+fn concurrent_reads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<World>();
+    assert_send_sync::<Archetype>();
+    assert_send_sync::<EntityRef<'_>>();
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    world.spawn_batch((0..100).map(|i| (i,)));
+    std::thread::scope(|s| {
+        let world = &world;
+        for _ in 0..4 {
+            s.spawn(move || {
+                let entity = world.entity(a).unwrap();
+                let x = entity.get::<i32>().unwrap();
+                assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+                let sum = world.query::<&i32>().iter().map(|(_, &x)| x).sum::<i32>();
+                assert_eq!(sum, 4951);
+                assert_eq!(*x, 1);
+            });
+        }
+    });
+}
+
+#[test]
+fn fifo_id_reuse() {
+    let mut config = WorldConfig::default();
+    config.fifo_id_reuse = true;
+    let mut world = World::from_config(config);
+    let entities = (0..4).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    world.despawn(entities[2]).unwrap();
+    world.despawn(entities[0]).unwrap();
+    world.despawn(entities[1]).unwrap();
+    let ids = (0..4).map(|_| world.spawn(()).id()).collect::<Vec<_>>();
+    assert_eq!(ids, [2, 0, 1, 4]);
+    assert_eq!(world.validate(), Ok(()));
+}
+
+#[test]
+fn delayed_id_reuse() {
+    let mut config = WorldConfig::default();
+    config.id_reuse_delay = 2;
+    let mut world = World::from_config(config);
+    let a = world.spawn((1,));
+    world.despawn(a).unwrap();
+    let b = world.spawn(());
+    assert_ne!(b.id(), a.id());
+    world.clear_trackers();
+    assert_ne!(world.spawn(()).id(), a.id());
+    world.despawn(b).unwrap();
+    world.advance_tick();
+    // `a`'s ID has waited 2 frames, but `b`'s only 1
+    let c = world.spawn(());
+    assert_eq!(c.id(), a.id());
+    assert_eq!(world.reserve_entity().id(), 3);
+    world.clear_changed();
+    assert_eq!(world.spawn(()).id(), b.id());
+    assert_eq!(world.validate(), Ok(()));
+
+    world.clear();
+    assert_eq!(world.spawn(()).id(), 4);
+    world.clear_changed();
+    world.end_frame();
+    assert_eq!(world.spawn(()).id(), 0);
+}
+
+#[test]
+fn end_frame_keeps_change_flags() {
+    let mut config = WorldConfig::default();
+    config.id_reuse_delay = 1;
+    let mut world = World::from_config(config);
+    let a = world.spawn((1,));
+    world.despawn(a).unwrap();
+    let b = world.spawn((2,));
+    world.end_frame();
+    assert!(world.is_changed::<i32>(b).unwrap());
+    assert_eq!(world.tick(), Tick(0));
+    assert_eq!(world.spawn(()).id(), a.id());
+}
+
+#[test]
+fn fifo_id_reuse_after_clear() {
+    let mut config = WorldConfig::default();
+    config.fifo_id_reuse = true;
+    let mut world = World::from_config(config);
+    for i in 0..3 {
+        world.spawn((i,));
+    }
+    world.clear();
+    let ids = (0..4).map(|_| world.spawn(()).id()).collect::<Vec<_>>();
+    assert_eq!(ids, [0, 1, 2, 3]);
+}
+
+#[test]
+fn collect_entities() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, "abc"));
+    let mut out = Vec::new();
+    world.collect_entities::<&i32>(&mut out);
+    out.sort();
+    assert_eq!(out, [a, b, c]);
+
+    out.clear();
+    world.collect_entities::<Without<bool, &i32>>(&mut out);
+    out.sort();
+    assert_eq!(out, [b, c]);
+
+    world.clear_changed();
+    *world.get_mut::<i32>(b).unwrap() += 1;
+    out.clear();
+    world.collect_entities::<Changed<i32, ()>>(&mut out);
+    assert_eq!(out, [b]);
+
+    let mut query = world.query::<&i32>().with::<bool>();
+    let mut items = Vec::new();
+    query.iter().collect_into(&mut items);
+    assert_eq!(items, [(a, &1)]);
+}
+
+#[test]
+fn user_data() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    assert_eq!(world.user_data(a), Ok(0));
+    world.set_user_data(a, 7).unwrap();
+    world.insert_one(a, "moved").unwrap();
+    assert_eq!(world.user_data(a), Ok(7));
+
+    let reserved = world.reserve_entity();
+    assert_eq!(world.user_data(reserved), Ok(0));
+    world.set_user_data(reserved, 9).unwrap();
+    assert_eq!(world.user_data(reserved), Ok(9));
+
+    world.despawn(a).unwrap();
+    assert_eq!(world.user_data(a), Err(NoSuchEntity));
+    assert_eq!(world.set_user_data(a, 1), Err(NoSuchEntity));
+    let b = world.spawn((2,));
+    assert_eq!(b.id(), a.id());
+    assert_eq!(world.user_data(b), Ok(0));
+}
+
+#[test]
+fn despawn_reason_propagates() {
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let sink = log.clone();
+    world.on_despawn_with_reason(move |entity, reason| sink.lock().unwrap().push((entity, reason)));
+    let parent = world.spawn((1,));
+    let child = world.spawn((2,));
+    let grandchild = world.spawn((3,));
+    world.attach(child, parent).unwrap();
+    world.attach(grandchild, child).unwrap();
+    world
+        .despawn_with_reason(parent, DespawnReason::Killed)
+        .unwrap();
+    assert_eq!(
+        world.drain_despawns().collect::<Vec<_>>(),
+        [
+            (parent, DespawnReason::Killed),
+            (child, DespawnReason::Killed),
+            (grandchild, DespawnReason::Killed),
+        ]
+    );
+
+    let plain = world.spawn((4,));
+    let plain_child = world.spawn((5,));
+    world.attach(plain_child, plain).unwrap();
+    world.despawn(plain).unwrap();
+    assert_eq!(world.drain_despawns().count(), 0);
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            (parent, Some(DespawnReason::Killed)),
+            (child, Some(DespawnReason::Killed)),
+            (grandchild, Some(DespawnReason::Killed)),
+            (plain, None),
+            (plain_child, None),
+        ]
+    );
+}
+
+#[test]
+fn absorb() {
+    let mut world = World::new();
+    let existing = world.spawn((1, "main"));
+    world.set_name(existing, "spawn").unwrap();
+
+    let mut scratch = World::new();
+    let gone = scratch.spawn((0,));
+    scratch.despawn(gone).unwrap();
+    let a = scratch.spawn((2, "scratch"));
+    let b = scratch.spawn((3.0f32,));
+    let c = scratch.reserve_entity();
+    scratch.attach(b, a).unwrap();
+    scratch.set_name(a, "spawn").unwrap();
+    scratch.set_user_data(b, 99).unwrap();
+
+    let map = world.absorb(&mut scratch);
+    assert_eq!(map.len(), 3);
+    assert!(scratch.is_empty());
+    assert_eq!(world.len(), 4);
+    assert_eq!(map.get(gone), None);
+
+    let (a, b, c) = (
+        map.get(a).unwrap(),
+        map.get(b).unwrap(),
+        map.get(c).unwrap(),
+    );
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+    assert_eq!(*world.get::<&str>(a).unwrap(), "scratch");
+    assert_eq!(*world.get::<f32>(b).unwrap(), 3.0);
+    assert!(world.entity(c).unwrap().is_empty());
+    assert_eq!(*world.get::<&str>(existing).unwrap(), "main");
+    assert_eq!(world.parent(b), Some(a));
+    assert_eq!(world.entity_by_name("spawn"), Some(a));
+    assert_eq!(world.name(existing), None);
+    assert_eq!(world.user_data(b), Ok(99));
+
+    let mut ints = world
+        .query::<&i32>()
+        .iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    ints.sort_unstable();
+    assert_eq!(ints, [1, 2]);
+
+    // The emptied world remains usable
+    let d = scratch.spawn((4,));
+    assert_eq!(*scratch.get::<i32>(d).unwrap(), 4);
+    assert_eq!(scratch.len(), 1);
+}
+
+/// Index of `i32` components by value, for checking that every spawn path notifies indexes
+#[derive(Default)]
+struct ByValue(std::collections::BTreeMap<i32, Entity>);
+
+impl SecondaryIndex for ByValue {
+    type Source = i32;
+    fn insert(&mut self, entity: Entity, &value: &i32) {
+        self.0.insert(value, entity);
+    }
+    fn remove(&mut self, _: Entity, value: &i32) {
+        self.0.remove(value);
+    }
+}
+
+fn indexed(world: &World) -> Vec<(i32, Entity)> {
+    let index = &world.index::<ByValue>().unwrap().0;
+    index.iter().map(|(&k, &v)| (k, v)).collect()
+}
+
+#[test]
+fn absorb_updates_indexes() {
+    use std::sync::{Arc, Mutex};
+
+    let inserted = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    world.add_index(ByValue::default());
+    let log = inserted.clone();
+    world.on_insert(move |_, &x: &i32| log.lock().unwrap().push(x));
+    let existing = world.spawn((1,));
+
+    let mut chunk = World::new();
+    let a = chunk.spawn((2,));
+    let b = chunk.spawn((3, true));
+    let map = world.absorb(&mut chunk);
+    let (a, b) = (map.get(a).unwrap(), map.get(b).unwrap());
+
+    assert_eq!(indexed(&world), [(1, existing), (2, a), (3, b)]);
+    inserted.lock().unwrap().sort_unstable();
+    assert_eq!(*inserted.lock().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn spawn_column_batch_updates_indexes() {
+    let mut world = World::new();
+    world.add_index(ByValue::default());
+    let mut batch = ColumnBatchType::new();
+    batch.add::<i32>();
+    let mut batch = batch.into_batch(2);
+    let mut ints = batch.writer::<i32>().unwrap();
+    ints.push(1).unwrap();
+    ints.push(2).unwrap();
+    let entities = world
+        .spawn_column_batch(batch.build().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(indexed(&world), [(1, entities[0]), (2, entities[1])]);
+}
+
+#[test]
+fn spawn_column_batch_at_updates_indexes() {
+    let mut world = World::new();
+    world.add_index(ByValue::default());
+    let a = world.spawn((1,));
+    let b = Entity::from_bits(1 << 32 | 10).unwrap();
+    let mut batch = ColumnBatchType::new();
+    batch.add::<i32>();
+    let mut batch = batch.into_batch(2);
+    let mut ints = batch.writer::<i32>().unwrap();
+    ints.push(2).unwrap();
+    ints.push(3).unwrap();
+    world.spawn_column_batch_at(&[a, b], batch.build().unwrap());
+    assert_eq!(indexed(&world), [(2, a), (3, b)]);
+}
+
+#[test]
+fn par_extend_updates_indexes() {
+    let mut world = World::new();
+    world.add_index(ByValue::default());
+    let mut staging = SpawnStaging::new();
+    staging.push((1,));
+    staging.push((2, true));
+    staging.push((3,));
+    let entities = world.par_extend([staging]);
+    assert_eq!(
+        indexed(&world),
+        [(1, entities[0]), (2, entities[1]), (3, entities[2])]
+    );
+}
+
+#[test]
+fn join_entities_and_components_updates_indexes() {
+    let mut world = World::new();
+    world.add_index(ByValue::default());
+    let (mut alloc, mut storage) = world.split_entities_and_components();
+    let a = alloc.reserve_entity();
+    let b = alloc.reserve_entity();
+    let c = alloc.reserve_entity();
+    storage.insert(a, (1,));
+    storage.insert(b, (2, true));
+    storage.insert(c, (3,));
+    alloc.despawn(c);
+    let world = World::join_entities_and_components(alloc, storage);
+    assert_eq!(indexed(&world), [(1, a), (2, b)]);
+}
+
+#[test]
+fn sorted_by_key() {
+    let mut world = World::new();
+    let a = world.spawn((5, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((5,));
+    let d = world.spawn((-1, "x"));
+    let order = world
+        .query::<&i32>()
+        .sorted_by_key(|&&x| x)
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    // Ties keep archetype order
+    assert_eq!(order, [(d, -1), (b, 2), (a, 5), (c, 5)]);
+
+    for (_, x) in world.query_mut::<&mut i32>() {
+        *x = -*x;
+    }
+    let mut query = world.query::<&mut i32>();
+    let order = query
+        .sorted_by_key(|x| **x)
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(order, [a, c, b, d]);
+}
+
+#[test]
+fn sorted_by_cached_key() {
+    let mut world = World::new();
+    let a = world.spawn((3, 0u8));
+    let b = world.spawn((1,));
+    let mut cache = SortCache::new();
+    let mut calls = 0;
+    let mut order = |world: &World, cache: &mut SortCache| {
+        world
+            .query::<&i32>()
+            .sorted_by_cached_key::<i32, _>(cache, |&&x| {
+                calls += 1;
+                x
+            })
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(order(&world, &mut cache), [b, a]);
+    world.clear_changed();
+    assert_eq!(order(&world, &mut cache), [b, a]);
+
+    // Changes to other components don't invalidate the cache
+    *world.get_mut::<u8>(a).unwrap() = 1;
+    assert_eq!(order(&world, &mut cache), [b, a]);
+
+    // Changes to the set of matched entities do
+    world.clear_changed();
+    let c = world.spawn((2,));
+    world.clear_changed();
+    assert_eq!(order(&world, &mut cache), [b, c, a]);
+    world.clear_changed();
+    world.despawn(c).unwrap();
+    assert_eq!(order(&world, &mut cache), [b, a]);
+
+    *world.get_mut::<i32>(a).unwrap() = 0;
+    assert_eq!(order(&world, &mut cache), [a, b]);
+    assert_eq!(calls, 2 + 3 + 2 + 2);
+}
+
+#[test]
+fn archetype_created_hooks() {
+    use std::sync::{Arc, Mutex};
+
+    let created = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let log = created.clone();
+    world.on_archetype_created(move |index, archetype| {
+        log.lock().unwrap().push((index, archetype.len()))
+    });
+    let a = world.spawn((1,));
+    world.spawn_batch((0..3).map(|i| (i, true)));
+    world.insert_one(a, "x").unwrap();
+    world.remove_one::<i32>(a).unwrap();
+    world.spawn((2,));
+    let mut other = World::new();
+    other.spawn((1.0f32,));
+    world.absorb(&mut other);
+
+    // Hooks run before any entity is moved into the new archetype, unless it's adopted whole
+    assert_eq!(
+        *created.lock().unwrap(),
+        [(1, 0), (2, 0), (3, 0), (4, 0), (5, 1)]
+    );
+    let lens = world.archetypes().map(|x| x.len()).collect::<Vec<_>>();
+    assert_eq!(lens, [0, 1, 3, 0, 1, 1]);
+}
+
+#[test]
+fn query_trait() {
+    trait Weight {
+        fn weight(&self) -> u32;
+    }
+    impl Weight for u8 {
+        fn weight(&self) -> u32 {
+            u32::from(*self)
+        }
+    }
+    impl Weight for u16 {
+        fn weight(&self) -> u32 {
+            u32::from(*self) * 10
+        }
+    }
+
+    let mut world = World::new();
+    assert_eq!(world.query_trait::<dyn Weight>().iter().count(), 0);
+    world.register_trait::<u8, dyn Weight>(|x| x, |x| x);
+    world.register_trait::<u16, dyn Weight>(|x| x, |x| x);
+    let a = world.spawn((1u8, 2u16));
+    let b = world.spawn((3u8,));
+    world.spawn((4u32,));
+
+    let mut query = world.query_trait::<dyn Weight>();
+    let iter = query.iter();
+    assert_eq!(iter.len(), 3);
+    let mut weights = iter.map(|(e, x)| (e, x.weight())).collect::<Vec<_>>();
+    weights.sort();
+    assert_eq!(weights, [(a, 1), (a, 20), (b, 3)]);
+    // Matched components stay borrowed immutably until the query is dropped
+    assert!(world.get::<u8>(a).is_ok());
+    assert!(world.try_get_mut::<u8>(a).is_err());
+    drop(query);
+    *world.get_mut::<u8>(a).unwrap() = 5;
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn try_get_reports_conflicts() {
+    static RENDER: BorrowLabel = BorrowLabel::new("render");
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn(());
+    assert_eq!(
+        world.try_get::<i32>(b).err(),
+        Some(ComponentError::MissingComponent(
+            MissingComponent::new::<i32>()
+        ))
+    );
+    world.despawn(b).unwrap();
+    assert_eq!(
+        world.try_get_mut::<i32>(b).err(),
+        Some(ComponentError::NoSuchEntity)
+    );
+
+    let shared = with_borrow_label(&RENDER, || world.get::<i32>(a).unwrap());
+    let err = match world.try_get_mut::<i32>(a) {
+        Err(ComponentError::Borrowed(err)) => err,
+        _ => panic!("expected a conflict"),
+    };
+    assert_eq!(err.type_name(), "i32");
+    #[cfg(feature = "track-borrows")]
+    {
+        assert_eq!(err.holder(), Some("render"));
+        assert!(err.to_string().contains("by render"));
+    }
+    #[cfg(not(feature = "track-borrows"))]
+    assert_eq!(err.holder(), None);
+    assert_eq!(*world.try_get::<i32>(a).unwrap(), 1);
+    drop(shared);
+
+    let unique = world.try_get_mut::<i32>(a).unwrap();
+    assert!(matches!(
+        world.try_get::<i32>(a),
+        Err(ComponentError::Borrowed(_))
+    ));
+    assert!(world.try_get::<bool>(a).is_ok());
+    drop(unique);
+    *world.try_get_mut::<i32>(a).unwrap() = 2;
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+}
+
+#[test]
+fn signatures() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((true, 2));
+    let c = world.spawn(());
+    let d = world.reserve_entity();
+    let sig = world.signature(a).unwrap();
+    assert_eq!(world.signature(b), Ok(sig));
+    assert_eq!(world.signature(c), world.signature(d));
+    assert!(sig.contains::<i32>(&world) && sig.contains::<bool>(&world));
+    assert!(!world.signature(c).unwrap().contains::<i32>(&world));
+    assert_eq!(world.archetypes().nth(sig.index()).unwrap().len(), 2);
+
+    // Signatures follow entities between archetypes, and are stable per component set
+    world.remove_one::<bool>(a).unwrap();
+    let moved = world.signature(a).unwrap();
+    assert_ne!(moved, sig);
+    assert!(!moved.contains::<bool>(&world));
+    world.insert_one(a, false).unwrap();
+    assert_eq!(world.signature(a), Ok(sig));
+
+    world.despawn(a).unwrap();
+    assert_eq!(world.signature(a), Err(NoSuchEntity));
+}
+
+#[test]
+fn swap_columns() {
+    #[repr(transparent)]
+    struct Current(String);
+    #[repr(transparent)]
+    struct Previous(String);
+
+    let mut world = World::new();
+    let a = world.spawn((Current("a1".into()), Previous("a0".into())));
+    let b = world.spawn((Current("b1".into()), Previous("b0".into()), true));
+    let c = world.spawn((Current("c1".into()),));
+    // Grow an archetype after the swap to check that relocation follows the swapped columns
+    unsafe {
+        world.swap_columns::<Current, Previous>();
+    }
+    let d = world.spawn((Current("d1".into()), Previous("d0".into())));
+    for i in 0..64 {
+        world.spawn((Current(i.to_string()), Previous(i.to_string())));
+    }
+
+    assert_eq!(world.get::<Current>(a).unwrap().0, "a0");
+    assert_eq!(world.get::<Previous>(a).unwrap().0, "a1");
+    assert_eq!(world.get::<Current>(b).unwrap().0, "b0");
+    assert_eq!(world.get::<Previous>(b).unwrap().0, "b1");
+    assert_eq!(world.get::<Current>(c).unwrap().0, "c1");
+    assert_eq!(world.get::<Current>(d).unwrap().0, "d1");
+    assert_eq!(world.get::<Previous>(d).unwrap().0, "d0");
+    assert!(world.is_changed::<Previous>(a).unwrap());
+
+    world.clear_changed();
+    unsafe {
+        world.swap_columns::<Current, Previous>();
+    }
+    assert!(world.is_changed::<Current>(b).unwrap());
+    assert!(!world.is_changed::<Current>(c).unwrap());
+    assert_eq!(world.get::<Current>(b).unwrap().0, "b1");
+    world.despawn(a).unwrap();
+    assert_eq!(world.take(b).unwrap().get::<Previous>().unwrap().0, "b0");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn stage_entities() {
+    let mut world = World::new();
+    let existing = world.spawn((0,));
+    let mut builder = EntityBuilder::new();
+    builder.add(1).add(true);
+    let a = world.stage(builder);
+    let mut builder = EntityBuilder::new();
+    builder.add(2);
+    let b = world.stage(builder);
+    let mut builder = EntityBuilder::new();
+    builder.add(3);
+    let c = world.stage(builder);
+    assert!(world.contains(a));
+    assert!(world.get::<i32>(a).is_err());
+
+    // Handles are usable before the entities are applied
+    world.despawn(c).unwrap();
+    world.insert_one(b, "b").unwrap();
+    assert_eq!(world.apply_staged(), 2);
+    assert_eq!(world.apply_staged(), 0);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert!(*world.get::<bool>(a).unwrap());
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert_eq!(*world.get::<&str>(b).unwrap(), "b");
+    assert!(!world.contains(c));
+    assert_eq!(world.len(), 3);
+    assert!(world.contains(existing));
+}
+
+#[test]
+#[cfg(feature = "stats")]
+fn stats() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2, true));
+    world.reserve_entity();
+    world.insert_one(a, "a").unwrap();
+    world.remove_one::<bool>(b).unwrap();
+    world.insert_one(b, 3).unwrap(); // Replacement doesn't move
+    for (_, x) in world.query::<&mut i32>().iter() {
+        *x += 1;
+    }
+    let stats = world.stats();
+    assert_eq!(stats.spawns, 3);
+    assert_eq!(stats.despawns, 0);
+    assert_eq!(stats.archetype_moves, 2);
+    assert!(stats.archetype_scans >= 3);
+    assert!(stats.borrows >= 1);
+
+    world.reset_stats();
+    assert_eq!(world.stats(), WorldStats::default());
+    world.despawn(a).unwrap();
+    let mut query = PreparedQuery::<&i32>::new();
+    assert_eq!(query.query(&world).iter().count(), 1);
+    let scans = world.stats().archetype_scans;
+    assert_eq!(scans, world.archetypes().len() as u64);
+    assert_eq!(query.query(&world).iter().count(), 1);
+    assert_eq!(world.stats().archetype_scans, scans);
+    world.clear();
+    assert_eq!(world.stats().despawns, 3);
+}
+
+#[test]
+#[cfg(all(feature = "stats", feature = "std"))]
+fn stats_sum_scans_across_threads() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.spawn((1, true));
+    world.reset_stats();
+    let world = &world;
+    std::thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for _ in 0..10 {
+                    world.query::<&i32>().iter().count();
+                }
+            });
+        }
+    });
+    assert_eq!(
+        world.stats().archetype_scans,
+        80 * world.archetypes().len() as u64
+    );
+}
+
+#[test]
+fn ensure() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2, 5u8));
+    let empty = world.spawn(());
+    *world.ensure::<u8>(a).unwrap() += 3;
+    *world.ensure::<u8>(b).unwrap() += 3;
+    *world.ensure::<u8>(empty).unwrap() += 1;
+    assert_eq!(*world.get::<u8>(a).unwrap(), 3);
+    assert_eq!(*world.get::<u8>(b).unwrap(), 8);
+    assert_eq!(*world.get::<u8>(empty).unwrap(), 1);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+
+    world.clear_trackers();
+    let _ = world.ensure::<u8>(b).unwrap();
+    assert_eq!(world.query::<Changed<u8, ()>>().iter().count(), 0);
+
+    world.despawn(a).unwrap();
+    assert!(world.ensure::<u8>(a).is_err());
+}
+
+#[test]
+fn world_view() {
+    fn assert_send_sync<T: Send + Sync + Copy>(_: T) {}
+
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2,));
+    world.insert_resource(7u64);
+    let view = world.as_view();
+    assert_send_sync(view);
+    assert_eq!(view.len(), 2);
+    assert!(view.contains(b));
+    assert_eq!(*view.get::<&str>(a).unwrap(), "a");
+    assert!(view.entity(b).unwrap().has::<i32>());
+    assert_eq!(view.iter().count(), 2);
+    assert_eq!(*view.resource::<u64>().unwrap(), 7);
+    let copy = view;
+    for (_, x) in copy.query::<&mut i32>().iter() {
+        *x *= 10;
+    }
+    assert_eq!(*view.query_one::<&i32>(b).unwrap().get().unwrap(), 20);
+}